@@ -162,10 +162,31 @@ pub(crate) mod tests {
 
     const BLOCK_SIZE: usize = 512;
 
+    /// Error returned by [`MockBlockDevice`] for a write made to fail
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(crate) struct MockError;
+
+    impl core::fmt::Display for MockError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str("mock write failure")
+        }
+    }
+
+    impl core::error::Error for MockError {}
+
     // Mock BlockDevice for testing
+    #[derive(Clone)]
     pub(crate) struct MockBlockDevice<const BLOCK_SIZE: usize> {
         data: HashMap<u32, [u8; BLOCK_SIZE]>,
         size: u64,
+        /// Blocks written so far
+        pub(crate) writes: usize,
+        /// Number of `sync` calls
+        pub(crate) syncs: usize,
+        /// Fail writes once this many more blocks have been written (a power loss)
+        pub(crate) write_budget: Option<usize>,
+        /// Fail any write touching this block, leaving the medium untouched
+        pub(crate) bad_block: Option<u32>,
     }
 
     impl<const BLOCK_SIZE: usize> MockBlockDevice<BLOCK_SIZE> {
@@ -173,12 +194,48 @@ pub(crate) mod tests {
             Self {
                 data: HashMap::new(),
                 size,
+                writes: 0,
+                syncs: 0,
+                write_budget: None,
+                bad_block: None,
+            }
+        }
+
+        fn check_bounds(&self, block_address: u32, count: usize) {
+            let end = u64::from(block_address) + count as u64;
+            assert!(
+                end * BLOCK_SIZE as u64 <= self.size,
+                "access to blocks {block_address}..{end} beyond the mock device"
+            );
+        }
+    }
+
+    // Helpers for the tests of the feature-gated adapters
+    #[allow(dead_code)]
+    impl<const BLOCK_SIZE: usize> MockBlockDevice<BLOCK_SIZE> {
+        /// Creates a device of `blocks` blocks whose block `i` initially holds the byte `i`
+        pub(crate) fn patterned(blocks: u32) -> Self {
+            let mut device = Self::new(u64::from(blocks) * BLOCK_SIZE as u64);
+            for addr in 0..blocks {
+                device.set_block(addr, [addr as u8; BLOCK_SIZE]);
             }
+            device
+        }
+
+        /// Returns the stored contents of a block, zeros if it was never written
+        pub(crate) fn block(&self, addr: u32) -> [u8; BLOCK_SIZE] {
+            self.data.get(&addr).copied().unwrap_or([0; BLOCK_SIZE])
+        }
+
+        /// Overwrites a block behind the back of whatever wraps the device
+        pub(crate) fn set_block(&mut self, addr: u32, data: [u8; BLOCK_SIZE]) {
+            self.check_bounds(addr, 1);
+            self.data.insert(addr, data);
         }
     }
 
     impl<const BLOCK_SIZE: usize> BlockDevice<BLOCK_SIZE> for MockBlockDevice<BLOCK_SIZE> {
-        type Error = std::io::Error;
+        type Error = MockError;
         type Align = aligned::A4;
 
         async fn read(
@@ -186,6 +243,7 @@ pub(crate) mod tests {
             block_address: u32,
             data: &mut [Aligned<Self::Align, [u8; BLOCK_SIZE]>],
         ) -> Result<(), Self::Error> {
+            self.check_bounds(block_address, data.len());
             for (i, block) in data.iter_mut().enumerate() {
                 let addr = block_address + i as u32;
                 if let Some(stored) = self.data.get(&addr) {
@@ -203,11 +261,26 @@ pub(crate) mod tests {
             block_address: u32,
             data: &[Aligned<Self::Align, [u8; BLOCK_SIZE]>],
         ) -> Result<(), Self::Error> {
+            self.check_bounds(block_address, data.len());
+            let end = block_address + data.len() as u32;
+            if self
+                .bad_block
+                .is_some_and(|bad| (block_address..end).contains(&bad))
+            {
+                return Err(MockError);
+            }
             for (i, block) in data.iter().enumerate() {
+                if let Some(budget) = &mut self.write_budget {
+                    if *budget == 0 {
+                        return Err(MockError);
+                    }
+                    *budget -= 1;
+                }
                 let addr = block_address + i as u32;
                 let mut stored = [0u8; BLOCK_SIZE];
                 stored.copy_from_slice(&block[..]);
                 self.data.insert(addr, stored);
+                self.writes += 1;
             }
             Ok(())
         }
@@ -217,10 +290,23 @@ pub(crate) mod tests {
         }
 
         async fn sync(&mut self) -> Result<(), Self::Error> {
+            self.syncs += 1;
             Ok(())
         }
     }
 
+    /// Polls a future to completion on the current thread
+    #[allow(dead_code)]
+    pub(crate) fn block_on<F: core::future::Future>(f: F) -> F::Output {
+        let mut cx = core::task::Context::from_waker(core::task::Waker::noop());
+        let mut f = core::pin::pin!(f);
+        loop {
+            if let core::task::Poll::Ready(val) = f.as_mut().poll(&mut cx) {
+                return val;
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_adapter_read_write() {
         let device = MockBlockDevice::<BLOCK_SIZE>::new(1024 * 1024);
@@ -0,0 +1,608 @@
+//! Per-block integrity checksums for block devices.
+//!
+//! [`ChecksummedBlockDevice`] wraps any [`BlockDevice`] and keeps a CRC32C of every
+//! block in a metadata region at the end of the inner device. Every read is verified
+//! and a block whose content does not match its checksum is reported as
+//! [`ChecksumError::Corrupted`] instead of being handed to the filesystem.
+//! [`ChecksummedBlockDevice::scrub`] walks the whole device and reports every
+//! mismatch, e.g. to be run periodically from a maintenance task.
+//!
+//! # Layout
+//!
+//! ```text
+//! ┌──────────────────────┬────────┬──────────────────────┐
+//! │ Data blocks          │ Header │ Checksum blocks      │
+//! │ (reported as size)   │        │ (8 bytes per block)  │
+//! └──────────────────────┴────────┴──────────────────────┘
+//! ```
+//!
+//! Each checksum entry holds two CRCs: the checksum of the current content and the
+//! checksum of the content it replaces. A block is valid if it matches either.
+//!
+//! # Crash Consistency
+//!
+//! A write first stores `{new, old}` in the checksum block, then writes the data, then
+//! stores `{new, new}`. The inner device is synced after each of the first two steps so
+//! a volatile write cache cannot persist them out of order. A power loss at any point
+//! leaves data that matches one of the two stored checksums, so an interrupted write
+//! never shows up as corruption. The next write to such a block reads it once to find
+//! out which of the two it holds. The price is two metadata writes and two syncs per
+//! write call (per checksum block touched).
+//!
+//! This relies on the inner device writing a single block atomically: a block either
+//! has its old or its new content after a power loss, never a mix. Checksum blocks
+//! are kept in a single copy, so if a torn write does leave one half-written, every
+//! data block it covers (`SIZE / 8`, i.e. 64 for 512-byte blocks) is reported as
+//! corrupted. SD cards and eMMC guarantee sector-atomic writes; raw NOR/NAND flash
+//! under a translation layer only does if the layer does.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fatrs_adapters::{ChecksummedBlockDevice, ChecksumError};
+//!
+//! // Once, when provisioning the card:
+//! let device = ChecksummedBlockDevice::<_, 512>::initialize(sd_card).await?;
+//! // On every boot:
+//! let device = ChecksummedBlockDevice::<_, 512>::open(sd_card).await?;
+//!
+//! let report = device.scrub(|block| warn!("block {} is corrupted", block)).await?;
+//! ```
+
+use aligned::Aligned;
+use crc::{CRC_32_ISCSI, Crc};
+use fatrs_block_device::BlockDevice;
+
+const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+const HEADER_MAGIC: [u8; 4] = *b"FCSM";
+const HEADER_VERSION: u8 = 1;
+/// Checksum algorithm id stored in the header (1 = CRC32C).
+const ALGORITHM_CRC32C: u8 = 1;
+const ENTRY_SIZE: usize = 8;
+
+/// Errors of a [`ChecksummedBlockDevice`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumError<E> {
+    /// Error from the inner device.
+    Device(E),
+    /// The content of a block does not match its checksum.
+    Corrupted {
+        /// Address of the corrupted block.
+        block: u32,
+    },
+    /// The inner device has no (or a foreign) checksum header.
+    NotInitialized,
+    /// The inner device is too small to hold data and checksums.
+    TooSmall,
+    /// The access is beyond the data region.
+    OutOfRange,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for ChecksumError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Device(e) => write!(f, "Device error: {}", e),
+            Self::Corrupted { block } => write!(f, "Checksum mismatch in block {}", block),
+            Self::NotInitialized => write!(f, "Device has no checksum metadata"),
+            Self::TooSmall => write!(f, "Device too small for checksum metadata"),
+            Self::OutOfRange => write!(f, "Block address out of range"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug + core::fmt::Display> core::error::Error for ChecksumError<E> {}
+
+/// Result of [`ChecksummedBlockDevice::scrub`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScrubReport {
+    /// Number of data blocks verified.
+    pub blocks_checked: u32,
+    /// Number of blocks whose content did not match the checksum.
+    pub corrupted_blocks: u32,
+}
+
+/// Block device wrapper verifying a CRC32C of every block on read.
+///
+/// The reported size is the data region; the header and the checksums live in the
+/// blocks after it. `SIZE` must be at least 16 bytes.
+///
+/// The inner device must write a block atomically. Checksums are stored in a single
+/// copy, so a torn write of a checksum block makes every data block it covers
+/// (`SIZE / 8`) read as [`ChecksumError::Corrupted`].
+pub struct ChecksummedBlockDevice<D, const SIZE: usize> {
+    inner: D,
+    /// Number of blocks available for data (also the header block address).
+    data_blocks: u32,
+}
+
+impl<D, const SIZE: usize> ChecksummedBlockDevice<D, SIZE> {
+    const ENTRIES_PER_BLOCK: u32 = (SIZE / ENTRY_SIZE) as u32;
+
+    /// Get a reference to the inner device.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Consume the wrapper and return the inner device.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// Number of data blocks (the reported size in blocks).
+    pub fn data_blocks(&self) -> u32 {
+        self.data_blocks
+    }
+
+    /// Number of data blocks fitting on a device of `total` blocks with their checksums.
+    fn data_blocks_for(total: u32) -> Option<u32> {
+        let epb = Self::ENTRIES_PER_BLOCK;
+        let available = total.checked_sub(1)?;
+        let mut data = (u64::from(available) * u64::from(epb) / u64::from(epb + 1)) as u32;
+        while data > 0 && data + data.div_ceil(epb) > available {
+            data -= 1;
+        }
+        (data > 0).then_some(data)
+    }
+
+    /// Address of the checksum block holding the entry of data block `block`.
+    fn meta_block(&self, block: u32) -> u32 {
+        self.data_blocks + 1 + block / Self::ENTRIES_PER_BLOCK
+    }
+
+    fn entry_offset(block: u32) -> usize {
+        (block % Self::ENTRIES_PER_BLOCK) as usize * ENTRY_SIZE
+    }
+
+    fn read_entry(meta: &[u8; SIZE], block: u32) -> (u32, u32) {
+        let o = Self::entry_offset(block);
+        let current = u32::from_le_bytes([meta[o], meta[o + 1], meta[o + 2], meta[o + 3]]);
+        let previous = u32::from_le_bytes([meta[o + 4], meta[o + 5], meta[o + 6], meta[o + 7]]);
+        (current, previous)
+    }
+
+    fn write_entry(meta: &mut [u8; SIZE], block: u32, current: u32, previous: u32) {
+        let o = Self::entry_offset(block);
+        meta[o..o + 4].copy_from_slice(&current.to_le_bytes());
+        meta[o + 4..o + 8].copy_from_slice(&previous.to_le_bytes());
+    }
+
+    fn encode_header(&self, header: &mut [u8; SIZE]) {
+        header.fill(0);
+        header[0..4].copy_from_slice(&HEADER_MAGIC);
+        header[4] = HEADER_VERSION;
+        header[5] = ALGORITHM_CRC32C;
+        header[8..12].copy_from_slice(&self.data_blocks.to_le_bytes());
+        let crc = CRC32C.checksum(&header[0..12]);
+        header[12..16].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    fn header_matches(&self, header: &[u8; SIZE]) -> bool {
+        let mut expected = [0u8; SIZE];
+        self.encode_header(&mut expected);
+        header[0..16] == expected[0..16]
+    }
+}
+
+impl<D: BlockDevice<SIZE>, const SIZE: usize> ChecksummedBlockDevice<D, SIZE> {
+    async fn layout(inner: &D) -> Result<u32, ChecksumError<D::Error>> {
+        const { assert!(SIZE >= 16, "Blocks must hold the checksum header") };
+        let size = inner.size().await.map_err(ChecksumError::Device)?;
+        let total = u32::try_from(size / SIZE as u64).unwrap_or(u32::MAX);
+        Self::data_blocks_for(total).ok_or(ChecksumError::TooSmall)
+    }
+
+    /// Open a device initialized with [`ChecksummedBlockDevice::initialize`].
+    ///
+    /// # Errors
+    ///
+    /// * `ChecksumError::NotInitialized` if the device has no matching checksum header.
+    /// * `ChecksumError::TooSmall` if the device cannot hold any data.
+    /// * `ChecksumError::Device` if the inner device returned an error.
+    pub async fn open(inner: D) -> Result<Self, ChecksumError<D::Error>> {
+        let data_blocks = Self::layout(&inner).await?;
+        let device = Self { inner, data_blocks };
+        let mut header: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        device
+            .inner
+            .read(data_blocks, core::slice::from_mut(&mut header))
+            .await
+            .map_err(ChecksumError::Device)?;
+        if !device.header_matches(&header) {
+            return Err(ChecksumError::NotInitialized);
+        }
+        Ok(device)
+    }
+
+    /// Compute checksums of the current content of all data blocks and write the header.
+    ///
+    /// The header is written last, so an interrupted initialization is detected by
+    /// [`ChecksummedBlockDevice::open`] and can simply be repeated.
+    ///
+    /// # Errors
+    ///
+    /// * `ChecksumError::TooSmall` if the device cannot hold any data.
+    /// * `ChecksumError::Device` if the inner device returned an error.
+    pub async fn initialize(inner: D) -> Result<Self, ChecksumError<D::Error>> {
+        let data_blocks = Self::layout(&inner).await?;
+        let mut device = Self { inner, data_blocks };
+        let mut data: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        let mut meta: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+
+        let mut block = 0;
+        while block < data_blocks {
+            let group_end = (block / Self::ENTRIES_PER_BLOCK + 1) * Self::ENTRIES_PER_BLOCK;
+            let group_end = group_end.min(data_blocks);
+            meta.fill(0);
+            for b in block..group_end {
+                device
+                    .inner
+                    .read(b, core::slice::from_mut(&mut data))
+                    .await
+                    .map_err(ChecksumError::Device)?;
+                let crc = CRC32C.checksum(&data[..]);
+                Self::write_entry(&mut meta, b, crc, crc);
+            }
+            device.write_meta(block, &meta).await?;
+            block = group_end;
+        }
+
+        device.encode_header(&mut meta);
+        device
+            .inner
+            .write(data_blocks, core::slice::from_ref(&meta))
+            .await
+            .map_err(ChecksumError::Device)?;
+        device.inner.sync().await.map_err(ChecksumError::Device)?;
+        Ok(device)
+    }
+
+    /// Verify every data block and report the mismatches.
+    ///
+    /// `on_mismatch` is called with the address of each corrupted block. Scrubbing
+    /// continues past corrupted blocks; only errors of the inner device abort it.
+    ///
+    /// # Errors
+    ///
+    /// * `ChecksumError::Device` if the inner device returned an error.
+    pub async fn scrub(
+        &self,
+        mut on_mismatch: impl FnMut(u32),
+    ) -> Result<ScrubReport, ChecksumError<D::Error>> {
+        let mut report = ScrubReport::default();
+        let mut data: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        let mut meta: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        for block in 0..self.data_blocks {
+            if block % Self::ENTRIES_PER_BLOCK == 0 {
+                self.read_meta(block, &mut meta).await?;
+            }
+            self.inner
+                .read(block, core::slice::from_mut(&mut data))
+                .await
+                .map_err(ChecksumError::Device)?;
+            report.blocks_checked += 1;
+            if !Self::verify(&meta, block, &data) {
+                report.corrupted_blocks += 1;
+                on_mismatch(block);
+            }
+        }
+        Ok(report)
+    }
+
+    fn verify(meta: &[u8; SIZE], block: u32, data: &[u8; SIZE]) -> bool {
+        let (current, previous) = Self::read_entry(meta, block);
+        let crc = CRC32C.checksum(data);
+        crc == current || crc == previous
+    }
+
+    async fn read_meta(
+        &self,
+        block: u32,
+        meta: &mut Aligned<D::Align, [u8; SIZE]>,
+    ) -> Result<(), ChecksumError<D::Error>> {
+        self.inner
+            .read(self.meta_block(block), core::slice::from_mut(meta))
+            .await
+            .map_err(ChecksumError::Device)
+    }
+
+    async fn write_meta(
+        &mut self,
+        block: u32,
+        meta: &Aligned<D::Align, [u8; SIZE]>,
+    ) -> Result<(), ChecksumError<D::Error>> {
+        self.inner
+            .write(self.meta_block(block), core::slice::from_ref(meta))
+            .await
+            .map_err(ChecksumError::Device)
+    }
+
+    /// Barrier between the steps of a write
+    async fn sync_inner(&mut self) -> Result<(), ChecksumError<D::Error>> {
+        self.inner.sync().await.map_err(ChecksumError::Device)
+    }
+
+    fn check_range(&self, block_address: u32, count: usize) -> Result<(), ChecksumError<D::Error>> {
+        let end = u64::from(block_address) + count as u64;
+        if end > u64::from(self.data_blocks) {
+            return Err(ChecksumError::OutOfRange);
+        }
+        Ok(())
+    }
+}
+
+impl<D, const SIZE: usize> BlockDevice<SIZE> for ChecksummedBlockDevice<D, SIZE>
+where
+    D: BlockDevice<SIZE>,
+{
+    type Error = ChecksumError<D::Error>;
+    type Align = D::Align;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        self.check_range(block_address, data.len())?;
+        self.inner
+            .read(block_address, data)
+            .await
+            .map_err(ChecksumError::Device)?;
+
+        let mut meta: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        let mut loaded = None;
+        for (i, block) in data.iter().enumerate() {
+            let address = block_address + i as u32;
+            let meta_block = self.meta_block(address);
+            if loaded != Some(meta_block) {
+                self.read_meta(address, &mut meta).await?;
+                loaded = Some(meta_block);
+            }
+            if !Self::verify(&meta, address, block) {
+                return Err(ChecksumError::Corrupted { block: address });
+            }
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        self.check_range(block_address, data.len())?;
+        let mut meta: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        let mut old: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+
+        // Blocks sharing a checksum block are updated together
+        let mut start = 0;
+        while start < data.len() {
+            let first = block_address + start as u32;
+            let in_group = (Self::ENTRIES_PER_BLOCK - first % Self::ENTRIES_PER_BLOCK) as usize;
+            let end = (start + in_group).min(data.len());
+            self.read_meta(first, &mut meta).await?;
+
+            // 1. Accept both the old and the new content
+            for (i, block) in data[start..end].iter().enumerate() {
+                let address = first + i as u32;
+                let (current, previous) = Self::read_entry(&meta, address);
+                let on_disk = if current == previous {
+                    current
+                } else {
+                    // An earlier write was interrupted: find out which version is stored
+                    self.inner
+                        .read(address, core::slice::from_mut(&mut old))
+                        .await
+                        .map_err(ChecksumError::Device)?;
+                    CRC32C.checksum(&old[..])
+                };
+                Self::write_entry(&mut meta, address, CRC32C.checksum(&block[..]), on_disk);
+            }
+            self.write_meta(first, &meta).await?;
+            self.sync_inner().await?;
+
+            // 2. Write the data
+            self.inner
+                .write(first, &data[start..end])
+                .await
+                .map_err(ChecksumError::Device)?;
+            self.sync_inner().await?;
+
+            // 3. Only accept the new content
+            for i in 0..end - start {
+                let address = first + i as u32;
+                let (current, _) = Self::read_entry(&meta, address);
+                Self::write_entry(&mut meta, address, current, current);
+            }
+            self.write_meta(first, &meta).await?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(u64::from(self.data_blocks) * SIZE as u64)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.inner.sync().await.map_err(ChecksumError::Device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::{MockBlockDevice, block_on};
+
+    const BLOCK: usize = 512;
+    const BLOCKS: usize = 300;
+
+    fn mock() -> MockBlockDevice<BLOCK> {
+        MockBlockDevice::new((BLOCKS * BLOCK) as u64)
+    }
+
+    type Device = ChecksummedBlockDevice<MockBlockDevice<BLOCK>, BLOCK>;
+
+    #[test]
+    fn test_layout_fits_device() {
+        // 64 entries per block: 295 data blocks + header + 5 checksum blocks > 300
+        let data = Device::data_blocks_for(BLOCKS as u32).unwrap();
+        assert_eq!(data, 294);
+        assert!(data + 1 + data.div_ceil(64) <= BLOCKS as u32);
+        assert!(Device::data_blocks_for(1).is_none());
+    }
+
+    #[test]
+    fn test_open_requires_initialization() {
+        block_on(async {
+            assert!(matches!(
+                Device::open(mock()).await,
+                Err(ChecksumError::NotInitialized)
+            ));
+            let device = Device::initialize(mock()).await.unwrap();
+            let device = Device::open(device.into_inner()).await.unwrap();
+            assert_eq!(device.size().await.unwrap(), 294 * BLOCK as u64);
+        });
+    }
+
+    #[test]
+    fn test_bit_rot_is_detected() {
+        block_on(async {
+            let mut device = Device::initialize(mock()).await.unwrap();
+            let blocks = [Aligned([0x11u8; BLOCK]), Aligned([0x22u8; BLOCK])];
+            device.write(63, &blocks).await.unwrap();
+
+            let mut read = [Aligned([0u8; BLOCK]), Aligned([0u8; BLOCK])];
+            device.read(63, &mut read).await.unwrap();
+            assert_eq!(*read[1], [0x22u8; BLOCK]);
+
+            // Flip a bit behind the wrapper's back
+            let mut inner = device.into_inner();
+            let mut block = inner.block(64);
+            block[100] ^= 0x04;
+            inner.set_block(64, block);
+            let device = Device::open(inner).await.unwrap();
+            assert_eq!(
+                device.read(63, &mut read).await,
+                Err(ChecksumError::Corrupted { block: 64 })
+            );
+
+            let mut bad = Vec::new();
+            let report = device.scrub(|block| bad.push(block)).await.unwrap();
+            assert_eq!(report.blocks_checked, 294);
+            assert_eq!(report.corrupted_blocks, 1);
+            assert_eq!(bad, vec![64]);
+        });
+    }
+
+    #[test]
+    fn test_interrupted_write_is_not_corruption() {
+        block_on(async {
+            let mut device = Device::initialize(mock()).await.unwrap();
+            device.write(5, &[Aligned([0xAAu8; BLOCK])]).await.unwrap();
+
+            // Power loss after the first checksum update, before the data write
+            device.inner.write_budget = Some(1);
+            assert!(device.write(5, &[Aligned([0xBBu8; BLOCK])]).await.is_err());
+            device.inner.write_budget = None;
+            let mut read = [Aligned([0u8; BLOCK])];
+            device.read(5, &mut read).await.unwrap();
+            assert_eq!(*read[0], [0xAAu8; BLOCK]);
+
+            // Power loss after the data write, before the final checksum update
+            device.inner.write_budget = Some(2);
+            assert!(device.write(5, &[Aligned([0xCCu8; BLOCK])]).await.is_err());
+            device.inner.write_budget = None;
+            device.read(5, &mut read).await.unwrap();
+            assert_eq!(*read[0], [0xCCu8; BLOCK]);
+
+            // Writing again resolves the pending entry
+            device.write(5, &[Aligned([0xDDu8; BLOCK])]).await.unwrap();
+            device.read(5, &mut read).await.unwrap();
+            assert_eq!(*read[0], [0xDDu8; BLOCK]);
+            let report = device.scrub(|_| {}).await.unwrap();
+            assert_eq!(report.corrupted_blocks, 0);
+        });
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[test]
+    fn test_power_cut_with_write_cache_is_not_corruption() {
+        use crate::adapters::{FaultPlan, FaultyBlockDevice, explore_cut_points};
+
+        block_on(async {
+            let mut device = Device::initialize(mock()).await.unwrap();
+            device.write(5, &[Aligned([0xAAu8; BLOCK])]).await.unwrap();
+            let image = device.into_inner();
+
+            // A volatile cache persists in address order: data before checksums
+            let report = explore_cut_points(
+                &image,
+                &FaultPlan::new().with_write_cache(true),
+                async |faulty: FaultyBlockDevice<MockBlockDevice<BLOCK>, BLOCK>| {
+                    let mut device = ChecksummedBlockDevice::<_, BLOCK>::open(faulty)
+                        .await
+                        .unwrap();
+                    let _ = device.write(5, &[Aligned([0xBBu8; BLOCK])]).await;
+                    let _ = device.sync().await;
+                    device.into_inner()
+                },
+                async |medium| {
+                    let device = Device::open(medium).await.map_err(|_| "header lost")?;
+                    let mut read = [Aligned([0u8; BLOCK])];
+                    device.read(5, &mut read).await.map_err(|_| "corrupted")?;
+                    if *read[0] != [0xAAu8; BLOCK] && *read[0] != [0xBBu8; BLOCK] {
+                        return Err("unexpected content");
+                    }
+                    Ok(())
+                },
+            )
+            .await;
+            assert!(report.is_consistent(), "{:?}", report.failures);
+            assert_eq!(report.cut_points, 4);
+        });
+    }
+
+    #[test]
+    fn test_4096_byte_blocks() {
+        type Device4k = ChecksummedBlockDevice<MockBlockDevice<4096>, 4096>;
+
+        block_on(async {
+            // 512 entries per block: 597 data blocks + header + 2 checksum blocks
+            let mut device = Device4k::initialize(MockBlockDevice::new(600 * 4096))
+                .await
+                .unwrap();
+            assert_eq!(device.data_blocks(), 597);
+
+            // Blocks 511 and 512 have their entries in different checksum blocks
+            let blocks = [Aligned([0x11u8; 4096]), Aligned([0x22u8; 4096])];
+            device.write(511, &blocks).await.unwrap();
+            let device = Device4k::open(device.into_inner()).await.unwrap();
+            let mut read = [Aligned([0u8; 4096]), Aligned([0u8; 4096])];
+            device.read(511, &mut read).await.unwrap();
+            assert_eq!((*read[0], *read[1]), (*blocks[0], *blocks[1]));
+
+            let mut inner = device.into_inner();
+            let mut block = inner.block(512);
+            block[4000] ^= 0x01;
+            inner.set_block(512, block);
+            let device = Device4k::open(inner).await.unwrap();
+            assert_eq!(
+                device.read(511, &mut read).await,
+                Err(ChecksumError::Corrupted { block: 512 })
+            );
+        });
+    }
+
+    #[test]
+    fn test_out_of_range_access() {
+        block_on(async {
+            let device = Device::initialize(mock()).await.unwrap();
+            let mut read = [Aligned([0u8; BLOCK])];
+            assert_eq!(
+                device.read(294, &mut read).await,
+                Err(ChecksumError::OutOfRange)
+            );
+        });
+    }
+}
@@ -0,0 +1,313 @@
+//! Transparent at-rest encryption for block devices (AES-256-XTS).
+//!
+//! [`EncryptedBlockDevice`] wraps any [`BlockDevice`] and encrypts every block on
+//! write and decrypts it on read. Each block is one XTS data unit and its block
+//! address is the tweak, so identical plaintext stored in different sectors yields
+//! different ciphertext and blocks can be read and written independently.
+//!
+//! The wrapper is itself a `BlockDevice`, so it composes under
+//! `BlockDeviceAdapter`, `StackPageStream` or `HeapPageStream` and the filesystem
+//! never sees plaintext keys or knows that the volume is encrypted.
+//!
+//! # Architecture
+//!
+//! ```text
+//! FileSystem ─► StackPageStream ─► EncryptedBlockDevice ─► SD card / flash
+//!               (plaintext pages)   (AES-XTS per block)    (ciphertext only)
+//! ```
+//!
+//! # Keys
+//!
+//! The 512-bit XTS key (two AES-256 keys) is fetched once from a [`KeyProvider`] when
+//! the device is opened, e.g. from a secure element, a key derived from a passphrase
+//! or a key stored in OTP memory. Key material is wiped from memory when it is dropped.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fatrs_adapters::{EncryptedBlockDevice, StackPageStream, XtsKey};
+//!
+//! let mut key = XtsKey::new(secure_element.read_key()?).expect("key halves must differ");
+//! let device = EncryptedBlockDevice::new(sd_card, &mut key).await?;
+//! let stream = StackPageStream::new(device);
+//! let fs = FileSystem::new(stream, FsOptions::new()).await?;
+//! ```
+//!
+//! # Limitations
+//!
+//! XTS provides confidentiality only: it does not detect tampering or replay of
+//! old sectors. Writes go to the inner device one block at a time because each
+//! block is encrypted into a single stack buffer.
+
+use aes::Aes256;
+use aes::cipher::KeyInit;
+use aligned::Aligned;
+use fatrs_block_device::BlockDevice;
+use xts_mode::{Xts128, get_tweak_default};
+use zeroize::Zeroize;
+
+/// Size of an AES-256-XTS key in bytes (data key followed by tweak key).
+pub const XTS_KEY_SIZE: usize = 64;
+
+/// AES-256-XTS key material, wiped from memory on drop.
+pub struct XtsKey([u8; XTS_KEY_SIZE]);
+
+impl XtsKey {
+    /// Create a key from 64 bytes: the data key followed by the tweak key.
+    ///
+    /// Returns `None` if both halves are equal, which IEEE 1619 forbids.
+    pub fn new(mut bytes: [u8; XTS_KEY_SIZE]) -> Option<Self> {
+        let key = Self(bytes);
+        bytes.zeroize();
+        if key.0[..32] == key.0[32..] {
+            return None;
+        }
+        Some(key)
+    }
+
+    fn data_key(&self) -> &[u8] {
+        &self.0[..32]
+    }
+
+    fn tweak_key(&self) -> &[u8] {
+        &self.0[32..]
+    }
+}
+
+impl Clone for XtsKey {
+    fn clone(&self) -> Self {
+        Self(self.0)
+    }
+}
+
+impl Drop for XtsKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl core::fmt::Debug for XtsKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("XtsKey(..)")
+    }
+}
+
+/// Source of the volume key for an [`EncryptedBlockDevice`].
+///
+/// Implement this to fetch the key from wherever it is kept (secure element,
+/// key derivation from user input, remote key server).
+pub trait KeyProvider {
+    /// Error returned when the key cannot be obtained.
+    type Error: core::fmt::Debug;
+
+    /// Return the XTS key of the volume.
+    async fn volume_key(&mut self) -> Result<XtsKey, Self::Error>;
+}
+
+/// A key held in memory is its own provider.
+impl KeyProvider for XtsKey {
+    type Error = core::convert::Infallible;
+
+    async fn volume_key(&mut self) -> Result<XtsKey, Self::Error> {
+        Ok(self.clone())
+    }
+}
+
+/// Block device wrapper encrypting every block with AES-256-XTS.
+///
+/// Block `n` of the wrapper is stored encrypted in block `n` of the inner device,
+/// with `n` as the XTS tweak. The block size must be a multiple of 16 bytes.
+pub struct EncryptedBlockDevice<D> {
+    inner: D,
+    xts: Xts128<Aes256>,
+}
+
+impl<D> EncryptedBlockDevice<D> {
+    /// Open an encrypted device, fetching the key from `provider`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the provider's error if the key cannot be obtained.
+    pub async fn new<P: KeyProvider>(inner: D, provider: &mut P) -> Result<Self, P::Error> {
+        let key = provider.volume_key().await?;
+        Ok(Self::with_key(inner, &key))
+    }
+
+    /// Open an encrypted device with a key that is already available.
+    pub fn with_key(inner: D, key: &XtsKey) -> Self {
+        // Key sizes are fixed, so construction cannot fail
+        let data_cipher = Aes256::new_from_slice(key.data_key()).unwrap();
+        let tweak_cipher = Aes256::new_from_slice(key.tweak_key()).unwrap();
+        Self {
+            inner,
+            xts: Xts128::new(data_cipher, tweak_cipher),
+        }
+    }
+
+    /// Get a reference to the inner device.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Consume the wrapper and return the inner device.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    #[inline]
+    fn tweak(block_address: u32) -> [u8; 16] {
+        get_tweak_default(u128::from(block_address))
+    }
+}
+
+impl<D, const SIZE: usize> BlockDevice<SIZE> for EncryptedBlockDevice<D>
+where
+    D: BlockDevice<SIZE>,
+{
+    type Error = D::Error;
+    type Align = D::Align;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        const {
+            assert!(
+                SIZE >= 16 && SIZE % 16 == 0,
+                "XTS needs blocks of 16-byte multiples"
+            )
+        };
+
+        self.inner.read(block_address, data).await?;
+        for (i, block) in data.iter_mut().enumerate() {
+            let tweak = Self::tweak(block_address + i as u32);
+            self.xts.decrypt_sector(&mut block[..], tweak);
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        const {
+            assert!(
+                SIZE >= 16 && SIZE % 16 == 0,
+                "XTS needs blocks of 16-byte multiples"
+            )
+        };
+
+        let mut scratch: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        let mut result = Ok(());
+        for (i, block) in data.iter().enumerate() {
+            let address = block_address + i as u32;
+            scratch.copy_from_slice(&block[..]);
+            self.xts
+                .encrypt_sector(&mut scratch[..], Self::tweak(address));
+            result = self
+                .inner
+                .write(address, core::slice::from_ref(&scratch))
+                .await;
+            if result.is_err() {
+                break;
+            }
+        }
+        // Do not leave key-dependent data on the stack
+        scratch.zeroize();
+        result
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        self.inner.size().await
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.inner.sync().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::{MockBlockDevice, block_on};
+
+    const BLOCK: usize = 512;
+
+    fn mock() -> MockBlockDevice<BLOCK> {
+        MockBlockDevice::new(8 * BLOCK as u64)
+    }
+
+    fn test_key() -> XtsKey {
+        let mut bytes = [0u8; XTS_KEY_SIZE];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        XtsKey::new(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip_and_ciphertext_on_disk() {
+        block_on(async {
+            let mut key = test_key();
+            let mut device = EncryptedBlockDevice::new(mock(), &mut key).await.unwrap();
+
+            let plain = [Aligned([0x42u8; BLOCK]), Aligned([0x42u8; BLOCK])];
+            device.write(2, &plain).await.unwrap();
+
+            // The medium holds ciphertext, different for each sector
+            let raw = device.inner();
+            assert_ne!(raw.block(2), [0x42u8; BLOCK]);
+            assert_ne!(raw.block(2), raw.block(3));
+
+            let mut read = [Aligned([0u8; BLOCK]), Aligned([0u8; BLOCK])];
+            device.read(2, &mut read).await.unwrap();
+            assert_eq!(*read[0], [0x42u8; BLOCK]);
+            assert_eq!(*read[1], [0x42u8; BLOCK]);
+        });
+    }
+
+    #[test]
+    fn test_wrong_key_does_not_decrypt() {
+        block_on(async {
+            let mut device = EncryptedBlockDevice::with_key(mock(), &test_key());
+            device.write(0, &[Aligned([7u8; BLOCK])]).await.unwrap();
+
+            let mut other = [0x99u8; XTS_KEY_SIZE];
+            other[0] = 0;
+            let device =
+                EncryptedBlockDevice::with_key(device.into_inner(), &XtsKey::new(other).unwrap());
+            let mut read = [Aligned([0u8; BLOCK])];
+            device.read(0, &mut read).await.unwrap();
+            assert_ne!(*read[0], [7u8; BLOCK]);
+        });
+    }
+
+    #[test]
+    fn test_4096_byte_blocks() {
+        block_on(async {
+            let inner = MockBlockDevice::<4096>::new(4 * 4096);
+            let mut device = EncryptedBlockDevice::with_key(inner, &test_key());
+
+            let plain = [Aligned([0x42u8; 4096]), Aligned([0x42u8; 4096])];
+            device.write(1, &plain).await.unwrap();
+
+            // Each 4096-byte block is one data unit tweaked by its address
+            let raw = device.inner();
+            assert_ne!(raw.block(1), [0x42u8; 4096]);
+            assert_ne!(raw.block(1)[..512], raw.block(1)[512..1024]);
+            assert_ne!(raw.block(1), raw.block(2));
+
+            let mut read = [Aligned([0u8; 4096]), Aligned([0u8; 4096])];
+            device.read(1, &mut read).await.unwrap();
+            assert_eq!(*read[0], [0x42u8; 4096]);
+            assert_eq!(*read[1], [0x42u8; 4096]);
+        });
+    }
+
+    #[test]
+    fn test_equal_key_halves_are_rejected() {
+        assert!(XtsKey::new([0x11; XTS_KEY_SIZE]).is_none());
+    }
+}
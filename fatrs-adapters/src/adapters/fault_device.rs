@@ -0,0 +1,666 @@
+//! Fault injection for power-loss and error testing.
+//!
+//! [`FaultyBlockDevice`] wraps any [`BlockDevice`] and injects the faults
+//! described by a [`FaultPlan`]: failing the Nth write, cutting power after a
+//! number of sectors reached the medium (optionally tearing the sector being
+//! written), flipping bits on read and returning transient errors. With the
+//! write cache enabled, writes are held in a volatile cache until the next
+//! sync, so a power cut drops everything that was not synced.
+//!
+//! [`explore_cut_points`] runs an operation once to count the sectors it
+//! writes, then replays it from the same starting image with a power cut after
+//! every possible sector and hands each resulting image to a consistency
+//! check, typically remounting the volume and walking it.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fatrs_adapters::{FaultPlan, explore_cut_points};
+//!
+//! let report = explore_cut_points(
+//!     &image,
+//!     &FaultPlan::new().with_write_cache(true),
+//!     async |device| {
+//!         let mut disk = MyStream::new(device);
+//!         if let Ok(fs) = FileSystem::new(&mut disk, FsOptions::new()).await {
+//!             let _ = fs.root_dir().create_file("log.txt").await;
+//!         }
+//!         disk.into_inner()
+//!     },
+//!     async |image| check_volume(image).await,
+//! )
+//! .await;
+//! assert!(report.is_consistent(), "{:?}", report.failures);
+//! ```
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aligned::Aligned;
+use fatrs_block_device::BlockDevice;
+
+/// Errors of a [`FaultyBlockDevice`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaultError<E> {
+    /// Error from the inner device.
+    Device(E),
+    /// An error injected by the fault plan; the device stays usable.
+    Injected,
+    /// The simulated power was cut; every operation fails until [`FaultyBlockDevice::power_on`].
+    PowerLost,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for FaultError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Device(e) => write!(f, "Device error: {}", e),
+            Self::Injected => write!(f, "Injected I/O error"),
+            Self::PowerLost => write!(f, "Power lost"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug + core::fmt::Display> core::error::Error for FaultError<E> {}
+
+/// Faults to inject, built with the `with_*` methods.
+///
+/// Writes are numbered from 0 in call order. Operations (reads, writes and
+/// syncs together) are numbered the same way for transient errors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaultPlan {
+    failed_writes: Vec<u64>,
+    power_cut: Option<u64>,
+    torn_bytes: usize,
+    bit_flips: Vec<(u32, u32)>,
+    transient: Option<(u64, u64)>,
+    write_cache: bool,
+}
+
+impl FaultPlan {
+    /// A plan without faults and without write cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the `nth` write call without writing anything.
+    pub fn with_failed_write(mut self, nth: u64) -> Self {
+        self.failed_writes.push(nth);
+        self
+    }
+
+    /// Cut power once `sectors` sectors have reached the medium.
+    ///
+    /// The write that would persist the next sector fails with
+    /// [`FaultError::PowerLost`], as does every operation after it.
+    pub fn with_power_cut(mut self, sectors: u64) -> Self {
+        self.power_cut = Some(sectors);
+        self
+    }
+
+    /// On a power cut, persist the first `bytes` bytes of the interrupted sector.
+    ///
+    /// The rest of the sector keeps its old content, like a torn write on
+    /// media without atomic sector writes.
+    pub fn with_torn_writes(mut self, bytes: usize) -> Self {
+        self.torn_bytes = bytes;
+        self
+    }
+
+    /// Invert bit `bit` (counted from the start of the block) whenever `block` is read.
+    pub fn with_bit_flip(mut self, block: u32, bit: u32) -> Self {
+        self.bit_flips.push((block, bit));
+        self
+    }
+
+    /// Fail `count` operations starting with operation `first`.
+    pub fn with_transient_errors(mut self, first: u64, count: u64) -> Self {
+        self.transient = Some((first, count));
+        self
+    }
+
+    /// Hold writes in a volatile cache until the next sync.
+    ///
+    /// A sync writes the cached blocks in ascending order, so a power cut in
+    /// the middle of a sync persists a prefix of them.
+    pub fn with_write_cache(mut self, enabled: bool) -> Self {
+        self.write_cache = enabled;
+        self
+    }
+
+    /// The configured power cut, in sectors.
+    pub fn power_cut(&self) -> Option<u64> {
+        self.power_cut
+    }
+
+    /// Whether writes are cached until sync.
+    pub fn write_cache(&self) -> bool {
+        self.write_cache
+    }
+}
+
+/// Counters of a [`FaultyBlockDevice`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    /// Read calls.
+    pub reads: u64,
+    /// Write calls.
+    pub writes: u64,
+    /// Sync calls.
+    pub syncs: u64,
+    /// Sectors that reached the inner device.
+    pub sectors_written: u64,
+    /// Errors injected by the plan, power loss excluded.
+    pub injected_errors: u64,
+}
+
+/// Block device wrapper injecting the faults of a [`FaultPlan`].
+pub struct FaultyBlockDevice<D: BlockDevice<SIZE>, const SIZE: usize> {
+    inner: D,
+    plan: FaultPlan,
+    cache: BTreeMap<u32, Aligned<D::Align, [u8; SIZE]>>,
+    powered: bool,
+    operations: AtomicUsize,
+    reads: AtomicUsize,
+    injected: AtomicUsize,
+    writes: u64,
+    syncs: u64,
+    sectors_written: u64,
+}
+
+impl<D: BlockDevice<SIZE>, const SIZE: usize> FaultyBlockDevice<D, SIZE> {
+    /// Wrap `inner`, injecting the faults of `plan`.
+    pub fn new(inner: D, plan: FaultPlan) -> Self {
+        Self {
+            inner,
+            plan,
+            cache: BTreeMap::new(),
+            powered: true,
+            operations: AtomicUsize::new(0),
+            reads: AtomicUsize::new(0),
+            injected: AtomicUsize::new(0),
+            writes: 0,
+            syncs: 0,
+            sectors_written: 0,
+        }
+    }
+
+    /// Get a reference to the inner device (the persistent medium).
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Return the inner device, dropping unsynced cached writes as a power loss would.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// The active fault plan.
+    pub fn plan(&self) -> &FaultPlan {
+        &self.plan
+    }
+
+    /// Replace the fault plan; counters keep running.
+    pub fn set_plan(&mut self, plan: FaultPlan) {
+        self.plan = plan;
+    }
+
+    /// Get the operation counters.
+    pub fn stats(&self) -> FaultStats {
+        FaultStats {
+            reads: self.reads.load(Ordering::Relaxed) as u64,
+            writes: self.writes,
+            syncs: self.syncs,
+            sectors_written: self.sectors_written,
+            injected_errors: self.injected.load(Ordering::Relaxed) as u64,
+        }
+    }
+
+    /// Whether the simulated power is on.
+    pub fn is_powered(&self) -> bool {
+        self.powered
+    }
+
+    /// Number of blocks written since the last sync that a power cut would lose.
+    pub fn unsynced_blocks(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Cut power now, dropping unsynced cached writes.
+    pub fn cut_power(&mut self) {
+        self.powered = false;
+        self.cache.clear();
+    }
+
+    /// Restore power after a cut and disarm the power cut of the plan.
+    pub fn power_on(&mut self) {
+        self.powered = true;
+        self.plan.power_cut = None;
+    }
+
+    /// Count an operation and check for power loss and transient errors.
+    fn begin(&self) -> Result<(), FaultError<D::Error>> {
+        if !self.powered {
+            return Err(FaultError::PowerLost);
+        }
+        let op = self.operations.fetch_add(1, Ordering::Relaxed) as u64;
+        if let Some((first, count)) = self.plan.transient {
+            if op >= first && op - first < count {
+                return Err(self.injected());
+            }
+        }
+        Ok(())
+    }
+
+    fn injected(&self) -> FaultError<D::Error> {
+        self.injected.fetch_add(1, Ordering::Relaxed);
+        FaultError::Injected
+    }
+
+    /// Write blocks to the medium, cutting power when the plan says so.
+    async fn persist(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<D::Align, [u8; SIZE]>],
+    ) -> Result<(), FaultError<D::Error>> {
+        let room = self
+            .plan
+            .power_cut
+            .map_or(u64::MAX, |cut| cut.saturating_sub(self.sectors_written));
+        let n = (data.len() as u64).min(room) as usize;
+        if n > 0 {
+            self.inner
+                .write(block_address, &data[..n])
+                .await
+                .map_err(FaultError::Device)?;
+            self.sectors_written += n as u64;
+        }
+        if n == data.len() {
+            return Ok(());
+        }
+
+        let torn = self.plan.torn_bytes.min(SIZE);
+        if torn > 0 {
+            let address = block_address + n as u32;
+            let mut block = [Aligned([0; SIZE])];
+            self.inner
+                .read(address, &mut block)
+                .await
+                .map_err(FaultError::Device)?;
+            block[0][..torn].copy_from_slice(&data[n][..torn]);
+            self.inner
+                .write(address, &block)
+                .await
+                .map_err(FaultError::Device)?;
+        }
+        self.cut_power();
+        Err(FaultError::PowerLost)
+    }
+}
+
+impl<D: BlockDevice<SIZE>, const SIZE: usize> BlockDevice<SIZE> for FaultyBlockDevice<D, SIZE> {
+    type Error = FaultError<D::Error>;
+    type Align = D::Align;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        self.begin()?;
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .read(block_address, data)
+            .await
+            .map_err(FaultError::Device)?;
+
+        for (i, block) in data.iter_mut().enumerate() {
+            let address = block_address + i as u32;
+            if let Some(cached) = self.cache.get(&address) {
+                block.copy_from_slice(&cached[..]);
+            }
+            for &(flip_block, bit) in &self.plan.bit_flips {
+                if flip_block == address {
+                    block[(bit / 8) as usize % SIZE] ^= 1 << (bit % 8);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        self.begin()?;
+        let nth = self.writes;
+        self.writes += 1;
+        if self.plan.failed_writes.contains(&nth) {
+            return Err(self.injected());
+        }
+
+        if self.plan.write_cache {
+            for (i, block) in data.iter().enumerate() {
+                self.cache.insert(block_address + i as u32, *block);
+            }
+            Ok(())
+        } else {
+            self.persist(block_address, data).await
+        }
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        self.inner.size().await.map_err(FaultError::Device)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.begin()?;
+        self.syncs += 1;
+
+        let cache = core::mem::take(&mut self.cache);
+        for (&address, block) in &cache {
+            if let Err(e) = self.persist(address, core::slice::from_ref(block)).await {
+                if self.powered {
+                    // Device error: keep what has not been written yet
+                    self.cache = cache.range(address..).map(|(&a, b)| (a, *b)).collect();
+                }
+                return Err(e);
+            }
+        }
+        self.inner.sync().await.map_err(FaultError::Device)
+    }
+}
+
+/// A cut point whose resulting image failed the consistency check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutPointFailure<E> {
+    /// Sectors persisted before the power cut.
+    pub after_sectors: u64,
+    /// Error returned by the check.
+    pub error: E,
+}
+
+/// Outcome of [`explore_cut_points`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutPointReport<E> {
+    /// Images checked: one per sector written by the operation, plus the uninterrupted run.
+    pub cut_points: u64,
+    /// Cut points whose image failed the check.
+    pub failures: Vec<CutPointFailure<E>>,
+}
+
+impl<E> CutPointReport<E> {
+    /// Whether every image passed the check.
+    pub fn is_consistent(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Check the image left by a power cut at every point of an operation.
+///
+/// `operation` receives a [`FaultyBlockDevice`] over a fresh clone of `image`
+/// and must hand it back when done; it should ignore the errors caused by the
+/// power cut. It runs once with `plan` unchanged to count the sectors it
+/// writes, then once per cut point with a power cut after 0, 1, ... sectors.
+/// After each run, `check` receives the medium as it would be found after a
+/// reboot: unsynced cached writes are gone. `D::clone` must copy the content,
+/// not share it, and the operation must write the same sectors on every run.
+pub async fn explore_cut_points<D, Op, Check, E, const SIZE: usize>(
+    image: &D,
+    plan: &FaultPlan,
+    mut operation: Op,
+    mut check: Check,
+) -> CutPointReport<E>
+where
+    D: BlockDevice<SIZE> + Clone,
+    Op: AsyncFnMut(FaultyBlockDevice<D, SIZE>) -> FaultyBlockDevice<D, SIZE>,
+    Check: AsyncFnMut(D) -> Result<(), E>,
+{
+    let mut report = CutPointReport {
+        cut_points: 0,
+        failures: Vec::new(),
+    };
+
+    let mut plan = plan.clone();
+    plan.power_cut = None;
+    let device = operation(FaultyBlockDevice::new(image.clone(), plan.clone())).await;
+    let total = device.stats().sectors_written;
+
+    for after_sectors in 0..=total {
+        let medium = if after_sectors == total {
+            device.inner.clone()
+        } else {
+            let cut_plan = plan.clone().with_power_cut(after_sectors);
+            operation(FaultyBlockDevice::new(image.clone(), cut_plan))
+                .await
+                .into_inner()
+        };
+        report.cut_points += 1;
+        if let Err(error) = check(medium).await {
+            report.failures.push(CutPointFailure {
+                after_sectors,
+                error,
+            });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::{MockBlockDevice, MockError, block_on};
+
+    const BLOCK: usize = 512;
+
+    fn mock() -> MockBlockDevice<BLOCK> {
+        MockBlockDevice::new(8 * BLOCK as u64)
+    }
+
+    fn filled(values: &[u8]) -> Vec<Aligned<aligned::A4, [u8; BLOCK]>> {
+        values.iter().map(|&v| Aligned([v; BLOCK])).collect()
+    }
+
+    #[test]
+    fn failed_write_leaves_medium_untouched() {
+        block_on(async {
+            let plan = FaultPlan::new().with_failed_write(1);
+            let mut device = FaultyBlockDevice::new(mock(), plan);
+
+            device.write(0, &filled(&[1])).await.unwrap();
+            assert_eq!(
+                device.write(1, &filled(&[2])).await,
+                Err(FaultError::Injected)
+            );
+            device.write(2, &filled(&[3])).await.unwrap();
+
+            let stats = device.stats();
+            assert_eq!(
+                (stats.writes, stats.sectors_written, stats.injected_errors),
+                (3, 2, 1)
+            );
+            let medium = device.into_inner();
+            assert_eq!(medium.block(1), [0; BLOCK]);
+            assert_eq!(medium.block(2), [3; BLOCK]);
+        });
+    }
+
+    #[test]
+    fn power_cut_persists_exact_prefix() {
+        block_on(async {
+            let plan = FaultPlan::new().with_power_cut(3);
+            let mut device = FaultyBlockDevice::new(mock(), plan);
+
+            device.write(0, &filled(&[1, 2])).await.unwrap();
+            assert_eq!(
+                device.write(2, &filled(&[3, 4])).await,
+                Err(FaultError::PowerLost)
+            );
+            assert!(!device.is_powered());
+            let mut buf = filled(&[0]);
+            assert_eq!(device.read(0, &mut buf).await, Err(FaultError::PowerLost));
+
+            device.power_on();
+            device.read(2, &mut buf).await.unwrap();
+            assert_eq!(buf[0][..], [3; BLOCK]);
+            let medium = device.into_inner();
+            assert_eq!(medium.block(3), [0; BLOCK]);
+        });
+    }
+
+    #[test]
+    fn write_cache_drops_unsynced_writes() {
+        block_on(async {
+            let plan = FaultPlan::new().with_write_cache(true);
+            let mut device = FaultyBlockDevice::new(mock(), plan);
+
+            device.write(0, &filled(&[1])).await.unwrap();
+            device.sync().await.unwrap();
+            device.write(1, &filled(&[2])).await.unwrap();
+            device.write(0, &filled(&[5])).await.unwrap();
+
+            let mut buf = filled(&[0, 0]);
+            device.read(0, &mut buf).await.unwrap();
+            assert_eq!((buf[0][0], buf[1][0]), (5, 2));
+            assert_eq!(device.unsynced_blocks(), 2);
+
+            device.cut_power();
+            let medium = device.into_inner();
+            assert_eq!(medium.block(0), [1; BLOCK]);
+            assert_eq!(medium.block(1), [0; BLOCK]);
+            assert_eq!(medium.syncs, 1);
+        });
+    }
+
+    #[test]
+    fn torn_write_mixes_old_and_new_content() {
+        block_on(async {
+            let mut medium = mock();
+            medium.set_block(1, [9; BLOCK]);
+            let plan = FaultPlan::new().with_power_cut(1).with_torn_writes(100);
+            let mut device = FaultyBlockDevice::new(medium, plan);
+
+            assert_eq!(
+                device.write(0, &filled(&[1, 2])).await,
+                Err(FaultError::PowerLost)
+            );
+            let medium = device.into_inner();
+            assert_eq!(medium.block(0), [1; BLOCK]);
+            assert!(medium.block(1)[..100].iter().all(|&b| b == 2));
+            assert!(medium.block(1)[100..].iter().all(|&b| b == 9));
+        });
+    }
+
+    #[test]
+    fn bit_flips_and_transient_errors() {
+        block_on(async {
+            let plan = FaultPlan::new()
+                .with_bit_flip(1, 9)
+                .with_transient_errors(1, 2);
+            let mut device = FaultyBlockDevice::new(mock(), plan);
+            let mut buf = filled(&[0, 0]);
+
+            device.read(0, &mut buf).await.unwrap();
+            assert_eq!(buf[1][1], 0b10);
+            assert_eq!(buf[0][..], [0; BLOCK]);
+
+            assert_eq!(device.read(0, &mut buf).await, Err(FaultError::Injected));
+            assert_eq!(device.sync().await, Err(FaultError::Injected));
+            device.sync().await.unwrap();
+            assert_eq!(device.stats().injected_errors, 2);
+
+            // The flip is applied on read only
+            assert_eq!(device.inner().block(1), [0; BLOCK]);
+        });
+    }
+
+    #[test]
+    fn blocks_of_4096_bytes() {
+        block_on(async {
+            let mut medium = MockBlockDevice::<4096>::new(4 * 4096);
+            medium.set_block(2, [9; 4096]);
+            let plan = FaultPlan::new().with_power_cut(2).with_torn_writes(3000);
+            let mut device = FaultyBlockDevice::new(medium, plan);
+
+            let data = [Aligned([1; 4096]), Aligned([2; 4096])];
+            device.write(0, &data).await.unwrap();
+            let mut buf = [Aligned([0; 4096]), Aligned([0; 4096])];
+            device.read(0, &mut buf).await.unwrap();
+            assert_eq!((buf[0][4095], buf[1][4095]), (1, 2));
+
+            // The tear falls inside the sector, past the first 512 bytes
+            assert_eq!(
+                device.write(2, &[Aligned([3; 4096])]).await,
+                Err(FaultError::PowerLost)
+            );
+            assert_eq!(device.stats().sectors_written, 2);
+            let medium = device.into_inner();
+            assert!(medium.block(2)[..3000].iter().all(|&b| b == 3));
+            assert!(medium.block(2)[3000..].iter().all(|&b| b == 9));
+        });
+    }
+
+    /// Write three data blocks, then a commit record in block 0
+    async fn commit(
+        mut device: FaultyBlockDevice<MockBlockDevice<BLOCK>, BLOCK>,
+        commit_first: bool,
+    ) -> FaultyBlockDevice<MockBlockDevice<BLOCK>, BLOCK> {
+        let data = filled(&[7, 7, 7]);
+        let record = filled(&[1]);
+        let _ = async {
+            if commit_first {
+                device.write(0, &record).await?;
+                device.sync().await?;
+            }
+            device.write(1, &data).await?;
+            device.sync().await?;
+            if !commit_first {
+                device.write(0, &record).await?;
+                device.sync().await?;
+            }
+            Ok::<_, FaultError<MockError>>(())
+        }
+        .await;
+        device
+    }
+
+    async fn check_commit(medium: MockBlockDevice<BLOCK>) -> Result<(), &'static str> {
+        let committed = medium.block(0)[0] == 1;
+        if committed && (1..4).any(|i| medium.block(i) != [7; BLOCK]) {
+            return Err("commit record without data");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn explore_cut_points_checks_every_cut() {
+        block_on(async {
+            let image = mock();
+            for plan in [FaultPlan::new(), FaultPlan::new().with_write_cache(true)] {
+                let report = explore_cut_points(
+                    &image,
+                    &plan,
+                    async |device| commit(device, false).await,
+                    async |medium| check_commit(medium).await,
+                )
+                .await;
+                assert_eq!(report.cut_points, 5);
+                assert!(report.is_consistent());
+            }
+
+            let report = explore_cut_points(
+                &image,
+                &FaultPlan::new(),
+                async |device| commit(device, true).await,
+                async |medium| check_commit(medium).await,
+            )
+            .await;
+            assert_eq!(report.cut_points, 5);
+            let cuts: Vec<u64> = report.failures.iter().map(|f| f.after_sectors).collect();
+            assert_eq!(cuts, [1, 2, 3]);
+        });
+    }
+}
@@ -0,0 +1,232 @@
+//! Heap-allocated multi-page cache adapter (runtime sizing).
+
+#[cfg(feature = "alloc")]
+use crate::{
+    adapters::{BlockDeviceAdapter, error::HeapAdapterError},
+    domain::{CachePolicy, CacheStats, HeapCacheMemory, PageCache, PageConfig, PageNumber},
+};
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use core::ops::Range;
+#[cfg(feature = "alloc")]
+use fatrs_block_device::BlockDevice;
+
+/// Heap-allocated write-back cache with page size and capacity chosen at runtime.
+///
+/// The multi-page counterpart of `HeapBuffer`. Requires the `alloc` feature.
+///
+/// # Type Parameters
+///
+/// - `D`: The block device type
+/// - `BLOCK_SIZE`: The block size in bytes (must match the device's block size)
+///
+/// # Examples
+///
+/// ```ignore
+/// use fatrs_adapters::adapters::{HeapCache, presets};
+///
+/// // Sixteen 4KB pages
+/// let device = MyBlockDevice::new();
+/// let mut cache = HeapCache::new(device, presets::PAGE_4K, 16)?;
+///
+/// cache.page_mut(3).await?[0] = 42;
+/// cache.flush().await?;
+/// println!("hit ratio: {}", cache.stats().hit_ratio());
+/// ```
+#[cfg(feature = "alloc")]
+pub struct HeapCache<D, const BLOCK_SIZE: usize>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    inner: PageCache<BlockDeviceAdapter<D, BLOCK_SIZE>, HeapCacheMemory, BLOCK_SIZE>,
+}
+
+#[cfg(feature = "alloc")]
+impl<D, const BLOCK_SIZE: usize> HeapCache<D, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    /// Create a cache of `pages` pages of `page_size` bytes with the default policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `page_size` is not a multiple of `BLOCK_SIZE` or `pages` is zero.
+    pub fn new(
+        device: D,
+        page_size: usize,
+        pages: usize,
+    ) -> Result<Self, HeapAdapterError<D::Error>> {
+        Self::with_policy(device, page_size, pages, CachePolicy::new())
+    }
+
+    /// Create a cache of `pages` pages of `page_size` bytes with the given policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `page_size` is not a multiple of `BLOCK_SIZE` or `pages` is zero.
+    pub fn with_policy(
+        device: D,
+        page_size: usize,
+        pages: usize,
+        policy: CachePolicy,
+    ) -> Result<Self, HeapAdapterError<D::Error>> {
+        use alloc::{format, string::String};
+
+        if pages == 0 {
+            return Err(HeapAdapterError::Domain(String::from(
+                "Page cache needs at least one page",
+            )));
+        }
+
+        let adapter = BlockDeviceAdapter::new(device);
+        let config = PageConfig::from_page_size(page_size)
+            .map_err(|e| HeapAdapterError::Domain(format!("{}", e)))?;
+        let memory = HeapCacheMemory::new(page_size, pages);
+        let inner = PageCache::new(adapter, config, memory, policy)
+            .map_err(|e| HeapAdapterError::Domain(format!("{}", e)))?;
+
+        Ok(Self { inner })
+    }
+
+    /// Get a page for reading, loading it on a miss.
+    pub async fn page(&mut self, page_num: u32) -> Result<&[u8], HeapAdapterError<D::Error>> {
+        self.inner
+            .page(PageNumber::new(page_num))
+            .await
+            .map_err(HeapAdapterError::from_domain)
+    }
+
+    /// Get a page for modification, loading it on a miss (marks page as dirty).
+    pub async fn page_mut(
+        &mut self,
+        page_num: u32,
+    ) -> Result<&mut [u8], HeapAdapterError<D::Error>> {
+        self.inner
+            .page_mut(PageNumber::new(page_num))
+            .await
+            .map_err(HeapAdapterError::from_domain)
+    }
+
+    /// Get a page that is about to be overwritten completely, without reading it on a miss.
+    pub async fn overwrite(
+        &mut self,
+        page_num: u32,
+    ) -> Result<&mut [u8], HeapAdapterError<D::Error>> {
+        self.inner
+            .overwrite(PageNumber::new(page_num))
+            .await
+            .map_err(HeapAdapterError::from_domain)
+    }
+
+    /// Write back every dirty page and flush the device.
+    pub async fn flush(&mut self) -> Result<(), HeapAdapterError<D::Error>> {
+        self.inner
+            .flush()
+            .await
+            .map_err(HeapAdapterError::from_domain)
+    }
+
+    /// Drop every cached page (discards uncommitted changes).
+    pub fn discard(&mut self) {
+        self.inner.discard();
+    }
+
+    /// Check if a page is resident.
+    pub fn contains(&self, page_num: u32) -> bool {
+        self.inner.contains(PageNumber::new(page_num))
+    }
+
+    /// Get the number of dirty pages.
+    pub fn dirty_pages(&self) -> usize {
+        self.inner.dirty_pages()
+    }
+
+    /// Get the capacity in pages.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Get the configured page size.
+    pub fn page_size(&self) -> usize {
+        self.inner.config().page_size()
+    }
+
+    /// Get the hit/miss and write-back counters.
+    pub const fn stats(&self) -> CacheStats {
+        self.inner.stats()
+    }
+
+    /// Reset the counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.inner.reset_stats();
+    }
+
+    /// Get the eviction and write-back policy.
+    pub const fn policy(&self) -> &CachePolicy {
+        self.inner.policy()
+    }
+
+    /// Set or clear the byte range occupied by the FATs.
+    pub fn set_fat_region(&mut self, region: Option<Range<u64>>) {
+        self.inner.set_fat_region(region);
+    }
+
+    /// Locate the FATs from the boot sector at the start of the device.
+    pub async fn detect_fat_region(
+        &mut self,
+    ) -> Result<Option<Range<u64>>, HeapAdapterError<D::Error>> {
+        self.inner
+            .detect_fat_region()
+            .await
+            .map_err(HeapAdapterError::from_domain)
+    }
+
+    /// Get storage size in bytes.
+    pub async fn size(&mut self) -> Result<u64, HeapAdapterError<D::Error>> {
+        self.inner
+            .storage_size()
+            .await
+            .map_err(HeapAdapterError::from_domain)
+    }
+
+    /// Get storage size in pages.
+    pub async fn size_in_pages(&mut self) -> Result<u64, HeapAdapterError<D::Error>> {
+        self.inner
+            .storage_size_in_pages()
+            .await
+            .map_err(HeapAdapterError::from_domain)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::MockBlockDevice;
+
+    #[tokio::test]
+    async fn test_heap_cache_invalid_config() {
+        assert!(HeapCache::new(MockBlockDevice::<512>::new(1024 * 1024), 4000, 4).is_err());
+        assert!(HeapCache::new(MockBlockDevice::<512>::new(1024 * 1024), 4096, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_heap_cache_round_trip() {
+        let device = MockBlockDevice::<512>::new(1024 * 1024);
+        let mut cache = HeapCache::new(device, 4096, 2).unwrap();
+
+        cache.page_mut(0).await.unwrap()[0] = 1;
+        cache.page_mut(1).await.unwrap()[0] = 2;
+        cache.page_mut(2).await.unwrap()[0] = 3;
+        assert!(!cache.contains(0));
+        assert_eq!(cache.stats().write_backs, 1);
+
+        cache.flush().await.unwrap();
+        assert_eq!(cache.dirty_pages(), 0);
+        assert_eq!(cache.page(0).await.unwrap()[0], 1);
+        assert_eq!(cache.page(2).await.unwrap()[0], 3);
+    }
+}
@@ -9,6 +9,7 @@
 //!     ┌──────────────────────────────────┐
 //!     │      Domain Layer                │
 //!     │  - PageBuffer (service)          │
+//!     │  - PageCache (service)           │
 //!     │  - BlockStorage (port)           │
 //!     └────────────┬─────────────────────┘
 //!                  │
@@ -19,6 +20,7 @@
 //!     │  - BlockDeviceAdapter            │
 //!     │  - StackBuffer                   │
 //!     │  - HeapBuffer                    │
+//!     │  - StackCache / HeapCache        │
 //!     └────────────┬─────────────────────┘
 //!                  │
 //!                  │ uses
@@ -33,27 +35,65 @@
 //! - **`BlockDeviceAdapter`**: Adapts `BlockDevice` to `BlockStorage` port
 //! - **`StackBuffer`**: Stack-allocated buffer with compile-time sizing
 //! - **`HeapBuffer`**: Heap-allocated buffer with runtime sizing (requires `alloc`)
+//! - **`StackCache`**: Stack-allocated multi-page LRU/CLOCK write-back cache
+//! - **`HeapCache`**: Heap-allocated multi-page cache with runtime sizing (requires `alloc`)
+//! - **`NorFlashFtl`**: Log-structured flash translation layer with wear leveling for raw NOR flash (requires `nor-ftl`)
+//! - **`NandFlashAdapter`**: Raw NAND flash with ECC, bad block table and block mapping (requires `nand`)
+//! - **`EncryptedBlockDevice`**: AES-256-XTS at-rest encryption wrapper (requires `encryption`)
+//! - **`ChecksummedBlockDevice`**: Per-block CRC32C verification and scrubbing (requires `checksums`)
+//! - **`OverlayBlockDevice`**: Copy-on-write overlay over a read-only base device (requires `overlay`)
+//! - **`RecordingBlockDevice`**: Records every read, write and sync to a replayable trace (requires `trace`)
+//! - **`FaultyBlockDevice`**: Injects write failures, power cuts, torn writes and bit flips (requires `fault-injection`)
 
-mod block_device_adapter;
+pub(crate) mod block_device_adapter;
 mod stack_buffer;
+mod stack_cache;
 mod error;
 
 #[cfg(feature = "alloc")]
 mod heap_buffer;
 
+#[cfg(feature = "alloc")]
+mod heap_cache;
+
 #[cfg(feature = "embedded-storage")]
 mod nor_flash_adapter;
 
 #[cfg(feature = "embedded-storage")]
 mod header_rotating_device;
 
+#[cfg(feature = "nor-ftl")]
+mod nor_flash_ftl;
+
+#[cfg(feature = "nand")]
+mod nand_flash_adapter;
+
+#[cfg(feature = "encryption")]
+mod encrypted_device;
+
+#[cfg(feature = "checksums")]
+mod checksum_device;
+
+#[cfg(feature = "overlay")]
+mod overlay_device;
+
+#[cfg(feature = "trace")]
+mod trace_device;
+
+#[cfg(feature = "fault-injection")]
+mod fault_device;
+
 pub use block_device_adapter::BlockDeviceAdapter;
 pub use stack_buffer::{StackBuffer, StackBuffer2K, StackBuffer4K, StackBuffer8K, StackBuffer4KBlock4K, StackBuffer128KBlock128K};
+pub use stack_cache::{StackCache, StackCache4K, StackCache4KBlock4K};
 pub use error::AdapterError;
 
 #[cfg(feature = "alloc")]
 pub use heap_buffer::{HeapBuffer, presets};
 
+#[cfg(feature = "alloc")]
+pub use heap_cache::HeapCache;
+
 #[cfg(feature = "alloc")]
 pub use error::HeapAdapterError;
 
@@ -62,3 +102,31 @@ pub use nor_flash_adapter::{NorFlashAdapter, NorFlashConfig, NorFlashError, NOR_
 
 #[cfg(feature = "embedded-storage")]
 pub use header_rotating_device::{HeaderRotatingDevice, HeaderRotationConfig, HEADER_ROTATION_BLOCK_SIZE};
+
+#[cfg(feature = "nor-ftl")]
+pub use nor_flash_ftl::{FTL_SECTOR_SIZE, NorFlashFtl, NorFtlConfig, NorFtlError, NorFtlStats};
+
+#[cfg(feature = "nand")]
+pub use nand_flash_adapter::{
+    NandConfig, NandEcc, NandError, NandFlash, NandFlashAdapter, NandGeometry, NandStats,
+};
+
+#[cfg(feature = "encryption")]
+pub use encrypted_device::{EncryptedBlockDevice, KeyProvider, XtsKey, XTS_KEY_SIZE};
+
+#[cfg(feature = "checksums")]
+pub use checksum_device::{ChecksumError, ChecksummedBlockDevice, ScrubReport};
+
+#[cfg(feature = "overlay")]
+pub use overlay_device::{DeltaStore, DeviceDelta, OverlayBlockDevice, OverlayError, RamDelta};
+
+#[cfg(feature = "trace")]
+pub use trace_device::{
+    RecordingBlockDevice, ReplayReport, TraceError, TraceMode, TraceOp, TraceRecord, TraceReplayer,
+};
+
+#[cfg(feature = "fault-injection")]
+pub use fault_device::{
+    CutPointFailure, CutPointReport, FaultError, FaultPlan, FaultStats, FaultyBlockDevice,
+    explore_cut_points,
+};
@@ -0,0 +1,1370 @@
+//! Raw NAND flash adapter with ECC and bad block management.
+//!
+//! NAND flash differs from NOR in ways a plain `BlockDevice` cannot hide:
+//!
+//! - Pages (e.g. 2 KB plus a 64-byte spare area) can only be programmed once per
+//!   erase, and the pages of a block must be programmed in ascending order.
+//! - Blocks (e.g. 128 KB) are shipped bad from the factory or wear out in the field.
+//! - Bits flip over time, so data must be stored with an error correcting code.
+//!
+//! [`NandFlashAdapter`] handles all three on top of a minimal [`NandFlash`] driver
+//! trait and exposes the flash as a `BlockDevice` of any sector size dividing the
+//! erase block.
+//!
+//! # Block Mapping
+//!
+//! Every logical erase block is stored in one good physical block. A write to pages
+//! beyond the last programmed page is appended in place. Any other write copies the
+//! block into a freshly erased one with the new data merged in, then erases the old
+//! block. Sequential writes, as done when filling files, therefore program each page
+//! once; rewriting FAT or directory sectors costs a block copy.
+//!
+//! # Spare Area Layout
+//!
+//! ```text
+//! ┌────────┬──────────────────────────────────────────┬──────────┬──────────────────┐
+//! │ 0      │ 1..15                                    │ 15..18   │ 18..             │
+//! │ bad    │ logical block | version | page count |   │ metadata │ data ECC         │
+//! │ marker │ CRC32C                                   │ ECC      │ (3 B per 256 B)  │
+//! └────────┴──────────────────────────────────────────┴──────────┴──────────────────┘
+//! ```
+//!
+//! With [`NandEcc::Hamming`] every 256 bytes get a 22-bit Hamming code that corrects
+//! one flipped bit and detects two. [`NandEcc::OnDie`] leaves error correction to
+//! chips that do it internally, as most SPI NAND parts can.
+//!
+//! # Bad Blocks
+//!
+//! A block whose first spare byte is not `0xFF` in its first or last page is bad;
+//! factory bad blocks are marked in the first page. The bad block table is rebuilt
+//! from these markers on [`mount`](NandFlashAdapter::mount). When a program or erase
+//! fails, the data is written to another block and the marker goes into the last
+//! page, provided that page is still erased. Pages are never programmed twice, so a
+//! block that failed on its last page is kept in the table only and gets retired
+//! again after the next mount.
+//!
+//! # Power Loss
+//!
+//! Each copy of a logical block carries a version, and page 0 records how many pages
+//! the copy will hold. After an interrupted copy, mount keeps the newest complete
+//! copy and erases the others. A page whose program was interrupted reads as zeros.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fatrs_adapters::{NandConfig, NandEcc, NandFlashAdapter, StackPageStream};
+//!
+//! let nand = SpiNand::new(spi); // implements NandFlash
+//! let device = NandFlashAdapter::mount(nand, NandConfig::new(NandEcc::OnDie)).await?;
+//! let stream = StackPageStream::<_, 4096>::new(device);
+//! let fs = FileSystem::new(stream, FsOptions::new()).await?;
+//! ```
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Cell, UnsafeCell};
+
+use aligned::Aligned;
+use crc::{CRC_32_ISCSI, Crc};
+use fatrs_block_device::{BlockDevice, blocks_to_slice, blocks_to_slice_mut};
+
+const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+/// Spare area offsets
+const MARKER: usize = 0;
+const META: usize = 1;
+const META_LEN: usize = 14;
+const META_ECC: usize = META + META_LEN;
+const DATA_ECC: usize = META_ECC + ECC_BYTES;
+/// Hamming code size and the data it covers
+const ECC_BYTES: usize = 3;
+const ECC_CHUNK: usize = 256;
+const GOOD_BLOCK: u8 = 0xFF;
+const UNMAPPED: u32 = u32::MAX;
+
+/// Page and block geometry of a NAND chip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NandGeometry {
+    /// Data bytes per page
+    pub page_size: u32,
+    /// Spare (out-of-band) bytes per page
+    pub spare_size: u32,
+    /// Pages per erase block
+    pub pages_per_block: u32,
+    /// Number of erase blocks
+    pub block_count: u32,
+}
+
+impl NandGeometry {
+    /// Create a geometry description.
+    pub const fn new(
+        page_size: u32,
+        spare_size: u32,
+        pages_per_block: u32,
+        block_count: u32,
+    ) -> Self {
+        Self {
+            page_size,
+            spare_size,
+            pages_per_block,
+            block_count,
+        }
+    }
+
+    /// Typical 1 Gbit SPI NAND: 2 KB + 64 B pages, 64 pages (128 KB) per block, 1024 blocks.
+    pub const fn spi_nand_1gbit() -> Self {
+        Self::new(2048, 64, 64, 1024)
+    }
+
+    /// Bytes of data per erase block
+    #[inline]
+    pub const fn block_size(&self) -> u32 {
+        self.page_size * self.pages_per_block
+    }
+
+    /// Bytes per page including the spare area
+    #[inline]
+    pub const fn raw_page_size(&self) -> usize {
+        (self.page_size + self.spare_size) as usize
+    }
+}
+
+/// Minimal driver interface of a raw NAND chip.
+///
+/// Addresses are physical: `block` is the erase block and `page` the page within it.
+/// Raw pages consist of the data area followed by the spare area.
+///
+/// `program` and `erase` return `Ok(false)` when the chip reports a failed operation
+/// (program or erase fail status). That wears the block out and makes the adapter
+/// retire it. `Err` is reserved for bus and driver errors and is passed through.
+pub trait NandFlash {
+    /// Bus or driver error.
+    type Error: core::fmt::Debug;
+
+    /// Geometry of the chip.
+    fn geometry(&self) -> NandGeometry;
+
+    /// Read `buf.len()` bytes of a raw page, starting at byte `column`.
+    async fn read(
+        &mut self,
+        block: u32,
+        page: u32,
+        column: u32,
+        buf: &mut [u8],
+    ) -> Result<(), Self::Error>;
+
+    /// Program a whole raw page (data followed by spare).
+    async fn program(&mut self, block: u32, page: u32, raw: &[u8]) -> Result<bool, Self::Error>;
+
+    /// Erase a block.
+    async fn erase(&mut self, block: u32) -> Result<bool, Self::Error>;
+}
+
+/// Error correction applied to page data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NandEcc {
+    /// Hamming code correcting one bit per 256 bytes, stored in the spare area
+    Hamming,
+    /// The chip corrects errors internally; no ECC is stored
+    OnDie,
+}
+
+/// Configuration of a [`NandFlashAdapter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NandConfig {
+    /// Error correction scheme
+    pub ecc: NandEcc,
+    /// Good blocks kept out of the capacity for block copies and to replace blocks
+    /// that go bad; `None` reserves 2% of the blocks plus 2
+    pub reserved_blocks: Option<u32>,
+}
+
+impl NandConfig {
+    /// Create a configuration with the default block reserve.
+    pub const fn new(ecc: NandEcc) -> Self {
+        Self {
+            ecc,
+            reserved_blocks: None,
+        }
+    }
+
+    /// Reserve a fixed number of blocks.
+    pub const fn with_reserved_blocks(mut self, reserved_blocks: u32) -> Self {
+        self.reserved_blocks = Some(reserved_blocks);
+        self
+    }
+}
+
+impl Default for NandConfig {
+    fn default() -> Self {
+        Self::new(NandEcc::Hamming)
+    }
+}
+
+/// Errors of a [`NandFlashAdapter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NandError<E> {
+    /// Error from the NAND driver.
+    Device(E),
+    /// A page has more bit errors than the ECC can correct.
+    Uncorrectable {
+        /// Physical block of the page.
+        block: u32,
+        /// Page within the block.
+        page: u32,
+    },
+    /// No good block is left to write to.
+    NoGoodBlocks,
+    /// The access is beyond the capacity.
+    OutOfRange,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for NandError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Device(e) => write!(f, "NAND error: {}", e),
+            Self::Uncorrectable { block, page } => {
+                write!(
+                    f,
+                    "Uncorrectable ECC error in block {} page {}",
+                    block, page
+                )
+            }
+            Self::NoGoodBlocks => write!(f, "No good NAND block left"),
+            Self::OutOfRange => write!(f, "Address out of range"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug + core::fmt::Display> core::error::Error for NandError<E> {}
+
+/// Health counters of a [`NandFlashAdapter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NandStats {
+    /// Blocks marked bad, from the factory or retired since
+    pub bad_blocks: u32,
+    /// Good blocks not holding data
+    pub free_blocks: u32,
+    /// Bit flips corrected by ECC since mount
+    pub corrected_bit_flips: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockState {
+    Bad,
+    /// `erased` is only set for blocks erased since mount
+    Free {
+        erased: bool,
+    },
+    Used {
+        logical: u32,
+        version: u32,
+        last_page: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PageMeta {
+    logical: u32,
+    version: u32,
+    page_count: u16,
+}
+
+enum PageState {
+    Erased,
+    /// Programmed, but the metadata is unreadable (interrupted program)
+    Torn,
+    Valid(PageMeta),
+}
+
+/// `BlockDevice` on raw NAND flash with ECC, bad block management and block mapping.
+///
+/// Any sector size works; sectors never straddle a page if the size divides the
+/// page size. Unwritten sectors read as zeros.
+///
+/// # Safety
+///
+/// This type uses `UnsafeCell` for interior mutability because the driver and the
+/// page buffer need `&mut` access while `BlockDevice::read` takes `&self`. This is
+/// safe in single-threaded embedded contexts.
+pub struct NandFlashAdapter<N> {
+    nand: UnsafeCell<N>,
+    config: NandConfig,
+    geometry: NandGeometry,
+    logical_blocks: u32,
+    blocks: Vec<BlockState>,
+    /// Logical block to physical block
+    map: Vec<u32>,
+    next_version: u32,
+    /// Where the search for a free block starts, so allocations rotate over the chip
+    next_free: u32,
+    /// Raw page buffer (data followed by spare)
+    page: UnsafeCell<Vec<u8>>,
+    corrected: Cell<u32>,
+}
+
+// SAFETY: NandFlashAdapter is Send if N is Send
+// The UnsafeCell is only used for interior mutability in single-threaded contexts
+unsafe impl<N: Send> Send for NandFlashAdapter<N> {}
+
+// SAFETY: NandFlashAdapter is Sync if N is Sync
+// Access must be externally synchronized in multi-threaded contexts
+unsafe impl<N: Sync> Sync for NandFlashAdapter<N> {}
+
+impl<N> NandFlashAdapter<N> {
+    /// Get the configuration
+    pub fn config(&self) -> &NandConfig {
+        &self.config
+    }
+
+    /// Get the chip geometry
+    pub fn geometry(&self) -> &NandGeometry {
+        &self.geometry
+    }
+
+    /// Usable capacity in bytes
+    pub fn capacity(&self) -> u64 {
+        u64::from(self.logical_blocks) * u64::from(self.geometry.block_size())
+    }
+
+    /// Current health counters
+    pub fn stats(&self) -> NandStats {
+        let count = |f: fn(&BlockState) -> bool| self.blocks.iter().filter(|b| f(b)).count() as u32;
+        NandStats {
+            bad_blocks: count(|b| *b == BlockState::Bad),
+            free_blocks: count(|b| matches!(b, BlockState::Free { .. })),
+            corrected_bit_flips: self.corrected.get(),
+        }
+    }
+
+    /// Consume the adapter and return the driver
+    pub fn into_inner(self) -> N {
+        self.nand.into_inner()
+    }
+
+    /// Get mutable access to the driver (internal use)
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn nand_mut(&self) -> &mut N {
+        // SAFETY: Safe in single-threaded embedded contexts
+        unsafe { &mut *self.nand.get() }
+    }
+
+    /// Get the raw page buffer (internal use)
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn page_mut(&self) -> &mut [u8] {
+        // SAFETY: Safe in single-threaded embedded contexts; never held across calls
+        unsafe { &mut *self.page.get() }
+    }
+
+    #[inline]
+    fn page_size(&self) -> usize {
+        self.geometry.page_size as usize
+    }
+
+    fn last_page(&self, block: u32) -> Option<u32> {
+        match self.blocks[block as usize] {
+            BlockState::Used { last_page, .. } => Some(last_page),
+            _ => None,
+        }
+    }
+
+    /// Parse (and with Hamming ECC repair) the metadata of a spare area
+    fn decode_spare(&self, spare: &mut [u8]) -> PageState {
+        if spare[META..DATA_ECC].iter().all(|&b| b == 0xFF) {
+            return PageState::Erased;
+        }
+        if self.config.ecc == NandEcc::Hamming {
+            let stored = read_ecc(&spare[META_ECC..]);
+            match hamming_correct(&mut spare[META..META + META_LEN], stored) {
+                None => return PageState::Torn,
+                Some(corrected) => self.count_corrected(corrected),
+            }
+        }
+        let meta = &spare[META..META + META_LEN];
+        let crc = u32::from_le_bytes(meta[10..14].try_into().unwrap());
+        if crc != CRC32C.checksum(&meta[..10]) {
+            return PageState::Torn;
+        }
+        PageState::Valid(PageMeta {
+            logical: u32::from_le_bytes(meta[0..4].try_into().unwrap()),
+            version: u32::from_le_bytes(meta[4..8].try_into().unwrap()),
+            page_count: u16::from_le_bytes(meta[8..10].try_into().unwrap()),
+        })
+    }
+
+    /// Fill the spare area of the page buffer for its data
+    fn seal_page(&self, logical: u32, version: u32, page_count: u16) {
+        let page_size = self.page_size();
+        let ecc = self.config.ecc;
+        let (data, spare) = self.page_mut().split_at_mut(page_size);
+        spare.fill(0xFF);
+        let meta = &mut spare[META..META + META_LEN];
+        meta[0..4].copy_from_slice(&logical.to_le_bytes());
+        meta[4..8].copy_from_slice(&version.to_le_bytes());
+        meta[8..10].copy_from_slice(&page_count.to_le_bytes());
+        let crc = CRC32C.checksum(&meta[..10]);
+        meta[10..14].copy_from_slice(&crc.to_le_bytes());
+        if ecc == NandEcc::Hamming {
+            let code = hamming_compute(&spare[META..META + META_LEN]);
+            write_ecc(&mut spare[META_ECC..], code);
+            for (i, chunk) in data.chunks(ECC_CHUNK).enumerate() {
+                write_ecc(
+                    &mut spare[DATA_ECC + i * ECC_BYTES..],
+                    hamming_compute(chunk),
+                );
+            }
+        }
+    }
+
+    /// Copy the part of `bytes` (starting at `start` in the block) that falls into
+    /// `page` into the page buffer
+    fn merge(&self, page: u32, start: usize, bytes: &[u8]) {
+        let page_size = self.page_size();
+        let page_start = page as usize * page_size;
+        let from = start.max(page_start);
+        let to = (start + bytes.len()).min(page_start + page_size);
+        if from < to {
+            self.page_mut()[from - page_start..to - page_start]
+                .copy_from_slice(&bytes[from - start..to - start]);
+        }
+    }
+
+    fn count_corrected(&self, corrected: bool) {
+        if corrected {
+            self.corrected.set(self.corrected.get() + 1);
+        }
+    }
+}
+
+impl<N: NandFlash> NandFlashAdapter<N> {
+    /// Mount the adapter, rebuilding the bad block table and the block map from flash.
+    ///
+    /// Blank (erased) flash mounts as an empty device.
+    ///
+    /// # Panics
+    /// Panics if the page size is not a multiple of 512 bytes, if the spare area is
+    /// too small for the metadata and ECC, or if the reserve leaves no capacity.
+    ///
+    /// # Errors
+    /// Returns [`NandError::Device`] if the chip cannot be read, and
+    /// [`NandError::NoGoodBlocks`] if there are too many bad blocks for the capacity.
+    pub async fn mount(nand: N, config: NandConfig) -> Result<Self, NandError<N::Error>> {
+        let geometry = nand.geometry();
+        assert!(
+            geometry.page_size > 0 && geometry.page_size % 512 == 0,
+            "page size must be a multiple of 512 bytes"
+        );
+        assert!(
+            geometry.pages_per_block >= 1 && geometry.pages_per_block <= u32::from(u16::MAX),
+            "pages per block out of range"
+        );
+        let spare_needed = match config.ecc {
+            NandEcc::Hamming => DATA_ECC + geometry.page_size as usize / ECC_CHUNK * ECC_BYTES,
+            NandEcc::OnDie => META_ECC,
+        };
+        assert!(
+            geometry.spare_size as usize >= spare_needed,
+            "spare area too small for the metadata and ECC"
+        );
+        let reserved = config
+            .reserved_blocks
+            .unwrap_or(geometry.block_count / 50 + 2);
+        assert!(
+            reserved >= 1 && reserved < geometry.block_count,
+            "reserved_blocks must leave room for data and at least one spare block"
+        );
+        let logical_blocks = geometry.block_count - reserved;
+
+        let mut adapter = Self {
+            nand: UnsafeCell::new(nand),
+            config,
+            geometry,
+            logical_blocks,
+            blocks: Vec::with_capacity(geometry.block_count as usize),
+            map: vec![UNMAPPED; logical_blocks as usize],
+            next_version: 0,
+            next_free: 0,
+            page: UnsafeCell::new(vec![0; geometry.raw_page_size()]),
+            corrected: Cell::new(0),
+        };
+        adapter.scan().await?;
+        Ok(adapter)
+    }
+
+    /// Build the bad block table and the map from the spare areas
+    async fn scan(&mut self) -> Result<(), NandError<N::Error>> {
+        let mut page_counts = Vec::with_capacity(self.geometry.block_count as usize);
+        for block in 0..self.geometry.block_count {
+            let (marker, state) = self.read_spare(block, 0).await?;
+            let (last_marker, _) = self
+                .read_spare(block, self.geometry.pages_per_block - 1)
+                .await?;
+            let state = match state {
+                _ if marker != GOOD_BLOCK || last_marker != GOOD_BLOCK => BlockState::Bad,
+                PageState::Valid(meta) if meta.logical < self.logical_blocks => {
+                    self.next_version = self.next_version.max(meta.version.wrapping_add(1));
+                    page_counts.push(meta.page_count);
+                    BlockState::Used {
+                        logical: meta.logical,
+                        version: meta.version,
+                        last_page: 0,
+                    }
+                }
+                _ => BlockState::Free { erased: false },
+            };
+            if !matches!(state, BlockState::Used { .. }) {
+                page_counts.push(0);
+            }
+            self.blocks.push(state);
+        }
+
+        // Several copies of a logical block: keep the newest one that is complete
+        for block in 0..self.geometry.block_count {
+            let BlockState::Used {
+                logical, version, ..
+            } = self.blocks[block as usize]
+            else {
+                continue;
+            };
+            let current = self.map[logical as usize];
+            let winner = match self.blocks.get(current as usize) {
+                Some(BlockState::Used { version: other, .. }) => {
+                    let (newer, older) = if version > *other {
+                        (block, current)
+                    } else {
+                        (current, block)
+                    };
+                    let complete =
+                        self.programmed_pages(newer).await? >= page_counts[newer as usize];
+                    if complete { newer } else { older }
+                }
+                _ => block,
+            };
+            self.map[logical as usize] = winner;
+        }
+        for block in 0..self.geometry.block_count {
+            if let BlockState::Used { logical, .. } = self.blocks[block as usize] {
+                if self.map[logical as usize] != block {
+                    // Stale copy; erased when the block is allocated again
+                    self.blocks[block as usize] = BlockState::Free { erased: false };
+                    continue;
+                }
+                let last = self.find_last_page(block).await?;
+                if let BlockState::Used { last_page, .. } = &mut self.blocks[block as usize] {
+                    *last_page = last;
+                }
+            }
+        }
+
+        let good = self
+            .blocks
+            .iter()
+            .filter(|b| **b != BlockState::Bad)
+            .count() as u32;
+        if good <= self.logical_blocks {
+            return Err(NandError::NoGoodBlocks);
+        }
+        Ok(())
+    }
+
+    /// Read the spare area of a page, returning the bad block marker and the page state
+    async fn read_spare(
+        &self,
+        block: u32,
+        page: u32,
+    ) -> Result<(u8, PageState), NandError<N::Error>> {
+        let page_size = self.page_size();
+        let spare = &mut self.page_mut()[page_size..];
+        self.nand_mut()
+            .read(block, page, page_size as u32, spare)
+            .await
+            .map_err(NandError::Device)?;
+        Ok((spare[MARKER], self.decode_spare(spare)))
+    }
+
+    /// Number of pages of a block with valid metadata
+    async fn programmed_pages(&self, block: u32) -> Result<u16, NandError<N::Error>> {
+        let mut count = 0;
+        for page in 0..self.geometry.pages_per_block {
+            if let (_, PageState::Valid(_)) = self.read_spare(block, page).await? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Highest page of a used block that must not be programmed again
+    async fn find_last_page(&self, block: u32) -> Result<u32, NandError<N::Error>> {
+        let mut last = 0;
+        for page in (1..self.geometry.pages_per_block).rev() {
+            if !matches!(self.read_spare(block, page).await?.1, PageState::Erased) {
+                last = page;
+                break;
+            }
+        }
+        // An interrupted program may have left data behind an erased spare area
+        let next = last + 1;
+        if next < self.geometry.pages_per_block {
+            let raw = self.page_mut();
+            self.nand_mut()
+                .read(block, next, 0, raw)
+                .await
+                .map_err(NandError::Device)?;
+            if raw.iter().any(|&b| b != 0xFF) {
+                last = next;
+            }
+        }
+        Ok(last)
+    }
+
+    /// Read a page into the page buffer and correct it; unprogrammed pages read as zeros.
+    ///
+    /// Returns whether the page holds data.
+    async fn load_page(&self, block: u32, page: u32) -> Result<bool, NandError<N::Error>> {
+        let page_size = self.page_size();
+        let raw = self.page_mut();
+        self.nand_mut()
+            .read(block, page, 0, raw)
+            .await
+            .map_err(NandError::Device)?;
+        let (data, spare) = raw.split_at_mut(page_size);
+        if !matches!(self.decode_spare(spare), PageState::Valid(_)) {
+            data.fill(0);
+            return Ok(false);
+        }
+        if self.config.ecc == NandEcc::Hamming {
+            for (i, chunk) in data.chunks_mut(ECC_CHUNK).enumerate() {
+                let stored = read_ecc(&spare[DATA_ECC + i * ECC_BYTES..]);
+                let corrected = hamming_correct(chunk, stored)
+                    .ok_or(NandError::Uncorrectable { block, page })?;
+                self.count_corrected(corrected);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Program the page buffer
+    async fn program_page(&self, block: u32, page: u32) -> Result<bool, NandError<N::Error>> {
+        self.nand_mut()
+            .program(block, page, self.page_mut())
+            .await
+            .map_err(NandError::Device)
+    }
+
+    /// Read `out.len()` bytes of logical data starting at `offset`
+    async fn read_bytes(&self, offset: u64, out: &mut [u8]) -> Result<(), NandError<N::Error>> {
+        let block_size = u64::from(self.geometry.block_size());
+        let page_size = self.page_size();
+        let mut done = 0;
+        while done < out.len() {
+            let pos = offset + done as u64;
+            let logical = (pos / block_size) as usize;
+            let in_block = (pos % block_size) as usize;
+            let page = (in_block / page_size) as u32;
+            let column = in_block % page_size;
+            let len = (page_size - column).min(out.len() - done);
+            let block = self.map[logical];
+            let out = &mut out[done..done + len];
+            if block != UNMAPPED && self.last_page(block).is_some_and(|last| page <= last) {
+                self.load_page(block, page).await?;
+                out.copy_from_slice(&self.page_mut()[column..column + len]);
+            } else {
+                out.fill(0);
+            }
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Write `data` to the logical bytes starting at `offset`
+    async fn write_bytes(&mut self, offset: u64, data: &[u8]) -> Result<(), NandError<N::Error>> {
+        let block_size = u64::from(self.geometry.block_size());
+        let mut done = 0;
+        while done < data.len() {
+            let pos = offset + done as u64;
+            let start = (pos % block_size) as usize;
+            let len = (block_size as usize - start).min(data.len() - done);
+            self.update_block((pos / block_size) as u32, start, &data[done..done + len])
+                .await?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Store `bytes` at offset `start` of a logical block
+    async fn update_block(
+        &mut self,
+        logical: u32,
+        start: usize,
+        bytes: &[u8],
+    ) -> Result<(), NandError<N::Error>> {
+        let page_size = self.page_size();
+        let first = (start / page_size) as u32;
+        let old = self.map[logical as usize];
+
+        // Pages after the last programmed one can be appended in place
+        let mut old_failed = None;
+        if let Some(BlockState::Used {
+            version, last_page, ..
+        }) = self.blocks.get(old as usize).copied()
+        {
+            if first > last_page {
+                old_failed = self.append(old, logical, version, start, bytes).await?;
+                if old_failed.is_none() {
+                    return Ok(());
+                }
+            }
+        }
+
+        let new = loop {
+            let block = self.allocate().await?;
+            match self.copy_block(old, block, logical, start, bytes).await? {
+                None => break block,
+                Some(page) => self.mark_bad(block, Some(page)).await?,
+            }
+        };
+        self.map[logical as usize] = new;
+        if old != UNMAPPED {
+            if let Some(page) = old_failed {
+                self.mark_bad(old, Some(page)).await?;
+            } else {
+                self.recycle(old).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Program the pages covered by `bytes` after the last programmed page of `block`.
+    /// Returns the page whose program failed, if any.
+    async fn append(
+        &mut self,
+        block: u32,
+        logical: u32,
+        version: u32,
+        start: usize,
+        bytes: &[u8],
+    ) -> Result<Option<u32>, NandError<N::Error>> {
+        let page_size = self.page_size();
+        let first = (start / page_size) as u32;
+        let last = ((start + bytes.len() - 1) / page_size) as u32;
+        for page in first..=last {
+            self.page_mut()[..page_size].fill(0);
+            self.merge(page, start, bytes);
+            self.seal_page(logical, version, 0);
+            if !self.program_page(block, page).await? {
+                return Ok(Some(page));
+            }
+            if let BlockState::Used { last_page, .. } = &mut self.blocks[block as usize] {
+                *last_page = page;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Copy logical block `logical` from `old` (if mapped) into the erased block `new`,
+    /// merging in `bytes`. Returns the page of `new` whose program failed, if any.
+    async fn copy_block(
+        &mut self,
+        old: u32,
+        new: u32,
+        logical: u32,
+        start: usize,
+        bytes: &[u8],
+    ) -> Result<Option<u32>, NandError<N::Error>> {
+        let page_size = self.page_size();
+        let first = (start / page_size) as u32;
+        let last = ((start + bytes.len() - 1) / page_size) as u32;
+        let old_last = if old == UNMAPPED {
+            None
+        } else {
+            self.last_page(old)
+        };
+        let top = old_last.unwrap_or(0).max(last);
+
+        // Page 0 carries the number of pages so mount can tell an interrupted copy
+        let mut keep = vec![false; top as usize + 1];
+        for page in 0..=top {
+            keep[page as usize] = page == 0
+                || (first..=last).contains(&page)
+                || (old_last.is_some_and(|l| page <= l)
+                    && matches!(self.read_spare(old, page).await?.1, PageState::Valid(_)));
+        }
+        let page_count = keep.iter().filter(|&&k| k).count() as u16;
+
+        let version = self.next_version;
+        self.next_version = self.next_version.wrapping_add(1);
+        let mut programmed = 0;
+        for page in (0..=top).filter(|&p| keep[p as usize]) {
+            let page_start = page as usize * page_size;
+            let covered = start <= page_start && start + bytes.len() >= page_start + page_size;
+            if covered || old_last.is_none_or(|l| page > l) {
+                self.page_mut()[..page_size].fill(0);
+            } else {
+                self.load_page(old, page).await?;
+            }
+            self.merge(page, start, bytes);
+            self.seal_page(logical, version, if page == 0 { page_count } else { 0 });
+            if !self.program_page(new, page).await? {
+                return Ok(Some(page));
+            }
+            programmed = page;
+        }
+        self.blocks[new as usize] = BlockState::Used {
+            logical,
+            version,
+            last_page: programmed,
+        };
+        Ok(None)
+    }
+
+    /// Take an erased good block, rotating over the chip
+    async fn allocate(&mut self) -> Result<u32, NandError<N::Error>> {
+        let count = self.geometry.block_count;
+        for i in 0..count {
+            let block = (self.next_free + i) % count;
+            let BlockState::Free { erased } = self.blocks[block as usize] else {
+                continue;
+            };
+            if !erased
+                && !self
+                    .nand_mut()
+                    .erase(block)
+                    .await
+                    .map_err(NandError::Device)?
+            {
+                self.mark_bad(block, None).await?;
+                continue;
+            }
+            // Not known to be erased any more once programming starts
+            self.blocks[block as usize] = BlockState::Free { erased: false };
+            self.next_free = (block + 1) % count;
+            return Ok(block);
+        }
+        Err(NandError::NoGoodBlocks)
+    }
+
+    /// Erase a block that no longer holds data
+    async fn recycle(&mut self, block: u32) -> Result<(), NandError<N::Error>> {
+        if self
+            .nand_mut()
+            .erase(block)
+            .await
+            .map_err(NandError::Device)?
+        {
+            self.blocks[block as usize] = BlockState::Free { erased: true };
+            Ok(())
+        } else {
+            self.mark_bad(block, None).await
+        }
+    }
+
+    /// Retire a block and write the bad block marker into its last page.
+    /// `failed_page` is the page whose program failed, if any.
+    async fn mark_bad(
+        &mut self,
+        block: u32,
+        failed_page: Option<u32>,
+    ) -> Result<(), NandError<N::Error>> {
+        self.blocks[block as usize] = BlockState::Bad;
+        let last = self.geometry.pages_per_block - 1;
+        if failed_page == Some(last) {
+            return Ok(());
+        }
+        let page_size = self.page_size();
+        let raw = self.page_mut();
+        self.nand_mut()
+            .read(block, last, 0, raw)
+            .await
+            .map_err(NandError::Device)?;
+        if raw.iter().any(|&b| b != 0xFF) {
+            return Ok(());
+        }
+        raw[page_size + MARKER] = 0;
+        // A failure here is fine: the marker only has to be readable as not 0xFF
+        self.program_page(block, last).await?;
+        Ok(())
+    }
+
+    fn check_range(&self, offset: u64, len: usize) -> Result<(), NandError<N::Error>> {
+        if offset + len as u64 > self.capacity() {
+            return Err(NandError::OutOfRange);
+        }
+        Ok(())
+    }
+}
+
+impl<N, const SIZE: usize> BlockDevice<SIZE> for NandFlashAdapter<N>
+where
+    N: NandFlash,
+{
+    type Error = NandError<N::Error>;
+    type Align = aligned::A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let offset = u64::from(block_address) * SIZE as u64;
+        self.check_range(offset, data.len() * SIZE)?;
+        self.read_bytes(offset, blocks_to_slice_mut(data)).await
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let offset = u64::from(block_address) * SIZE as u64;
+        self.check_range(offset, data.len() * SIZE)?;
+        self.write_bytes(offset, blocks_to_slice(data)).await
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.capacity() / SIZE as u64 * SIZE as u64)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        // Every page program is complete when it returns
+        Ok(())
+    }
+}
+
+/// Compute the 22-bit Hamming code of up to 256 bytes.
+///
+/// For each of the 11 bits of the bit index, the code holds the parity of all data
+/// bits whose index has that bit set (low 11 bits) and clear (high 11 bits).
+fn hamming_compute(data: &[u8]) -> u32 {
+    let mut columns = 0u8;
+    let (mut set, mut clear) = (0u32, 0u32);
+    for (i, &byte) in data.iter().enumerate() {
+        columns ^= byte;
+        if byte.count_ones() % 2 == 1 {
+            for k in 0..8 {
+                if (i >> k) & 1 == 1 {
+                    set ^= 1 << (k + 3);
+                } else {
+                    clear ^= 1 << (k + 3);
+                }
+            }
+        }
+    }
+    // Bit position within the byte: bits 0..3 of the index
+    const COLUMN_MASKS: [u8; 3] = [0xAA, 0xCC, 0xF0];
+    for (k, mask) in COLUMN_MASKS.iter().enumerate() {
+        set |= ((columns & mask).count_ones() % 2) << k;
+        clear |= ((columns & !mask).count_ones() % 2) << k;
+    }
+    set | (clear << 11)
+}
+
+/// Check `data` against its stored Hamming code, fixing a single flipped bit.
+///
+/// Returns `Some(true)` if a bit (in the data or the code) was corrected,
+/// `Some(false)` if the data is intact and `None` if it cannot be corrected.
+fn hamming_correct(data: &mut [u8], stored: u32) -> Option<bool> {
+    let syndrome = (stored ^ hamming_compute(data)) & 0x3F_FFFF;
+    if syndrome == 0 {
+        return Some(false);
+    }
+    let (set, clear) = (syndrome & 0x7FF, syndrome >> 11);
+    if set ^ clear == 0x7FF {
+        // One data bit flipped: `set` is its index
+        let byte = data.get_mut(set as usize >> 3)?;
+        *byte ^= 1 << (set & 7);
+        return Some(true);
+    }
+    // A single flipped bit in the code itself
+    (syndrome.count_ones() == 1).then_some(true)
+}
+
+fn read_ecc(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16
+}
+
+fn write_ecc(bytes: &mut [u8], code: u32) {
+    bytes[..ECC_BYTES].copy_from_slice(&code.to_le_bytes()[..ECC_BYTES]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::block_on;
+
+    const GEOMETRY: NandGeometry = NandGeometry::new(2048, 64, 8, 16);
+    const PAGE: usize = 2048;
+    const BLOCK: usize = PAGE * 8;
+
+    #[derive(Debug, PartialEq)]
+    struct PowerLoss;
+
+    /// In-memory NAND simulator enforcing program-once and in-order page programming
+    #[derive(Clone)]
+    struct NandSimulator {
+        raw: Vec<u8>,
+        /// Lowest page of each block that may still be programmed
+        next_page: Vec<u32>,
+        /// Blocks whose program and erase operations fail; bad block markers still stick
+        worn: Vec<u32>,
+        erases: u32,
+        /// Program/erase operations left before the power fails
+        power_left: Option<usize>,
+    }
+
+    impl NandSimulator {
+        fn new() -> Self {
+            Self {
+                raw: vec![
+                    0xFF;
+                    GEOMETRY.raw_page_size()
+                        * (GEOMETRY.pages_per_block * GEOMETRY.block_count) as usize
+                ],
+                next_page: vec![0; GEOMETRY.block_count as usize],
+                worn: Vec::new(),
+                erases: 0,
+                power_left: None,
+            }
+        }
+
+        fn page_range(block: u32, page: u32) -> core::ops::Range<usize> {
+            let start =
+                (block * GEOMETRY.pages_per_block + page) as usize * GEOMETRY.raw_page_size();
+            start..start + GEOMETRY.raw_page_size()
+        }
+
+        fn factory_bad(&mut self, block: u32) {
+            let range = Self::page_range(block, 0);
+            self.raw[range.start + PAGE + MARKER] = 0;
+        }
+
+        fn flip(&mut self, block: u32, page: u32, byte: usize, bit: u8) {
+            let range = Self::page_range(block, page);
+            self.raw[range.start + byte] ^= 1 << bit;
+        }
+
+        fn powered(&mut self) -> Result<(), PowerLoss> {
+            match self.power_left.as_mut() {
+                Some(0) => Err(PowerLoss),
+                Some(left) => {
+                    *left -= 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl NandFlash for NandSimulator {
+        type Error = PowerLoss;
+
+        fn geometry(&self) -> NandGeometry {
+            GEOMETRY
+        }
+
+        async fn read(
+            &mut self,
+            block: u32,
+            page: u32,
+            column: u32,
+            buf: &mut [u8],
+        ) -> Result<(), Self::Error> {
+            let start = Self::page_range(block, page).start + column as usize;
+            buf.copy_from_slice(&self.raw[start..start + buf.len()]);
+            Ok(())
+        }
+
+        async fn program(
+            &mut self,
+            block: u32,
+            page: u32,
+            raw: &[u8],
+        ) -> Result<bool, Self::Error> {
+            self.powered()?;
+            let range = Self::page_range(block, page);
+            assert!(
+                page >= self.next_page[block as usize],
+                "block {block}: page {page} programmed out of order"
+            );
+            assert!(
+                self.raw[range.clone()].iter().all(|&b| b == 0xFF),
+                "block {block}: page {page} programmed twice"
+            );
+            self.next_page[block as usize] = page + 1;
+            let marker_only = raw[..PAGE].iter().all(|&b| b == 0xFF)
+                && raw[PAGE + 1..].iter().all(|&b| b == 0xFF);
+            if self.worn.contains(&block) && !marker_only {
+                return Ok(false);
+            }
+            for (cell, byte) in self.raw[range].iter_mut().zip(raw) {
+                *cell &= *byte;
+            }
+            Ok(true)
+        }
+
+        async fn erase(&mut self, block: u32) -> Result<bool, Self::Error> {
+            self.powered()?;
+            if self.worn.contains(&block) {
+                return Ok(false);
+            }
+            let start = Self::page_range(block, 0).start;
+            let end = Self::page_range(block, GEOMETRY.pages_per_block - 1).end;
+            self.raw[start..end].fill(0xFF);
+            self.next_page[block as usize] = 0;
+            self.erases += 1;
+            Ok(true)
+        }
+    }
+
+    type Adapter = NandFlashAdapter<NandSimulator>;
+
+    async fn mount(nand: NandSimulator) -> Adapter {
+        NandFlashAdapter::mount(nand, NandConfig::default().with_reserved_blocks(4))
+            .await
+            .unwrap()
+    }
+
+    async fn write_sector(
+        adapter: &mut Adapter,
+        address: u32,
+        fill: u8,
+    ) -> Result<(), NandError<PowerLoss>> {
+        BlockDevice::<512>::write(adapter, address, &[Aligned([fill; 512])]).await
+    }
+
+    async fn read_sector(
+        adapter: &Adapter,
+        address: u32,
+    ) -> Result<[u8; 512], NandError<PowerLoss>> {
+        let mut buf = [Aligned([0xAA; 512])];
+        BlockDevice::<512>::read(adapter, address, &mut buf).await?;
+        Ok(*buf[0])
+    }
+
+    #[test]
+    fn test_read_write_and_remount() {
+        block_on(async {
+            let mut adapter = mount(NandSimulator::new()).await;
+            // 4 of 16 blocks are reserved
+            assert_eq!(adapter.capacity(), 12 * BLOCK as u64);
+            assert_eq!(read_sector(&adapter, 3).await.unwrap(), [0; 512]);
+
+            write_sector(&mut adapter, 3, 0x11).await.unwrap();
+            // Rewriting a programmed page copies the block
+            write_sector(&mut adapter, 2, 0x22).await.unwrap();
+            // Pages after the last programmed one are appended in place
+            let erases = adapter.nand_mut().erases;
+            let page = Aligned([0x33u8; 4096]);
+            BlockDevice::<4096>::write(&mut adapter, 1, core::slice::from_ref(&page))
+                .await
+                .unwrap();
+            assert_eq!(adapter.nand_mut().erases, erases);
+            // Writes spanning logical blocks
+            let pages = [Aligned([0x44u8; 4096]), Aligned([0x55u8; 4096])];
+            BlockDevice::<4096>::write(&mut adapter, 3, &pages)
+                .await
+                .unwrap();
+            assert!(matches!(
+                write_sector(&mut adapter, 12 * 32, 0).await,
+                Err(NandError::OutOfRange)
+            ));
+
+            let adapter = mount(adapter.into_inner()).await;
+            assert_eq!(read_sector(&adapter, 2).await.unwrap(), [0x22; 512]);
+            assert_eq!(read_sector(&adapter, 3).await.unwrap(), [0x11; 512]);
+            assert_eq!(read_sector(&adapter, 4).await.unwrap(), [0; 512]);
+            for sector in 8..16 {
+                assert_eq!(read_sector(&adapter, sector).await.unwrap(), [0x33; 512]);
+            }
+            let mut read = [Aligned([0u8; 4096]), Aligned([0u8; 4096])];
+            BlockDevice::<4096>::read(&adapter, 3, &mut read)
+                .await
+                .unwrap();
+            assert_eq!(*read[0], [0x44; 4096]);
+            assert_eq!(*read[1], [0x55; 4096]);
+        });
+    }
+
+    #[test]
+    fn test_factory_bad_blocks_are_skipped() {
+        block_on(async {
+            let mut nand = NandSimulator::new();
+            nand.factory_bad(0);
+            nand.factory_bad(7);
+            let mut adapter = mount(nand).await;
+            assert_eq!(adapter.stats().bad_blocks, 2);
+
+            // Fill the whole capacity, rewriting every block once
+            let sectors = (adapter.capacity() / 512) as u32;
+            for round in 0..2u8 {
+                for sector in 0..sectors {
+                    write_sector(&mut adapter, sector, sector as u8 ^ round)
+                        .await
+                        .unwrap();
+                }
+            }
+
+            let nand = adapter.into_inner();
+            for block in [0, 7] {
+                let range = NandSimulator::page_range(block, 0);
+                assert_eq!(nand.raw[range.start + PAGE + MARKER], 0);
+                assert!(
+                    nand.raw[range.start..range.start + PAGE]
+                        .iter()
+                        .all(|&b| b == 0xFF)
+                );
+            }
+            let adapter = mount(nand).await;
+            assert_eq!(adapter.stats().bad_blocks, 2);
+            for sector in 0..sectors {
+                assert_eq!(
+                    read_sector(&adapter, sector).await.unwrap(),
+                    [sector as u8 ^ 1; 512]
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn test_worn_blocks_are_retired() {
+        block_on(async {
+            let mut adapter = mount(NandSimulator::new()).await;
+            write_sector(&mut adapter, 0, 0x11).await.unwrap();
+
+            // Appending to the block fails: the data moves and the block is retired
+            let worn = adapter.map[0];
+            adapter.nand_mut().worn.push(worn);
+            write_sector(&mut adapter, 4, 0x22).await.unwrap();
+            assert_ne!(adapter.map[0], worn);
+            assert_eq!(adapter.stats().bad_blocks, 1);
+
+            // The next free block is worn out as well and gets retired
+            let next = adapter.next_free;
+            adapter.nand_mut().worn.push(next);
+            write_sector(&mut adapter, 0, 0x33).await.unwrap();
+            assert_eq!(adapter.stats().bad_blocks, 2);
+
+            let adapter = mount(adapter.into_inner()).await;
+            assert_eq!(adapter.stats().bad_blocks, 2);
+            assert_eq!(read_sector(&adapter, 0).await.unwrap(), [0x33; 512]);
+            assert_eq!(read_sector(&adapter, 4).await.unwrap(), [0x22; 512]);
+        });
+    }
+
+    #[test]
+    fn test_block_failing_on_last_page_is_not_reprogrammed() {
+        block_on(async {
+            let mut adapter = mount(NandSimulator::new()).await;
+            let sectors_per_page = (PAGE / 512) as u32;
+            for page in 0..7 {
+                write_sector(&mut adapter, page * sectors_per_page, 0x11)
+                    .await
+                    .unwrap();
+            }
+
+            // The marker has no erased page left: the block is retired in RAM only
+            let worn = adapter.map[0];
+            adapter.nand_mut().worn.push(worn);
+            write_sector(&mut adapter, 7 * sectors_per_page, 0x22)
+                .await
+                .unwrap();
+            assert_ne!(adapter.map[0], worn);
+            assert_eq!(adapter.stats().bad_blocks, 1);
+
+            let adapter = mount(adapter.into_inner()).await;
+            assert_eq!(read_sector(&adapter, 0).await.unwrap(), [0x11; 512]);
+            assert_eq!(
+                read_sector(&adapter, 7 * sectors_per_page).await.unwrap(),
+                [0x22; 512]
+            );
+        });
+    }
+
+    #[test]
+    fn test_ecc_corrects_single_bit_flips() {
+        block_on(async {
+            let mut adapter = mount(NandSimulator::new()).await;
+            write_sector(&mut adapter, 1, 0x5A).await.unwrap();
+            let block = adapter.map[0];
+
+            let nand = adapter.nand_mut();
+            nand.flip(block, 0, 600, 3);
+            // Metadata and ECC bytes are covered too
+            nand.flip(block, 0, PAGE + META + 2, 0);
+            nand.flip(block, 0, PAGE + DATA_ECC + 4, 6);
+            assert_eq!(read_sector(&adapter, 1).await.unwrap(), [0x5A; 512]);
+            assert_eq!(adapter.stats().corrected_bit_flips, 3);
+
+            // Two flips in the same 256 bytes are detected, not miscorrected
+            adapter.nand_mut().flip(block, 0, 1030, 1);
+            adapter.nand_mut().flip(block, 0, 1100, 5);
+            assert_eq!(
+                read_sector(&adapter, 2).await,
+                Err(NandError::Uncorrectable { block, page: 0 })
+            );
+        });
+    }
+
+    #[test]
+    fn test_interrupted_copy_keeps_a_complete_version() {
+        block_on(async {
+            let mut adapter = mount(NandSimulator::new()).await;
+            for sector in 0..16 {
+                write_sector(&mut adapter, sector, 0x11).await.unwrap();
+            }
+
+            let base = adapter.into_inner();
+
+            // Cut the power at every program and erase of a block copy
+            let (mut old_kept, mut new_kept) = (false, false);
+            for cut in 0.. {
+                let mut nand = base.clone();
+                nand.power_left = Some(cut);
+                let mut adapter = mount(nand).await;
+                let completed = write_sector(&mut adapter, 0, 0x22).await.is_ok();
+                let mut nand = adapter.into_inner();
+                nand.power_left = None;
+
+                let mut adapter = mount(nand).await;
+                let first = read_sector(&adapter, 0).await.unwrap();
+                assert!(first == [0x11; 512] || first == [0x22; 512], "cut {cut}");
+                old_kept |= first == [0x11; 512];
+                new_kept |= !completed && first == [0x22; 512];
+                for sector in 1..16 {
+                    assert_eq!(read_sector(&adapter, sector).await.unwrap(), [0x11; 512]);
+                }
+                write_sector(&mut adapter, 5, 0x33).await.unwrap();
+                assert_eq!(read_sector(&adapter, 5).await.unwrap(), [0x33; 512]);
+                if completed {
+                    assert_eq!(first, [0x22; 512]);
+                    break;
+                }
+            }
+            // Both an unfinished copy and an unfinished erase were hit
+            assert!(old_kept && new_kept);
+        });
+    }
+
+    #[test]
+    fn test_hamming_code() {
+        let mut data = [0u8; 256];
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = (i * 7) as u8;
+        }
+        let code = hamming_compute(&data);
+        for bit in [0, 7, 1000, 2047] {
+            let mut damaged = data;
+            damaged[bit / 8] ^= 1 << (bit % 8);
+            assert_eq!(hamming_correct(&mut damaged, code), Some(true));
+            assert_eq!(damaged, data);
+        }
+        assert_eq!(
+            hamming_correct(&mut data.clone(), code ^ 1 << 15),
+            Some(true)
+        );
+        assert_eq!(hamming_correct(&mut data.clone(), code), Some(false));
+    }
+}
@@ -0,0 +1,1035 @@
+//! Log-structured flash translation layer for raw NOR flash.
+//!
+//! [`NorFlashAdapter`](super::NorFlashAdapter) maps logical pages 1:1 onto flash
+//! sectors, so every rewrite of a FAT sector or directory entry erases the same
+//! physical sector again. [`NorFlashFtl`] instead writes every 512-byte logical
+//! sector to the next free slot of an erase block (out-of-place writes) and keeps a
+//! logical-to-physical map in RAM. Older copies become stale and are reclaimed by
+//! garbage collection, which spreads erases over the whole region.
+//!
+//! # On-Flash Layout
+//!
+//! ```text
+//! Erase block (ERASE_SIZE bytes):
+//! ┌────────────────────────────────────┬──────────┬──────────┬─────┐
+//! │ Metadata sectors                   │ Slot 0   │ Slot 1   │ ... │
+//! │ header | record 0 | record 1 | ... │ 512 B    │ 512 B    │     │
+//! └────────────────────────────────────┴──────────┴──────────┴─────┘
+//!
+//! Header (16 B): magic | erase count | CRC32C | retired marker
+//! Record (16 B): logical sector | sequence | data CRC32C | record CRC32C
+//! ```
+//!
+//! Slots of a block are filled in order. A slot's data is programmed before its
+//! record, so a record that passes its CRC always describes complete data.
+//!
+//! # Power-Safe Recovery
+//!
+//! Nothing but the flash holds state. On [`mount`](NorFlashFtl::mount) every block
+//! is scanned and each logical sector maps to its copy with the highest sequence
+//! number. Torn records fail their CRC and are ignored, a slot whose data was
+//! programmed without its record is skipped, and a block is marked retired before
+//! it is erased so a half-erased block is never trusted. Garbage collection gives
+//! moved sectors new sequence numbers, so an interrupted collection is finished on
+//! the next mount.
+//!
+//! # Wear Leveling
+//!
+//! - **Dynamic**: a new block is always the free block with the lowest erase count.
+//! - **Static**: when the erase counts of the coldest block holding data and the
+//!   most worn block differ by more than
+//!   [`wear_leveling_threshold`](NorFtlConfig::wear_leveling_threshold), garbage
+//!   collection moves the cold data so its block returns to circulation.
+//!
+//! # Limitations
+//!
+//! - The map takes 4 bytes of RAM per logical sector (8 KB per MB of flash).
+//! - `BlockDevice<4096>` writes are split into eight sector writes and are not atomic.
+//! - Sequence numbers are 32-bit, enough for 4 billion sector writes.
+//! - `WRITE_SIZE` of the flash must divide 4 bytes.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fatrs_adapters::{NorFlashFtl, NorFtlConfig, StackPageStream};
+//!
+//! let flash = SpiNorFlash::new(spi);
+//! // 1 MB at offset 0x10_0000, 4 KB erase blocks
+//! let config = NorFtlConfig::new(0x10_0000, 256);
+//! let ftl = NorFlashFtl::mount(flash, config)?;
+//! let stream = StackPageStream::new(ftl);
+//! let fs = FileSystem::new(stream, FsOptions::new()).await?;
+//! ```
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+
+use aligned::Aligned;
+use crc::{CRC_32_ISCSI, Crc};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use fatrs_block_device::BlockDevice;
+
+use super::nor_flash_adapter::NOR_FLASH_BLOCK_SIZE;
+
+/// Size of a logical sector of the flash translation layer.
+pub const FTL_SECTOR_SIZE: usize = 512;
+
+const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+/// Marks a formatted erase block ("NFTL").
+const BLOCK_MAGIC: u32 = u32::from_le_bytes(*b"NFTL");
+/// Size of the block header and of each slot record.
+const RECORD_SIZE: u32 = 16;
+/// Offset of the retired marker inside the block header.
+const RETIRED_OFFSET: u32 = 12;
+const UNMAPPED: u32 = u32::MAX;
+const SECTORS_PER_PAGE: usize = NOR_FLASH_BLOCK_SIZE / FTL_SECTOR_SIZE;
+
+/// Configuration of the flash region managed by [`NorFlashFtl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NorFtlConfig {
+    /// Start offset in flash (must be aligned to the erase size)
+    pub start_offset: u32,
+    /// Number of erase blocks in the region
+    pub erase_block_count: u32,
+    /// Erase blocks kept out of the logical capacity for garbage collection (at least 2)
+    pub spare_blocks: u32,
+    /// Largest erase count spread tolerated before cold data is moved
+    pub wear_leveling_threshold: u32,
+}
+
+impl NorFtlConfig {
+    /// Create a configuration with 2 spare blocks and a wear leveling threshold of 64.
+    ///
+    /// # Arguments
+    /// * `start_offset` - Byte offset in flash (aligned to the erase size)
+    /// * `erase_block_count` - Number of erase blocks to manage
+    pub const fn new(start_offset: u32, erase_block_count: u32) -> Self {
+        Self {
+            start_offset,
+            erase_block_count,
+            spare_blocks: 2,
+            wear_leveling_threshold: 64,
+        }
+    }
+
+    /// Reserve more spare blocks, trading capacity for less garbage collection work.
+    ///
+    /// # Panics
+    /// Panics if `spare_blocks` is less than 2.
+    pub const fn with_spare_blocks(mut self, spare_blocks: u32) -> Self {
+        assert!(spare_blocks >= 2, "at least 2 spare blocks are required");
+        self.spare_blocks = spare_blocks;
+        self
+    }
+
+    /// Set the erase count spread that triggers static wear leveling.
+    pub const fn with_wear_leveling_threshold(mut self, threshold: u32) -> Self {
+        self.wear_leveling_threshold = threshold;
+        self
+    }
+}
+
+/// Error type for flash translation layer operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NorFtlError {
+    /// The underlying flash reported an error
+    Flash,
+    /// Sector address beyond the logical capacity
+    OutOfRange,
+    /// Stored data does not match its checksum, or the metadata is inconsistent
+    Corrupted,
+}
+
+impl core::fmt::Display for NorFtlError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Flash => write!(f, "NOR flash error"),
+            Self::OutOfRange => write!(f, "sector out of range"),
+            Self::Corrupted => write!(f, "flash translation layer corrupted"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NorFtlError {}
+
+/// Wear statistics of a [`NorFlashFtl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NorFtlStats {
+    /// Erased blocks available for new writes
+    pub free_blocks: u32,
+    /// Lowest erase count of any block
+    pub min_erase_count: u32,
+    /// Highest erase count of any block
+    pub max_erase_count: u32,
+}
+
+/// RAM state of one erase block.
+#[derive(Debug, Clone, Copy)]
+struct BlockInfo {
+    erase_count: u32,
+    /// Slots consumed since the last erase, valid or stale
+    used: u32,
+    /// Slots holding the current copy of a logical sector
+    valid: u32,
+    free: bool,
+    /// Erased with a valid header, ready to take writes without another erase
+    formatted: bool,
+}
+
+/// Decoded slot record.
+struct Record {
+    logical: u32,
+    seq: u32,
+    data_crc: u32,
+}
+
+enum HeaderState {
+    /// Blank, torn or foreign header: erase count unknown
+    Unformatted,
+    /// Marked for erase; the erase may have been interrupted
+    Retired(u32),
+    Live(u32),
+}
+
+enum RecordState {
+    Blank,
+    Torn,
+    Valid(Record),
+}
+
+/// Flash translation layer exposing raw NOR flash as a `BlockDevice`.
+///
+/// Implements both `BlockDevice<512>` and `BlockDevice<4096>` over the same
+/// logical sectors. Unwritten sectors read as zeros.
+///
+/// # Safety
+///
+/// This type uses `UnsafeCell` for interior mutability because
+/// `embedded-storage` traits require `&mut self` for reads, but
+/// `BlockDevice::read` takes `&self`. This is safe in single-threaded
+/// embedded contexts.
+pub struct NorFlashFtl<F> {
+    flash: UnsafeCell<F>,
+    config: NorFtlConfig,
+    block_size: u32,
+    /// Sectors at the start of each block holding the header and records
+    meta_sectors: u32,
+    slots_per_block: u32,
+    sector_count: u32,
+    blocks: Vec<BlockInfo>,
+    /// Logical sector to physical slot (`block * slots_per_block + slot`)
+    map: Vec<u32>,
+    active: Option<u32>,
+    next_seq: u32,
+}
+
+// SAFETY: NorFlashFtl is Send if F is Send
+// The UnsafeCell is only used for interior mutability in single-threaded contexts
+unsafe impl<F: Send> Send for NorFlashFtl<F> {}
+
+// SAFETY: NorFlashFtl is Sync if F is Sync
+// Access must be externally synchronized in multi-threaded contexts
+unsafe impl<F: Sync> Sync for NorFlashFtl<F> {}
+
+impl<F> NorFlashFtl<F> {
+    /// Get the configuration
+    pub fn config(&self) -> &NorFtlConfig {
+        &self.config
+    }
+
+    /// Number of 512-byte logical sectors
+    pub fn sector_count(&self) -> u32 {
+        self.sector_count
+    }
+
+    /// Current wear statistics
+    pub fn stats(&self) -> NorFtlStats {
+        let counts = self.blocks.iter().map(|b| b.erase_count);
+        NorFtlStats {
+            free_blocks: self.free_blocks(),
+            min_erase_count: counts.clone().min().unwrap_or(0),
+            max_erase_count: counts.max().unwrap_or(0),
+        }
+    }
+
+    /// Consume the FTL and return the underlying flash
+    pub fn into_inner(self) -> F {
+        self.flash.into_inner()
+    }
+
+    /// Get mutable access to the flash (internal use)
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn flash_mut(&self) -> &mut F {
+        // SAFETY: Safe in single-threaded embedded contexts
+        unsafe { &mut *self.flash.get() }
+    }
+
+    #[inline]
+    fn block_offset(&self, block: u32) -> u32 {
+        self.config.start_offset + block * self.block_size
+    }
+
+    #[inline]
+    fn record_offset(&self, slot: u32) -> u32 {
+        let (block, index) = (slot / self.slots_per_block, slot % self.slots_per_block);
+        self.block_offset(block) + RECORD_SIZE * (index + 1)
+    }
+
+    #[inline]
+    fn data_offset(&self, slot: u32) -> u32 {
+        let (block, index) = (slot / self.slots_per_block, slot % self.slots_per_block);
+        self.block_offset(block) + (self.meta_sectors + index) * FTL_SECTOR_SIZE as u32
+    }
+
+    fn free_blocks(&self) -> u32 {
+        self.blocks.iter().filter(|b| b.free).count() as u32
+    }
+
+    /// Free block with the lowest erase count (dynamic wear leveling)
+    fn least_worn_free_block(&self) -> Option<u32> {
+        (0..self.blocks.len() as u32)
+            .filter(|&b| self.blocks[b as usize].free)
+            .min_by_key(|&b| self.blocks[b as usize].erase_count)
+    }
+
+    fn active_has_room(&self) -> bool {
+        self.active
+            .is_some_and(|b| self.blocks[b as usize].used < self.slots_per_block)
+    }
+
+    /// Block holding data that is due for static wear leveling
+    fn cold_block(&self) -> Option<u32> {
+        let max = self.blocks.iter().map(|b| b.erase_count).max()?;
+        let cold = (0..self.blocks.len() as u32)
+            .filter(|&b| Some(b) != self.active)
+            .filter(|&b| !self.blocks[b as usize].free && self.blocks[b as usize].valid > 0)
+            .min_by_key(|&b| self.blocks[b as usize].erase_count)?;
+        let spread = max - self.blocks[cold as usize].erase_count;
+        (spread > self.config.wear_leveling_threshold).then_some(cold)
+    }
+
+    /// Used block with the fewest valid slots, preferring less worn blocks
+    fn gc_victim(&self) -> Option<u32> {
+        (0..self.blocks.len() as u32)
+            .filter(|&b| Some(b) != self.active && !self.blocks[b as usize].free)
+            .min_by_key(|&b| {
+                let info = &self.blocks[b as usize];
+                (info.valid, info.erase_count)
+            })
+    }
+}
+
+impl<F> NorFlashFtl<F>
+where
+    F: NorFlash + ReadNorFlash,
+{
+    /// Mount the flash translation layer, rebuilding the map from flash.
+    ///
+    /// Blank flash mounts as an empty device; no separate format step is needed.
+    ///
+    /// # Panics
+    /// Panics if the erase size is not a multiple of 512 bytes of at least 1 KB,
+    /// if `start_offset` is not erase-aligned, if the region does not fit in the
+    /// flash, or if there are not more erase blocks than spare blocks.
+    ///
+    /// # Errors
+    /// Returns [`NorFtlError::Flash`] if the flash cannot be read, and
+    /// [`NorFtlError::Corrupted`] if the metadata leaves no room to continue.
+    pub fn mount(flash: F, config: NorFtlConfig) -> Result<Self, NorFtlError> {
+        let block_size = F::ERASE_SIZE as u32;
+        assert!(
+            block_size % FTL_SECTOR_SIZE as u32 == 0 && block_size >= 2 * FTL_SECTOR_SIZE as u32,
+            "erase size must be a multiple of 512 bytes and at least 1 KB"
+        );
+        assert!(
+            4 % F::WRITE_SIZE == 0,
+            "flash write size must divide 4 bytes"
+        );
+        assert!(
+            RECORD_SIZE % F::READ_SIZE as u32 == 0,
+            "flash read size must divide 16 bytes"
+        );
+        assert!(
+            config.start_offset % block_size == 0,
+            "start_offset must be aligned to the erase size"
+        );
+        assert!(
+            config.erase_block_count > config.spare_blocks && config.spare_blocks >= 2,
+            "erase_block_count must exceed spare_blocks, which must be at least 2"
+        );
+        assert!(
+            config.start_offset as usize + (config.erase_block_count * block_size) as usize
+                <= flash.capacity(),
+            "region does not fit in the flash"
+        );
+
+        // Smallest metadata area holding the header plus one record per data slot
+        let sectors = block_size / FTL_SECTOR_SIZE as u32;
+        let mut meta_sectors = 1;
+        while meta_sectors * (FTL_SECTOR_SIZE as u32) < RECORD_SIZE * (1 + sectors - meta_sectors) {
+            meta_sectors += 1;
+        }
+        let slots_per_block = sectors - meta_sectors;
+        let sector_count = (config.erase_block_count - config.spare_blocks) * slots_per_block;
+
+        let mut ftl = Self {
+            flash: UnsafeCell::new(flash),
+            config,
+            block_size,
+            meta_sectors,
+            slots_per_block,
+            sector_count,
+            blocks: Vec::with_capacity(config.erase_block_count as usize),
+            map: vec![UNMAPPED; sector_count as usize],
+            active: None,
+            next_seq: 0,
+        };
+        ftl.scan()?;
+        Ok(ftl)
+    }
+
+    /// Rebuild block state and the map from the headers and records on flash
+    fn scan(&mut self) -> Result<(), NorFtlError> {
+        let mut seqs = vec![0u32; self.sector_count as usize];
+        let mut newest = None;
+        let mut highest_seq = None;
+        let mut unknown_wear = Vec::new();
+
+        for block in 0..self.config.erase_block_count {
+            let header = self.read_header(block)?;
+            let mut info = BlockInfo {
+                erase_count: 0,
+                used: 0,
+                valid: 0,
+                free: true,
+                formatted: false,
+            };
+            match header {
+                HeaderState::Unformatted => unknown_wear.push(block),
+                HeaderState::Retired(erase_count) => info.erase_count = erase_count,
+                HeaderState::Live(erase_count) => {
+                    info.erase_count = erase_count;
+                    info.formatted = true;
+                }
+            }
+            if info.formatted {
+                let mut block_seq = None;
+                while info.used < self.slots_per_block {
+                    let slot = block * self.slots_per_block + info.used;
+                    let record = match self.read_record(slot)? {
+                        RecordState::Blank => break,
+                        RecordState::Torn => {
+                            info.used += 1;
+                            continue;
+                        }
+                        RecordState::Valid(record) => record,
+                    };
+                    info.used += 1;
+                    self.next_seq = self.next_seq.max(record.seq.wrapping_add(1));
+                    block_seq = block_seq.max(Some(record.seq));
+                    let logical = record.logical as usize;
+                    if logical < self.map.len()
+                        && (self.map[logical] == UNMAPPED || record.seq > seqs[logical])
+                    {
+                        self.map[logical] = slot;
+                        seqs[logical] = record.seq;
+                    }
+                }
+                // Data programmed without its record leaves the slot unusable
+                if info.used < self.slots_per_block {
+                    let slot = block * self.slots_per_block + info.used;
+                    if !self.slot_is_blank(slot)? {
+                        info.used += 1;
+                    }
+                }
+                if info.used > 0 {
+                    info.free = false;
+                    info.formatted = false;
+                    if block_seq > highest_seq && info.used < self.slots_per_block {
+                        highest_seq = block_seq;
+                        newest = Some(block);
+                    }
+                }
+            }
+            self.blocks.push(info);
+        }
+
+        // Blocks whose erase count was lost are assumed to be as worn as the worst
+        let max = self.blocks.iter().map(|b| b.erase_count).max().unwrap_or(0);
+        for block in unknown_wear {
+            self.blocks[block as usize].erase_count = max;
+        }
+        for &slot in self.map.iter().filter(|&&slot| slot != UNMAPPED) {
+            self.blocks[(slot / self.slots_per_block) as usize].valid += 1;
+        }
+        self.active = newest;
+
+        // A collection interrupted by power loss leaves no free block; finish it
+        if self.free_blocks() == 0 {
+            let victim = self.gc_victim().ok_or(NorFtlError::Corrupted)?;
+            let room = self
+                .active
+                .map_or(0, |b| self.slots_per_block - self.blocks[b as usize].used);
+            if self.blocks[victim as usize].valid > room {
+                return Err(NorFtlError::Corrupted);
+            }
+            self.relocate(victim)?;
+            self.recycle(victim)?;
+        }
+        Ok(())
+    }
+
+    /// Read and classify a block header
+    fn read_header(&self, block: u32) -> Result<HeaderState, NorFtlError> {
+        let mut header = [0u8; RECORD_SIZE as usize];
+        self.flash_mut()
+            .read(self.block_offset(block), &mut header)
+            .map_err(|_| NorFtlError::Flash)?;
+        let magic = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let erase_count = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let crc = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let retired = u32::from_le_bytes(header[12..16].try_into().unwrap());
+        if magic != BLOCK_MAGIC || crc != CRC32C.checksum(&header[0..8]) {
+            return Ok(HeaderState::Unformatted);
+        }
+        if retired != u32::MAX {
+            return Ok(HeaderState::Retired(erase_count));
+        }
+        Ok(HeaderState::Live(erase_count))
+    }
+
+    fn read_record(&self, slot: u32) -> Result<RecordState, NorFtlError> {
+        let mut raw = [0u8; RECORD_SIZE as usize];
+        self.flash_mut()
+            .read(self.record_offset(slot), &mut raw)
+            .map_err(|_| NorFtlError::Flash)?;
+        if raw.iter().all(|&b| b == 0xFF) {
+            return Ok(RecordState::Blank);
+        }
+        let word = |i: usize| u32::from_le_bytes(raw[i..i + 4].try_into().unwrap());
+        if word(12) != CRC32C.checksum(&raw[0..12]) {
+            return Ok(RecordState::Torn);
+        }
+        Ok(RecordState::Valid(Record {
+            logical: word(0),
+            seq: word(4),
+            data_crc: word(8),
+        }))
+    }
+
+    fn slot_is_blank(&self, slot: u32) -> Result<bool, NorFtlError> {
+        let mut data = [0u8; FTL_SECTOR_SIZE];
+        self.flash_mut()
+            .read(self.data_offset(slot), &mut data)
+            .map_err(|_| NorFtlError::Flash)?;
+        Ok(data.iter().all(|&b| b == 0xFF))
+    }
+
+    /// Read the current copy of a logical sector
+    fn read_sector(&self, logical: u32, data: &mut [u8]) -> Result<(), NorFtlError> {
+        let slot = *self
+            .map
+            .get(logical as usize)
+            .ok_or(NorFtlError::OutOfRange)?;
+        if slot == UNMAPPED {
+            data.fill(0);
+            return Ok(());
+        }
+        self.read_slot(slot, data)
+    }
+
+    /// Read a slot and verify it against its record
+    fn read_slot(&self, slot: u32, data: &mut [u8]) -> Result<(), NorFtlError> {
+        let RecordState::Valid(record) = self.read_record(slot)? else {
+            return Err(NorFtlError::Corrupted);
+        };
+        self.flash_mut()
+            .read(self.data_offset(slot), data)
+            .map_err(|_| NorFtlError::Flash)?;
+        if CRC32C.checksum(data) != record.data_crc {
+            return Err(NorFtlError::Corrupted);
+        }
+        Ok(())
+    }
+
+    /// Write a logical sector out of place
+    fn write_sector(&mut self, logical: u32, data: &[u8]) -> Result<(), NorFtlError> {
+        if logical >= self.sector_count {
+            return Err(NorFtlError::OutOfRange);
+        }
+        self.make_room()?;
+        self.program(logical, data)
+    }
+
+    /// Program `data` as the newest copy of `logical` into the active block
+    fn program(&mut self, logical: u32, data: &[u8]) -> Result<(), NorFtlError> {
+        let block = self.active.ok_or(NorFtlError::Corrupted)?;
+        let slot = block * self.slots_per_block + self.blocks[block as usize].used;
+        // The slot is consumed even if programming fails
+        self.blocks[block as usize].used += 1;
+
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        let mut record = [0u8; RECORD_SIZE as usize];
+        record[0..4].copy_from_slice(&logical.to_le_bytes());
+        record[4..8].copy_from_slice(&seq.to_le_bytes());
+        record[8..12].copy_from_slice(&CRC32C.checksum(data).to_le_bytes());
+        let crc = CRC32C.checksum(&record[0..12]);
+        record[12..16].copy_from_slice(&crc.to_le_bytes());
+
+        // Data first: a valid record always describes complete data
+        let (data_offset, record_offset) = (self.data_offset(slot), self.record_offset(slot));
+        let flash = self.flash.get_mut();
+        flash
+            .write(data_offset, data)
+            .map_err(|_| NorFtlError::Flash)?;
+        flash
+            .write(record_offset, &record)
+            .map_err(|_| NorFtlError::Flash)?;
+
+        let old = core::mem::replace(&mut self.map[logical as usize], slot);
+        if old != UNMAPPED {
+            self.blocks[(old / self.slots_per_block) as usize].valid -= 1;
+        }
+        self.blocks[block as usize].valid += 1;
+        Ok(())
+    }
+
+    /// Make sure the active block has a free slot, collecting garbage if needed
+    fn make_room(&mut self) -> Result<(), NorFtlError> {
+        let mut leveled = false;
+        while !self.active_has_room() {
+            if self.free_blocks() > 1 {
+                let block = self.least_worn_free_block().ok_or(NorFtlError::Corrupted)?;
+                self.open_block(block)?;
+                continue;
+            }
+            let victim = match self.cold_block() {
+                Some(cold) if !leveled => {
+                    leveled = true;
+                    cold
+                }
+                _ => self.gc_victim().ok_or(NorFtlError::Corrupted)?,
+            };
+            let block = self.least_worn_free_block().ok_or(NorFtlError::Corrupted)?;
+            self.open_block(block)?;
+            self.relocate(victim)?;
+            self.recycle(victim)?;
+        }
+        Ok(())
+    }
+
+    /// Make a free block the active block, erasing it first if needed
+    fn open_block(&mut self, block: u32) -> Result<(), NorFtlError> {
+        if !self.blocks[block as usize].formatted {
+            self.erase_block(block)?;
+        }
+        let info = &mut self.blocks[block as usize];
+        info.free = false;
+        info.formatted = false;
+        info.used = 0;
+        info.valid = 0;
+        self.active = Some(block);
+        Ok(())
+    }
+
+    /// Copy the valid slots of `victim` into the active block
+    fn relocate(&mut self, victim: u32) -> Result<(), NorFtlError> {
+        let mut data = [0u8; FTL_SECTOR_SIZE];
+        for index in 0..self.blocks[victim as usize].used {
+            let slot = victim * self.slots_per_block + index;
+            let RecordState::Valid(record) = self.read_record(slot)? else {
+                continue;
+            };
+            if self.map.get(record.logical as usize) != Some(&slot) {
+                continue;
+            }
+            self.read_slot(slot, &mut data)?;
+            // A new sequence number makes the copy win if power fails before the erase
+            self.program(record.logical, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Erase a block whose data has been moved and return it to the free pool
+    fn recycle(&mut self, block: u32) -> Result<(), NorFtlError> {
+        // Retire first so a half-erased block is never mistaken for live data
+        let offset = self.block_offset(block) + RETIRED_OFFSET;
+        self.flash
+            .get_mut()
+            .write(offset, &[0u8; 4])
+            .map_err(|_| NorFtlError::Flash)?;
+        self.erase_block(block)?;
+        let info = &mut self.blocks[block as usize];
+        info.free = true;
+        info.used = 0;
+        info.valid = 0;
+        if self.active == Some(block) {
+            self.active = None;
+        }
+        Ok(())
+    }
+
+    /// Erase a block and write a fresh header carrying its erase count
+    fn erase_block(&mut self, block: u32) -> Result<(), NorFtlError> {
+        let offset = self.block_offset(block);
+        let erase_count = self.blocks[block as usize].erase_count.saturating_add(1);
+        let flash = self.flash.get_mut();
+        flash
+            .erase(offset, offset + self.block_size)
+            .map_err(|_| NorFtlError::Flash)?;
+
+        let mut header = [0xFFu8; RECORD_SIZE as usize];
+        header[0..4].copy_from_slice(&BLOCK_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&erase_count.to_le_bytes());
+        let crc = CRC32C.checksum(&header[0..8]);
+        header[8..12].copy_from_slice(&crc.to_le_bytes());
+        flash
+            .write(offset, &header)
+            .map_err(|_| NorFtlError::Flash)?;
+
+        let info = &mut self.blocks[block as usize];
+        info.erase_count = erase_count;
+        info.formatted = true;
+        Ok(())
+    }
+}
+
+impl<F> BlockDevice<FTL_SECTOR_SIZE> for NorFlashFtl<F>
+where
+    F: NorFlash + ReadNorFlash,
+{
+    type Error = NorFtlError;
+    type Align = aligned::A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; FTL_SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        for (i, block) in data.iter_mut().enumerate() {
+            self.read_sector(block_address + i as u32, &mut block[..])?;
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; FTL_SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        for (i, block) in data.iter().enumerate() {
+            self.write_sector(block_address + i as u32, &block[..])?;
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.sector_count as u64 * FTL_SECTOR_SIZE as u64)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        // Every sector write is complete on flash when it returns
+        Ok(())
+    }
+}
+
+impl<F> BlockDevice<NOR_FLASH_BLOCK_SIZE> for NorFlashFtl<F>
+where
+    F: NorFlash + ReadNorFlash,
+{
+    type Error = NorFtlError;
+    type Align = aligned::A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; NOR_FLASH_BLOCK_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        for (i, block) in data.iter_mut().enumerate() {
+            let first = (block_address + i as u32) * SECTORS_PER_PAGE as u32;
+            for (j, sector) in block.chunks_exact_mut(FTL_SECTOR_SIZE).enumerate() {
+                self.read_sector(first + j as u32, sector)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; NOR_FLASH_BLOCK_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        for (i, block) in data.iter().enumerate() {
+            let first = (block_address + i as u32) * SECTORS_PER_PAGE as u32;
+            for (j, sector) in block.chunks_exact(FTL_SECTOR_SIZE).enumerate() {
+                self.write_sector(first + j as u32, sector)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        let pages = self.sector_count as u64 / SECTORS_PER_PAGE as u64;
+        Ok(pages * NOR_FLASH_BLOCK_SIZE as u64)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::block_on;
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind};
+
+    const ERASE: usize = 4096;
+    const BLOCKS: u32 = 16;
+
+    /// Mock NOR flash: programming only clears bits, and a power cut can be
+    /// scheduled after a number of program/erase operations
+    struct MockFlash {
+        data: Vec<u8>,
+        erases: Vec<u32>,
+        /// Operations left before the power fails; the failing one is torn
+        power_left: Option<usize>,
+    }
+
+    impl MockFlash {
+        fn new() -> Self {
+            Self {
+                data: vec![0xFF; ERASE * BLOCKS as usize],
+                erases: vec![0; BLOCKS as usize],
+                power_left: None,
+            }
+        }
+
+        /// Returns false if the power is off; the current operation is torn
+        fn powered(&mut self) -> bool {
+            match self.power_left.as_mut() {
+                Some(0) => false,
+                Some(left) => {
+                    *left -= 1;
+                    true
+                }
+                None => true,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct MockFlashError;
+
+    impl embedded_storage::nor_flash::NorFlashError for MockFlashError {
+        fn kind(&self) -> NorFlashErrorKind {
+            NorFlashErrorKind::Other
+        }
+    }
+
+    impl ErrorType for MockFlash {
+        type Error = MockFlashError;
+    }
+
+    impl ReadNorFlash for MockFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let start = offset as usize;
+            bytes.copy_from_slice(&self.data[start..start + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.data.len()
+        }
+    }
+
+    impl NorFlash for MockFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = ERASE;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            let range = from as usize..to as usize;
+            if !self.powered() {
+                // Half of the block is erased when the power fails
+                let mid = range.start + range.len() / 2;
+                self.data[range.start..mid].fill(0xFF);
+                return Err(MockFlashError);
+            }
+            self.data[range.clone()].fill(0xFF);
+            for block in range.step_by(ERASE) {
+                self.erases[block / ERASE] += 1;
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let powered = self.powered();
+            let len = if powered {
+                bytes.len()
+            } else {
+                bytes.len() / 2
+            };
+            let start = offset as usize;
+            for (cell, byte) in self.data[start..start + len].iter_mut().zip(bytes) {
+                *cell &= *byte;
+            }
+            if powered { Ok(()) } else { Err(MockFlashError) }
+        }
+    }
+
+    fn config() -> NorFtlConfig {
+        NorFtlConfig::new(0, BLOCKS).with_wear_leveling_threshold(8)
+    }
+
+    fn sector(fill: u8) -> Aligned<aligned::A4, [u8; FTL_SECTOR_SIZE]> {
+        Aligned([fill; FTL_SECTOR_SIZE])
+    }
+
+    async fn read_fill(ftl: &NorFlashFtl<MockFlash>, address: u32) -> u8 {
+        let mut buf = sector(0xAA);
+        ftl.read(address, core::slice::from_mut(&mut buf))
+            .await
+            .unwrap();
+        assert!(
+            buf.iter().all(|&b| b == buf[0]),
+            "sector {address} is mixed"
+        );
+        buf[0]
+    }
+
+    #[test]
+    fn test_read_write_and_remount() {
+        block_on(async {
+            let mut ftl = NorFlashFtl::mount(MockFlash::new(), config()).unwrap();
+            // 7 slots per 4 KB block, 2 spare blocks
+            assert_eq!(ftl.sector_count(), 14 * 7);
+            assert_eq!(
+                BlockDevice::<FTL_SECTOR_SIZE>::size(&ftl).await.unwrap(),
+                14 * 7 * 512
+            );
+            assert_eq!(read_fill(&ftl, 5).await, 0);
+
+            ftl.write(5, &[sector(1), sector(2)]).await.unwrap();
+            ftl.write(5, &[sector(3)]).await.unwrap();
+            let page = Aligned([9u8; NOR_FLASH_BLOCK_SIZE]);
+            ftl.write(2, core::slice::from_ref(&page)).await.unwrap();
+            assert!(matches!(
+                ftl.write(ftl.sector_count(), &[sector(1)]).await,
+                Err(NorFtlError::OutOfRange)
+            ));
+
+            let ftl = NorFlashFtl::mount(ftl.into_inner(), config()).unwrap();
+            assert_eq!(read_fill(&ftl, 5).await, 3);
+            assert_eq!(read_fill(&ftl, 6).await, 2);
+            assert_eq!(read_fill(&ftl, 16).await, 9);
+            let mut page = Aligned([0u8; NOR_FLASH_BLOCK_SIZE]);
+            ftl.read(2, core::slice::from_mut(&mut page)).await.unwrap();
+            assert!(page.iter().all(|&b| b == 9));
+        });
+    }
+
+    #[test]
+    fn test_garbage_collection_and_wear_leveling() {
+        block_on(async {
+            let mut ftl = NorFlashFtl::mount(MockFlash::new(), config()).unwrap();
+            let count = ftl.sector_count();
+            // Cold data over most of the device, then hammer a few hot sectors
+            for address in 0..count - 4 {
+                ftl.write(address, &[sector(address as u8)]).await.unwrap();
+            }
+            for round in 0..3000u32 {
+                let address = count - 4 + round % 4;
+                ftl.write(address, &[sector(round as u8)]).await.unwrap();
+            }
+
+            let stats = ftl.stats();
+            assert!(stats.free_blocks >= 1);
+            assert!(stats.min_erase_count > 1, "cold blocks were never moved");
+            assert!(
+                stats.max_erase_count - stats.min_erase_count <= 8 + 2,
+                "erase counts spread too far: {stats:?}"
+            );
+
+            let ftl = NorFlashFtl::mount(ftl.into_inner(), config()).unwrap();
+            for address in 0..count - 4 {
+                assert_eq!(read_fill(&ftl, address).await, address as u8);
+            }
+            for round in 2996..3000u32 {
+                assert_eq!(read_fill(&ftl, count - 4 + round % 4).await, round as u8);
+            }
+            let erases = ftl.into_inner().erases;
+            let (min, max) = (erases.iter().min().unwrap(), erases.iter().max().unwrap());
+            assert!(max - min <= 8 + 2, "flash erase counts: {erases:?}");
+        });
+    }
+
+    #[test]
+    fn test_power_loss_at_every_operation() {
+        block_on(async {
+            // Build a device that is full enough for writes to trigger collection
+            let mut ftl = NorFlashFtl::mount(MockFlash::new(), config()).unwrap();
+            let count = ftl.sector_count();
+            let mut expected = vec![0u8; count as usize];
+            for round in 0..2 * count {
+                let address = round * 7 % count;
+                expected[address as usize] = round as u8;
+                ftl.write(address, &[sector(round as u8)]).await.unwrap();
+            }
+            let base = ftl.into_inner();
+
+            for cut in 0..120 {
+                let mut flash = MockFlash {
+                    data: base.data.clone(),
+                    erases: base.erases.clone(),
+                    power_left: Some(cut),
+                };
+                let mut expected = expected.clone();
+                let mut interrupted = None;
+                {
+                    let mut ftl = NorFlashFtl::mount(flash, config()).unwrap();
+                    for i in 0..40u32 {
+                        let address = i * 3 % count;
+                        let fill = 0x80 | i as u8;
+                        if ftl.write(address, &[sector(fill)]).await.is_err() {
+                            interrupted = Some((address, fill));
+                            break;
+                        }
+                        expected[address as usize] = fill;
+                    }
+                    flash = ftl.into_inner();
+                }
+
+                flash.power_left = None;
+                let mut ftl = NorFlashFtl::mount(flash, config()).unwrap();
+                for address in 0..count {
+                    let fill = read_fill(&ftl, address).await;
+                    let torn = interrupted.is_some_and(|(a, f)| a == address && f == fill);
+                    assert!(
+                        fill == expected[address as usize] || torn,
+                        "cut {cut}: sector {address} reads {fill}"
+                    );
+                }
+                // The device keeps working after recovery
+                for i in 0..2 * count {
+                    ftl.write(i % count, &[sector(0x11)]).await.unwrap();
+                }
+                assert_eq!(read_fill(&ftl, count - 1).await, 0x11);
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 2 spare blocks")]
+    fn test_config_needs_spare_blocks() {
+        let _ = NorFtlConfig::new(0, 16).with_spare_blocks(1);
+    }
+}
@@ -0,0 +1,548 @@
+//! Copy-on-write overlay over a read-only base device.
+//!
+//! [`OverlayBlockDevice`] reads through to an immutable base device (a golden
+//! image, a production card) and redirects every write to a [`DeltaStore`].
+//! The base is never written until [`OverlayBlockDevice::commit`] merges the
+//! delta into it; [`OverlayBlockDevice::discard`] drops the changes instead.
+//!
+//! Two delta stores are provided:
+//!
+//! - [`RamDelta`]: modified blocks kept in memory.
+//! - [`DeviceDelta`]: modified blocks written at their own address to another
+//!   block device, e.g. a `StreamBlockDevice` over a sparse scratch file, so
+//!   only the blocks actually written take up space on disk.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fatrs_adapters::{DeviceDelta, OverlayBlockDevice};
+//! use fatrs_block_platform::StreamBlockDevice;
+//!
+//! let base = StreamBlockDevice::new(FromTokio::new(File::open("golden.img").await?));
+//! let scratch = StreamBlockDevice::new(FromTokio::new(File::create("golden.delta").await?));
+//! let mut overlay = OverlayBlockDevice::new(base, DeviceDelta::new(scratch));
+//!
+//! // ... mount, modify, inspect ...
+//!
+//! if keep_changes {
+//!     overlay.commit().await?;
+//! } else {
+//!     overlay.discard().await?;
+//! }
+//! ```
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::convert::Infallible;
+
+use aligned::Aligned;
+use fatrs_block_device::BlockDevice;
+
+/// Errors of an [`OverlayBlockDevice`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverlayError<B, D> {
+    /// Error from the base device.
+    Base(B),
+    /// Error from the delta store.
+    Delta(D),
+    /// A write extends past the end of the base device.
+    OutOfBounds,
+}
+
+impl<B: core::fmt::Display, D: core::fmt::Display> core::fmt::Display for OverlayError<B, D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Base(e) => write!(f, "Base device error: {}", e),
+            Self::Delta(e) => write!(f, "Delta store error: {}", e),
+            Self::OutOfBounds => write!(f, "Write past the end of the base device"),
+        }
+    }
+}
+
+impl<B, D> core::error::Error for OverlayError<B, D>
+where
+    B: core::fmt::Debug + core::fmt::Display,
+    D: core::fmt::Debug + core::fmt::Display,
+{
+}
+
+/// Storage for the blocks written through an [`OverlayBlockDevice`].
+pub trait DeltaStore<const SIZE: usize> {
+    /// The error type of the store.
+    type Error: core::fmt::Debug;
+
+    /// Whether the store holds a copy of `block`.
+    fn contains(&self, block: u32) -> bool;
+
+    /// Number of blocks held.
+    fn len(&self) -> usize;
+
+    /// Whether no block is held.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Addresses of the blocks held, in ascending order.
+    fn blocks(&self) -> impl Iterator<Item = u32> + '_;
+
+    /// Read a block the store holds.
+    async fn read(&self, block: u32, data: &mut [u8; SIZE]) -> Result<(), Self::Error>;
+
+    /// Store a block, replacing any previous copy.
+    async fn write(&mut self, block: u32, data: &[u8; SIZE]) -> Result<(), Self::Error>;
+
+    /// Forget every block.
+    async fn clear(&mut self) -> Result<(), Self::Error>;
+
+    /// Make the stored blocks durable.
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Delta store keeping modified blocks in memory.
+#[derive(Debug, Clone, Default)]
+pub struct RamDelta<const SIZE: usize> {
+    blocks: BTreeMap<u32, Box<[u8; SIZE]>>,
+}
+
+impl<const SIZE: usize> RamDelta<SIZE> {
+    /// Create an empty store.
+    pub const fn new() -> Self {
+        Self {
+            blocks: BTreeMap::new(),
+        }
+    }
+}
+
+impl<const SIZE: usize> DeltaStore<SIZE> for RamDelta<SIZE> {
+    type Error = Infallible;
+
+    fn contains(&self, block: u32) -> bool {
+        self.blocks.contains_key(&block)
+    }
+
+    fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = u32> + '_ {
+        self.blocks.keys().copied()
+    }
+
+    async fn read(&self, block: u32, data: &mut [u8; SIZE]) -> Result<(), Self::Error> {
+        if let Some(stored) = self.blocks.get(&block) {
+            data.copy_from_slice(&stored[..]);
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, block: u32, data: &[u8; SIZE]) -> Result<(), Self::Error> {
+        match self.blocks.get_mut(&block) {
+            Some(stored) => stored.copy_from_slice(data),
+            None => {
+                self.blocks.insert(block, Box::new(*data));
+            }
+        }
+        Ok(())
+    }
+
+    async fn clear(&mut self) -> Result<(), Self::Error> {
+        self.blocks.clear();
+        Ok(())
+    }
+}
+
+/// Delta store writing modified blocks to another block device.
+///
+/// Each block is stored at its own address on `device`, which must therefore
+/// be addressable up to the size of the base device. The set of stored blocks
+/// is tracked in memory, so the content of `device` is only meaningful to the
+/// overlay that wrote it. Backing it with a sparse file keeps the space used
+/// proportional to the number of modified blocks.
+pub struct DeviceDelta<D> {
+    device: D,
+    present: BTreeSet<u32>,
+}
+
+impl<D> DeviceDelta<D> {
+    /// Create an empty store on `device`.
+    pub const fn new(device: D) -> Self {
+        Self {
+            device,
+            present: BTreeSet::new(),
+        }
+    }
+
+    /// Get a reference to the underlying device.
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Consume the store and return the underlying device.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D: BlockDevice<SIZE>, const SIZE: usize> DeltaStore<SIZE> for DeviceDelta<D> {
+    type Error = D::Error;
+
+    fn contains(&self, block: u32) -> bool {
+        self.present.contains(&block)
+    }
+
+    fn len(&self) -> usize {
+        self.present.len()
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = u32> + '_ {
+        self.present.iter().copied()
+    }
+
+    async fn read(&self, block: u32, data: &mut [u8; SIZE]) -> Result<(), Self::Error> {
+        let mut buf: Aligned<D::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        self.device
+            .read(block, core::slice::from_mut(&mut buf))
+            .await?;
+        data.copy_from_slice(&buf[..]);
+        Ok(())
+    }
+
+    async fn write(&mut self, block: u32, data: &[u8; SIZE]) -> Result<(), Self::Error> {
+        let buf: Aligned<D::Align, [u8; SIZE]> = Aligned(*data);
+        self.device
+            .write(block, core::slice::from_ref(&buf))
+            .await?;
+        self.present.insert(block);
+        Ok(())
+    }
+
+    async fn clear(&mut self) -> Result<(), Self::Error> {
+        self.present.clear();
+        Ok(())
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.device.sync().await
+    }
+}
+
+/// Block device reading through to a base device and writing to a delta store.
+///
+/// Reads of blocks that were never written through the overlay are served
+/// from the base (in runs, so sequential reads stay sequential); all other
+/// reads and every write go to the delta. [`sync`](BlockDevice::sync) only
+/// syncs the delta: the base is written by [`commit`](Self::commit) alone.
+pub struct OverlayBlockDevice<B, S, const SIZE: usize> {
+    base: B,
+    delta: S,
+}
+
+impl<B, const SIZE: usize> OverlayBlockDevice<B, RamDelta<SIZE>, SIZE> {
+    /// Create an overlay keeping all changes in memory.
+    pub const fn in_memory(base: B) -> Self {
+        Self::new(base, RamDelta::new())
+    }
+}
+
+impl<B, S, const SIZE: usize> OverlayBlockDevice<B, S, SIZE> {
+    /// Create an overlay over `base` storing changes in `delta`.
+    pub const fn new(base: B, delta: S) -> Self {
+        Self { base, delta }
+    }
+
+    /// Get a reference to the base device.
+    pub fn base(&self) -> &B {
+        &self.base
+    }
+
+    /// Get a reference to the delta store.
+    pub fn delta(&self) -> &S {
+        &self.delta
+    }
+
+    /// Consume the overlay and return the base device and the delta store.
+    pub fn into_parts(self) -> (B, S) {
+        (self.base, self.delta)
+    }
+}
+
+impl<B, S, const SIZE: usize> OverlayBlockDevice<B, S, SIZE>
+where
+    B: BlockDevice<SIZE>,
+    S: DeltaStore<SIZE>,
+{
+    /// Whether `block` has been written through the overlay.
+    pub fn is_modified(&self, block: u32) -> bool {
+        self.delta.contains(block)
+    }
+
+    /// Number of blocks written through the overlay.
+    pub fn modified_blocks(&self) -> usize {
+        self.delta.len()
+    }
+
+    /// Write every modified block to the base device, sync it and clear the delta.
+    ///
+    /// Blocks are written in ascending order. If the commit fails part way, the
+    /// delta is left intact and the commit can be retried.
+    ///
+    /// # Errors
+    ///
+    /// * `OverlayError::Base` if writing or syncing the base device failed.
+    /// * `OverlayError::Delta` if reading or clearing the delta failed.
+    pub async fn commit(&mut self) -> Result<(), OverlayError<B::Error, S::Error>> {
+        let mut buf: Aligned<B::Align, [u8; SIZE]> = Aligned([0; SIZE]);
+        for block in self.delta.blocks() {
+            self.delta
+                .read(block, &mut buf)
+                .await
+                .map_err(OverlayError::Delta)?;
+            self.base
+                .write(block, core::slice::from_ref(&buf))
+                .await
+                .map_err(OverlayError::Base)?;
+        }
+        self.base.sync().await.map_err(OverlayError::Base)?;
+        self.delta.clear().await.map_err(OverlayError::Delta)
+    }
+
+    /// Drop every change, making the overlay read the base again.
+    ///
+    /// # Errors
+    ///
+    /// * `OverlayError::Delta` if clearing the delta failed.
+    pub async fn discard(&mut self) -> Result<(), OverlayError<B::Error, S::Error>> {
+        self.delta.clear().await.map_err(OverlayError::Delta)
+    }
+}
+
+impl<B, S, const SIZE: usize> BlockDevice<SIZE> for OverlayBlockDevice<B, S, SIZE>
+where
+    B: BlockDevice<SIZE>,
+    S: DeltaStore<SIZE>,
+{
+    type Error = OverlayError<B::Error, S::Error>;
+    type Align = B::Align;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        if self.delta.is_empty() {
+            return self
+                .base
+                .read(block_address, data)
+                .await
+                .map_err(OverlayError::Base);
+        }
+
+        let mut start = 0;
+        while start < data.len() {
+            let block = block_address + start as u32;
+            if self.delta.contains(block) {
+                self.delta
+                    .read(block, &mut data[start])
+                    .await
+                    .map_err(OverlayError::Delta)?;
+                start += 1;
+                continue;
+            }
+
+            let mut end = start + 1;
+            while end < data.len() && !self.delta.contains(block_address + end as u32) {
+                end += 1;
+            }
+            self.base
+                .read(block, &mut data[start..end])
+                .await
+                .map_err(OverlayError::Base)?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        // The delta would accept any address; only the base knows the size
+        let end = (u64::from(block_address) + data.len() as u64) * SIZE as u64;
+        if end > self.size().await? {
+            return Err(OverlayError::OutOfBounds);
+        }
+        for (i, block) in data.iter().enumerate() {
+            self.delta
+                .write(block_address + i as u32, block)
+                .await
+                .map_err(OverlayError::Delta)?;
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        self.base.size().await.map_err(OverlayError::Base)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.delta.sync().await.map_err(OverlayError::Delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::{MockBlockDevice, block_on};
+
+    const BLOCK: usize = 512;
+    const BLOCKS: u32 = 64;
+
+    /// Base device whose block `i` initially holds the byte `i`
+    fn mock() -> MockBlockDevice<BLOCK> {
+        MockBlockDevice::patterned(BLOCKS)
+    }
+
+    fn blocks(n: usize) -> Vec<Aligned<aligned::A4, [u8; BLOCK]>> {
+        vec![Aligned([0; BLOCK]); n]
+    }
+
+    fn filled(value: u8) -> [Aligned<aligned::A4, [u8; BLOCK]>; 1] {
+        [Aligned([value; BLOCK])]
+    }
+
+    #[test]
+    fn writes_go_to_delta_and_reads_merge() {
+        block_on(async {
+            let mut overlay = OverlayBlockDevice::in_memory(mock());
+            overlay.write(3, &filled(0xAA)).await.unwrap();
+            overlay.write(5, &filled(0xBB)).await.unwrap();
+            overlay.sync().await.unwrap();
+
+            assert_eq!(overlay.base().writes, 0);
+            assert_eq!(overlay.base().syncs, 0);
+            assert_eq!(overlay.base().block(3), [3; BLOCK]);
+            assert_eq!(overlay.modified_blocks(), 2);
+            assert!(overlay.is_modified(5));
+            assert!(!overlay.is_modified(4));
+
+            let mut buf = blocks(6);
+            overlay.read(1, &mut buf).await.unwrap();
+            let firsts: Vec<u8> = buf.iter().map(|b| b[0]).collect();
+            assert_eq!(firsts, [1, 2, 0xAA, 4, 0xBB, 6]);
+        });
+    }
+
+    #[test]
+    fn commit_merges_into_base() {
+        block_on(async {
+            let mut overlay = OverlayBlockDevice::in_memory(mock());
+            overlay.write(7, &filled(0x11)).await.unwrap();
+            overlay.write(2, &filled(0x22)).await.unwrap();
+            overlay.write(7, &filled(0x33)).await.unwrap();
+
+            overlay.commit().await.unwrap();
+            assert_eq!(overlay.modified_blocks(), 0);
+            assert_eq!(overlay.base().writes, 2);
+            assert_eq!(overlay.base().syncs, 1);
+            assert_eq!(overlay.base().block(2), [0x22; BLOCK]);
+            assert_eq!(overlay.base().block(7), [0x33; BLOCK]);
+
+            let mut buf = blocks(1);
+            overlay.read(7, &mut buf).await.unwrap();
+            assert_eq!(buf[0][0], 0x33);
+        });
+    }
+
+    #[test]
+    fn discard_restores_base_view() {
+        block_on(async {
+            let mut overlay = OverlayBlockDevice::in_memory(mock());
+            overlay
+                .write(0, &[Aligned([9; BLOCK]), Aligned([9; BLOCK])])
+                .await
+                .unwrap();
+            overlay.discard().await.unwrap();
+
+            let mut buf = blocks(2);
+            overlay.read(0, &mut buf).await.unwrap();
+            assert_eq!(buf[0][0], 0);
+            assert_eq!(buf[1][0], 1);
+            assert_eq!(overlay.base().writes, 0);
+        });
+    }
+
+    #[test]
+    fn writes_past_base_end_are_rejected() {
+        block_on(async {
+            let mut overlay = OverlayBlockDevice::in_memory(mock());
+            assert_eq!(
+                overlay.write(BLOCKS, &filled(1)).await,
+                Err(OverlayError::OutOfBounds)
+            );
+            assert_eq!(
+                overlay
+                    .write(BLOCKS - 1, &[Aligned([1; BLOCK]), Aligned([1; BLOCK])])
+                    .await,
+                Err(OverlayError::OutOfBounds)
+            );
+            assert_eq!(overlay.modified_blocks(), 0);
+
+            overlay.write(BLOCKS - 1, &filled(1)).await.unwrap();
+            assert!(overlay.is_modified(BLOCKS - 1));
+        });
+    }
+
+    #[test]
+    fn device_delta_stores_blocks_on_scratch_device() {
+        block_on(async {
+            let scratch = mock();
+            let mut overlay = OverlayBlockDevice::new(mock(), DeviceDelta::new(scratch));
+            overlay.write(10, &filled(0x5A)).await.unwrap();
+            overlay.sync().await.unwrap();
+
+            let scratch = overlay.delta().device();
+            assert_eq!(scratch.block(10), [0x5A; BLOCK]);
+            assert_eq!(scratch.syncs, 1);
+            assert_eq!(overlay.base().block(10), [10; BLOCK]);
+
+            let mut buf = blocks(3);
+            overlay.read(9, &mut buf).await.unwrap();
+            assert_eq!([buf[0][0], buf[1][0], buf[2][0]], [9, 0x5A, 11]);
+
+            overlay.commit().await.unwrap();
+            let (base, delta) = overlay.into_parts();
+            assert_eq!(base.block(10), [0x5A; BLOCK]);
+            assert!(delta.is_empty());
+        });
+    }
+
+    #[test]
+    fn blocks_of_4096_bytes() {
+        block_on(async {
+            let base = MockBlockDevice::<4096>::patterned(8);
+            let scratch = MockBlockDevice::<4096>::new(8 * 4096);
+            let mut overlay = OverlayBlockDevice::new(base, DeviceDelta::new(scratch));
+            overlay
+                .write(3, &[Aligned([0xAA; 4096]), Aligned([0xBB; 4096])])
+                .await
+                .unwrap();
+            assert_eq!(overlay.delta().device().block(4), [0xBB; 4096]);
+            assert_eq!(overlay.base().block(4), [4; 4096]);
+
+            let mut buf = vec![Aligned([0; 4096]); 4];
+            overlay.read(2, &mut buf).await.unwrap();
+            let lasts: Vec<u8> = buf.iter().map(|b| b[4095]).collect();
+            assert_eq!(lasts, [2, 0xAA, 0xBB, 5]);
+
+            overlay.commit().await.unwrap();
+            let (base, _) = overlay.into_parts();
+            assert_eq!(base.block(3), [0xAA; 4096]);
+            assert_eq!(base.writes, 2);
+        });
+    }
+}
@@ -0,0 +1,171 @@
+//! Stack-allocated multi-page cache adapter (const generic sizing).
+
+use core::ops::Range;
+
+use crate::{
+    adapters::{BlockDeviceAdapter, error::AdapterError},
+    domain::{CachePolicy, CacheStats, PageCache, PageConfig, PageNumber, StackCacheMemory},
+};
+use fatrs_block_device::BlockDevice;
+
+/// Stack-allocated write-back cache of `PAGES` pages of `N` bytes.
+///
+/// The multi-page counterpart of `StackBuffer`: pages stay resident until
+/// evicted, so switching between a few hot pages (a FAT sector and a data
+/// sector, say) costs no I/O. All memory is inline, making it suitable for
+/// `no_std` targets without an allocator.
+///
+/// # Type Parameters
+///
+/// - `D`: The block device type
+/// - `N`: Page size in bytes (must be a multiple of BLOCK_SIZE)
+/// - `PAGES`: Number of pages held (must be non-zero)
+/// - `BLOCK_SIZE`: The block size in bytes (must match the device's block size)
+///
+/// # Examples
+///
+/// ```ignore
+/// use fatrs_adapters::adapters::StackCache;
+///
+/// // Eight 4KB pages with 512-byte blocks
+/// let device = MyBlockDevice::new();
+/// let mut cache = StackCache::<_, 4096, 8, 512>::new(device);
+///
+/// cache.detect_fat_region().await?;
+/// cache.page_mut(3).await?[0] = 42;
+/// cache.flush().await?;
+/// ```
+pub struct StackCache<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    inner: PageCache<BlockDeviceAdapter<D, BLOCK_SIZE>, StackCacheMemory<N, PAGES>, BLOCK_SIZE>,
+}
+
+impl<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize>
+    StackCache<D, N, PAGES, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    /// Create a new cache with the default policy (LRU, FAT written last).
+    pub fn new(device: D) -> Self {
+        Self::with_policy(device, CachePolicy::new())
+    }
+
+    /// Create a new cache with the given eviction and write-back policy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not a multiple of `BLOCK_SIZE` or `PAGES` is zero.
+    pub fn with_policy(device: D, policy: CachePolicy) -> Self {
+        let adapter = BlockDeviceAdapter::new(device);
+        let config = PageConfig::new(N, N / BLOCK_SIZE);
+        let inner = PageCache::new(adapter, config, StackCacheMemory::new(), policy)
+            .expect("cache memory page size matches the page config");
+
+        Self { inner }
+    }
+
+    /// Get a page for reading, loading it on a miss.
+    pub async fn page(&mut self, page_num: u32) -> Result<&[u8], AdapterError<D::Error>> {
+        self.inner
+            .page(PageNumber::new(page_num))
+            .await
+            .map_err(AdapterError::from_domain)
+    }
+
+    /// Get a page for modification, loading it on a miss (marks page as dirty).
+    pub async fn page_mut(&mut self, page_num: u32) -> Result<&mut [u8], AdapterError<D::Error>> {
+        self.inner
+            .page_mut(PageNumber::new(page_num))
+            .await
+            .map_err(AdapterError::from_domain)
+    }
+
+    /// Get a page that is about to be overwritten completely, without reading it on a miss.
+    pub async fn overwrite(&mut self, page_num: u32) -> Result<&mut [u8], AdapterError<D::Error>> {
+        self.inner
+            .overwrite(PageNumber::new(page_num))
+            .await
+            .map_err(AdapterError::from_domain)
+    }
+
+    /// Write back every dirty page and flush the device.
+    pub async fn flush(&mut self) -> Result<(), AdapterError<D::Error>> {
+        self.inner.flush().await.map_err(AdapterError::from_domain)
+    }
+
+    /// Drop every cached page (discards uncommitted changes).
+    pub fn discard(&mut self) {
+        self.inner.discard();
+    }
+
+    /// Check if a page is resident.
+    pub fn contains(&self, page_num: u32) -> bool {
+        self.inner.contains(PageNumber::new(page_num))
+    }
+
+    /// Get the number of dirty pages.
+    pub fn dirty_pages(&self) -> usize {
+        self.inner.dirty_pages()
+    }
+
+    /// Get the hit/miss and write-back counters.
+    pub const fn stats(&self) -> CacheStats {
+        self.inner.stats()
+    }
+
+    /// Reset the counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.inner.reset_stats();
+    }
+
+    /// Get the eviction and write-back policy.
+    pub const fn policy(&self) -> &CachePolicy {
+        self.inner.policy()
+    }
+
+    /// Set or clear the byte range occupied by the FATs.
+    pub fn set_fat_region(&mut self, region: Option<Range<u64>>) {
+        self.inner.set_fat_region(region);
+    }
+
+    /// Locate the FATs from the boot sector at the start of the device.
+    pub async fn detect_fat_region(
+        &mut self,
+    ) -> Result<Option<Range<u64>>, AdapterError<D::Error>> {
+        self.inner
+            .detect_fat_region()
+            .await
+            .map_err(AdapterError::from_domain)
+    }
+
+    /// Get storage size in bytes.
+    pub async fn size(&mut self) -> Result<u64, AdapterError<D::Error>> {
+        self.inner
+            .storage_size()
+            .await
+            .map_err(AdapterError::from_domain)
+    }
+
+    /// Get storage size in pages.
+    pub async fn size_in_pages(&mut self) -> Result<u64, AdapterError<D::Error>> {
+        self.inner
+            .storage_size_in_pages()
+            .await
+            .map_err(AdapterError::from_domain)
+    }
+
+    /// Get the page configuration.
+    pub const fn config(&self) -> &PageConfig<BLOCK_SIZE> {
+        self.inner.config()
+    }
+}
+
+/// Type alias for a cache of 4KB pages with 512-byte blocks.
+pub type StackCache4K<D, const PAGES: usize> = StackCache<D, 4096, PAGES, 512>;
+
+/// Type alias for a cache of 4KB pages with 4096-byte blocks.
+pub type StackCache4KBlock4K<D, const PAGES: usize> = StackCache<D, 4096, PAGES, 4096>;
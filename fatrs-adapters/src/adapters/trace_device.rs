@@ -0,0 +1,821 @@
+//! Block I/O trace recording and deterministic replay.
+//!
+//! [`RecordingBlockDevice`] wraps any [`BlockDevice`] and appends every read,
+//! write and sync to a compact binary trace written to an
+//! [`embedded_io_async::Write`] sink (a file on a dev machine, a spare flash
+//! partition or a RAM buffer in the field). [`TraceReplayer`] reads such a
+//! trace back and re-executes it against another device, typically a copy of
+//! the image the trace was recorded on. Replaying only the first `n`
+//! operations and checking the result lets the operation that first produced
+//! a bad state be bisected.
+//!
+//! # Trace Format
+//!
+//! ```text
+//! Header (12 bytes): "FBTR" | version u8 | mode u8 | reserved u16 | block size u32
+//! Record:            op u8 | block u32 | count u32 | payload      (read, write)
+//!                    op u8                                        (sync)
+//! ```
+//!
+//! All integers are little endian. `op` is 0 (read), 1 (write) or 2 (sync),
+//! with bit 7 set if the inner device returned an error, in which case the
+//! record has no payload. A record with a payload covers at most 1024 blocks;
+//! longer operations are split. The payload holds, per block, either its CRC32C
+//! ([`TraceMode::Hashes`]) or its full content ([`TraceMode::FullData`]).
+//! Only full-data traces can replay writes; hash traces can still verify
+//! that an image reads back what the device read when the trace was taken.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use fatrs_adapters::{RecordingBlockDevice, TraceMode, TraceReplayer};
+//!
+//! // On the device
+//! let device = RecordingBlockDevice::<_, _, 512>::new(sd_card, trace_file, TraceMode::FullData);
+//! let fs = FileSystem::new(BufStream::new(device), FsOptions::new()).await?;
+//! // ...
+//!
+//! // On the dev machine, replay the first 1200 operations onto a copy of the image
+//! let mut replayer = TraceReplayer::<_, 512>::open(trace).await?;
+//! let report = replayer.replay(&mut image, Some(1200)).await?;
+//! assert_eq!(report.first_divergence, None);
+//! ```
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+
+use aligned::Aligned;
+use async_lock::Mutex;
+use crc::{CRC_32_ISCSI, Crc};
+use embedded_io_async::{Read, ReadExactError, Write};
+use fatrs_block_device::BlockDevice;
+
+const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+const TRACE_MAGIC: [u8; 4] = *b"FBTR";
+const TRACE_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 12;
+
+const OP_READ: u8 = 0;
+const OP_WRITE: u8 = 1;
+const OP_SYNC: u8 = 2;
+const OP_FAILED: u8 = 0x80;
+
+/// Buffered trace bytes are written to the sink once they exceed this size.
+const FLUSH_THRESHOLD: usize = 64 * 1024;
+
+/// Most blocks a record carries a payload for, so a replayer never has to
+/// trust a count read from the trace with an unbounded allocation.
+const MAX_RECORD_BLOCKS: usize = 1024;
+
+/// Errors of trace recording and replay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceError<D, I> {
+    /// Error from the block device.
+    Device(D),
+    /// Error from the trace sink or source.
+    Io(I),
+    /// The trace is malformed, truncated or recorded with another block size.
+    InvalidTrace,
+    /// The trace only holds hashes, so its writes cannot be replayed.
+    NotReplayable,
+}
+
+impl<D: core::fmt::Display, I: core::fmt::Display> core::fmt::Display for TraceError<D, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Device(e) => write!(f, "Device error: {}", e),
+            Self::Io(e) => write!(f, "Trace I/O error: {}", e),
+            Self::InvalidTrace => write!(f, "Invalid or truncated trace"),
+            Self::NotReplayable => write!(f, "Trace holds hashes only and cannot replay writes"),
+        }
+    }
+}
+
+impl<D, I> core::error::Error for TraceError<D, I>
+where
+    D: core::fmt::Debug + core::fmt::Display,
+    I: core::fmt::Debug + core::fmt::Display,
+{
+}
+
+/// What a trace stores for every block read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    /// A CRC32C per block: 4 bytes per block, enough to detect divergence.
+    Hashes,
+    /// The full block content: replayable, and reconstructs everything read.
+    FullData,
+}
+
+impl TraceMode {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Hashes => 0,
+            Self::FullData => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Hashes),
+            1 => Some(Self::FullData),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of a traced operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOp {
+    /// A block read.
+    Read,
+    /// A block write.
+    Write,
+    /// A sync.
+    Sync,
+}
+
+/// One operation read back from a trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Position of the operation in the trace, starting at 0.
+    pub index: u64,
+    /// Kind of operation.
+    pub op: TraceOp,
+    /// First block (0 for syncs).
+    pub block: u32,
+    /// Number of blocks (0 for syncs).
+    pub count: u32,
+    /// Whether the inner device returned an error.
+    pub failed: bool,
+    /// Per-block hashes or data, depending on the [`TraceMode`]; empty for failed operations and syncs.
+    pub payload: Vec<u8>,
+}
+
+/// Outcome of [`TraceReplayer::replay`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Operations processed.
+    pub operations: u64,
+    /// Reads whose result was compared with the trace.
+    pub reads_verified: u64,
+    /// Reads that returned different content than when the trace was recorded.
+    pub read_mismatches: u64,
+    /// Operations that failed while recording and were skipped.
+    pub skipped_failures: u64,
+    /// Index of the first read that returned different content, if any.
+    pub first_divergence: Option<u64>,
+}
+
+/// Block device wrapper recording every operation to a trace.
+///
+/// Records are buffered in memory and written to the sink once the buffer
+/// grows past 64 KiB, on every [`sync`](BlockDevice::sync), and by
+/// [`flush_trace`](Self::flush_trace) and [`finish`](Self::finish). Reads take
+/// `&self`, so the sink and the buffer sit behind an async mutex.
+pub struct RecordingBlockDevice<D, W, const SIZE: usize> {
+    inner: D,
+    mode: TraceMode,
+    recorder: Mutex<Recorder<W>>,
+    operations: Cell<u64>,
+}
+
+/// Trace sink and the records not yet written to it.
+struct Recorder<W> {
+    sink: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Recorder<W> {
+    async fn flush_if_full(&mut self) -> Result<(), W::Error> {
+        if self.pending.len() >= FLUSH_THRESHOLD {
+            self.sink.write_all(&self.pending).await?;
+            self.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<D, W, const SIZE: usize> RecordingBlockDevice<D, W, SIZE>
+where
+    D: BlockDevice<SIZE>,
+    W: Write,
+{
+    /// Start recording operations on `inner` to `sink`.
+    ///
+    /// The trace header is buffered immediately and reaches the sink with the first flush.
+    pub fn new(inner: D, sink: W, mode: TraceMode) -> Self {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(&TRACE_MAGIC);
+        header.push(TRACE_VERSION);
+        header.push(mode.to_byte());
+        header.extend_from_slice(&[0, 0]);
+        header.extend_from_slice(&(SIZE as u32).to_le_bytes());
+
+        Self {
+            inner,
+            mode,
+            recorder: Mutex::new(Recorder {
+                sink,
+                pending: header,
+            }),
+            operations: Cell::new(0),
+        }
+    }
+
+    /// Get a reference to the inner device.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// The recording mode.
+    pub fn mode(&self) -> TraceMode {
+        self.mode
+    }
+
+    /// Number of operations recorded so far.
+    pub fn operations(&self) -> u64 {
+        self.operations.get()
+    }
+
+    /// Write all buffered records to the sink and flush it.
+    ///
+    /// # Errors
+    ///
+    /// * `TraceError::Io` if the sink returned an error; the records stay buffered.
+    pub async fn flush_trace(&mut self) -> Result<(), TraceError<D::Error, W::Error>> {
+        let recorder = self.recorder.get_mut();
+        if !recorder.pending.is_empty() {
+            recorder
+                .sink
+                .write_all(&recorder.pending)
+                .await
+                .map_err(TraceError::Io)?;
+            recorder.pending.clear();
+        }
+        recorder.sink.flush().await.map_err(TraceError::Io)
+    }
+
+    /// Flush the trace and return the inner device and the sink.
+    ///
+    /// # Errors
+    ///
+    /// * `TraceError::Io` if the sink returned an error.
+    pub async fn finish(mut self) -> Result<(D, W), TraceError<D::Error, W::Error>> {
+        self.flush_trace().await?;
+        Ok((self.inner, self.recorder.into_inner().sink))
+    }
+
+    /// Append a record (several for more than [`MAX_RECORD_BLOCKS`] blocks) and
+    /// write the buffer to the sink if it is full.
+    async fn record(
+        &self,
+        op: u8,
+        block: u32,
+        data: Option<&[Aligned<D::Align, [u8; SIZE]>]>,
+        count: usize,
+    ) -> Result<(), TraceError<D::Error, W::Error>> {
+        let mut recorder = self.recorder.lock().await;
+        let pending = &mut recorder.pending;
+        match data {
+            Some(blocks) => {
+                for (i, chunk) in blocks.chunks(MAX_RECORD_BLOCKS).enumerate() {
+                    let first = block + (i * MAX_RECORD_BLOCKS) as u32;
+                    pending.push(op);
+                    pending.extend_from_slice(&first.to_le_bytes());
+                    pending.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+                    for b in chunk {
+                        match self.mode {
+                            TraceMode::Hashes => {
+                                pending.extend_from_slice(&CRC32C.checksum(&b[..]).to_le_bytes())
+                            }
+                            TraceMode::FullData => pending.extend_from_slice(&b[..]),
+                        }
+                    }
+                    self.operations.set(self.operations.get() + 1);
+                }
+            }
+            None if op & !OP_FAILED == OP_SYNC => {
+                pending.push(op);
+                self.operations.set(self.operations.get() + 1);
+            }
+            None => {
+                pending.push(op);
+                pending.extend_from_slice(&block.to_le_bytes());
+                pending.extend_from_slice(&(count as u32).to_le_bytes());
+                self.operations.set(self.operations.get() + 1);
+            }
+        }
+        recorder.flush_if_full().await.map_err(TraceError::Io)
+    }
+}
+
+impl<D, W, const SIZE: usize> BlockDevice<SIZE> for RecordingBlockDevice<D, W, SIZE>
+where
+    D: BlockDevice<SIZE>,
+    W: Write,
+{
+    type Error = TraceError<D::Error, W::Error>;
+    type Align = D::Align;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        match self.inner.read(block_address, data).await {
+            Ok(()) => {
+                self.record(OP_READ, block_address, Some(data), data.len())
+                    .await
+            }
+            Err(e) => {
+                self.record(OP_READ | OP_FAILED, block_address, None, data.len())
+                    .await?;
+                Err(TraceError::Device(e))
+            }
+        }
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let result = self.inner.write(block_address, data).await;
+        match &result {
+            Ok(()) => {
+                self.record(OP_WRITE, block_address, Some(data), data.len())
+                    .await?;
+            }
+            Err(_) => {
+                self.record(OP_WRITE | OP_FAILED, block_address, None, data.len())
+                    .await?;
+            }
+        }
+        result.map_err(TraceError::Device)
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        self.inner.size().await.map_err(TraceError::Device)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        let result = self.inner.sync().await;
+        let op = if result.is_ok() {
+            OP_SYNC
+        } else {
+            OP_SYNC | OP_FAILED
+        };
+        self.record(op, 0, None, 0).await?;
+        self.flush_trace().await?;
+        result.map_err(TraceError::Device)
+    }
+}
+
+/// Reader re-executing a trace recorded by [`RecordingBlockDevice`].
+pub struct TraceReplayer<R, const SIZE: usize> {
+    source: R,
+    mode: TraceMode,
+    next_index: u64,
+}
+
+impl<R: Read, const SIZE: usize> TraceReplayer<R, SIZE> {
+    /// Read and validate the trace header.
+    ///
+    /// # Errors
+    ///
+    /// * `TraceError::InvalidTrace` if the header is malformed or the block size is not `SIZE`.
+    /// * `TraceError::Io` if the source returned an error.
+    pub async fn open<E>(mut source: R) -> Result<Self, TraceError<E, R::Error>> {
+        let mut header = [0u8; HEADER_SIZE];
+        read_exact(&mut source, &mut header).await?;
+        let block_size = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        let mode = TraceMode::from_byte(header[5]);
+        match mode {
+            Some(mode)
+                if header[0..4] == TRACE_MAGIC
+                    && header[4] == TRACE_VERSION
+                    && block_size as usize == SIZE =>
+            {
+                Ok(Self {
+                    source,
+                    mode,
+                    next_index: 0,
+                })
+            }
+            _ => Err(TraceError::InvalidTrace),
+        }
+    }
+
+    /// The mode the trace was recorded with.
+    pub fn mode(&self) -> TraceMode {
+        self.mode
+    }
+
+    /// Read the next record, or `None` at the end of the trace.
+    ///
+    /// # Errors
+    ///
+    /// * `TraceError::InvalidTrace` if the trace is malformed or truncated, or a
+    ///   record claims more blocks than a recorder writes.
+    /// * `TraceError::Io` if the source returned an error.
+    pub async fn next_record<E>(&mut self) -> Result<Option<TraceRecord>, TraceError<E, R::Error>> {
+        let mut op = [0u8; 1];
+        if self.source.read(&mut op).await.map_err(TraceError::Io)? == 0 {
+            return Ok(None);
+        }
+        let failed = op[0] & OP_FAILED != 0;
+        let kind = match op[0] & !OP_FAILED {
+            OP_READ => TraceOp::Read,
+            OP_WRITE => TraceOp::Write,
+            OP_SYNC => TraceOp::Sync,
+            _ => return Err(TraceError::InvalidTrace),
+        };
+
+        let mut record = TraceRecord {
+            index: self.next_index,
+            op: kind,
+            block: 0,
+            count: 0,
+            failed,
+            payload: Vec::new(),
+        };
+        self.next_index += 1;
+        if kind == TraceOp::Sync {
+            return Ok(Some(record));
+        }
+
+        let mut fields = [0u8; 8];
+        read_exact(&mut self.source, &mut fields).await?;
+        record.block = u32::from_le_bytes([fields[0], fields[1], fields[2], fields[3]]);
+        record.count = u32::from_le_bytes([fields[4], fields[5], fields[6], fields[7]]);
+        if !failed {
+            let per_block = match self.mode {
+                TraceMode::Hashes => 4,
+                TraceMode::FullData => SIZE,
+            };
+            if record.count as usize > MAX_RECORD_BLOCKS {
+                return Err(TraceError::InvalidTrace);
+            }
+            let len = (record.count as usize)
+                .checked_mul(per_block)
+                .ok_or(TraceError::InvalidTrace)?;
+            record.payload = vec![0; len];
+            read_exact(&mut self.source, &mut record.payload).await?;
+        }
+        Ok(Some(record))
+    }
+
+    /// Re-execute the trace against `device`, stopping after `limit` operations if given.
+    ///
+    /// Writes and syncs are applied; every read is performed and its result
+    /// compared with the trace. Operations that failed while recording are
+    /// skipped. Replay continues past diverging reads, so the report counts
+    /// them all.
+    ///
+    /// # Errors
+    ///
+    /// * `TraceError::NotReplayable` on a write in a [`TraceMode::Hashes`] trace.
+    /// * `TraceError::Device` if `device` returned an error.
+    /// * `TraceError::InvalidTrace` or `TraceError::Io` if the trace cannot be read.
+    pub async fn replay<D: BlockDevice<SIZE>>(
+        &mut self,
+        device: &mut D,
+        limit: Option<u64>,
+    ) -> Result<ReplayReport, TraceError<D::Error, R::Error>> {
+        let mut report = ReplayReport::default();
+        let mut blocks: Vec<Aligned<D::Align, [u8; SIZE]>> = Vec::new();
+
+        while limit.is_none_or(|limit| report.operations < limit) {
+            let Some(record) = self.next_record().await? else {
+                break;
+            };
+            report.operations += 1;
+            if record.failed {
+                report.skipped_failures += 1;
+                continue;
+            }
+
+            match record.op {
+                TraceOp::Sync => device.sync().await.map_err(TraceError::Device)?,
+                TraceOp::Write => {
+                    if self.mode == TraceMode::Hashes {
+                        return Err(TraceError::NotReplayable);
+                    }
+                    blocks.clear();
+                    blocks.extend(record.payload.chunks_exact(SIZE).map(|chunk| {
+                        let mut block = Aligned([0; SIZE]);
+                        block.copy_from_slice(chunk);
+                        block
+                    }));
+                    device
+                        .write(record.block, &blocks)
+                        .await
+                        .map_err(TraceError::Device)?;
+                }
+                TraceOp::Read => {
+                    blocks.clear();
+                    blocks.resize(record.count as usize, Aligned([0; SIZE]));
+                    device
+                        .read(record.block, &mut blocks)
+                        .await
+                        .map_err(TraceError::Device)?;
+
+                    let matches = match self.mode {
+                        TraceMode::Hashes => blocks
+                            .iter()
+                            .zip(record.payload.chunks_exact(4))
+                            .all(|(block, crc)| CRC32C.checksum(&block[..]).to_le_bytes() == crc),
+                        TraceMode::FullData => blocks
+                            .iter()
+                            .zip(record.payload.chunks_exact(SIZE))
+                            .all(|(block, expected)| block[..] == *expected),
+                    };
+                    report.reads_verified += 1;
+                    if !matches {
+                        report.read_mismatches += 1;
+                        report.first_divergence.get_or_insert(record.index);
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+async fn read_exact<R: Read, E>(
+    source: &mut R,
+    buf: &mut [u8],
+) -> Result<(), TraceError<E, R::Error>> {
+    source.read_exact(buf).await.map_err(|e| match e {
+        ReadExactError::UnexpectedEof => TraceError::InvalidTrace,
+        ReadExactError::Other(e) => TraceError::Io(e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::{MockBlockDevice, block_on};
+
+    const BLOCK: usize = 512;
+    const BLOCKS: u32 = 32;
+
+    fn mock() -> MockBlockDevice<BLOCK> {
+        MockBlockDevice::new(u64::from(BLOCKS) * BLOCK as u64)
+    }
+
+    fn contents(device: &MockBlockDevice<BLOCK>) -> Vec<[u8; BLOCK]> {
+        (0..BLOCKS).map(|i| device.block(i)).collect()
+    }
+
+    fn filled(values: &[u8]) -> Vec<Aligned<aligned::A4, [u8; BLOCK]>> {
+        values.iter().map(|&v| Aligned([v; BLOCK])).collect()
+    }
+
+    /// Record a small workload on a fresh device and return the trace and final device.
+    fn record(mode: TraceMode) -> (Vec<u8>, MockBlockDevice<BLOCK>) {
+        block_on(async {
+            let mut device = mock();
+            device.bad_block = Some(20);
+            let mut rec = RecordingBlockDevice::<_, _, BLOCK>::new(device, Vec::new(), mode);
+
+            rec.write(2, &filled(&[1, 2])).await.unwrap();
+            let mut buf = filled(&[0; 3]);
+            rec.read(1, &mut buf).await.unwrap();
+            assert!(rec.write(20, &filled(&[9])).await.is_err());
+            rec.sync().await.unwrap();
+            rec.write(3, &filled(&[7])).await.unwrap();
+            rec.read(3, &mut buf[..1]).await.unwrap();
+            assert_eq!(rec.operations(), 6);
+
+            let (device, trace) = rec.finish().await.unwrap();
+            (trace, device)
+        })
+    }
+
+    #[test]
+    fn full_trace_replays_to_identical_image() {
+        let (trace, recorded) = record(TraceMode::FullData);
+        block_on(async {
+            let mut image = mock();
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            let report = replayer.replay(&mut image, None).await.unwrap();
+
+            assert_eq!(report.operations, 6);
+            assert_eq!(report.reads_verified, 2);
+            assert_eq!(report.read_mismatches, 0);
+            assert_eq!(report.skipped_failures, 1);
+            assert_eq!(contents(&image), contents(&recorded));
+            assert_eq!(image.syncs, 1);
+        });
+    }
+
+    #[test]
+    fn replay_stops_at_limit() {
+        let (trace, _) = record(TraceMode::FullData);
+        block_on(async {
+            let mut image = mock();
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            let report = replayer.replay(&mut image, Some(2)).await.unwrap();
+
+            assert_eq!(report.operations, 2);
+            assert_eq!(image.block(2), [1; BLOCK]);
+            assert_eq!(image.block(3), [2; BLOCK]);
+            assert_eq!(image.syncs, 0);
+
+            // The rest of the trace continues where the first call stopped
+            let report = replayer.replay(&mut image, None).await.unwrap();
+            assert_eq!(report.operations, 4);
+            assert_eq!(image.block(3), [7; BLOCK]);
+        });
+    }
+
+    #[test]
+    fn hash_trace_detects_divergence_but_cannot_replay_writes() {
+        let (trace, recorded) = record(TraceMode::Hashes);
+        assert!(trace.len() < 200);
+        block_on(async {
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            let first = replayer.next_record::<()>().await.unwrap().unwrap();
+            assert_eq!((first.op, first.block, first.count), (TraceOp::Write, 2, 2));
+            assert_eq!(first.payload.len(), 8);
+
+            // Hashes verify reads: the state right after the first write
+            // matches, the final state (block 3 rewritten) diverges
+            let mut image = mock();
+            image.set_block(2, [1; BLOCK]);
+            image.set_block(3, [2; BLOCK]);
+            let report = replayer.replay(&mut image, Some(3)).await.unwrap();
+            assert_eq!(report.first_divergence, None);
+            assert_eq!(report.reads_verified, 1);
+
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            replayer.next_record::<()>().await.unwrap();
+            let report = replayer
+                .replay(&mut recorded.clone(), Some(3))
+                .await
+                .unwrap();
+            assert_eq!(report.first_divergence, Some(1));
+
+            // But the writes cannot be replayed
+            let mut image = recorded.clone();
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            assert_eq!(
+                replayer.replay(&mut image, Some(1)).await,
+                Err(TraceError::NotReplayable)
+            );
+        });
+    }
+
+    #[test]
+    fn reads_alone_flush_a_full_buffer() {
+        block_on(async {
+            let mut rec =
+                RecordingBlockDevice::<_, _, BLOCK>::new(mock(), Vec::new(), TraceMode::FullData);
+            let mut buf = filled(&[0; BLOCKS as usize]);
+            for _ in 0..3 {
+                rec.read(0, &mut buf).await.unwrap();
+            }
+            assert!(rec.recorder.get_mut().sink.is_empty());
+
+            // The fourth read of 16 KiB takes the buffer past 64 KiB
+            rec.read(0, &mut buf).await.unwrap();
+            let recorder = rec.recorder.get_mut();
+            assert!(recorder.sink.len() >= FLUSH_THRESHOLD);
+            assert!(recorder.pending.is_empty());
+        });
+    }
+
+    #[test]
+    fn long_operations_are_split_into_bounded_records() {
+        block_on(async {
+            let device = MockBlockDevice::<BLOCK>::new(2048 * BLOCK as u64);
+            let mut rec =
+                RecordingBlockDevice::<_, _, BLOCK>::new(device, Vec::new(), TraceMode::Hashes);
+            let data = vec![Aligned([3; BLOCK]); MAX_RECORD_BLOCKS + 6];
+            rec.write(10, &data).await.unwrap();
+            assert_eq!(rec.operations(), 2);
+            let (_, trace) = rec.finish().await.unwrap();
+
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            let first = replayer.next_record::<()>().await.unwrap().unwrap();
+            let second = replayer.next_record::<()>().await.unwrap().unwrap();
+            assert_eq!((first.block, first.count), (10, MAX_RECORD_BLOCKS as u32));
+            assert_eq!((second.block, second.count), (1034, 6));
+        });
+    }
+
+    #[test]
+    fn rejects_record_with_oversized_count() {
+        block_on(async {
+            let (mut trace, _) = record(TraceMode::FullData);
+            // Count of the first record, right after the header, op and block
+            let count = HEADER_SIZE + 5;
+            trace[count..count + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            assert_eq!(
+                replayer.next_record::<()>().await,
+                Err(TraceError::InvalidTrace)
+            );
+        });
+    }
+
+    #[test]
+    fn blocks_of_4096_bytes_replay() {
+        block_on(async {
+            let device = MockBlockDevice::<4096>::new(8 * 4096);
+            let mut rec =
+                RecordingBlockDevice::<_, _, 4096>::new(device, Vec::new(), TraceMode::FullData);
+            rec.write(1, &[Aligned([0xAA; 4096]), Aligned([0xBB; 4096])])
+                .await
+                .unwrap();
+            rec.sync().await.unwrap();
+            let mut buf = [Aligned([0; 4096])];
+            rec.read(2, &mut buf).await.unwrap();
+            let (recorded, trace) = rec.finish().await.unwrap();
+
+            let mut image = MockBlockDevice::<4096>::new(8 * 4096);
+            let mut replayer = TraceReplayer::<_, 4096>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            let report = replayer.replay(&mut image, None).await.unwrap();
+            assert_eq!(report.operations, 3);
+            assert_eq!((report.reads_verified, report.read_mismatches), (1, 0));
+            assert_eq!(image.block(1), recorded.block(1));
+            assert_eq!(image.block(2), [0xBB; 4096]);
+
+            // A 4096-byte trace does not replay onto 512-byte blocks
+            assert!(matches!(
+                TraceReplayer::<_, BLOCK>::open::<()>(&trace[..]).await,
+                Err(TraceError::InvalidTrace)
+            ));
+        });
+    }
+
+    #[test]
+    fn divergence_reports_first_mismatching_read() {
+        let (trace, _) = record(TraceMode::FullData);
+        block_on(async {
+            // Replaying the reads against an image that misses the first write
+            let mut image = mock();
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(&trace[..])
+                .await
+                .unwrap();
+            replayer.next_record::<()>().await.unwrap();
+            let report = replayer.replay(&mut image, None).await.unwrap();
+
+            assert_eq!(report.read_mismatches, 1);
+            assert_eq!(report.first_divergence, Some(1));
+        });
+    }
+
+    #[test]
+    fn rejects_foreign_trace() {
+        block_on(async {
+            let garbage = [0u8; 32];
+            assert!(matches!(
+                TraceReplayer::<_, BLOCK>::open::<()>(&garbage[..]).await,
+                Err(TraceError::InvalidTrace)
+            ));
+
+            let (trace, _) = record(TraceMode::Hashes);
+            assert!(matches!(
+                TraceReplayer::<_, 4096>::open::<()>(&trace[..]).await,
+                Err(TraceError::InvalidTrace)
+            ));
+
+            let truncated = &trace[..trace.len() - 3];
+            let mut replayer = TraceReplayer::<_, BLOCK>::open::<()>(truncated)
+                .await
+                .unwrap();
+            let mut last = Ok(None);
+            for _ in 0..6 {
+                last = replayer.next_record::<()>().await;
+                if last.is_err() {
+                    break;
+                }
+            }
+            assert_eq!(last, Err(TraceError::InvalidTrace));
+        });
+    }
+}
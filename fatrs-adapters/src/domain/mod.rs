@@ -3,7 +3,7 @@
 //! This is the core of the hexagonal architecture. The domain layer contains:
 //! - **Entities**: Objects with identity (e.g., `Page`)
 //! - **Value Objects**: Immutable validated data (e.g., `PageNumber`, `BlockAddress`)
-//! - **Domain Services**: Business logic (e.g., `PageBuffer`)
+//! - **Domain Services**: Business logic (e.g., `PageBuffer`, `PageCache`)
 //! - **Ports**: Interfaces to the outside world (e.g., `BlockStorage`)
 //! - **Domain Errors**: Business rule violations
 //!
@@ -22,6 +22,7 @@
 //!     │  ┌────────────────────────────┐  │
 //!     │  │    Domain Services         │  │
 //!     │  │    - PageBuffer            │  │
+//!     │  │    - PageCache             │  │
 //!     │  └────────────────────────────┘  │
 //!     │              │                   │
 //!     │              ▼                   │
@@ -38,6 +39,7 @@
 //!     │  - BlockDeviceAdapter            │
 //!     │  - StackBuffer                   │
 //!     │  - HeapBuffer                    │
+//!     │  - StackCache / HeapCache        │
 //!     └──────────────────────────────────┘
 //! ```
 //!
@@ -70,6 +72,7 @@ pub mod ports;
 pub mod error;
 
 mod page_buffer;
+mod page_cache;
 
 // Re-export commonly used types
 pub use entities::{Page, PageState};
@@ -77,3 +80,10 @@ pub use value_objects::{PageNumber, BlockAddress, PageConfig, PageConfigError, B
 pub use ports::BlockStorage;
 pub use error::DomainError;
 pub use page_buffer::PageBuffer;
+pub use page_cache::{
+    fat_region, CacheMemory, CachePolicy, CacheSlot, CacheStats, EvictionPolicy, PageCache,
+    StackCacheMemory, WriteBackOrder,
+};
+
+#[cfg(feature = "alloc")]
+pub use page_cache::HeapCacheMemory;
@@ -0,0 +1,997 @@
+//! PageCache domain service - multi-page write-back caching.
+//!
+//! `PageBuffer` holds exactly one page, so a workload that alternates between a
+//! FAT sector and a data sector evicts and rewrites a page on every switch.
+//! `PageCache` keeps several pages resident instead, writes dirty pages back
+//! only when they are evicted or flushed, and lets the caller decide whether
+//! the FAT is written before or after everything else.
+
+use core::ops::Range;
+
+use crate::domain::{
+    error::DomainError,
+    ports::BlockStorage,
+    value_objects::{PageConfig, PageConfigError, PageNumber},
+};
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+/// Strategy used to choose the page to evict when every slot is in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// Evict the least recently used page.
+    #[default]
+    Lru,
+    /// Second-chance (CLOCK) approximation of LRU.
+    ///
+    /// A hand sweeps over the slots, clearing reference bits, and evicts the
+    /// first page that has not been touched since the hand last passed it.
+    Clock,
+}
+
+/// Order in which a [`PageCache`] writes FAT pages relative to other dirty pages,
+/// both on [`flush`](PageCache::flush) and when evicting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteBackOrder {
+    /// Write data and directory pages first and FAT pages last, so a cluster
+    /// chain never references clusters whose contents are not on disk yet.
+    #[default]
+    FatLast,
+    /// Write FAT pages first, so clusters are marked allocated on disk before
+    /// any directory entry refers to them.
+    FatFirst,
+}
+
+/// Eviction and write-back policy of a [`PageCache`].
+///
+/// # Examples
+///
+/// ```
+/// use fatrs_adapters::domain::{CachePolicy, EvictionPolicy, WriteBackOrder};
+///
+/// let policy = CachePolicy::new()
+///     .with_eviction(EvictionPolicy::Clock)
+///     .with_write_back_order(WriteBackOrder::FatFirst)
+///     .with_fat_region(16_384..81_920);
+/// assert_eq!(policy.eviction(), EvictionPolicy::Clock);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CachePolicy {
+    eviction: EvictionPolicy,
+    write_back: WriteBackOrder,
+    fat_region: Option<Range<u64>>,
+}
+
+impl CachePolicy {
+    /// LRU eviction, FAT written last, no FAT region known yet.
+    pub const fn new() -> Self {
+        Self {
+            eviction: EvictionPolicy::Lru,
+            write_back: WriteBackOrder::FatLast,
+            fat_region: None,
+        }
+    }
+
+    /// Set the eviction strategy.
+    pub const fn with_eviction(mut self, eviction: EvictionPolicy) -> Self {
+        self.eviction = eviction;
+        self
+    }
+
+    /// Set whether FAT pages are flushed before or after the other dirty pages.
+    pub const fn with_write_back_order(mut self, order: WriteBackOrder) -> Self {
+        self.write_back = order;
+        self
+    }
+
+    /// Set the byte range of the volume occupied by the FATs.
+    ///
+    /// See [`fat_region`] to compute it from a boot sector.
+    pub fn with_fat_region(mut self, region: Range<u64>) -> Self {
+        self.fat_region = Some(region);
+        self
+    }
+
+    /// The eviction strategy.
+    pub const fn eviction(&self) -> EvictionPolicy {
+        self.eviction
+    }
+
+    /// The FAT write-back order.
+    pub const fn write_back_order(&self) -> WriteBackOrder {
+        self.write_back
+    }
+
+    /// The byte range occupied by the FATs, if known.
+    pub const fn fat_region(&self) -> Option<&Range<u64>> {
+        self.fat_region.as_ref()
+    }
+}
+
+/// Locate the FATs of a FAT12/16/32 volume from its boot sector.
+///
+/// Returns the byte range covering every FAT copy, relative to the start of
+/// the volume, or `None` if `boot_sector` does not look like a FAT boot sector.
+///
+/// # Examples
+///
+/// ```
+/// use fatrs_adapters::domain::fat_region;
+///
+/// let mut boot = [0u8; 512];
+/// boot[11..13].copy_from_slice(&512u16.to_le_bytes()); // bytes per sector
+/// boot[14..16].copy_from_slice(&32u16.to_le_bytes()); // reserved sectors
+/// boot[16] = 2; // number of FATs
+/// boot[36..40].copy_from_slice(&100u32.to_le_bytes()); // sectors per FAT (FAT32)
+/// boot[510..512].copy_from_slice(&[0x55, 0xAA]);
+///
+/// assert_eq!(fat_region(&boot), Some(16_384..118_784));
+/// ```
+pub fn fat_region(boot_sector: &[u8]) -> Option<Range<u64>> {
+    if boot_sector.len() < 512 || boot_sector[510..512] != [0x55, 0xAA] {
+        return None;
+    }
+
+    let u16_at = |offset: usize| u16::from_le_bytes([boot_sector[offset], boot_sector[offset + 1]]);
+    let bytes_per_sector = u64::from(u16_at(11));
+    let reserved_sectors = u64::from(u16_at(14));
+    let fats = u64::from(boot_sector[16]);
+    let sectors_per_fat = match u16_at(22) {
+        0 => u64::from(u32::from_le_bytes([
+            boot_sector[36],
+            boot_sector[37],
+            boot_sector[38],
+            boot_sector[39],
+        ])),
+        n => u64::from(n),
+    };
+
+    if !bytes_per_sector.is_power_of_two()
+        || bytes_per_sector < 512
+        || reserved_sectors == 0
+        || fats == 0
+        || sectors_per_fat == 0
+    {
+        return None;
+    }
+
+    let start = reserved_sectors * bytes_per_sector;
+    Some(start..start + fats * sectors_per_fat * bytes_per_sector)
+}
+
+/// Hit/miss and write-back counters of a [`PageCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Lookups served from a resident page.
+    pub hits: u64,
+    /// Lookups that had to claim a slot for a page that was not resident.
+    pub misses: u64,
+    /// Resident pages dropped to make room for another page.
+    pub evictions: u64,
+    /// Dirty pages written to storage, by eviction or flush.
+    pub write_backs: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups that were hits, or `0.0` if there were none.
+    pub fn hit_ratio(&self) -> f32 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f32 / lookups as f32
+        }
+    }
+}
+
+/// Bookkeeping for one slot of a [`PageCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheSlot {
+    page: Option<PageNumber>,
+    dirty: bool,
+    referenced: bool,
+    last_used: u64,
+}
+
+impl CacheSlot {
+    /// A slot holding no page.
+    pub const EMPTY: Self = Self {
+        page: None,
+        dirty: false,
+        referenced: false,
+        last_used: 0,
+    };
+
+    /// The page held by this slot, if any.
+    pub const fn page(&self) -> Option<PageNumber> {
+        self.page
+    }
+
+    /// Whether the page held by this slot has unwritten changes.
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+/// Backing memory of a [`PageCache`]: slot bookkeeping plus one page of data per slot.
+///
+/// Implemented by [`StackCacheMemory`] (fixed capacity, no allocation) and
+/// `HeapCacheMemory` (runtime capacity, requires `alloc`).
+pub trait CacheMemory {
+    /// Size of each cached page in bytes.
+    fn page_size(&self) -> usize;
+
+    /// Bookkeeping for every slot; the length is the capacity in pages.
+    fn slots(&self) -> &[CacheSlot];
+
+    /// Mutable bookkeeping for every slot.
+    fn slots_mut(&mut self) -> &mut [CacheSlot];
+
+    /// Data of the page in slot `index`.
+    fn page(&self, index: usize) -> &[u8];
+
+    /// Mutable data of the page in slot `index`.
+    fn page_mut(&mut self, index: usize) -> &mut [u8];
+}
+
+/// Cache memory for `PAGES` pages of `N` bytes, stored inline.
+pub struct StackCacheMemory<const N: usize, const PAGES: usize> {
+    slots: [CacheSlot; PAGES],
+    pages: [[u8; N]; PAGES],
+}
+
+impl<const N: usize, const PAGES: usize> StackCacheMemory<N, PAGES> {
+    /// Create empty cache memory.
+    pub const fn new() -> Self {
+        Self {
+            slots: [CacheSlot::EMPTY; PAGES],
+            pages: [[0; N]; PAGES],
+        }
+    }
+}
+
+impl<const N: usize, const PAGES: usize> Default for StackCacheMemory<N, PAGES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const PAGES: usize> CacheMemory for StackCacheMemory<N, PAGES> {
+    fn page_size(&self) -> usize {
+        N
+    }
+
+    fn slots(&self) -> &[CacheSlot] {
+        &self.slots
+    }
+
+    fn slots_mut(&mut self) -> &mut [CacheSlot] {
+        &mut self.slots
+    }
+
+    fn page(&self, index: usize) -> &[u8] {
+        &self.pages[index]
+    }
+
+    fn page_mut(&mut self, index: usize) -> &mut [u8] {
+        &mut self.pages[index]
+    }
+}
+
+/// Cache memory with page size and capacity chosen at runtime.
+#[cfg(feature = "alloc")]
+pub struct HeapCacheMemory {
+    page_size: usize,
+    slots: Vec<CacheSlot>,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl HeapCacheMemory {
+    /// Allocate memory for `pages` pages of `page_size` bytes.
+    pub fn new(page_size: usize, pages: usize) -> Self {
+        Self {
+            page_size,
+            slots: vec![CacheSlot::EMPTY; pages],
+            data: vec![0; page_size * pages],
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl CacheMemory for HeapCacheMemory {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn slots(&self) -> &[CacheSlot] {
+        &self.slots
+    }
+
+    fn slots_mut(&mut self) -> &mut [CacheSlot] {
+        &mut self.slots
+    }
+
+    fn page(&self, index: usize) -> &[u8] {
+        &self.data[index * self.page_size..(index + 1) * self.page_size]
+    }
+
+    fn page_mut(&mut self, index: usize) -> &mut [u8] {
+        &mut self.data[index * self.page_size..(index + 1) * self.page_size]
+    }
+}
+
+/// Domain service caching several pages of block storage with write-back.
+///
+/// # Business Rules
+///
+/// - A lookup of a resident page is a hit and never touches storage
+/// - A miss claims a free slot or evicts a page chosen by the [`EvictionPolicy`];
+///   an evicted dirty page is written back first, preceded by the dirty pages the
+///   [`WriteBackOrder`] puts ahead of it and a storage flush
+/// - [`flush`](Self::flush) writes every dirty page in ascending page order,
+///   FAT pages in a separate pass placed by the [`WriteBackOrder`] with a storage
+///   flush between the passes, then flushes storage
+///
+/// # Type Parameters
+///
+/// - `S`: The storage implementation (must implement `BlockStorage`)
+/// - `M`: The cache memory ([`StackCacheMemory`] or `HeapCacheMemory`)
+/// - `BLOCK_SIZE`: The block size in bytes (must match the BlockStorage implementation)
+///
+/// # Examples
+///
+/// ```ignore
+/// use fatrs_adapters::domain::{CachePolicy, PageCache, PageConfig, PageNumber, StackCacheMemory};
+///
+/// let config = PageConfig::<512>::new(4096, 8);
+/// let memory = StackCacheMemory::<4096, 8>::new();
+/// let mut cache = PageCache::new(storage, config, memory, CachePolicy::new())?;
+///
+/// cache.detect_fat_region().await?;
+/// cache.page_mut(PageNumber::new(3)).await?[0] = 42;
+/// let first = cache.page(PageNumber::new(0)).await?[0];
+/// cache.flush().await?;
+/// ```
+pub struct PageCache<S: BlockStorage, M, const BLOCK_SIZE: usize> {
+    storage: S,
+    config: PageConfig<BLOCK_SIZE>,
+    policy: CachePolicy,
+    memory: M,
+    clock: u64,
+    hand: usize,
+    stats: CacheStats,
+}
+
+impl<S: BlockStorage, M: CacheMemory, const BLOCK_SIZE: usize> PageCache<S, M, BLOCK_SIZE> {
+    /// Create a cache over `storage` using `memory` for the resident pages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the page size of `memory` differs from `config`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `memory` has no slots.
+    pub fn new(
+        storage: S,
+        config: PageConfig<BLOCK_SIZE>,
+        memory: M,
+        policy: CachePolicy,
+    ) -> Result<Self, PageConfigError> {
+        if memory.page_size() != config.page_size() {
+            return Err(PageConfigError::InvalidPageSize {
+                page_size: memory.page_size(),
+                block_size: BLOCK_SIZE,
+            });
+        }
+        assert!(
+            !memory.slots().is_empty(),
+            "a page cache needs at least one slot"
+        );
+
+        Ok(Self {
+            storage,
+            config,
+            policy,
+            memory,
+            clock: 0,
+            hand: 0,
+            stats: CacheStats::default(),
+        })
+    }
+
+    /// Get a page for reading, loading it from storage on a miss.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing back an evicted page or reading the page fails.
+    pub async fn page(&mut self, number: PageNumber) -> Result<&[u8], DomainError<S::Error>> {
+        let index = self.slot_for(number, true).await?;
+        Ok(self.memory.page(index))
+    }
+
+    /// Get a page for modification, loading it from storage on a miss.
+    ///
+    /// The page is marked dirty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing back an evicted page or reading the page fails.
+    pub async fn page_mut(
+        &mut self,
+        number: PageNumber,
+    ) -> Result<&mut [u8], DomainError<S::Error>> {
+        let index = self.slot_for(number, true).await?;
+        self.memory.slots_mut()[index].dirty = true;
+        Ok(self.memory.page_mut(index))
+    }
+
+    /// Get a page that the caller is about to overwrite completely.
+    ///
+    /// Unlike [`page_mut`](Self::page_mut) a miss does not read the page from
+    /// storage, so the returned contents are unspecified unless the page was
+    /// already resident. The page is marked dirty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing back an evicted page fails.
+    pub async fn overwrite(
+        &mut self,
+        number: PageNumber,
+    ) -> Result<&mut [u8], DomainError<S::Error>> {
+        let index = self.slot_for(number, false).await?;
+        self.memory.slots_mut()[index].dirty = true;
+        Ok(self.memory.page_mut(index))
+    }
+
+    /// Write every dirty page back to storage and flush the storage.
+    ///
+    /// Pages are written in ascending order, FAT pages in their own pass
+    /// before or after the others according to the [`WriteBackOrder`]. The
+    /// storage is flushed between the two passes so the device cannot
+    /// reorder the second pass ahead of the first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a storage write or flush fails. Pages written
+    /// before the failure are clean; the rest stay dirty.
+    pub async fn flush(&mut self) -> Result<(), DomainError<S::Error>> {
+        let fat_first = self.fat_first();
+        let mut wrote_first_pass = false;
+        while let Some(index) = self.next_dirty(fat_first) {
+            self.write_back(index).await?;
+            wrote_first_pass = true;
+        }
+        if wrote_first_pass && self.next_dirty(!fat_first).is_some() {
+            self.storage.flush().await.map_err(DomainError::Storage)?;
+        }
+        while let Some(index) = self.next_dirty(!fat_first) {
+            self.write_back(index).await?;
+        }
+        self.storage.flush().await.map_err(DomainError::Storage)
+    }
+
+    /// Drop every resident page.
+    ///
+    /// **Warning**: Any uncommitted changes are lost!
+    pub fn discard(&mut self) {
+        self.memory.slots_mut().fill(CacheSlot::EMPTY);
+        self.hand = 0;
+    }
+
+    /// Whether `number` is resident.
+    pub fn contains(&self, number: PageNumber) -> bool {
+        self.find(number).is_some()
+    }
+
+    /// Number of resident pages with unwritten changes.
+    pub fn dirty_pages(&self) -> usize {
+        self.memory.slots().iter().filter(|slot| slot.dirty).count()
+    }
+
+    /// Capacity of the cache in pages.
+    pub fn capacity(&self) -> usize {
+        self.memory.slots().len()
+    }
+
+    /// Hit/miss and write-back counters.
+    pub const fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Reset the counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// The eviction and write-back policy.
+    pub const fn policy(&self) -> &CachePolicy {
+        &self.policy
+    }
+
+    /// Set or clear the byte range occupied by the FATs.
+    pub fn set_fat_region(&mut self, region: Option<Range<u64>>) {
+        self.policy.fat_region = region;
+    }
+
+    /// Read the boot sector at the start of the storage and use it to locate the FATs.
+    ///
+    /// Returns the detected region, or `None` (leaving the region unset) if
+    /// the storage does not start with a FAT boot sector.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the first page fails.
+    pub async fn detect_fat_region(&mut self) -> Result<Option<Range<u64>>, DomainError<S::Error>> {
+        let region = fat_region(self.page(PageNumber::new(0)).await?);
+        self.policy.fat_region = region.clone();
+        Ok(region)
+    }
+
+    /// Get the page configuration.
+    pub const fn config(&self) -> &PageConfig<BLOCK_SIZE> {
+        &self.config
+    }
+
+    /// Get the storage size in bytes.
+    pub async fn storage_size(&mut self) -> Result<u64, DomainError<S::Error>> {
+        self.storage.size().await.map_err(DomainError::Storage)
+    }
+
+    /// Get the storage size in pages.
+    pub async fn storage_size_in_pages(&mut self) -> Result<u64, DomainError<S::Error>> {
+        let bytes = self.storage.size().await.map_err(DomainError::Storage)?;
+        Ok(bytes / self.config.page_size() as u64)
+    }
+
+    fn find(&self, number: PageNumber) -> Option<usize> {
+        self.memory
+            .slots()
+            .iter()
+            .position(|slot| slot.page == Some(number))
+    }
+
+    fn touch(&mut self, index: usize) {
+        self.clock += 1;
+        let slot = &mut self.memory.slots_mut()[index];
+        slot.last_used = self.clock;
+        slot.referenced = true;
+    }
+
+    /// Resolve `number` to a slot, claiming one (and reading the page if `read`) on a miss.
+    async fn slot_for(
+        &mut self,
+        number: PageNumber,
+        read: bool,
+    ) -> Result<usize, DomainError<S::Error>> {
+        if let Some(index) = self.find(number) {
+            self.stats.hits += 1;
+            self.touch(index);
+            return Ok(index);
+        }
+        self.stats.misses += 1;
+
+        let index = self.victim();
+        let slot = self.memory.slots()[index];
+        if let Some(evicted) = slot.page {
+            if slot.dirty {
+                // Pages of the first write-back pass must reach storage before this one
+                let fat_first = self.fat_first();
+                if self.is_fat_page(evicted) != fat_first {
+                    let mut wrote_earlier = false;
+                    while let Some(earlier) = self.next_dirty(fat_first) {
+                        self.write_back(earlier).await?;
+                        wrote_earlier = true;
+                    }
+                    if wrote_earlier {
+                        self.storage.flush().await.map_err(DomainError::Storage)?;
+                    }
+                }
+                self.write_back(index).await?;
+            }
+            self.stats.evictions += 1;
+        }
+
+        // Unmap the slot first so a failed read cannot leave stale data behind
+        self.memory.slots_mut()[index] = CacheSlot::EMPTY;
+        if read {
+            let block_addr = self.config.page_to_block(number);
+            self.storage
+                .read_blocks(block_addr, self.memory.page_mut(index))
+                .await
+                .map_err(DomainError::Storage)?;
+        }
+
+        self.memory.slots_mut()[index].page = Some(number);
+        self.touch(index);
+        Ok(index)
+    }
+
+    fn victim(&mut self) -> usize {
+        if let Some(free) = self
+            .memory
+            .slots()
+            .iter()
+            .position(|slot| slot.page.is_none())
+        {
+            return free;
+        }
+
+        match self.policy.eviction {
+            EvictionPolicy::Lru => self
+                .memory
+                .slots()
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, slot)| slot.last_used)
+                .map(|(index, _)| index)
+                .unwrap_or(0),
+            EvictionPolicy::Clock => {
+                let capacity = self.capacity();
+                loop {
+                    let index = self.hand;
+                    self.hand = (self.hand + 1) % capacity;
+                    let slot = &mut self.memory.slots_mut()[index];
+                    if !slot.referenced {
+                        return index;
+                    }
+                    slot.referenced = false;
+                }
+            }
+        }
+    }
+
+    async fn write_back(&mut self, index: usize) -> Result<(), DomainError<S::Error>> {
+        let Some(number) = self.memory.slots()[index].page else {
+            return Ok(());
+        };
+
+        let block_addr = self.config.page_to_block(number);
+        self.storage
+            .write_blocks(block_addr, self.memory.page(index))
+            .await
+            .map_err(DomainError::Storage)?;
+
+        self.memory.slots_mut()[index].dirty = false;
+        self.stats.write_backs += 1;
+        Ok(())
+    }
+
+    fn fat_first(&self) -> bool {
+        self.policy.write_back == WriteBackOrder::FatFirst
+    }
+
+    /// The dirty slot with the lowest page number inside (or outside) the FAT region.
+    fn next_dirty(&self, fat: bool) -> Option<usize> {
+        self.memory
+            .slots()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| match slot.page {
+                Some(number) if slot.dirty && self.is_fat_page(number) == fat => {
+                    Some((index, number))
+                }
+                _ => None,
+            })
+            .min_by_key(|&(_, number)| number)
+            .map(|(index, _)| index)
+    }
+
+    fn is_fat_page(&self, number: PageNumber) -> bool {
+        let Some(region) = &self.policy.fat_region else {
+            return false;
+        };
+        let page_size = self.config.page_size() as u64;
+        let start = u64::from(number.value()) * page_size;
+        start < region.end && start + page_size > region.start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::value_objects::BlockAddress;
+    use std::collections::HashMap;
+
+    // Mock storage that records the first block of every write, and the
+    // number of writes issued before every flush
+    struct MockStorage {
+        data: HashMap<u32, Vec<u8>>,
+        writes: Vec<u32>,
+        flushes: Vec<usize>,
+        block_size: usize,
+    }
+
+    impl MockStorage {
+        fn with_block_size(block_size: usize) -> Self {
+            Self {
+                data: HashMap::new(),
+                writes: Vec::new(),
+                flushes: Vec::new(),
+                block_size,
+            }
+        }
+    }
+
+    impl Default for MockStorage {
+        fn default() -> Self {
+            Self::with_block_size(512)
+        }
+    }
+
+    impl BlockStorage for MockStorage {
+        type Error = std::io::Error;
+
+        async fn read_blocks(
+            &self,
+            start: BlockAddress,
+            dest: &mut [u8],
+        ) -> Result<(), Self::Error> {
+            for (i, chunk) in dest.chunks_mut(self.block_size).enumerate() {
+                match self.data.get(&(start.value() + i as u32)) {
+                    Some(block) => chunk.copy_from_slice(block),
+                    None => chunk.fill(0),
+                }
+            }
+            Ok(())
+        }
+
+        async fn write_blocks(
+            &mut self,
+            start: BlockAddress,
+            src: &[u8],
+        ) -> Result<(), Self::Error> {
+            self.writes.push(start.value());
+            for (i, chunk) in src.chunks(self.block_size).enumerate() {
+                self.data.insert(start.value() + i as u32, chunk.to_vec());
+            }
+            Ok(())
+        }
+
+        async fn size(&self) -> Result<u64, Self::Error> {
+            Ok(1024 * 1024)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            self.flushes.push(self.writes.len());
+            Ok(())
+        }
+    }
+
+    type StackCache = PageCache<MockStorage, StackCacheMemory<1024, 3>, 512>;
+
+    fn stack_cache(policy: CachePolicy) -> StackCache {
+        PageCache::new(
+            MockStorage::default(),
+            PageConfig::new(1024, 2),
+            StackCacheMemory::new(),
+            policy,
+        )
+        .unwrap()
+    }
+
+    fn page(n: u32) -> PageNumber {
+        PageNumber::new(n)
+    }
+
+    #[tokio::test]
+    async fn test_alternating_pages_stay_resident() {
+        let mut cache = stack_cache(CachePolicy::new());
+
+        for i in 0..10u8 {
+            cache.page_mut(page(1)).await.unwrap()[0] = i;
+            cache.page_mut(page(40)).await.unwrap()[0] = i;
+        }
+
+        assert!(cache.storage.writes.is_empty());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 18,
+                misses: 2,
+                evictions: 0,
+                write_backs: 0
+            }
+        );
+        assert_eq!(cache.dirty_pages(), 2);
+
+        cache.flush().await.unwrap();
+        assert_eq!(cache.storage.writes, [2, 80]);
+        assert_eq!(cache.storage.flushes, [2]);
+        assert_eq!(cache.dirty_pages(), 0);
+        assert_eq!(cache.storage.data[&80][0], 9);
+    }
+
+    #[tokio::test]
+    async fn test_lru_evicts_least_recently_used() {
+        let mut cache = stack_cache(CachePolicy::new());
+
+        cache.page_mut(page(0)).await.unwrap()[0] = 1;
+        cache.page(page(1)).await.unwrap();
+        cache.page(page(2)).await.unwrap();
+        cache.page(page(0)).await.unwrap();
+
+        // Page 1 is the least recently used and clean: evicted without a write
+        cache.page(page(3)).await.unwrap();
+        assert!(!cache.contains(page(1)));
+        assert!(cache.storage.writes.is_empty());
+
+        // Now page 2, then the dirty page 0, which must be written back
+        cache.page(page(4)).await.unwrap();
+        cache.page(page(5)).await.unwrap();
+        assert!(!cache.contains(page(0)));
+        assert_eq!(cache.storage.writes, [0]);
+        assert_eq!(cache.stats().evictions, 3);
+        assert_eq!(cache.stats().write_backs, 1);
+
+        // The written-back data is read again on the next miss
+        assert_eq!(cache.page(page(0)).await.unwrap()[0], 1);
+    }
+
+    #[tokio::test]
+    async fn test_clock_gives_referenced_pages_a_second_chance() {
+        let mut cache = stack_cache(CachePolicy::new().with_eviction(EvictionPolicy::Clock));
+
+        cache.page(page(0)).await.unwrap();
+        cache.page(page(1)).await.unwrap();
+        cache.page(page(2)).await.unwrap();
+
+        // Full sweep clears every bit, then slot 0 goes
+        cache.page(page(3)).await.unwrap();
+        assert!(!cache.contains(page(0)));
+
+        // Page 1 is referenced again and survives the next eviction; page 2 does not
+        cache.page(page(1)).await.unwrap();
+        cache.page(page(4)).await.unwrap();
+        assert!(cache.contains(page(1)));
+        assert!(!cache.contains(page(2)));
+    }
+
+    #[tokio::test]
+    async fn test_flush_orders_fat_pages() {
+        // FAT occupies pages 1 and 2
+        let region = 1024..3072;
+
+        // Writes, and the number of writes before each storage flush
+        for (order, expected, flushes) in [
+            (WriteBackOrder::FatLast, [10u32, 2, 4], [1, 3]),
+            (WriteBackOrder::FatFirst, [2, 4, 10], [2, 3]),
+        ] {
+            let policy = CachePolicy::new()
+                .with_write_back_order(order)
+                .with_fat_region(region.clone());
+            let mut cache = stack_cache(policy);
+
+            cache.page_mut(page(2)).await.unwrap();
+            cache.page_mut(page(5)).await.unwrap();
+            cache.page_mut(page(1)).await.unwrap();
+            cache.flush().await.unwrap();
+
+            assert_eq!(cache.storage.writes, expected, "{order:?}");
+            assert_eq!(cache.storage.flushes, flushes, "{order:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_eviction_orders_fat_pages() {
+        // FAT occupies pages 1 and 2
+        let region = 1024..3072;
+
+        // Writes, and the number of writes before each storage flush
+        for (order, expected, flushes) in [
+            (WriteBackOrder::FatLast, &[10u32, 4][..], [1]),
+            (WriteBackOrder::FatFirst, &[2, 4, 10][..], [2]),
+        ] {
+            let policy = CachePolicy::new()
+                .with_write_back_order(order)
+                .with_fat_region(region.clone());
+            let mut cache = stack_cache(policy);
+
+            cache.page_mut(page(2)).await.unwrap();
+            cache.page_mut(page(5)).await.unwrap();
+            cache.page_mut(page(1)).await.unwrap();
+            // Page 2 is evicted under FatLast, page 5 under FatFirst
+            if order == WriteBackOrder::FatFirst {
+                cache.page(page(2)).await.unwrap();
+            }
+            cache.page(page(7)).await.unwrap();
+
+            assert_eq!(cache.storage.writes, expected, "{order:?}");
+            assert_eq!(cache.storage.flushes, flushes, "{order:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_skips_read() {
+        let mut cache = stack_cache(CachePolicy::new());
+        cache.storage.data.insert(8, vec![7; 512]);
+
+        cache.overwrite(page(4)).await.unwrap().fill(1);
+        cache.flush().await.unwrap();
+
+        assert_eq!(cache.storage.data[&8], vec![1; 512]);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[tokio::test]
+    async fn test_detect_fat_region() {
+        let mut cache = stack_cache(CachePolicy::new());
+        let mut boot = vec![0u8; 512];
+        boot[11..13].copy_from_slice(&512u16.to_le_bytes());
+        boot[14..16].copy_from_slice(&4u16.to_le_bytes());
+        boot[16] = 2;
+        boot[22..24].copy_from_slice(&3u16.to_le_bytes());
+        boot[510] = 0x55;
+        boot[511] = 0xAA;
+        cache.storage.data.insert(0, boot);
+
+        assert_eq!(cache.detect_fat_region().await.unwrap(), Some(2048..5120));
+        assert!(cache.is_fat_page(page(2)));
+        assert!(cache.is_fat_page(page(4)));
+        assert!(!cache.is_fat_page(page(1)));
+        assert!(!cache.is_fat_page(page(5)));
+    }
+
+    #[test]
+    fn test_fat_region_rejects_non_boot_sector() {
+        assert_eq!(fat_region(&[0u8; 512]), None);
+        assert_eq!(fat_region(&[0u8; 16]), None);
+    }
+
+    #[tokio::test]
+    async fn test_heap_memory() {
+        let mut cache = PageCache::new(
+            MockStorage::default(),
+            PageConfig::<512>::new(512, 1),
+            HeapCacheMemory::new(512, 2),
+            CachePolicy::new(),
+        )
+        .unwrap();
+
+        cache.page_mut(page(0)).await.unwrap()[0] = 1;
+        cache.page_mut(page(1)).await.unwrap()[0] = 2;
+        cache.page(page(2)).await.unwrap();
+
+        assert_eq!(cache.capacity(), 2);
+        assert_eq!(cache.storage.writes, [0]);
+        assert_eq!(cache.page(page(1)).await.unwrap()[0], 2);
+    }
+
+    #[tokio::test]
+    async fn test_4096_byte_blocks() {
+        let mut cache = PageCache::new(
+            MockStorage::with_block_size(4096),
+            PageConfig::<4096>::new(8192, 2),
+            StackCacheMemory::<8192, 2>::new(),
+            CachePolicy::new(),
+        )
+        .unwrap();
+
+        cache.page_mut(page(0)).await.unwrap()[0] = 1;
+        cache.page_mut(page(1)).await.unwrap()[4096] = 2;
+        // Loading a third page evicts page 0 to blocks 0 and 1
+        cache.page(page(2)).await.unwrap();
+        assert_eq!(cache.storage.writes, [0]);
+        assert_eq!(cache.storage.data[&0][0], 1);
+
+        cache.flush().await.unwrap();
+        assert_eq!(cache.storage.writes, [0, 2]);
+        assert_eq!(cache.storage.data[&3][0], 2);
+        assert_eq!(cache.page(page(0)).await.unwrap()[0], 1);
+    }
+
+    #[test]
+    fn test_mismatched_page_size() {
+        let result = PageCache::new(
+            MockStorage::default(),
+            PageConfig::<512>::new(2048, 4),
+            StackCacheMemory::<1024, 2>::new(),
+            CachePolicy::new(),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -8,9 +8,9 @@ use crate::infrastructure::streaming::StreamError;
 use fatrs_block_device::BlockDevice;
 
 #[cfg(feature = "alloc")]
-use crate::infrastructure::streaming::HeapPageStream;
+use crate::infrastructure::streaming::{HeapCacheStream, HeapPageStream};
 
-use crate::infrastructure::streaming::StackPageStream;
+use crate::infrastructure::streaming::{StackCacheStream, StackPageStream};
 use embedded_io_async::ErrorType;
 
 // Convert our SeekFrom to embedded_io_async's SeekFrom
@@ -111,3 +111,93 @@ where
         HeapPageStream::seek(self, convert_seek_from(pos)).await
     }
 }
+
+// Implement for StackCacheStream
+impl<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize> ErrorType for StackCacheStream<D, N, PAGES, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    type Error = StreamError<D::Error>;
+}
+
+impl<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize> embedded_io_async::Read for StackCacheStream<D, N, PAGES, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        StackCacheStream::read(self, buf).await
+    }
+}
+
+impl<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize> embedded_io_async::Write for StackCacheStream<D, N, PAGES, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        StackCacheStream::write(self, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        StackCacheStream::flush(self).await
+    }
+}
+
+impl<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize> embedded_io_async::Seek for StackCacheStream<D, N, PAGES, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    async fn seek(&mut self, pos: embedded_io_async::SeekFrom) -> Result<u64, Self::Error> {
+        StackCacheStream::seek(self, convert_seek_from(pos)).await
+    }
+}
+
+// Implement for HeapCacheStream
+#[cfg(feature = "alloc")]
+impl<D, const BLOCK_SIZE: usize> ErrorType for HeapCacheStream<D, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    type Error = StreamError<D::Error>;
+}
+
+#[cfg(feature = "alloc")]
+impl<D, const BLOCK_SIZE: usize> embedded_io_async::Read for HeapCacheStream<D, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        HeapCacheStream::read(self, buf).await
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<D, const BLOCK_SIZE: usize> embedded_io_async::Write for HeapCacheStream<D, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        HeapCacheStream::write(self, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        HeapCacheStream::flush(self).await
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<D, const BLOCK_SIZE: usize> embedded_io_async::Seek for HeapCacheStream<D, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    async fn seek(&mut self, pos: embedded_io_async::SeekFrom) -> Result<u64, Self::Error> {
+        HeapCacheStream::seek(self, convert_seek_from(pos)).await
+    }
+}
@@ -0,0 +1,236 @@
+//! Heap-allocated streaming multi-page cache with runtime-configurable size.
+
+#[cfg(feature = "alloc")]
+use crate::{
+    adapters::{HeapAdapterError, HeapCache},
+    infrastructure::streaming::{SeekFrom, StreamError},
+};
+#[cfg(feature = "alloc")]
+use fatrs_block_device::BlockDevice;
+
+/// Heap-allocated stream over a write-back cache sized at runtime.
+///
+/// Like `HeapPageStream`, but backed by `HeapCache` instead of a single page
+/// buffer: seeking does not flush, and dirty pages are only written when
+/// evicted or when the stream is flushed.
+///
+/// # Type Parameters
+///
+/// - `D`: The block device type
+/// - `BLOCK_SIZE`: The block size in bytes (must match the device's block size)
+///
+/// # Examples
+///
+/// ```ignore
+/// use fatrs_adapters::adapters::presets;
+/// use fatrs_adapters::infrastructure::streaming::HeapCacheStream;
+///
+/// // Sixteen 4KB pages
+/// let device = MyBlockDevice::new();
+/// let mut stream = HeapCacheStream::new(device, presets::PAGE_4K, 16)?;
+/// stream.cache_mut().detect_fat_region().await?;
+///
+/// let fs = FileSystem::new(stream, FsOptions::new()).await?;
+/// ```
+#[cfg(feature = "alloc")]
+pub struct HeapCacheStream<D, const BLOCK_SIZE: usize>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    cache: HeapCache<D, BLOCK_SIZE>,
+    position: u64,
+    page_size: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<D, const BLOCK_SIZE: usize> HeapCacheStream<D, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    /// Create a new stream caching `pages` pages of `page_size` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `page_size` is not a multiple of `BLOCK_SIZE` or `pages` is zero.
+    pub fn new(
+        device: D,
+        page_size: usize,
+        pages: usize,
+    ) -> Result<Self, HeapAdapterError<D::Error>> {
+        Ok(Self::from_cache(HeapCache::new(device, page_size, pages)?))
+    }
+
+    /// Create a stream over an existing cache.
+    pub fn from_cache(cache: HeapCache<D, BLOCK_SIZE>) -> Self {
+        Self {
+            page_size: cache.page_size(),
+            cache,
+            position: 0,
+        }
+    }
+
+    /// Read data from the stream.
+    ///
+    /// Reads up to `buf.len()` bytes from the current position, stopping at the page boundary.
+    /// Returns the number of bytes read.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Read` trait.
+    pub(crate) async fn read(&mut self, buf: &mut [u8]) -> Result<usize, StreamError<D::Error>> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let page_num = (self.position / self.page_size as u64) as u32;
+        let page_offset = (self.position % self.page_size as u64) as usize;
+        let to_read = buf.len().min(self.page_size - page_offset);
+
+        let data = self.cache.page(page_num).await.map_err(map_error)?;
+        buf[..to_read].copy_from_slice(&data[page_offset..page_offset + to_read]);
+        self.position += to_read as u64;
+
+        Ok(to_read)
+    }
+
+    /// Write data to the stream.
+    ///
+    /// Writes up to `buf.len()` bytes at the current position, stopping at the page boundary.
+    /// A write covering a whole page does not read that page first.
+    /// Returns the number of bytes written.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Write` trait.
+    pub(crate) async fn write(&mut self, buf: &[u8]) -> Result<usize, StreamError<D::Error>> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let page_num = (self.position / self.page_size as u64) as u32;
+        let page_offset = (self.position % self.page_size as u64) as usize;
+        let to_write = buf.len().min(self.page_size - page_offset);
+
+        let data = if to_write == self.page_size {
+            self.cache.overwrite(page_num).await
+        } else {
+            self.cache.page_mut(page_num).await
+        }
+        .map_err(map_error)?;
+        data[page_offset..page_offset + to_write].copy_from_slice(&buf[..to_write]);
+        self.position += to_write as u64;
+
+        Ok(to_write)
+    }
+
+    /// Write back every dirty page and flush the device.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Write` trait.
+    pub(crate) async fn flush(&mut self) -> Result<(), StreamError<D::Error>> {
+        self.cache.flush().await.map_err(map_error)
+    }
+
+    /// Seek to a new position in the stream.
+    ///
+    /// Cached pages stay resident; nothing is written.
+    /// Returns the new position from the start of the stream.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Seek` trait.
+    pub(crate) async fn seek(&mut self, pos: SeekFrom) -> Result<u64, StreamError<D::Error>> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+            SeekFrom::End(offset) => self.size().await? as i64 + offset,
+        };
+
+        if new_pos < 0 {
+            return Err(StreamError::InvalidSeek);
+        }
+
+        self.position = new_pos as u64;
+        Ok(self.position)
+    }
+
+    /// Get the current position in the stream.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Get the size of the underlying storage in bytes.
+    pub async fn size(&mut self) -> Result<u64, StreamError<D::Error>> {
+        self.cache.size().await.map_err(map_error)
+    }
+
+    /// Get the page size in bytes.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Get the underlying cache (statistics, policy).
+    pub fn cache(&self) -> &HeapCache<D, BLOCK_SIZE> {
+        &self.cache
+    }
+
+    /// Get mutable access to the underlying cache (FAT region, discarding).
+    pub fn cache_mut(&mut self) -> &mut HeapCache<D, BLOCK_SIZE> {
+        &mut self.cache
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn map_error<E>(err: HeapAdapterError<E>) -> StreamError<E> {
+    match err {
+        HeapAdapterError::Storage(s) => StreamError::Storage(s),
+        _ => StreamError::OutOfBounds,
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::MockBlockDevice;
+
+    #[tokio::test]
+    async fn test_seek_between_pages_keeps_them_cached() {
+        let device = MockBlockDevice::<512>::new(1024 * 1024);
+        let mut stream = HeapCacheStream::new(device, 512, 4).unwrap();
+
+        for i in 0..8u8 {
+            stream.seek(SeekFrom::Start(100)).await.unwrap();
+            stream.write(&[i]).await.unwrap();
+            stream.seek(SeekFrom::Start(64 * 1024 + 10)).await.unwrap();
+            stream.write(&[i, i]).await.unwrap();
+        }
+        assert_eq!(stream.cache().stats().misses, 2);
+        assert_eq!(stream.cache().stats().write_backs, 0);
+
+        stream.flush().await.unwrap();
+        assert_eq!(stream.cache().stats().write_backs, 2);
+
+        let mut buf = [0u8; 2];
+        stream.seek(SeekFrom::Start(64 * 1024 + 10)).await.unwrap();
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 2);
+        assert_eq!(buf, [7, 7]);
+    }
+
+    #[tokio::test]
+    async fn test_write_spanning_pages() {
+        let device = MockBlockDevice::<512>::new(1024 * 1024);
+        let mut stream = HeapCacheStream::new(device, 1024, 2).unwrap();
+
+        let data: Vec<u8> = (0..3000u32).map(|i| i as u8).collect();
+        stream.seek(SeekFrom::Start(500)).await.unwrap();
+        let mut written = 0;
+        while written < data.len() {
+            written += stream.write(&data[written..]).await.unwrap();
+        }
+        stream.flush().await.unwrap();
+        stream.cache_mut().discard();
+
+        let mut back = vec![0u8; data.len()];
+        stream.seek(SeekFrom::Start(500)).await.unwrap();
+        let mut read = 0;
+        while read < back.len() {
+            read += stream.read(&mut back[read..]).await.unwrap();
+        }
+        assert_eq!(back, data);
+    }
+}
@@ -109,13 +109,7 @@ where
         let page_offset = (self.position % self.page_size as u64) as usize;
 
         // Load the page containing current position
-        self.buffer
-            .load(page_num)
-            .await
-            .map_err(|e| match e {
-                HeapAdapterError::Storage(s) => StreamError::Storage(s),
-                _ => StreamError::OutOfBounds,
-            })?;
+        self.load_page(page_num).await?;
 
         // Read from current page
         let data = self.buffer.data().map_err(|e| match e {
@@ -149,13 +143,7 @@ where
         let page_offset = (self.position % self.page_size as u64) as usize;
 
         // Load the page containing current position
-        self.buffer
-            .load(page_num)
-            .await
-            .map_err(|e| match e {
-                HeapAdapterError::Storage(s) => StreamError::Storage(s),
-                _ => StreamError::OutOfBounds,
-            })?;
+        self.load_page(page_num).await?;
 
         // Write to current page
         let data = self.buffer.data_mut().map_err(|e| match e {
@@ -174,6 +162,18 @@ where
         Ok(to_write)
     }
 
+    /// Load `page_num`, first writing back the current page if it is a
+    /// different, dirty one (sequential I/O crossing a page boundary).
+    async fn load_page(&mut self, page_num: u32) -> Result<(), StreamError<D::Error>> {
+        if self.buffer.current_page() != Some(page_num) {
+            self.flush().await?;
+        }
+        self.buffer.load(page_num).await.map_err(|e| match e {
+            HeapAdapterError::Storage(s) => StreamError::Storage(s),
+            _ => StreamError::OutOfBounds,
+        })
+    }
+
     /// Flush any uncommitted changes to storage.
     ///
     /// Note: This method is internal. Users should use the `embedded_io_async::Write` trait.
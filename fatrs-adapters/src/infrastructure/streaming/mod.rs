@@ -4,6 +4,10 @@
 //! implementing async Read/Write/Seek traits for integration with file systems
 //! and other I/O frameworks.
 //!
+//! `StackPageStream` and `HeapPageStream` hold a single page and flush it on
+//! every seek; `StackCacheStream` and `HeapCacheStream` keep several pages
+//! resident and only write back on eviction or flush.
+//!
 //! # Send/Sync Properties
 //!
 //! These streams automatically adapt to your environment:
@@ -14,13 +18,19 @@
 
 mod stack_page_stream;
 mod heap_page_stream;
+mod stack_cache_stream;
+mod heap_cache_stream;
 mod embedded_io_impl;
 
 pub use stack_page_stream::StackPageStream;
+pub use stack_cache_stream::StackCacheStream;
 
 #[cfg(feature = "alloc")]
 pub use heap_page_stream::HeapPageStream;
 
+#[cfg(feature = "alloc")]
+pub use heap_cache_stream::HeapCacheStream;
+
 use core::fmt;
 
 /// Unified I/O error type for streaming operations.
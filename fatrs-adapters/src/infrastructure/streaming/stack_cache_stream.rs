@@ -0,0 +1,191 @@
+//! Stack-allocated streaming multi-page cache.
+
+use crate::{
+    adapters::{AdapterError, StackCache},
+    infrastructure::streaming::{SeekFrom, StreamError},
+};
+use fatrs_block_device::BlockDevice;
+
+/// Stack-allocated stream over a write-back cache of `PAGES` pages.
+///
+/// Like `StackPageStream`, but backed by `StackCache` instead of a single
+/// page buffer: seeking does not flush, and dirty pages are only written when
+/// evicted or when the stream is flushed.
+///
+/// # Type Parameters
+///
+/// - `D`: The block device type
+/// - `N`: Page size in bytes (must be a multiple of BLOCK_SIZE)
+/// - `PAGES`: Number of pages held (must be non-zero)
+/// - `BLOCK_SIZE`: The block size in bytes (must match the device's block size)
+///
+/// # Examples
+///
+/// ```ignore
+/// use fatrs_adapters::infrastructure::streaming::StackCacheStream;
+///
+/// // Eight 4KB pages with 512-byte blocks
+/// let device = MyBlockDevice::new();
+/// let mut stream = StackCacheStream::<_, 4096, 8, 512>::new(device);
+/// stream.cache_mut().detect_fat_region().await?;
+///
+/// let fs = FileSystem::new(stream, FsOptions::new()).await?;
+/// ```
+pub struct StackCacheStream<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    cache: StackCache<D, N, PAGES, BLOCK_SIZE>,
+    position: u64,
+}
+
+impl<D, const N: usize, const PAGES: usize, const BLOCK_SIZE: usize>
+    StackCacheStream<D, N, PAGES, BLOCK_SIZE>
+where
+    D: BlockDevice<BLOCK_SIZE> + Send + Sync,
+    D::Error: core::error::Error + Send + Sync + 'static,
+{
+    /// Create a new stream with the default cache policy.
+    pub fn new(device: D) -> Self {
+        Self::from_cache(StackCache::new(device))
+    }
+
+    /// Create a stream over an existing cache.
+    pub fn from_cache(cache: StackCache<D, N, PAGES, BLOCK_SIZE>) -> Self {
+        Self { cache, position: 0 }
+    }
+
+    /// Read data from the stream.
+    ///
+    /// Reads up to `buf.len()` bytes from the current position, stopping at the page boundary.
+    /// Returns the number of bytes read.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Read` trait.
+    pub(crate) async fn read(&mut self, buf: &mut [u8]) -> Result<usize, StreamError<D::Error>> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let page_num = (self.position / N as u64) as u32;
+        let page_offset = (self.position % N as u64) as usize;
+        let to_read = buf.len().min(N - page_offset);
+
+        let data = self.cache.page(page_num).await.map_err(map_error)?;
+        buf[..to_read].copy_from_slice(&data[page_offset..page_offset + to_read]);
+        self.position += to_read as u64;
+
+        Ok(to_read)
+    }
+
+    /// Write data to the stream.
+    ///
+    /// Writes up to `buf.len()` bytes at the current position, stopping at the page boundary.
+    /// A write covering a whole page does not read that page first.
+    /// Returns the number of bytes written.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Write` trait.
+    pub(crate) async fn write(&mut self, buf: &[u8]) -> Result<usize, StreamError<D::Error>> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let page_num = (self.position / N as u64) as u32;
+        let page_offset = (self.position % N as u64) as usize;
+        let to_write = buf.len().min(N - page_offset);
+
+        let data = if to_write == N {
+            self.cache.overwrite(page_num).await
+        } else {
+            self.cache.page_mut(page_num).await
+        }
+        .map_err(map_error)?;
+        data[page_offset..page_offset + to_write].copy_from_slice(&buf[..to_write]);
+        self.position += to_write as u64;
+
+        Ok(to_write)
+    }
+
+    /// Write back every dirty page and flush the device.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Write` trait.
+    pub(crate) async fn flush(&mut self) -> Result<(), StreamError<D::Error>> {
+        self.cache.flush().await.map_err(map_error)
+    }
+
+    /// Seek to a new position in the stream.
+    ///
+    /// Cached pages stay resident; nothing is written.
+    /// Returns the new position from the start of the stream.
+    ///
+    /// Note: This method is internal. Users should use the `embedded_io_async::Seek` trait.
+    pub(crate) async fn seek(&mut self, pos: SeekFrom) -> Result<u64, StreamError<D::Error>> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+            SeekFrom::End(offset) => self.size().await? as i64 + offset,
+        };
+
+        if new_pos < 0 {
+            return Err(StreamError::InvalidSeek);
+        }
+
+        self.position = new_pos as u64;
+        Ok(self.position)
+    }
+
+    /// Get the current position in the stream.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Get the size of the underlying storage in bytes.
+    pub async fn size(&mut self) -> Result<u64, StreamError<D::Error>> {
+        self.cache.size().await.map_err(map_error)
+    }
+
+    /// Get the underlying cache (statistics, policy).
+    pub fn cache(&self) -> &StackCache<D, N, PAGES, BLOCK_SIZE> {
+        &self.cache
+    }
+
+    /// Get mutable access to the underlying cache (FAT region, discarding).
+    pub fn cache_mut(&mut self) -> &mut StackCache<D, N, PAGES, BLOCK_SIZE> {
+        &mut self.cache
+    }
+}
+
+fn map_error<E>(err: AdapterError<E>) -> StreamError<E> {
+    match err {
+        AdapterError::Storage(s) => StreamError::Storage(s),
+        _ => StreamError::OutOfBounds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::block_device_adapter::tests::MockBlockDevice;
+
+    #[tokio::test]
+    async fn test_full_page_write_skips_read() {
+        let device = MockBlockDevice::<512>::new(1024 * 1024);
+        let mut stream = StackCacheStream::<_, 1024, 2, 512>::new(device);
+
+        stream.seek(SeekFrom::Start(2048)).await.unwrap();
+        assert_eq!(stream.write(&[0xAB; 1500]).await.unwrap(), 1024);
+        assert_eq!(stream.write(&[0xCD; 10]).await.unwrap(), 10);
+        stream.flush().await.unwrap();
+
+        // One overwrite and one read-modify-write miss, both written back
+        assert_eq!(stream.cache().stats().misses, 2);
+        assert_eq!(stream.cache().stats().write_backs, 2);
+
+        let mut buf = [0u8; 4];
+        stream.seek(SeekFrom::Start(3070)).await.unwrap();
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 2);
+        assert_eq!(buf[..2], [0xAB, 0xAB]);
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 4);
+        assert_eq!(buf, [0xCD; 4]);
+    }
+}
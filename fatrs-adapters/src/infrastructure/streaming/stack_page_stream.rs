@@ -56,7 +56,7 @@ where
 {
     /// Create a new streaming page buffer.
     ///
-    /// The page size is determined by `N * BLOCK_SIZE`.
+    /// The page size is `N` bytes.
     pub fn new(device: D) -> Self {
         Self {
             buffer: StackBuffer::new(device),
@@ -75,18 +75,12 @@ where
             return Ok(0);
         }
 
-        let page_size = (N * BLOCK_SIZE) as u64;
+        let page_size = N as u64;
         let page_num = (self.position / page_size) as u32;
         let page_offset = (self.position % page_size) as usize;
 
         // Load the page containing current position
-        self.buffer
-            .load(page_num)
-            .await
-            .map_err(|e| match e {
-                AdapterError::Storage(s) => StreamError::Storage(s),
-                _ => StreamError::OutOfBounds,
-            })?;
+        self.load_page(page_num).await?;
 
         // Read from current page
         let data = self.buffer.data().map_err(|e| match e {
@@ -116,18 +110,12 @@ where
             return Ok(0);
         }
 
-        let page_size = (N * BLOCK_SIZE) as u64;
+        let page_size = N as u64;
         let page_num = (self.position / page_size) as u32;
         let page_offset = (self.position % page_size) as usize;
 
         // Load the page containing current position
-        self.buffer
-            .load(page_num)
-            .await
-            .map_err(|e| match e {
-                AdapterError::Storage(s) => StreamError::Storage(s),
-                _ => StreamError::OutOfBounds,
-            })?;
+        self.load_page(page_num).await?;
 
         // Write to current page
         let data = self.buffer.data_mut().map_err(|e| match e {
@@ -146,6 +134,18 @@ where
         Ok(to_write)
     }
 
+    /// Load `page_num`, first writing back the current page if it is a
+    /// different, dirty one (sequential I/O crossing a page boundary).
+    async fn load_page(&mut self, page_num: u32) -> Result<(), StreamError<D::Error>> {
+        if self.buffer.current_page() != Some(page_num) {
+            self.flush().await?;
+        }
+        self.buffer.load(page_num).await.map_err(|e| match e {
+            AdapterError::Storage(s) => StreamError::Storage(s),
+            _ => StreamError::OutOfBounds,
+        })
+    }
+
     /// Flush any uncommitted changes to storage.
     ///
     /// Note: This method is internal. Users should use the `embedded_io_async::Write` trait.
@@ -180,7 +180,7 @@ where
             return Err(StreamError::InvalidSeek);
         }
 
-        let page_size = (N * BLOCK_SIZE) as u64;
+        let page_size = N as u64;
         let old_page = self.position / page_size;
         let new_page = new_pos as u64 / page_size;
 
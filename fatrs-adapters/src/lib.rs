@@ -11,7 +11,7 @@
 //! Pure business logic with no infrastructure dependencies:
 //! - **Entities**: `Page` with state machine
 //! - **Value Objects**: `PageNumber`, `BlockAddress`, `PageConfig`
-//! - **Services**: `PageBuffer` with business rules
+//! - **Services**: `PageBuffer` with business rules, `PageCache` for multi-page write-back caching
 //! - **Ports**: `BlockStorage` interface
 //!
 //! ## Adapter Layer (`adapters`)
@@ -19,6 +19,7 @@
 //! - **`BlockDeviceAdapter`**: Implements `BlockStorage` using `BlockDevice`
 //! - **`StackBuffer`**: Compile-time sized buffer (no_std compatible)
 //! - **`HeapBuffer`**: Runtime sized buffer (requires `alloc`)
+//! - **`StackCache`** / **`HeapCache`**: Multi-page LRU/CLOCK write-back caches
 //!
 //! ## Infrastructure Layer (`infrastructure`)
 //! High-level utilities built on the domain:
@@ -63,12 +64,19 @@
 //!
 //! # Features
 //!
-//! - `alloc`: Enable heap-allocated adapters (`HeapBuffer`)
+//! - `alloc`: Enable heap-allocated adapters (`HeapBuffer`, `HeapCache`)
 //! - `std`: Enable standard library features
 //! - `log`: Enable logging support
 //! - `defmt`: Enable defmt logging for embedded
 //! - `runtime-tokio`: Use tokio synchronization primitives
 //! - `runtime-generic`: Use async-lock (portable async)
+//! - `nor-ftl`: Wear-leveling flash translation layer for raw NOR flash (`NorFlashFtl`)
+//! - `nand`: Raw NAND flash adapter with ECC and bad block management (`NandFlashAdapter`)
+//! - `encryption`: AES-256-XTS encrypting block device (`EncryptedBlockDevice`)
+//! - `checksums`: Per-block CRC32C verification and scrubbing (`ChecksummedBlockDevice`)
+//! - `overlay`: Copy-on-write overlay with commit/discard over a read-only base (`OverlayBlockDevice`)
+//! - `trace`: Block I/O trace recording and replay (`RecordingBlockDevice`, `TraceReplayer`)
+//! - `fault-injection`: Power-loss and error injection for tests (`FaultyBlockDevice`, `explore_cut_points`)
 
 #![cfg_attr(not(test), no_std)]
 #![warn(missing_docs)]
@@ -83,15 +91,16 @@ pub mod infrastructure;
 pub use domain::{
     BlockAddress, BlockStorage, DomainError, Page, PageConfig, PageConfigError, PageNumber,
     PageState, BLOCK_SIZE_512, BLOCK_SIZE_4096, BLOCK_SIZE_128K, BLOCK_SIZE_256K,
+    CachePolicy, CacheStats, EvictionPolicy, WriteBackOrder,
 };
 
 pub use adapters::{
     AdapterError, BlockDeviceAdapter, StackBuffer, StackBuffer2K, StackBuffer4K, StackBuffer8K,
-    StackBuffer4KBlock4K, StackBuffer128KBlock128K,
+    StackBuffer4KBlock4K, StackBuffer128KBlock128K, StackCache, StackCache4K, StackCache4KBlock4K,
 };
 
 #[cfg(feature = "alloc")]
-pub use adapters::{presets, HeapBuffer};
+pub use adapters::{presets, HeapBuffer, HeapCache};
 
 #[cfg(feature = "embedded-storage")]
 pub use adapters::{NorFlashAdapter, NorFlashConfig, NorFlashError, NOR_FLASH_BLOCK_SIZE};
@@ -99,11 +108,39 @@ pub use adapters::{NorFlashAdapter, NorFlashConfig, NorFlashError, NOR_FLASH_BLO
 #[cfg(feature = "embedded-storage")]
 pub use adapters::{HeaderRotatingDevice, HeaderRotationConfig, HEADER_ROTATION_BLOCK_SIZE};
 
+#[cfg(feature = "nor-ftl")]
+pub use adapters::{FTL_SECTOR_SIZE, NorFlashFtl, NorFtlConfig, NorFtlError, NorFtlStats};
+
+#[cfg(feature = "nand")]
+pub use adapters::{
+    NandConfig, NandEcc, NandError, NandFlash, NandFlashAdapter, NandGeometry, NandStats,
+};
+
+#[cfg(feature = "encryption")]
+pub use adapters::{EncryptedBlockDevice, KeyProvider, XtsKey, XTS_KEY_SIZE};
+
+#[cfg(feature = "checksums")]
+pub use adapters::{ChecksumError, ChecksummedBlockDevice, ScrubReport};
+
+#[cfg(feature = "overlay")]
+pub use adapters::{DeltaStore, DeviceDelta, OverlayBlockDevice, OverlayError, RamDelta};
+
+#[cfg(feature = "trace")]
+pub use adapters::{
+    RecordingBlockDevice, ReplayReport, TraceError, TraceMode, TraceOp, TraceRecord, TraceReplayer,
+};
+
+#[cfg(feature = "fault-injection")]
+pub use adapters::{
+    CutPointFailure, CutPointReport, FaultError, FaultPlan, FaultStats, FaultyBlockDevice,
+    explore_cut_points,
+};
+
 // Infrastructure layer exports
-pub use infrastructure::streaming::{StackPageStream, StreamError};
+pub use infrastructure::streaming::{StackCacheStream, StackPageStream, StreamError};
 
 #[cfg(feature = "alloc")]
-pub use infrastructure::streaming::HeapPageStream;
+pub use infrastructure::streaming::{HeapCacheStream, HeapPageStream};
 
 // Re-export embedded_io_async for convenience
 pub use embedded_io_async;
@@ -0,0 +1,357 @@
+//! Virtual disk image detection and shared helpers
+//!
+//! Provides [`DiskImage`], a `BlockDevice<512>` that detects the format of an
+//! image stream by its magic numbers and opens it with the matching backend:
+//! raw, fixed or dynamic VHD ([`VhdDevice`]), VHDX ([`VhdxDevice`], writes
+//! limited to allocated blocks) or qcow2 ([`Qcow2Device`], read-only).
+
+use aligned::{A4, Aligned};
+use embedded_io_async::{Read, Seek, SeekFrom, Write};
+use fatrs_block_device::BlockDevice;
+
+use crate::qcow2::Qcow2Device;
+use crate::stream::StreamBlockDevice;
+use crate::vhd::VhdDevice;
+use crate::vhdx::VhdxDevice;
+
+pub(crate) const SECTOR_SIZE: usize = 512;
+
+/// Error type for disk image backends
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-logging", derive(defmt::Format))]
+pub enum ImageError<E> {
+    /// Error from the underlying stream
+    Io(E),
+    /// The image ended before a structure it references
+    UnexpectedEof,
+    /// A header or table is malformed or fails its checksum
+    Corrupt(&'static str),
+    /// The image uses a feature this backend does not implement
+    Unsupported(&'static str),
+    /// The format is opened read-only
+    ReadOnly,
+    /// Access past the end of the virtual disk
+    OutOfBounds,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for ImageError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "I/O error: {}", e),
+            ImageError::UnexpectedEof => write!(f, "Unexpected end of image"),
+            ImageError::Corrupt(what) => write!(f, "Corrupt image: {}", what),
+            ImageError::Unsupported(what) => write!(f, "Unsupported image feature: {}", what),
+            ImageError::ReadOnly => write!(f, "Image format is read-only"),
+            ImageError::OutOfBounds => write!(f, "Access past the end of the virtual disk"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug + core::fmt::Display> core::error::Error for ImageError<E> {}
+
+impl<E> From<E> for ImageError<E> {
+    fn from(e: E) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+/// Disk image formats recognised by [`detect_format`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-logging", derive(defmt::Format))]
+pub enum ImageFormat {
+    /// No known header: the image is the disk itself
+    Raw,
+    /// VHD (fixed or dynamic)
+    Vhd,
+    /// VHDX
+    Vhdx,
+    /// QEMU copy-on-write v2/v3
+    Qcow2,
+}
+
+/// Detect the format of an image by its magic numbers
+///
+/// Checks for the VHDX file identifier and the qcow2 magic at the start of
+/// the image, then for a VHD footer (`conectix`) at the start (dynamic disks
+/// keep a copy there) or in the last 512 bytes. Anything else is raw.
+pub async fn detect_format<T: Read + Seek>(
+    io: &mut T,
+) -> Result<ImageFormat, ImageError<T::Error>> {
+    let len = io.seek(SeekFrom::End(0)).await?;
+    let mut magic = [0u8; 8];
+    if len >= 8 {
+        read_exact_at(io, 0, &mut magic).await?;
+        if &magic == crate::vhdx::FILE_SIGNATURE {
+            return Ok(ImageFormat::Vhdx);
+        }
+        if magic[..4] == crate::qcow2::MAGIC {
+            return Ok(ImageFormat::Qcow2);
+        }
+        if &magic == crate::vhd::COOKIE {
+            return Ok(ImageFormat::Vhd);
+        }
+    }
+    if len >= SECTOR_SIZE as u64 {
+        read_exact_at(io, len - SECTOR_SIZE as u64, &mut magic).await?;
+        if &magic == crate::vhd::COOKIE {
+            return Ok(ImageFormat::Vhd);
+        }
+    }
+    Ok(ImageFormat::Raw)
+}
+
+/// Block device over a disk image of any supported format
+///
+/// # Example
+///
+/// ```ignore
+/// use fatrs_block_platform::DiskImage;
+/// use embedded_io_adapters::tokio_1::FromTokio;
+///
+/// let file = tokio::fs::File::open("disk.vhdx").await?;
+/// let device = DiskImage::open(FromTokio::new(file)).await?;
+/// println!("{:?}", device.format());
+/// ```
+#[allow(clippy::large_enum_variant)] // no_std: variants can't be boxed
+pub enum DiskImage<T> {
+    /// Raw image
+    Raw(StreamBlockDevice<T>),
+    /// VHD image
+    Vhd(VhdDevice<T>),
+    /// VHDX image
+    Vhdx(VhdxDevice<T>),
+    /// qcow2 image
+    Qcow2(Qcow2Device<T>),
+}
+
+impl<T: Read + Write + Seek> DiskImage<T> {
+    /// Detect the format of `io` and open it with the matching backend
+    pub async fn open(mut io: T) -> Result<Self, ImageError<T::Error>> {
+        let format = detect_format(&mut io).await?;
+        io.seek(SeekFrom::Start(0)).await?;
+        Ok(match format {
+            ImageFormat::Raw => DiskImage::Raw(StreamBlockDevice::new(io)),
+            ImageFormat::Vhd => DiskImage::Vhd(VhdDevice::open(io).await?),
+            ImageFormat::Vhdx => DiskImage::Vhdx(VhdxDevice::open(io).await?),
+            ImageFormat::Qcow2 => DiskImage::Qcow2(Qcow2Device::open(io).await?),
+        })
+    }
+
+    /// Get the detected format
+    pub fn format(&self) -> ImageFormat {
+        match self {
+            DiskImage::Raw(_) => ImageFormat::Raw,
+            DiskImage::Vhd(_) => ImageFormat::Vhd,
+            DiskImage::Vhdx(_) => ImageFormat::Vhdx,
+            DiskImage::Qcow2(_) => ImageFormat::Qcow2,
+        }
+    }
+}
+
+impl<T: Read + Write + Seek> BlockDevice<SECTOR_SIZE> for DiskImage<T> {
+    type Error = ImageError<T::Error>;
+    type Align = A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        match self {
+            DiskImage::Raw(d) => d.read(block_address, data).await.map_err(ImageError::Io),
+            DiskImage::Vhd(d) => d.read(block_address, data).await,
+            DiskImage::Vhdx(d) => d.read(block_address, data).await,
+            DiskImage::Qcow2(d) => d.read(block_address, data).await,
+        }
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        match self {
+            DiskImage::Raw(d) => d.write(block_address, data).await.map_err(ImageError::Io),
+            DiskImage::Vhd(d) => d.write(block_address, data).await,
+            DiskImage::Vhdx(d) => d.write(block_address, data).await,
+            DiskImage::Qcow2(d) => d.write(block_address, data).await,
+        }
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        match self {
+            DiskImage::Raw(d) => d.size().await.map_err(ImageError::Io),
+            DiskImage::Vhd(d) => d.size().await,
+            DiskImage::Vhdx(d) => d.size().await,
+            DiskImage::Qcow2(d) => d.size().await,
+        }
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        match self {
+            DiskImage::Raw(d) => d.sync().await.map_err(ImageError::Io),
+            DiskImage::Vhd(d) => d.sync().await,
+            DiskImage::Vhdx(d) => d.sync().await,
+            DiskImage::Qcow2(d) => d.sync().await,
+        }
+    }
+}
+
+/// Read exactly `buf.len()` bytes at `offset`
+pub(crate) async fn read_exact_at<T: Read + Seek>(
+    io: &mut T,
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<(), ImageError<T::Error>> {
+    io.seek(SeekFrom::Start(offset)).await?;
+    let mut done = 0;
+    while done < buf.len() {
+        let n = io.read(&mut buf[done..]).await?;
+        if n == 0 {
+            return Err(ImageError::UnexpectedEof);
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+/// Write all of `buf` at `offset`
+pub(crate) async fn write_all_at<T: Write + Seek>(
+    io: &mut T,
+    offset: u64,
+    buf: &[u8],
+) -> Result<(), ImageError<T::Error>> {
+    io.seek(SeekFrom::Start(offset)).await?;
+    let mut done = 0;
+    while done < buf.len() {
+        let n = io.write(&buf[done..]).await?;
+        if n == 0 {
+            return Err(ImageError::UnexpectedEof);
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+/// Read consecutive sectors starting at byte `offset`
+pub(crate) async fn read_sectors<T: Read + Seek>(
+    io: &mut T,
+    offset: u64,
+    data: &mut [Aligned<A4, [u8; SECTOR_SIZE]>],
+) -> Result<(), ImageError<T::Error>> {
+    for (i, sector) in data.iter_mut().enumerate() {
+        read_exact_at(io, offset + (i * SECTOR_SIZE) as u64, &mut sector[..]).await?;
+    }
+    Ok(())
+}
+
+/// Write consecutive sectors starting at byte `offset`
+pub(crate) async fn write_sectors<T: Write + Seek>(
+    io: &mut T,
+    offset: u64,
+    data: &[Aligned<A4, [u8; SECTOR_SIZE]>],
+) -> Result<(), ImageError<T::Error>> {
+    for (i, sector) in data.iter().enumerate() {
+        write_all_at(io, offset + (i * SECTOR_SIZE) as u64, &sector[..]).await?;
+    }
+    Ok(())
+}
+
+pub(crate) fn be_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+pub(crate) fn be_u64(buf: &[u8], at: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[at..at + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+pub(crate) fn le_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+pub(crate) fn le_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+pub(crate) fn le_u64(buf: &[u8], at: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Check that `count` sectors at `block_address` lie within a disk of `size` bytes
+pub(crate) fn check_bounds<E>(
+    size: u64,
+    block_address: u32,
+    count: usize,
+) -> Result<u64, ImageError<E>> {
+    let offset = u64::from(block_address) * SECTOR_SIZE as u64;
+    if offset + (count * SECTOR_SIZE) as u64 > size {
+        return Err(ImageError::OutOfBounds);
+    }
+    Ok(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::{MemStream, block_on};
+    use std::vec;
+    use std::vec::Vec;
+
+    fn detect(data: Vec<u8>) -> ImageFormat {
+        block_on(detect_format(&mut MemStream::new(data))).unwrap()
+    }
+
+    fn with_magic(len: usize, at: usize, magic: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        data[at..at + magic.len()].copy_from_slice(magic);
+        data
+    }
+
+    #[test]
+    fn test_detect_format_by_magic() {
+        assert_eq!(detect(with_magic(4096, 0, b"vhdxfile")), ImageFormat::Vhdx);
+        assert_eq!(detect(with_magic(4096, 0, b"QFI\xfb")), ImageFormat::Qcow2);
+        // Dynamic VHDs keep a copy of the footer at the start
+        assert_eq!(detect(with_magic(4096, 0, b"conectix")), ImageFormat::Vhd);
+        // Fixed VHDs only have the footer in the last sector
+        assert_eq!(
+            detect(with_magic(4096, 3584, b"conectix")),
+            ImageFormat::Vhd
+        );
+        assert_eq!(detect(with_magic(512, 0, b"conectix")), ImageFormat::Vhd);
+    }
+
+    #[test]
+    fn test_detect_format_falls_back_to_raw() {
+        assert_eq!(detect(vec![0u8; 4096]), ImageFormat::Raw);
+        // A cookie that is neither at the start nor in the footer slot
+        assert_eq!(
+            detect(with_magic(4096, 3000, b"conectix")),
+            ImageFormat::Raw
+        );
+        assert_eq!(detect(vec![0u8; 4]), ImageFormat::Raw);
+        assert_eq!(detect(Vec::new()), ImageFormat::Raw);
+    }
+
+    #[test]
+    fn test_raw_image_round_trip() {
+        block_on(async {
+            let mut image = DiskImage::open(MemStream::new(vec![0u8; 4096]))
+                .await
+                .unwrap();
+            assert_eq!(image.format(), ImageFormat::Raw);
+            assert_eq!(image.size().await.unwrap(), 4096);
+
+            image.write(3, &[Aligned([9; 512])]).await.unwrap();
+            let mut buf = [Aligned([0u8; 512])];
+            image.read(3, &mut buf).await.unwrap();
+            assert_eq!(buf[0][0], 9);
+        });
+    }
+}
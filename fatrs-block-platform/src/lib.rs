@@ -4,15 +4,21 @@
 //!
 //! - **Embedded (SPI)**: SD cards over SPI for microcontrollers (ARM, ESP32, RP2040, etc.)
 //! - **Windows**: Direct device access via Win32 APIs (USB drives, flash cards)
-//! - **Linux**: Block device access via `/dev/sdX` and ioctl
+//! - **Linux**: Block device access via `/dev/sdX` and ioctl (512n/512e/4Kn, TRIM), optionally through io_uring
 //! - **macOS**: Disk access via `/dev/diskX`
+//! - **RAM**: In-memory block device over a static or owned buffer (`no_std`)
+//! - **Disk images**: VHD, VHDX (no block allocation) and qcow2 (read-only) images with
+//!   format auto-detection
 //!
 //! ## Feature Flags
 //!
 //! - `sdspi` - SD card over SPI (embedded, `no_std`)
 //! - `windows` - Windows device access (requires `std`)
 //! - `linux` - Linux block device access (requires `std`)
+//! - `linux-uring` - io_uring-backed Linux block device with batched, concurrent I/O (requires `std`)
 //! - `macos` - macOS disk access (requires `std`)
+//! - `ram` - RAM-backed block device with optional latency and erase simulation (`no_std`)
+//! - `disk-images` - VHD, VHDX and qcow2 image backends over any async stream (`no_std`)
 //! - `logging` - Enable `log` crate integration
 //! - `defmt-logging` - Enable `defmt` logging for embedded
 //!
@@ -43,7 +49,39 @@
 //! ```ignore
 //! use fatrs_block_platform::LinuxBlockDevice;
 //!
+//! // Refuses devices with a mounted partition; use `open_forced` to override
 //! let device = LinuxBlockDevice::open("/dev/sdb", false).await?;
+//! println!("{} byte logical sectors", device.logical_sector_size());
+//! ```
+//!
+//! ### Linux (io_uring)
+//!
+//! ```ignore
+//! use fatrs_block_platform::UringBlockDevice;
+//!
+//! let device = UringBlockDevice::open("/dev/sdb", false).await?;
+//! ```
+//!
+//! ### RAM (no_std)
+//!
+//! ```ignore
+//! use fatrs_block_platform::{RamBlockDevice, RamBuffer};
+//!
+//! // 64 KiB scratch disk with owned storage
+//! static mut DISK: RamBuffer<512, 128> = RamBuffer::new();
+//! let device = RamBlockDevice::<512, 128, _>::new(unsafe { &mut *core::ptr::addr_of_mut!(DISK) });
+//! ```
+//!
+//! ### Disk images
+//!
+//! ```ignore
+//! use fatrs_block_platform::{DiskImage, ImageFormat};
+//! use embedded_io_adapters::tokio_1::FromTokio;
+//!
+//! // Raw, VHD, VHDX or qcow2, detected by magic
+//! let file = tokio::fs::OpenOptions::new().read(true).write(true).open("disk.vhdx").await?;
+//! let device = DiskImage::open(FromTokio::new(file)).await?;
+//! assert_eq!(device.format(), ImageFormat::Vhdx);
 //! ```
 
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -58,6 +96,10 @@ mod fmt;
 // Re-export core types
 pub use fatrs_block_device::{BlockDevice, SendBlockDevice};
 
+// Helpers shared by the unit tests
+#[cfg(test)]
+mod test_util;
+
 // Generic stream adapter (requires embedded-io-async)
 #[cfg(feature = "embedded-io-async")]
 pub mod stream;
@@ -82,12 +124,42 @@ pub mod linux;
 #[cfg(all(target_os = "linux", feature = "linux"))]
 pub use linux::{BlockDeviceInfo, LinuxBlockDevice, list_block_devices};
 
+// Linux io_uring module
+#[cfg(all(target_os = "linux", feature = "linux-uring"))]
+pub mod uring;
+#[cfg(all(target_os = "linux", feature = "linux-uring"))]
+pub use uring::{UringBlockDevice, UringConfig};
+
 // macOS module
 #[cfg(all(target_os = "macos", feature = "macos"))]
 pub mod macos;
 #[cfg(all(target_os = "macos", feature = "macos"))]
 pub use macos::{DiskInfo, MacOSBlockDevice, list_disks};
 
+// RAM block device module
+#[cfg(feature = "ram")]
+pub mod ram;
+#[cfg(feature = "ram")]
+pub use ram::{Delay, Error as RamError, Latency, NoDelay, RamBlockDevice, RamBuffer, RamStats};
+
+// Virtual disk image modules
+#[cfg(feature = "disk-images")]
+pub mod image;
+#[cfg(feature = "disk-images")]
+pub mod qcow2;
+#[cfg(feature = "disk-images")]
+pub mod vhd;
+#[cfg(feature = "disk-images")]
+pub mod vhdx;
+#[cfg(feature = "disk-images")]
+pub use image::{DiskImage, ImageError, ImageFormat, detect_format};
+#[cfg(feature = "disk-images")]
+pub use qcow2::Qcow2Device;
+#[cfg(feature = "disk-images")]
+pub use vhd::{VhdDevice, VhdType};
+#[cfg(feature = "disk-images")]
+pub use vhdx::VhdxDevice;
+
 // RP2040/RP2350 flash module
 #[cfg(feature = "rpflash")]
 pub mod rpflash;
@@ -1,15 +1,31 @@
 //! Linux block device implementation
 //!
 //! Provides direct access to block devices (e.g., /dev/sdb, /dev/mmcblk0) on Linux.
+//!
+//! The device implements both `BlockDevice<512>` and `BlockDevice<4096>`. Block
+//! I/O goes through sector-aligned bounce buffers, so 512-byte access to a 4Kn
+//! device is emulated with read-modify-write of the enclosing logical sectors.
 
 use aligned::{A4, Aligned};
 use embedded_io_async::{ErrorType, Read, Seek, SeekFrom, Write};
 use fatrs_block_device::BlockDevice;
+use std::alloc::Layout;
 use std::io;
-use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::fs::{FileExt, FileTypeExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
 
 const BLOCK_SIZE: usize = 512;
+/// Bounce buffer alignment; covers the DMA alignment of 512e and 4Kn devices
+const BUFFER_ALIGN: usize = 4096;
+
+// Block device ioctls from <linux/fs.h>
+const BLKROGET: libc::Ioctl = 0x125E;
+const BLKSSZGET: libc::Ioctl = 0x1268;
+const BLKGETSIZE64: libc::Ioctl = 0x80081272;
+const BLKDISCARD: libc::Ioctl = 0x1277;
+const BLKPBSZGET: libc::Ioctl = 0x127B;
+const BLKSECDISCARD: libc::Ioctl = 0x127D;
 
 /// Linux block device wrapper for async block I/O
 ///
@@ -18,11 +34,18 @@ pub struct LinuxBlockDevice {
     inner: std::sync::Arc<std::sync::Mutex<std::fs::File>>,
     size: u64,
     position: std::sync::Arc<std::sync::Mutex<u64>>,
+    logical_sector_size: u32,
+    physical_sector_size: u32,
+    read_only: bool,
 }
 
 impl LinuxBlockDevice {
     /// Open a Linux block device for direct access
     ///
+    /// Fails with [`io::ErrorKind::ResourceBusy`] if the device or any of its
+    /// partitions is mounted; use [`open_forced`](Self::open_forced) to skip
+    /// that check.
+    ///
     /// # Arguments
     /// * `path` - Device path (e.g., "/dev/sdb", "/dev/mmcblk0")
     /// * `writable` - Whether to open for write access
@@ -32,44 +55,48 @@ impl LinuxBlockDevice {
     /// let dev = LinuxBlockDevice::open("/dev/sdb", false).await?;
     /// ```
     pub async fn open(path: impl AsRef<Path>, writable: bool) -> io::Result<Self> {
-        let path = path.as_ref();
-
-        // Open with O_DIRECT for unbuffered I/O (requires proper alignment)
-        let file = tokio::task::spawn_blocking({
-            let path = path.to_owned();
-            move || {
-                std::fs::OpenOptions::new()
-                    .read(true)
-                    .write(writable)
-                    .custom_flags(libc::O_DIRECT)
-                    .open(&path)
-            }
-        })
-        .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+        Self::open_with(path.as_ref(), writable, false).await
+    }
+
+    /// Open a Linux block device even if it or its partitions are mounted
+    ///
+    /// Writing to a mounted filesystem's device corrupts it; only use this
+    /// when the mount is known to be idle or read-only.
+    pub async fn open_forced(path: impl AsRef<Path>, writable: bool) -> io::Result<Self> {
+        Self::open_with(path.as_ref(), writable, true).await
+    }
 
-        // Get device size using BLKGETSIZE64 ioctl
-        let size = tokio::task::spawn_blocking({
-            let file = file.try_clone()?;
-            move || {
-                use std::os::unix::io::AsRawFd;
-                let mut size: u64 = 0;
-                unsafe {
-                    // BLKGETSIZE64 = 0x80081272
-                    if libc::ioctl(file.as_raw_fd(), 0x80081272, &mut size) < 0 {
-                        return Err(io::Error::last_os_error());
-                    }
+    async fn open_with(path: &Path, writable: bool, force: bool) -> io::Result<Self> {
+        let path = path.to_owned();
+        let (file, geometry) = tokio::task::spawn_blocking(move || {
+            if !force {
+                if let Some((source, target)) = find_mount(&path)? {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ResourceBusy,
+                        format!("{} is mounted at {}", source, target),
+                    ));
                 }
-                Ok::<u64, io::Error>(size)
             }
+
+            // Open with O_DIRECT for unbuffered I/O (requires proper alignment)
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(writable)
+                .custom_flags(libc::O_DIRECT)
+                .open(&path)?;
+            let geometry = Geometry::query(&file)?;
+            Ok((file, geometry))
         })
         .await
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
 
         Ok(Self {
             inner: std::sync::Arc::new(std::sync::Mutex::new(file)),
-            size,
+            size: geometry.size,
             position: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            logical_sector_size: geometry.logical_sector_size,
+            physical_sector_size: geometry.physical_sector_size,
+            read_only: geometry.read_only,
         })
     }
 
@@ -77,6 +104,133 @@ impl LinuxBlockDevice {
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    /// Get the logical sector size (`BLKSSZGET`), the smallest addressable unit
+    pub fn logical_sector_size(&self) -> u32 {
+        self.logical_sector_size
+    }
+
+    /// Get the physical sector size (`BLKPBSZGET`), the device's internal write unit
+    pub fn physical_sector_size(&self) -> u32 {
+        self.physical_sector_size
+    }
+
+    /// Whether the kernel reports the device as read-only (`BLKROGET`)
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Discard (TRIM) a byte range so the device can reclaim it
+    ///
+    /// `offset` and `len` must be multiples of the logical sector size.
+    /// Discarded data may read back as zeros or as its old contents.
+    pub async fn discard(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        self.discard_ioctl(BLKDISCARD, offset, len).await
+    }
+
+    /// Securely discard a byte range, also erasing any copies the device
+    /// keeps internally (`BLKSECDISCARD`)
+    ///
+    /// Fails with `EOPNOTSUPP` on devices without secure erase support.
+    pub async fn secure_discard(&mut self, offset: u64, len: u64) -> io::Result<()> {
+        self.discard_ioctl(BLKSECDISCARD, offset, len).await
+    }
+
+    async fn discard_ioctl(
+        &mut self,
+        request: libc::Ioctl,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<()> {
+        let sector = u64::from(self.logical_sector_size);
+        if offset % sector != 0 || len % sector != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Discard range must be aligned to the logical sector size",
+            ));
+        }
+        if offset.checked_add(len).is_none_or(|end| end > self.size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Discard range beyond end of device",
+            ));
+        }
+
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let file = inner.lock().unwrap();
+            let range: [u64; 2] = [offset, len];
+            if unsafe { libc::ioctl(file.as_raw_fd(), request, &range) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+    }
+
+    async fn read_blocks<const SIZE: usize>(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<A4, [u8; SIZE]>],
+    ) -> io::Result<()> {
+        let inner = self.inner.clone();
+        let offset = (block_address as u64) * SIZE as u64;
+        let len = data.len() * SIZE;
+        let sector = self.logical_sector_size as usize;
+
+        let (buf, skip) = tokio::task::spawn_blocking(move || {
+            let file = inner.lock().unwrap();
+            read_span(&file, offset, len, sector)
+        })
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+
+        for (block, src) in data
+            .iter_mut()
+            .zip(buf[skip..skip + len].chunks_exact(SIZE))
+        {
+            block.copy_from_slice(src);
+        }
+        Ok(())
+    }
+
+    async fn write_blocks<const SIZE: usize>(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<A4, [u8; SIZE]>],
+    ) -> io::Result<()> {
+        let inner = self.inner.clone();
+        let offset = (block_address as u64) * SIZE as u64;
+        let sector = self.logical_sector_size as usize;
+
+        // Flatten aligned blocks into a Vec
+        let write_data: Vec<u8> = data
+            .iter()
+            .flat_map(|block| block.iter().copied())
+            .collect();
+
+        tokio::task::spawn_blocking(move || {
+            // Held across the read-modify-write so clones cannot interleave
+            let file = inner.lock().unwrap();
+            write_span(&file, offset, &write_data, sector)
+        })
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+    }
+
+    async fn sync_data(&mut self) -> io::Result<()> {
+        let inner = self.inner.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let file = inner.lock().unwrap();
+            // fdatasync: the block device has no metadata worth syncing, and
+            // the kernel turns this into a cache FLUSH for the device
+            file.sync_data()
+        })
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+    }
 }
 
 impl Clone for LinuxBlockDevice {
@@ -85,6 +239,9 @@ impl Clone for LinuxBlockDevice {
             inner: std::sync::Arc::clone(&self.inner),
             size: self.size,
             position: std::sync::Arc::clone(&self.position),
+            logical_sector_size: self.logical_sector_size,
+            physical_sector_size: self.physical_sector_size,
+            read_only: self.read_only,
         }
     }
 }
@@ -175,95 +332,257 @@ impl BlockDevice<BLOCK_SIZE> for LinuxBlockDevice {
         block_address: u32,
         data: &mut [Aligned<Self::Align, [u8; BLOCK_SIZE]>],
     ) -> Result<(), Self::Error> {
-        let inner = self.inner.clone();
-        let position = self.position.clone();
-        let block_offset = (block_address as u64) * BLOCK_SIZE as u64;
-        let total_len = data.len() * BLOCK_SIZE;
-
-        let result = tokio::task::spawn_blocking(move || {
-            use std::io::{Read as _, Seek as _};
-            let mut file = inner.lock().unwrap();
+        self.read_blocks(block_address, data).await
+    }
 
-            // Seek to block position
-            file.seek(std::io::SeekFrom::Start(block_offset))?;
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; BLOCK_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        self.write_blocks(block_address, data).await
+    }
 
-            // Read all blocks into a temporary buffer
-            let mut temp_buf = vec![0u8; total_len];
-            let mut offset = 0;
-            while offset < total_len {
-                let n = file.read(&mut temp_buf[offset..])?;
-                if n == 0 {
-                    return Err(io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        "Unexpected EOF",
-                    ));
-                }
-                offset += n;
-            }
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(LinuxBlockDevice::size(self))
+    }
 
-            // Update position
-            *position.lock().unwrap() = block_offset + total_len as u64;
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.sync_data().await
+    }
+}
 
-            Ok::<Vec<u8>, io::Error>(temp_buf)
-        })
-        .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+/// Native block size for 4Kn devices; also usable on 512e devices
+impl BlockDevice<4096> for LinuxBlockDevice {
+    type Error = io::Error;
+    type Align = A4;
 
-        // Copy result into aligned blocks
-        for (i, block) in data.iter_mut().enumerate() {
-            let start = i * BLOCK_SIZE;
-            block.copy_from_slice(&result[start..start + BLOCK_SIZE]);
-        }
-        Ok(())
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; 4096]>],
+    ) -> Result<(), Self::Error> {
+        self.read_blocks(block_address, data).await
     }
 
     async fn write(
         &mut self,
         block_address: u32,
-        data: &[Aligned<Self::Align, [u8; BLOCK_SIZE]>],
+        data: &[Aligned<Self::Align, [u8; 4096]>],
     ) -> Result<(), Self::Error> {
-        let inner = self.inner.clone();
-        let block_offset = (block_address as u64) * BLOCK_SIZE as u64;
+        self.write_blocks(block_address, data).await
+    }
 
-        // Flatten aligned blocks into a Vec
-        let write_data: Vec<u8> = data.iter().flat_map(|block| block.iter().copied()).collect();
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(LinuxBlockDevice::size(self))
+    }
 
-        tokio::task::spawn_blocking(move || {
-            use std::io::{Seek as _, Write as _};
-            let mut file = inner.lock().unwrap();
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.sync_data().await
+    }
+}
 
-            file.seek(std::io::SeekFrom::Start(block_offset))?;
+/// Sector-aligned heap buffer suitable for `O_DIRECT`
+struct AlignedBuf {
+    ptr: std::ptr::NonNull<u8>,
+    layout: Layout,
+}
 
-            let mut offset = 0;
-            while offset < write_data.len() {
-                let n = file.write(&write_data[offset..])?;
-                if n == 0 {
-                    return Err(io::Error::new(io::ErrorKind::WriteZero, "Write returned 0"));
-                }
-                offset += n;
+// Plain owned memory
+unsafe impl Send for AlignedBuf {}
+
+impl AlignedBuf {
+    fn zeroed(len: usize) -> io::Result<Self> {
+        let layout = Layout::from_size_align(len.max(1), BUFFER_ALIGN)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let ptr = std::ptr::NonNull::new(unsafe { std::alloc::alloc_zeroed(layout) })
+            .ok_or_else(|| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        Ok(Self { ptr, layout })
+    }
+}
+
+impl std::ops::Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl std::ops::DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+/// Read the logical sectors covering `offset..offset + len`
+///
+/// Returns the buffer and the position of `offset` within it.
+fn read_span(
+    file: &std::fs::File,
+    offset: u64,
+    len: usize,
+    sector: usize,
+) -> io::Result<(AlignedBuf, usize)> {
+    let start = offset - offset % sector as u64;
+    let end = (offset + len as u64).next_multiple_of(sector as u64);
+    let mut buf = AlignedBuf::zeroed((end - start) as usize)?;
+    file.read_exact_at(&mut buf, start)?;
+    Ok((buf, (offset - start) as usize))
+}
+
+/// Write `data` at `offset`, merging with the existing contents of partially
+/// covered logical sectors
+fn write_span(file: &std::fs::File, offset: u64, data: &[u8], sector: usize) -> io::Result<()> {
+    let (mut buf, skip) = if offset % sector as u64 == 0 && data.len() % sector == 0 {
+        (AlignedBuf::zeroed(data.len())?, 0)
+    } else {
+        read_span(file, offset, data.len(), sector)?
+    };
+    buf[skip..skip + data.len()].copy_from_slice(data);
+    file.write_all_at(&buf, offset - skip as u64)
+}
+
+/// Size, sector sizes and read-only flag of an open block device
+struct Geometry {
+    size: u64,
+    logical_sector_size: u32,
+    physical_sector_size: u32,
+    read_only: bool,
+}
+
+impl Geometry {
+    fn query(file: &std::fs::File) -> io::Result<Self> {
+        let fd = file.as_raw_fd();
+        let mut physical: libc::c_uint = 0;
+        let mut read_only: libc::c_int = 0;
+        unsafe {
+            if libc::ioctl(fd, BLKPBSZGET, &mut physical) < 0
+                || libc::ioctl(fd, BLKROGET, &mut read_only) < 0
+            {
+                return Err(io::Error::last_os_error());
             }
-            Ok::<(), io::Error>(())
+        }
+
+        let logical_sector_size = logical_sector_size(file)?;
+        Ok(Self {
+            size: device_size(file)?,
+            logical_sector_size,
+            physical_sector_size: physical.max(logical_sector_size),
+            read_only: read_only != 0,
         })
-        .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+    }
+}
 
-        Ok(())
+/// Query the logical sector size of a block device (`BLKSSZGET`)
+///
+/// Fails with [`io::ErrorKind::Unsupported`] unless it is a power of two
+/// between 512 and 4096 bytes.
+pub(crate) fn logical_sector_size(file: &std::fs::File) -> io::Result<u32> {
+    let mut logical: libc::c_int = 0;
+    unsafe {
+        if libc::ioctl(file.as_raw_fd(), BLKSSZGET, &mut logical) < 0 {
+            return Err(io::Error::last_os_error());
+        }
     }
+    let size = logical as u32;
+    if !size.is_power_of_two() || !(512..=4096).contains(&size) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Unsupported logical sector size {}", logical),
+        ));
+    }
+    Ok(size)
+}
 
-    async fn size(&self) -> Result<u64, Self::Error> {
-        Ok(LinuxBlockDevice::size(self))
+/// Query the size of a block device in bytes
+pub(crate) fn device_size(file: &std::fs::File) -> io::Result<u64> {
+    let mut size: u64 = 0;
+    unsafe {
+        if libc::ioctl(file.as_raw_fd(), BLKGETSIZE64, &mut size) < 0 {
+            return Err(io::Error::last_os_error());
+        }
     }
+    Ok(size)
+}
 
-    async fn sync(&mut self) -> Result<(), Self::Error> {
-        let inner = self.inner.clone();
+/// Find a mount of the block device at `path` or of one of its partitions
+///
+/// Returns the mount source and target from `/proc/self/mounts`.
+fn find_mount(path: &Path) -> io::Result<Option<(String, String)>> {
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.file_type().is_block_device() {
+        return Ok(None);
+    }
+    let mounts = std::fs::read_to_string("/proc/self/mounts")?;
+    Ok(match_mount(
+        metadata.rdev(),
+        &mounts,
+        source_device,
+        parent_device,
+    ))
+}
 
-        tokio::task::spawn_blocking(move || {
-            let file = inner.lock().unwrap();
-            file.sync_all()
-        })
-        .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+/// Find the first entry of a mount table whose source is `device` or one of
+/// its partitions
+///
+/// `mounts` is in `/proc/self/mounts` format. `source_device` resolves a
+/// mount source to its device number and `parent_of` maps a partition to the
+/// "major:minor" of its disk.
+fn match_mount(
+    device: libc::dev_t,
+    mounts: &str,
+    source_device: impl Fn(&str) -> Option<libc::dev_t>,
+    parent_of: impl Fn(libc::dev_t) -> Option<String>,
+) -> Option<(String, String)> {
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(source), Some(target)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some(mounted) = source_device(source) else {
+            continue;
+        };
+        if mounted == device || parent_of(mounted).as_deref() == Some(&dev_name(device)) {
+            // Octal escapes (e.g. "\040" for spaces) are left as-is
+            return Some((source.to_string(), target.to_string()));
+        }
+    }
+    None
+}
+
+/// Device number of a mount source, if it is a block device
+fn source_device(source: &str) -> Option<libc::dev_t> {
+    let metadata = std::fs::metadata(source).ok()?;
+    metadata
+        .file_type()
+        .is_block_device()
+        .then(|| metadata.rdev())
+}
+
+fn dev_name(device: libc::dev_t) -> String {
+    format!("{}:{}", libc::major(device), libc::minor(device))
+}
+
+/// For a partition, the "major:minor" of the whole disk it belongs to
+fn parent_device(device: libc::dev_t) -> Option<String> {
+    parent_device_in(Path::new("/sys"), device)
+}
+
+/// [`parent_device`] against the sysfs tree mounted at `sys`
+fn parent_device_in(sys: &Path, device: libc::dev_t) -> Option<String> {
+    let sys = std::fs::canonicalize(sys.join("dev/block").join(dev_name(device))).ok()?;
+    if !sys.join("partition").exists() {
+        return None;
     }
+    let parent = std::fs::read_to_string(sys.parent()?.join("dev")).ok()?;
+    Some(parent.trim().to_string())
 }
 
 /// Information about a block device
@@ -332,3 +651,136 @@ pub async fn list_block_devices() -> io::Result<Vec<BlockDeviceInfo>> {
     .await
     .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Scratch path under the system temp directory, unique to this process
+    fn scratch(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("fatrs-linux-{}-{name}", std::process::id()))
+    }
+
+    fn numbered_file(path: &Path) -> std::fs::File {
+        let data: Vec<u8> = (0..16384u32).map(|i| (i / 512) as u8).collect();
+        std::fs::write(path, data).unwrap();
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_read_span_covers_whole_sectors() {
+        let path = scratch("read-span");
+        let file = numbered_file(&path);
+
+        let (buf, skip) = read_span(&file, 4608, 1024, 4096).unwrap();
+        assert_eq!((buf.len(), skip), (4096, 512));
+        assert_eq!(buf.as_ptr() as usize % BUFFER_ALIGN, 0);
+        assert_eq!((buf[skip], buf[skip + 1023]), (9, 10));
+
+        // A span crossing a sector boundary reads both sectors
+        let (buf, skip) = read_span(&file, 7680, 1024, 4096).unwrap();
+        assert_eq!((buf.len(), skip), (8192, 3584));
+        assert_eq!((buf[skip], buf[skip + 1023]), (15, 16));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_span_merges_partial_sectors() {
+        let path = scratch("write-span");
+        let file = numbered_file(&path);
+
+        write_span(&file, 4608, &[0xAA; 512], 4096).unwrap();
+        write_span(&file, 8192, &[0xBB; 4096], 4096).unwrap();
+        write_span(&file, 16000, &[0xCC; 384], 512).unwrap();
+
+        let after = std::fs::read(&path).unwrap();
+        assert_eq!(after.len(), 16384);
+        assert_eq!(
+            (after[4607], after[4608], after[5119], after[5120]),
+            (8, 0xAA, 0xAA, 10)
+        );
+        assert_eq!(
+            (after[8191], after[8192], after[12287], after[12288]),
+            (15, 0xBB, 0xBB, 24)
+        );
+        assert_eq!((after[15999], after[16000], after[16383]), (31, 0xCC, 0xCC));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    const MOUNTS: &str = "\
+proc /proc proc rw,nosuid 0 0
+/dev/sda1 /boot ext4 rw,relatime 0 0
+/dev/sdb /media/usb vfat rw 0 0
+tmpfs /tmp tmpfs rw 0 0
+";
+
+    fn fixture_device(source: &str) -> Option<libc::dev_t> {
+        match source {
+            "/dev/sda1" => Some(libc::makedev(8, 1)),
+            "/dev/sdb" => Some(libc::makedev(8, 16)),
+            _ => None,
+        }
+    }
+
+    fn fixture_parent(device: libc::dev_t) -> Option<String> {
+        (device == libc::makedev(8, 1)).then(|| "8:0".to_string())
+    }
+
+    fn mount_of(device: libc::dev_t) -> Option<(String, String)> {
+        match_mount(device, MOUNTS, fixture_device, fixture_parent)
+    }
+
+    #[test]
+    fn test_match_mount() {
+        let mount = |source: &str, target: &str| Some((source.to_string(), target.to_string()));
+        assert_eq!(mount_of(libc::makedev(8, 1)), mount("/dev/sda1", "/boot"));
+        // The whole disk counts as mounted through its partition
+        assert_eq!(mount_of(libc::makedev(8, 0)), mount("/dev/sda1", "/boot"));
+        assert_eq!(
+            mount_of(libc::makedev(8, 16)),
+            mount("/dev/sdb", "/media/usb")
+        );
+        assert_eq!(mount_of(libc::makedev(8, 32)), None);
+        assert_eq!(
+            match_mount(libc::makedev(8, 1), "", fixture_device, fixture_parent),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_mount_ignores_non_block_devices() {
+        assert_eq!(find_mount(Path::new("/proc/self/mounts")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parent_device_in_sysfs_fixture() {
+        use std::os::unix::fs::symlink;
+
+        let sys = scratch("sys");
+        let disk = sys.join("devices/virtual/block/sda");
+        std::fs::create_dir_all(disk.join("sda1")).unwrap();
+        std::fs::create_dir_all(sys.join("dev/block")).unwrap();
+        std::fs::write(disk.join("dev"), "8:0\n").unwrap();
+        std::fs::write(disk.join("sda1/dev"), "8:1\n").unwrap();
+        std::fs::write(disk.join("sda1/partition"), "1\n").unwrap();
+        symlink(&disk, sys.join("dev/block/8:0")).unwrap();
+        symlink(disk.join("sda1"), sys.join("dev/block/8:1")).unwrap();
+
+        assert_eq!(
+            parent_device_in(&sys, libc::makedev(8, 1)).as_deref(),
+            Some("8:0")
+        );
+        // Whole disks and unknown devices have no parent
+        assert_eq!(parent_device_in(&sys, libc::makedev(8, 0)), None);
+        assert_eq!(parent_device_in(&sys, libc::makedev(8, 16)), None);
+
+        std::fs::remove_dir_all(&sys).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+//! qcow2 image backend (read-only)
+//!
+//! Reads version 2 and 3 qcow2 images through the two-level L1/L2 cluster
+//! tables. Unallocated and zero clusters read as zeros. Backing files,
+//! encryption, compressed clusters, external data files and extended L2
+//! entries are not supported; writes fail with [`ImageError::ReadOnly`].
+
+use aligned::{A4, Aligned};
+use core::cell::RefCell;
+use embedded_io_async::{Read, Seek, Write};
+use fatrs_block_device::BlockDevice;
+
+use crate::image::{
+    ImageError, SECTOR_SIZE, be_u32, be_u64, check_bounds, read_exact_at, read_sectors,
+};
+
+pub(crate) const MAGIC: [u8; 4] = *b"QFI\xfb";
+
+const OFFSET_MASK: u64 = 0x00FF_FFFF_FFFF_FE00;
+const L2_COMPRESSED: u64 = 1 << 62;
+const L2_ZERO: u64 = 1;
+
+const INCOMPAT_DIRTY: u64 = 1 << 0;
+const INCOMPAT_CORRUPT: u64 = 1 << 1;
+const INCOMPAT_COMPRESSION_TYPE: u64 = 1 << 3;
+
+/// Read-only block device over a qcow2 image
+///
+/// # Example
+///
+/// ```ignore
+/// use fatrs_block_platform::Qcow2Device;
+/// use embedded_io_adapters::tokio_1::FromTokio;
+///
+/// let file = tokio::fs::File::open("disk.qcow2").await?;
+/// let device = Qcow2Device::open(FromTokio::new(file)).await?;
+/// ```
+pub struct Qcow2Device<T> {
+    io: RefCell<T>,
+    version: u32,
+    cluster_bits: u32,
+    l1_offset: u64,
+    l1_size: u32,
+    size: u64,
+}
+
+impl<T: Read + Write + Seek> Qcow2Device<T> {
+    /// Open a qcow2 image and validate its header
+    pub async fn open(mut io: T) -> Result<Self, ImageError<T::Error>> {
+        let mut header = [0u8; 104];
+        read_exact_at(&mut io, 0, &mut header[..72]).await?;
+        if header[..4] != MAGIC {
+            return Err(ImageError::Corrupt("missing qcow2 magic"));
+        }
+
+        let version = be_u32(&header, 4);
+        match version {
+            2 => {}
+            3 => {
+                read_exact_at(&mut io, 72, &mut header[72..]).await?;
+                let incompatible = be_u64(&header, 72);
+                if incompatible & INCOMPAT_CORRUPT != 0 {
+                    return Err(ImageError::Corrupt("qcow2 image marked corrupt"));
+                }
+                // A dirty image only has stale refcounts, which reads never use
+                if incompatible & !(INCOMPAT_DIRTY | INCOMPAT_COMPRESSION_TYPE) != 0 {
+                    return Err(ImageError::Unsupported("qcow2 incompatible feature"));
+                }
+            }
+            _ => return Err(ImageError::Unsupported("qcow2 version")),
+        }
+        if be_u64(&header, 8) != 0 {
+            return Err(ImageError::Unsupported("qcow2 backing file"));
+        }
+        if be_u32(&header, 32) != 0 {
+            return Err(ImageError::Unsupported("encrypted qcow2"));
+        }
+
+        let cluster_bits = be_u32(&header, 20);
+        if !(9..=21).contains(&cluster_bits) {
+            return Err(ImageError::Corrupt("invalid qcow2 cluster size"));
+        }
+        let size = be_u64(&header, 24);
+        let l1_size = be_u32(&header, 36);
+        let l2_entries = 1u64 << (cluster_bits - 3);
+        if u64::from(l1_size) * l2_entries < size.div_ceil(1 << cluster_bits) {
+            return Err(ImageError::Corrupt("qcow2 L1 table smaller than disk"));
+        }
+
+        Ok(Self {
+            io: RefCell::new(io),
+            version,
+            cluster_bits,
+            l1_offset: be_u64(&header, 40),
+            l1_size,
+            size,
+        })
+    }
+
+    /// Get the qcow2 format version (2 or 3)
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Get the virtual disk size in bytes
+    pub fn virtual_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the cluster size in bytes
+    pub fn cluster_size(&self) -> u32 {
+        1 << self.cluster_bits
+    }
+
+    /// Consume the device and return the inner stream
+    pub fn into_inner(self) -> T {
+        self.io.into_inner()
+    }
+
+    /// Host offset of a guest cluster, or `None` if it reads as zeros
+    async fn cluster_offset(
+        &self,
+        io: &mut T,
+        cluster: u64,
+    ) -> Result<Option<u64>, ImageError<T::Error>> {
+        let l2_bits = self.cluster_bits - 3;
+        let l1_index = cluster >> l2_bits;
+        if l1_index >= u64::from(self.l1_size) {
+            return Err(ImageError::OutOfBounds);
+        }
+
+        let mut entry = [0u8; 8];
+        read_exact_at(io, self.l1_offset + l1_index * 8, &mut entry).await?;
+        let l2_offset = u64::from_be_bytes(entry) & OFFSET_MASK;
+        if l2_offset == 0 {
+            return Ok(None);
+        }
+
+        let l2_index = cluster & ((1 << l2_bits) - 1);
+        read_exact_at(io, l2_offset + l2_index * 8, &mut entry).await?;
+        let l2_entry = u64::from_be_bytes(entry);
+        if l2_entry & L2_COMPRESSED != 0 {
+            return Err(ImageError::Unsupported("compressed qcow2 cluster"));
+        }
+        let offset = l2_entry & OFFSET_MASK;
+        if offset == 0 || (self.version >= 3 && l2_entry & L2_ZERO != 0) {
+            return Ok(None);
+        }
+        Ok(Some(offset))
+    }
+}
+
+impl<T: Read + Write + Seek> BlockDevice<SECTOR_SIZE> for Qcow2Device<T> {
+    type Error = ImageError<T::Error>;
+    type Align = A4;
+
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let mut offset = check_bounds(self.size, block_address, data.len())?;
+        let mut io = self.io.borrow_mut();
+        let cluster_size = 1u64 << self.cluster_bits;
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            let within = offset % cluster_size;
+            let count = remaining
+                .len()
+                .min(((cluster_size - within) / SECTOR_SIZE as u64) as usize);
+            let (chunk, rest) = remaining.split_at_mut(count);
+
+            match self
+                .cluster_offset(&mut io, offset >> self.cluster_bits)
+                .await?
+            {
+                Some(host) => read_sectors(&mut *io, host + within, chunk).await?,
+                None => chunk.iter_mut().for_each(|s| s.fill(0)),
+            }
+            offset += (count * SECTOR_SIZE) as u64;
+            remaining = rest;
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        _block_address: u32,
+        _data: &[Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        Err(ImageError::ReadOnly)
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.size)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::{MemStream, block_on};
+    use std::vec;
+    use std::vec::Vec;
+
+    const COPIED: u64 = 1 << 63;
+    /// 512-byte clusters, so one L2 table maps 64 clusters
+    const CLUSTER_BITS: u32 = 9;
+    const DISK_SIZE: u64 = 2 * 64 * 512;
+
+    fn put(image: &mut [u8], at: usize, bytes: &[u8]) {
+        image[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// L1 at 512 with only its first L2 table (at 1024) present. Cluster 2
+    /// is allocated at 1536, cluster 3 is a v3 zero cluster over the data at
+    /// 2048 and cluster 4 is compressed.
+    fn image(version: u32) -> Vec<u8> {
+        let mut image = vec![0u8; 4096];
+        put(&mut image, 0, &MAGIC);
+        put(&mut image, 4, &version.to_be_bytes());
+        put(&mut image, 20, &CLUSTER_BITS.to_be_bytes());
+        put(&mut image, 24, &DISK_SIZE.to_be_bytes());
+        put(&mut image, 36, &2u32.to_be_bytes());
+        put(&mut image, 40, &512u64.to_be_bytes());
+        if version == 3 {
+            put(&mut image, 72, &INCOMPAT_DIRTY.to_be_bytes());
+            put(&mut image, 100, &104u32.to_be_bytes());
+        }
+
+        put(&mut image, 512, &(1024 | COPIED).to_be_bytes());
+        put(&mut image, 1024 + 2 * 8, &(1536 | COPIED).to_be_bytes());
+        put(&mut image, 1024 + 3 * 8, &(2048 | L2_ZERO).to_be_bytes());
+        put(
+            &mut image,
+            1024 + 4 * 8,
+            &(2560 | L2_COMPRESSED).to_be_bytes(),
+        );
+        image[1536..2048].fill(0x77);
+        image[2048..2560].fill(0x88);
+        image
+    }
+
+    fn sectors<const N: usize>(fill: u8) -> [Aligned<A4, [u8; SECTOR_SIZE]>; N] {
+        [Aligned([fill; SECTOR_SIZE]); N]
+    }
+
+    #[test]
+    fn test_cluster_mapping() {
+        block_on(async {
+            let device = Qcow2Device::open(MemStream::new(image(3))).await.unwrap();
+            assert_eq!(device.version(), 3);
+            assert_eq!(device.cluster_size(), 512);
+            assert_eq!(device.size().await.unwrap(), DISK_SIZE);
+
+            // Unallocated, allocated, then a zero cluster hiding stale data
+            let mut buf = sectors::<3>(1);
+            device.read(1, &mut buf).await.unwrap();
+            assert!(buf[0].iter().all(|&b| b == 0));
+            assert!(buf[1].iter().all(|&b| b == 0x77));
+            assert!(buf[2].iter().all(|&b| b == 0));
+
+            // Clusters behind an empty L1 entry read as zeros
+            device.read(64, &mut buf).await.unwrap();
+            assert!(buf.iter().all(|s| s.iter().all(|&b| b == 0)));
+        });
+    }
+
+    #[test]
+    fn test_zero_flag_is_ignored_before_version_3() {
+        block_on(async {
+            let device = Qcow2Device::open(MemStream::new(image(2))).await.unwrap();
+            let mut buf = sectors::<1>(1);
+            device.read(3, &mut buf).await.unwrap();
+            assert!(buf[0].iter().all(|&b| b == 0x88));
+        });
+    }
+
+    #[test]
+    fn test_compressed_clusters_and_writes_are_rejected() {
+        block_on(async {
+            let mut device = Qcow2Device::open(MemStream::new(image(3))).await.unwrap();
+            let mut buf = sectors::<2>(1);
+            assert_eq!(
+                device.read(4, &mut buf[..1]).await,
+                Err(ImageError::Unsupported("compressed qcow2 cluster"))
+            );
+            // A read spanning into the compressed cluster fails as a whole
+            assert_eq!(
+                device.read(3, &mut buf).await,
+                Err(ImageError::Unsupported("compressed qcow2 cluster"))
+            );
+            assert_eq!(device.write(0, &buf[..1]).await, Err(ImageError::ReadOnly));
+            assert_eq!(
+                device.read(255, &mut buf).await,
+                Err(ImageError::OutOfBounds)
+            );
+        });
+    }
+
+    #[test]
+    fn test_unsupported_headers_are_rejected() {
+        block_on(async {
+            let mut corrupt = image(3);
+            put(&mut corrupt, 72, &INCOMPAT_CORRUPT.to_be_bytes());
+            assert!(matches!(
+                Qcow2Device::open(MemStream::new(corrupt)).await,
+                Err(ImageError::Corrupt("qcow2 image marked corrupt"))
+            ));
+
+            let mut backing = image(3);
+            put(&mut backing, 8, &4096u64.to_be_bytes());
+            assert!(matches!(
+                Qcow2Device::open(MemStream::new(backing)).await,
+                Err(ImageError::Unsupported("qcow2 backing file"))
+            ));
+        });
+    }
+}
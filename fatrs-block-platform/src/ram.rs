@@ -0,0 +1,638 @@
+//! In-memory RAM block device
+//!
+//! Provides a `BlockDevice<SIZE>` implementation backed by a byte buffer, for
+//! RAM-backed scratch volumes (e.g. a FAT in external PSRAM), tests and
+//! benchmarks. Works in `no_std` without an allocator.
+
+use aligned::{A4, Aligned};
+use core::cell::Cell;
+use core::future::Future;
+use fatrs_block_device::BlockDevice;
+
+/// Error type for RAM block device operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-logging", derive(defmt::Format))]
+pub enum Error {
+    /// Access past the end of the device
+    OutOfBounds,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::OutOfBounds => write!(f, "Access past the end of the RAM device"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Error::OutOfBounds => embedded_io::ErrorKind::InvalidInput,
+        }
+    }
+}
+
+/// Owned, inline storage for `BLOCKS` blocks of `SIZE` bytes
+///
+/// Large buffers should be placed in a `static` rather than built on the stack.
+pub struct RamBuffer<const SIZE: usize, const BLOCKS: usize>(pub [[u8; SIZE]; BLOCKS]);
+
+impl<const SIZE: usize, const BLOCKS: usize> RamBuffer<SIZE, BLOCKS> {
+    /// Create a zero-filled buffer
+    pub const fn new() -> Self {
+        Self([[0; SIZE]; BLOCKS])
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize> Default for RamBuffer<SIZE, BLOCKS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize> AsRef<[u8]> for RamBuffer<SIZE, BLOCKS> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_flattened()
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize> AsMut<[u8]> for RamBuffer<SIZE, BLOCKS> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.0.as_flattened_mut()
+    }
+}
+
+/// Delay source used to simulate access latency
+///
+/// Implement it on top of the timer of your runtime, e.g.
+/// `embassy_time::Timer::after_nanos(ns).await` or
+/// `tokio::time::sleep(Duration::from_nanos(ns)).await`.
+pub trait Delay {
+    /// Wait for `ns` nanoseconds
+    fn delay_ns(&self, ns: u64) -> impl Future<Output = ()>;
+}
+
+/// No delay: every operation completes immediately
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoDelay;
+
+impl Delay for NoDelay {
+    async fn delay_ns(&self, _ns: u64) {}
+}
+
+/// Simulated access times, in nanoseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Latency {
+    /// Time to read one block
+    pub read_ns: u64,
+    /// Time to program one block
+    pub write_ns: u64,
+    /// Time to erase one erase unit (see [`RamBlockDevice::with_erase_size`])
+    pub erase_ns: u64,
+    /// Time for a sync
+    pub sync_ns: u64,
+}
+
+/// Operation counters of a [`RamBlockDevice`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RamStats {
+    /// Blocks read
+    pub blocks_read: u64,
+    /// Blocks written
+    pub blocks_written: u64,
+    /// Erase units erased
+    pub erases: u64,
+    /// Sync calls
+    pub syncs: u64,
+}
+
+/// Block device backed by RAM
+///
+/// Holds `BLOCKS` blocks of `SIZE` bytes in any `AsRef<[u8]> + AsMut<[u8]>`
+/// buffer: a `&'static mut [u8]` (the default, e.g. a PSRAM region), an owned
+/// [`RamBuffer`], or a `Vec<u8>` with `std`.
+///
+/// Besides `BlockDevice`, the device implements the `embedded-io` and
+/// `embedded-io-async` `Read`, `Write` and `Seek` traits over its bytes.
+///
+/// For benchmarking, [`with_latency`](Self::with_latency) adds simulated
+/// access times and [`with_erase_size`](Self::with_erase_size) models media
+/// that must erase a whole unit before rewriting it: every write erases each
+/// unit it touches, as an SD card without a flash translation layer would.
+///
+/// # Example
+///
+/// ```ignore
+/// use fatrs_block_platform::RamBlockDevice;
+///
+/// #[link_section = ".psram"]
+/// static mut DISK: [u8; 512 * 4096] = [0; 512 * 4096];
+///
+/// let device = RamBlockDevice::<512, 4096>::new(unsafe { &mut *core::ptr::addr_of_mut!(DISK) });
+/// ```
+pub struct RamBlockDevice<
+    const SIZE: usize,
+    const BLOCKS: usize,
+    S = &'static mut [u8],
+    L = NoDelay,
+> {
+    storage: S,
+    delay: L,
+    latency: Latency,
+    erase_size: u32,
+    position: u64,
+    blocks_read: Cell<u64>,
+    blocks_written: u64,
+    erases: u64,
+    syncs: u64,
+}
+
+impl<const SIZE: usize, const BLOCKS: usize> RamBlockDevice<SIZE, BLOCKS, RamBuffer<SIZE, BLOCKS>> {
+    /// Create a zero-filled device with owned, inline storage
+    pub fn zeroed() -> Self {
+        Self::new(RamBuffer::new())
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S> RamBlockDevice<SIZE, BLOCKS, S>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Create a device over `storage`, keeping its current content
+    ///
+    /// # Panics
+    /// Panics if `storage` is shorter than `SIZE * BLOCKS` bytes.
+    pub fn new(storage: S) -> Self {
+        assert!(
+            storage.as_ref().len() >= SIZE * BLOCKS,
+            "RAM device storage is smaller than SIZE * BLOCKS"
+        );
+        Self {
+            storage,
+            delay: NoDelay,
+            latency: Latency::default(),
+            erase_size: 0,
+            position: 0,
+            blocks_read: Cell::new(0),
+            blocks_written: 0,
+            erases: 0,
+            syncs: 0,
+        }
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    /// Simulate access times, waiting on `delay`
+    pub fn with_latency<L2: Delay>(
+        self,
+        delay: L2,
+        latency: Latency,
+    ) -> RamBlockDevice<SIZE, BLOCKS, S, L2> {
+        RamBlockDevice {
+            storage: self.storage,
+            delay,
+            latency,
+            erase_size: self.erase_size,
+            position: self.position,
+            blocks_read: self.blocks_read,
+            blocks_written: self.blocks_written,
+            erases: self.erases,
+            syncs: self.syncs,
+        }
+    }
+
+    /// Model an erase unit of `blocks` blocks (0 disables erase simulation)
+    pub fn with_erase_size(mut self, blocks: u32) -> Self {
+        self.erase_size = blocks;
+        self
+    }
+
+    /// Get the device content
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.storage.as_ref()[..SIZE * BLOCKS]
+    }
+
+    /// Get the device content for modification
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.storage.as_mut()[..SIZE * BLOCKS]
+    }
+
+    /// Consume the device and return its storage
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// Get the operation counters
+    pub fn stats(&self) -> RamStats {
+        RamStats {
+            blocks_read: self.blocks_read.get(),
+            blocks_written: self.blocks_written,
+            erases: self.erases,
+            syncs: self.syncs,
+        }
+    }
+
+    /// Reset the operation counters to zero
+    pub fn reset_stats(&mut self) {
+        self.blocks_read.set(0);
+        self.blocks_written = 0;
+        self.erases = 0;
+        self.syncs = 0;
+    }
+
+    /// Byte range of `count` blocks starting at `block_address`
+    fn range(block_address: u32, count: usize) -> Result<core::ops::Range<usize>, Error> {
+        let start = block_address as usize;
+        if start.checked_add(count).is_none_or(|end| end > BLOCKS) {
+            return Err(Error::OutOfBounds);
+        }
+        Ok(start * SIZE..(start + count) * SIZE)
+    }
+
+    /// Number of erase units touched by `count` blocks starting at `block_address`
+    fn erase_units(&self, block_address: u32, count: usize) -> u64 {
+        if self.erase_size == 0 || count == 0 {
+            return 0;
+        }
+        let first = block_address / self.erase_size;
+        let last = (block_address + count as u32 - 1) / self.erase_size;
+        u64::from(last - first + 1)
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> BlockDevice<SIZE>
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    type Error = Error;
+    type Align = A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let range = Self::range(block_address, data.len())?;
+        for (block, chunk) in data
+            .iter_mut()
+            .zip(self.storage.as_ref()[range].chunks_exact(SIZE))
+        {
+            block.copy_from_slice(chunk);
+        }
+        self.blocks_read
+            .set(self.blocks_read.get() + data.len() as u64);
+
+        if self.latency.read_ns > 0 {
+            self.delay
+                .delay_ns(self.latency.read_ns * data.len() as u64)
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let range = Self::range(block_address, data.len())?;
+        for (chunk, block) in self.storage.as_mut()[range]
+            .chunks_exact_mut(SIZE)
+            .zip(data)
+        {
+            chunk.copy_from_slice(&block[..]);
+        }
+        let erases = self.erase_units(block_address, data.len());
+        self.blocks_written += data.len() as u64;
+        self.erases += erases;
+
+        let ns = self.latency.write_ns * data.len() as u64 + self.latency.erase_ns * erases;
+        if ns > 0 {
+            self.delay.delay_ns(ns).await;
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok((SIZE * BLOCKS) as u64)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.syncs += 1;
+        if self.latency.sync_ns > 0 {
+            self.delay.delay_ns(self.latency.sync_ns).await;
+        }
+        Ok(())
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    /// Copy bytes at the stream position into `buf`, without latency
+    fn read_bytes(&mut self, buf: &mut [u8]) -> usize {
+        let bytes = &self.storage.as_ref()[..SIZE * BLOCKS];
+        let start = (self.position as usize).min(bytes.len());
+        let len = buf.len().min(bytes.len() - start);
+        buf[..len].copy_from_slice(&bytes[start..start + len]);
+        self.position += len as u64;
+        len
+    }
+
+    /// Copy `buf` to the stream position, without latency or erase accounting
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let bytes = &mut self.storage.as_mut()[..SIZE * BLOCKS];
+        let start = (self.position as usize).min(bytes.len());
+        let len = buf.len().min(bytes.len() - start);
+        if len == 0 && !buf.is_empty() {
+            return Err(Error::OutOfBounds);
+        }
+        bytes[start..start + len].copy_from_slice(&buf[..len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+
+    fn seek_to(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Error> {
+        let size = (SIZE * BLOCKS) as i64;
+        let new_pos = match pos {
+            embedded_io::SeekFrom::Start(n) => n as i64,
+            embedded_io::SeekFrom::End(n) => size + n,
+            embedded_io::SeekFrom::Current(n) => self.position as i64 + n,
+        };
+        if new_pos < 0 {
+            return Err(Error::OutOfBounds);
+        }
+        self.position = new_pos as u64;
+        Ok(self.position)
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> embedded_io::ErrorType
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+{
+    type Error = Error;
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> embedded_io::Read
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.read_bytes(buf))
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> embedded_io::Write
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_bytes(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> embedded_io::Seek
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
+        self.seek_to(pos)
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> embedded_io_async::Read
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.read_bytes(buf))
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> embedded_io_async::Write
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_bytes(buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<const SIZE: usize, const BLOCKS: usize, S, L> embedded_io_async::Seek
+    for RamBlockDevice<SIZE, BLOCKS, S, L>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+    L: Delay,
+{
+    async fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Self::Error> {
+        self.seek_to(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::block_on;
+
+    type Device = RamBlockDevice<512, 64, RamBuffer<512, 64>>;
+
+    /// Adds up the requested delays instead of waiting
+    struct RecordedDelay<'a>(&'a Cell<u64>);
+
+    impl Delay for RecordedDelay<'_> {
+        async fn delay_ns(&self, ns: u64) {
+            self.0.set(self.0.get() + ns);
+        }
+    }
+
+    fn blocks<const N: usize>(fill: u8) -> [Aligned<A4, [u8; 512]>; N] {
+        [Aligned([fill; 512]); N]
+    }
+
+    #[test]
+    fn test_read_write_round_trip() {
+        block_on(async {
+            let mut device = Device::zeroed();
+            device
+                .write(7, &[Aligned([7; 512]), Aligned([8; 512])])
+                .await
+                .unwrap();
+
+            let mut buf = blocks::<2>(0);
+            device.read(7, &mut buf).await.unwrap();
+            assert_eq!((buf[0][0], buf[1][511]), (7, 8));
+            assert_eq!(device.as_bytes()[7 * 512], 7);
+            assert_eq!(device.size().await.unwrap(), 64 * 512);
+        });
+    }
+
+    #[test]
+    fn test_access_past_end_is_rejected() {
+        block_on(async {
+            let mut device = Device::zeroed();
+            let mut buf = blocks::<2>(0);
+            assert_eq!(device.read(63, &mut buf).await, Err(Error::OutOfBounds));
+            assert_eq!(
+                device.read(64, &mut buf[..1]).await,
+                Err(Error::OutOfBounds)
+            );
+            assert_eq!(
+                device.read(u32::MAX, &mut buf).await,
+                Err(Error::OutOfBounds)
+            );
+            assert_eq!(
+                device.write(63, &blocks::<2>(1)).await,
+                Err(Error::OutOfBounds)
+            );
+
+            // Nothing was written and nothing was counted
+            assert!(device.as_bytes().iter().all(|&b| b == 0));
+            assert_eq!(device.stats(), RamStats::default());
+
+            device.read(63, &mut buf[..1]).await.unwrap();
+            device.write(63, &blocks::<1>(1)).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_erase_units_touched_by_writes() {
+        block_on(async {
+            let mut device = Device::zeroed().with_erase_size(8);
+            // Blocks 6..10 straddle the units 0..8 and 8..16
+            device.write(6, &blocks::<4>(1)).await.unwrap();
+            assert_eq!(device.stats().erases, 2);
+            device.write(8, &blocks::<8>(1)).await.unwrap();
+            assert_eq!(device.stats().erases, 3);
+
+            // Erase simulation is off by default
+            let mut device = Device::zeroed();
+            device.write(6, &blocks::<4>(1)).await.unwrap();
+            assert_eq!(device.stats().erases, 0);
+        });
+    }
+
+    #[test]
+    fn test_latency_per_block_and_erase() {
+        block_on(async {
+            let waited = Cell::new(0);
+            let latency = Latency {
+                read_ns: 10,
+                write_ns: 100,
+                erase_ns: 1000,
+                sync_ns: 5,
+            };
+            let mut device = Device::zeroed()
+                .with_erase_size(8)
+                .with_latency(RecordedDelay(&waited), latency);
+
+            device.write(7, &blocks::<2>(1)).await.unwrap();
+            assert_eq!(waited.get(), 2 * 100 + 2 * 1000);
+            device.read(0, &mut blocks::<3>(0)).await.unwrap();
+            assert_eq!(waited.get(), 2200 + 3 * 10);
+            device.sync().await.unwrap();
+            assert_eq!(waited.get(), 2230 + 5);
+
+            assert_eq!(
+                device.stats(),
+                RamStats {
+                    blocks_read: 3,
+                    blocks_written: 2,
+                    erases: 2,
+                    syncs: 1,
+                }
+            );
+            device.reset_stats();
+            assert_eq!(device.stats(), RamStats::default());
+        });
+    }
+
+    #[test]
+    fn test_byte_stream() {
+        use embedded_io_async::{Read, Seek, SeekFrom, Write};
+
+        block_on(async {
+            let mut device = Device::zeroed();
+            device.as_bytes_mut()[510..514].copy_from_slice(&[1, 2, 3, 4]);
+
+            device.seek(SeekFrom::Start(510)).await.unwrap();
+            let mut buf = [0; 4];
+            assert_eq!(Read::read(&mut device, &mut buf).await.unwrap(), 4);
+            assert_eq!(buf, [1, 2, 3, 4]);
+
+            // Writes stop at the end of the device
+            device.seek(SeekFrom::End(-2)).await.unwrap();
+            assert_eq!(Write::write(&mut device, &[5, 6, 7]).await.unwrap(), 2);
+            assert_eq!(
+                Write::write(&mut device, &[8]).await,
+                Err(Error::OutOfBounds)
+            );
+            assert_eq!(Read::read(&mut device, &mut buf).await.unwrap(), 0);
+            assert_eq!(&device.as_bytes()[64 * 512 - 2..], &[5, 6]);
+            assert_eq!(
+                device.seek(SeekFrom::Current(-3)).await.unwrap(),
+                64 * 512 - 3
+            );
+            assert_eq!(device.seek(SeekFrom::Start(0)).await.unwrap(), 0);
+            assert_eq!(
+                device.seek(SeekFrom::Current(-1)).await,
+                Err(Error::OutOfBounds)
+            );
+        });
+    }
+
+    #[test]
+    fn test_borrowed_and_vec_storage() {
+        block_on(async {
+            let mut storage = [0u8; 4 * 512];
+            storage[512] = 9;
+            let mut device = RamBlockDevice::<512, 4, _>::new(&mut storage[..]);
+            let mut buf = blocks::<1>(0);
+            device.read(1, &mut buf).await.unwrap();
+            assert_eq!(buf[0][0], 9);
+            device.write(3, &blocks::<1>(3)).await.unwrap();
+            assert_eq!(storage[3 * 512], 3);
+
+            // Storage may be larger than the device
+            let device = RamBlockDevice::<512, 2, _>::new(std::vec![1u8; 4096]);
+            assert_eq!(device.size().await.unwrap(), 1024);
+            assert_eq!(device.as_bytes().len(), 1024);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "smaller than SIZE * BLOCKS")]
+    fn test_short_storage_panics() {
+        let _ = RamBlockDevice::<512, 4, _>::new(std::vec![0u8; 2047]);
+    }
+}
@@ -44,6 +44,8 @@ pub const WRITE_MULTIPLE_TOKEN: u8 = 0xFC;
 pub const DATA_RES_MASK: u8 = 0x1F;
 /// Write data accepted token
 pub const DATA_RES_ACCEPTED: u8 = 0x05;
+/// Size of the blocks addressed and transferred by SDHC/SDXC cards
+const CARD_BLOCK_SIZE: usize = 512;
 
 #[derive(Clone, Copy, Debug, Default)]
 /// SD Card
@@ -277,14 +279,25 @@ where
         block_address: u32,
         data: &mut [Aligned<ALIGN, [u8; SIZE]>],
     ) -> Result<(), Error> {
+        const {
+            assert!(
+                SIZE % CARD_BLOCK_SIZE == 0,
+                "block size must be a multiple of 512"
+            )
+        };
+        // Larger blocks (e.g. 4096) span several 512-byte card blocks
+        let card_address = block_address * (SIZE / CARD_BLOCK_SIZE) as u32;
+        let card_blocks = data.len() * (SIZE / CARD_BLOCK_SIZE);
         let r = async {
-            if data.len() == 1 {
-                self.cmd(read_single_block(block_address)).await?;
+            if card_blocks == 1 {
+                self.cmd(read_single_block(card_address)).await?;
                 self.read_data(&mut data[0][..]).await?;
             } else {
-                self.cmd(read_multiple_blocks(block_address)).await?;
+                self.cmd(read_multiple_blocks(card_address)).await?;
                 for block in data {
-                    self.read_data(&mut block[..]).await?;
+                    for card_block in block.chunks_mut(CARD_BLOCK_SIZE) {
+                        self.read_data(card_block).await?;
+                    }
                 }
                 self.cmd(stop_transmission()).await?;
             }
@@ -302,9 +315,17 @@ where
         block_address: u32,
         data: &[Aligned<ALIGN, [u8; SIZE]>],
     ) -> Result<(), Error> {
+        const {
+            assert!(
+                SIZE % CARD_BLOCK_SIZE == 0,
+                "block size must be a multiple of 512"
+            )
+        };
+        let card_address = block_address * (SIZE / CARD_BLOCK_SIZE) as u32;
+        let card_blocks = data.len() * (SIZE / CARD_BLOCK_SIZE);
         let r = async {
-            if data.len() == 1 {
-                self.cmd(write_single_block(block_address)).await?;
+            if card_blocks == 1 {
+                self.cmd(write_single_block(card_address)).await?;
                 self.write_data(DATA_START_BLOCK, &data[0][..]).await?;
                 self.wait_idle().await?;
                 // check status, in SD SPI mode, the status is two bytes
@@ -319,13 +340,13 @@ where
                 // This will pre-erase blocks to improve write performance.
                 // We ignore the return value, because whether its accepted
                 // or not doesn't matter we will still proceed with the write
-                self.acmd(cmd::<R1>(0x17, data.len() as u32)).await?;
+                self.acmd(cmd::<R1>(0x17, card_blocks as u32)).await?;
                 self.wait_idle().await?;
 
-                self.cmd(write_multiple_blocks(block_address)).await?;
-                for block in data {
+                self.cmd(write_multiple_blocks(card_address)).await?;
+                for card_block in data.iter().flat_map(|block| block.chunks(CARD_BLOCK_SIZE)) {
                     self.wait_idle().await?;
-                    self.write_data(WRITE_MULTIPLE_TOKEN, &block[..]).await?;
+                    self.write_data(WRITE_MULTIPLE_TOKEN, card_block).await?;
                 }
                 // stop the write
                 self.wait_idle().await?;
@@ -520,3 +541,239 @@ fn crc7(data: &[u8]) -> u8 {
 fn crc16(data: &[u8]) -> u16 {
     SD_CRC16.checksum(data)
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::block_on;
+    use aligned::A4;
+    use embedded_hal_async::spi::{ErrorType, Operation, SpiDevice};
+    use fatrs_block_device::BlockDevice;
+    use std::collections::{BTreeMap, VecDeque};
+    use std::vec::Vec;
+
+    /// Data transfer in progress on the card side
+    enum Transfer {
+        Idle,
+        /// Streaming blocks from this card address until CMD12
+        Reading(u32),
+        /// Receiving data blocks for this card address
+        Writing {
+            address: u32,
+            multiple: bool,
+        },
+    }
+
+    /// SD card in SPI mode, simulated at the byte level
+    ///
+    /// Only answers the commands used for data transfers. Bytes clocked in
+    /// by the host come from `out`, or are 0xFF (idle) when it is empty.
+    struct MockCard {
+        blocks: BTreeMap<u32, [u8; CARD_BLOCK_SIZE]>,
+        commands: Vec<(u8, u32)>,
+        out: VecDeque<u8>,
+        transfer: Transfer,
+        /// Token, data and CRC of the block being written
+        incoming: Vec<u8>,
+    }
+
+    impl MockCard {
+        fn new() -> Self {
+            Self {
+                blocks: BTreeMap::new(),
+                commands: Vec::new(),
+                out: VecDeque::new(),
+                transfer: Transfer::Idle,
+                incoming: Vec::new(),
+            }
+        }
+
+        fn send_block(&mut self, address: u32) {
+            let data = self
+                .blocks
+                .get(&address)
+                .copied()
+                .unwrap_or([0; CARD_BLOCK_SIZE]);
+            self.out.push_back(DATA_START_BLOCK);
+            self.out.extend(data);
+            self.out.extend(crc16(&data).to_be_bytes());
+        }
+
+        fn command(&mut self, frame: &[u8]) {
+            assert_eq!(frame.len(), 6, "command frames are 6 bytes");
+            assert_eq!(frame[5], crc7(&frame[..5]));
+            let (index, arg) = (frame[0] & 0x3F, be_u32(&frame[1..5]));
+            self.commands.push((index, arg));
+            match index {
+                17 => {
+                    self.out.push_back(R1_READY_STATE);
+                    self.send_block(arg);
+                }
+                18 => {
+                    self.out.push_back(R1_READY_STATE);
+                    self.transfer = Transfer::Reading(arg);
+                }
+                12 => {
+                    // Drop the block being streamed; a stuff byte precedes R1
+                    self.out.clear();
+                    self.out.extend([0xFF, R1_READY_STATE]);
+                    self.transfer = Transfer::Idle;
+                }
+                24 | 25 => {
+                    self.out.push_back(R1_READY_STATE);
+                    self.transfer = Transfer::Writing {
+                        address: arg,
+                        multiple: index == 25,
+                    };
+                }
+                // SEND_STATUS answers with R2
+                13 => self.out.extend([R1_READY_STATE, 0]),
+                23 | 55 => self.out.push_back(R1_READY_STATE),
+                _ => self.out.push_back(R1_ILLEGAL_COMMAND),
+            }
+        }
+
+        fn receive(&mut self, bytes: &[u8]) {
+            let Transfer::Writing { address, multiple } = self.transfer else {
+                return self.command(bytes);
+            };
+            self.incoming.extend_from_slice(bytes);
+            if multiple && self.incoming == [STOP_TRAN_TOKEN] {
+                self.incoming.clear();
+                self.transfer = Transfer::Idle;
+                return;
+            }
+            if self.incoming.len() < 1 + CARD_BLOCK_SIZE + 2 {
+                return;
+            }
+
+            let token = if multiple {
+                WRITE_MULTIPLE_TOKEN
+            } else {
+                DATA_START_BLOCK
+            };
+            assert_eq!(self.incoming[0], token);
+            let mut data = [0u8; CARD_BLOCK_SIZE];
+            data.copy_from_slice(&self.incoming[1..=CARD_BLOCK_SIZE]);
+            let crc = u16::from_be_bytes([
+                self.incoming[CARD_BLOCK_SIZE + 1],
+                self.incoming[CARD_BLOCK_SIZE + 2],
+            ]);
+            assert_eq!(crc, crc16(&data));
+            self.blocks.insert(address, data);
+            self.incoming.clear();
+            self.out.push_back(0xE0 | DATA_RES_ACCEPTED);
+            self.transfer = if multiple {
+                Transfer::Writing {
+                    address: address + 1,
+                    multiple,
+                }
+            } else {
+                Transfer::Idle
+            };
+        }
+
+        fn next_byte(&mut self) -> u8 {
+            if let Transfer::Reading(address) = self.transfer {
+                if self.out.is_empty() {
+                    // Idle fill before the next block's start token
+                    self.out.push_back(0xFF);
+                    self.send_block(address);
+                    self.transfer = Transfer::Reading(address + 1);
+                }
+            }
+            self.out.pop_front().unwrap_or(0xFF)
+        }
+    }
+
+    fn be_u32(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    impl ErrorType for MockCard {
+        type Error = core::convert::Infallible;
+    }
+
+    impl SpiDevice for MockCard {
+        async fn transaction(
+            &mut self,
+            operations: &mut [Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => self.receive(bytes),
+                    Operation::TransferInPlace(buf) => {
+                        buf.iter_mut().for_each(|b| *b = self.next_byte());
+                    }
+                    _ => unimplemented!("not used by the driver"),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Never expires: the simulated card answers immediately
+    #[derive(Clone)]
+    struct NoTimeout;
+
+    impl embedded_hal_async::delay::DelayNs for NoTimeout {
+        async fn delay_ns(&mut self, _ns: u32) {
+            core::future::pending().await
+        }
+    }
+
+    type Device = SdSpi<MockCard, NoTimeout, A4>;
+
+    #[test]
+    fn test_4096_byte_blocks_span_eight_card_blocks() {
+        block_on(async {
+            let mut sd = Device::new(MockCard::new(), NoTimeout);
+            let mut block = Aligned::<A4, _>([0u8; 4096]);
+            for (i, b) in block.iter_mut().enumerate() {
+                *b = (i / CARD_BLOCK_SIZE) as u8 + 1;
+            }
+            BlockDevice::<4096>::write(&mut sd, 1, &[block])
+                .await
+                .unwrap();
+
+            // ACMD23 pre-erases the eight card blocks CMD25 then writes
+            let card = sd.spi();
+            assert_eq!(card.commands, [(55, 0), (23, 8), (25, 8)]);
+            assert_eq!(card.blocks.len(), 8);
+            for i in 0..8 {
+                assert_eq!(card.blocks[&(8 + i)], [i as u8 + 1; CARD_BLOCK_SIZE]);
+            }
+            card.commands.clear();
+
+            let mut read = [Aligned([0u8; 4096]), Aligned([0xAA; 4096])];
+            BlockDevice::<4096>::read(&sd, 1, &mut read).await.unwrap();
+            assert_eq!(*read[0], *block);
+            assert!(read[1].iter().all(|&b| b == 0));
+            assert_eq!(sd.spi().commands, [(18, 8), (12, 0)]);
+        });
+    }
+
+    #[test]
+    fn test_512_byte_blocks_address_card_blocks() {
+        block_on(async {
+            let mut sd = Device::new(MockCard::new(), NoTimeout);
+            BlockDevice::<512>::write(&mut sd, 9, &[Aligned([0x5A; 512])])
+                .await
+                .unwrap();
+            assert_eq!(sd.spi().commands, [(24, 9), (13, 0)]);
+
+            // The same card block is the second 512 bytes of 4096-byte block 1
+            let mut read = [Aligned([0u8; 4096])];
+            BlockDevice::<4096>::read(&sd, 1, &mut read).await.unwrap();
+            assert!(read[0][..512].iter().all(|&b| b == 0));
+            assert!(read[0][512..1024].iter().all(|&b| b == 0x5A));
+
+            let mut sector = [Aligned([0u8; 512])];
+            BlockDevice::<512>::read(&sd, 9, &mut sector).await.unwrap();
+            assert_eq!(*sector[0], [0x5A; 512]);
+            assert_eq!(sd.spi().commands.last(), Some(&(17, 9)));
+        });
+    }
+}
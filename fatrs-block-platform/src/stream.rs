@@ -1,18 +1,17 @@
 //! Generic stream block device adapter
 //!
-//! Provides a `BlockDevice<512>` implementation wrapping any async I/O stream.
+//! Provides a `BlockDevice<SIZE>` implementation wrapping any async I/O stream.
 
 use aligned::{A4, Aligned};
 use core::cell::RefCell;
 use embedded_io_async::{ErrorType, Read, Seek, SeekFrom, Write};
 use fatrs_block_device::BlockDevice;
 
-const BLOCK_SIZE: usize = 512;
-
 /// Block device wrapper for async I/O streams
 ///
 /// Wraps any type implementing `embedded_io_async::{Read, Write, Seek}`
-/// and provides the `BlockDevice<512>` trait.
+/// and provides the `BlockDevice<SIZE>` trait. `SIZE` defaults to 512; use
+/// `StreamBlockDevice<T, 4096>` to expose a 4Kn image.
 ///
 /// Uses `RefCell` internally for interior mutability to allow `&self` on read operations.
 ///
@@ -29,9 +28,9 @@ const BLOCK_SIZE: usize = 512;
 /// let stream = FromTokio::new(file);
 /// let block_dev = StreamBlockDevice::new(stream);
 /// ```
-pub struct StreamBlockDevice<T>(RefCell<T>);
+pub struct StreamBlockDevice<T, const SIZE: usize = 512>(RefCell<T>);
 
-impl<T> StreamBlockDevice<T> {
+impl<T, const SIZE: usize> StreamBlockDevice<T, SIZE> {
     /// Create a new StreamBlockDevice wrapping the given stream.
     pub fn new(inner: T) -> Self {
         Self(RefCell::new(inner))
@@ -59,11 +58,11 @@ impl<T> StreamBlockDevice<T> {
     }
 }
 
-impl<T: ErrorType> ErrorType for StreamBlockDevice<T> {
+impl<T: ErrorType, const SIZE: usize> ErrorType for StreamBlockDevice<T, SIZE> {
     type Error = T::Error;
 }
 
-impl<T> BlockDevice<BLOCK_SIZE> for StreamBlockDevice<T>
+impl<T, const SIZE: usize> BlockDevice<SIZE> for StreamBlockDevice<T, SIZE>
 where
     T: Read + Write + Seek,
 {
@@ -73,15 +72,15 @@ where
     async fn read(
         &self,
         block_address: u32,
-        data: &mut [Aligned<Self::Align, [u8; BLOCK_SIZE]>],
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
     ) -> Result<(), Self::Error> {
         let mut inner = self.0.borrow_mut();
         inner
-            .seek(SeekFrom::Start((block_address as u64) * BLOCK_SIZE as u64))
+            .seek(SeekFrom::Start((block_address as u64) * SIZE as u64))
             .await?;
         for block in data {
             let mut offset = 0;
-            while offset < BLOCK_SIZE {
+            while offset < SIZE {
                 let n = inner.read(&mut block[offset..]).await?;
                 if n == 0 {
                     break; // EOF
@@ -95,15 +94,15 @@ where
     async fn write(
         &mut self,
         block_address: u32,
-        data: &[Aligned<Self::Align, [u8; BLOCK_SIZE]>],
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
     ) -> Result<(), Self::Error> {
         let mut inner = self.0.borrow_mut();
         inner
-            .seek(SeekFrom::Start((block_address as u64) * BLOCK_SIZE as u64))
+            .seek(SeekFrom::Start((block_address as u64) * SIZE as u64))
             .await?;
         for block in data {
             let mut offset = 0;
-            while offset < BLOCK_SIZE {
+            while offset < SIZE {
                 let n = inner.write(&block[offset..]).await?;
                 if n == 0 {
                     break; // Can't write more
@@ -129,3 +128,56 @@ where
         inner.flush().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::{MemStream, block_on};
+    use std::vec;
+
+    #[test]
+    fn test_512_byte_blocks() {
+        block_on(async {
+            let mut device = StreamBlockDevice::<_>::new(MemStream::new(vec![0; 4096]));
+            device
+                .write(3, &[Aligned([3; 512]), Aligned([4; 512])])
+                .await
+                .unwrap();
+            assert_eq!(device.size().await.unwrap(), 4096);
+
+            let mut buf = [Aligned([0; 512]); 2];
+            device.read(3, &mut buf).await.unwrap();
+            assert_eq!((buf[0][511], buf[1][0]), (3, 4));
+            assert_eq!(device.inner().data[3 * 512], 3);
+        });
+    }
+
+    #[test]
+    fn test_4096_byte_blocks() {
+        block_on(async {
+            let mut device = StreamBlockDevice::<_, 4096>::new(MemStream::new(vec![0; 16384]));
+            device
+                .write(1, &[Aligned([0xAA; 4096]), Aligned([0xBB; 4096])])
+                .await
+                .unwrap();
+            device.sync().await.unwrap();
+            assert_eq!(device.size().await.unwrap(), 16384);
+
+            // Short stream reads are looped until each block is full
+            let mut buf = [Aligned([0; 4096]); 3];
+            device.read(1, &mut buf).await.unwrap();
+            assert_eq!(*buf[0], [0xAA; 4096]);
+            assert_eq!(*buf[1], [0xBB; 4096]);
+            assert_eq!(*buf[2], [0; 4096]);
+
+            // 512-byte blocks 15 and 16 straddle the end of 4096-byte block 1
+            let stream = device.into_inner();
+            let device = StreamBlockDevice::<_>::new(stream);
+            let mut sectors = [Aligned([0; 512]); 2];
+            device.read(15, &mut sectors).await.unwrap();
+            assert_eq!((sectors[0][0], sectors[1][0]), (0xAA, 0xBB));
+        });
+    }
+}
@@ -0,0 +1,88 @@
+//! Helpers shared by the unit tests
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+/// Poll a future to completion on the current thread
+///
+/// The devices under test never wait on I/O, so a no-op waker is enough.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io-async")]
+pub(crate) use mem_stream::MemStream;
+
+#[cfg(feature = "embedded-io-async")]
+mod mem_stream {
+    extern crate std;
+
+    use embedded_io_async::{ErrorKind, ErrorType, Read, Seek, SeekFrom, Write};
+    use std::vec::Vec;
+
+    /// Growable in-memory stream with short reads
+    ///
+    /// Reads return at most 100 bytes so callers have to loop; writes past
+    /// the end extend the buffer with zeros the way a file would.
+    pub(crate) struct MemStream {
+        pub(crate) data: Vec<u8>,
+        pos: usize,
+    }
+
+    impl MemStream {
+        pub(crate) fn new(data: Vec<u8>) -> Self {
+            Self { data, pos: 0 }
+        }
+    }
+
+    impl ErrorType for MemStream {
+        type Error = ErrorKind;
+    }
+
+    impl Read for MemStream {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let n = buf
+                .len()
+                .min(self.data.len().saturating_sub(self.pos))
+                .min(100);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    impl Write for MemStream {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let end = self.pos + buf.len();
+            if self.data.len() < end {
+                self.data.resize(end, 0);
+            }
+            self.data[self.pos..end].copy_from_slice(buf);
+            self.pos = end;
+            Ok(buf.len())
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Seek for MemStream {
+        async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            let target = match pos {
+                SeekFrom::Start(n) => i128::from(n),
+                SeekFrom::End(n) => self.data.len() as i128 + i128::from(n),
+                SeekFrom::Current(n) => self.pos as i128 + i128::from(n),
+            };
+            self.pos = usize::try_from(target).map_err(|_| ErrorKind::InvalidInput)?;
+            Ok(self.pos as u64)
+        }
+    }
+}
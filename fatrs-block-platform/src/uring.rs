@@ -0,0 +1,889 @@
+//! io_uring Linux block device implementation
+//!
+//! Provides a `BlockDevice<512>` and a `BlockDevice<4096>` for Linux block
+//! devices that submits I/O through io_uring instead of blocking system calls.
+//! Every request must cover whole logical sectors: use the 4096-byte block
+//! size on 4Kn devices. Requests are split into
+//! chunks that each use one buffer from a pool of page-aligned buffers
+//! registered with the kernel; all chunks of a request are submitted with a
+//! single `io_uring_enter` call and run concurrently. A reaper thread waits
+//! on an eventfd for completions and wakes the waiting futures.
+//!
+//! Data is staged through the pool rather than read into the caller's slices
+//! directly, so dropping a future mid-request never leaves the kernel
+//! writing into freed memory.
+
+use aligned::{A4, Aligned};
+use fatrs_block_device::BlockDevice;
+use io_uring::{IoUring, opcode, squeue, types};
+use std::alloc::Layout;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// Alignment of pool buffers; satisfies O_DIRECT on 512e and 4Kn devices
+const BUFFER_ALIGN: usize = 4096;
+
+/// Configuration for [`UringBlockDevice`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UringConfig {
+    queue_depth: u32,
+    buffer_size: usize,
+}
+
+impl UringConfig {
+    /// Create the default configuration: 32 in-flight chunks of 128 KiB
+    pub const fn new() -> Self {
+        Self {
+            queue_depth: 32,
+            buffer_size: 128 * 1024,
+        }
+    }
+
+    /// Set the ring size, which is also the number of pool buffers
+    ///
+    /// # Panics
+    /// Panics if `depth` is zero or larger than 4096.
+    pub const fn with_queue_depth(mut self, depth: u32) -> Self {
+        assert!(depth > 0 && depth <= 4096, "queue depth must be 1..=4096");
+        self.queue_depth = depth;
+        self
+    }
+
+    /// Set the size of each pool buffer, i.e. the largest single I/O
+    ///
+    /// # Panics
+    /// Panics if `size` is not a non-zero multiple of 4096.
+    pub const fn with_buffer_size(mut self, size: usize) -> Self {
+        assert!(
+            size > 0 && size % BUFFER_ALIGN == 0,
+            "buffer size must be a multiple of 4096"
+        );
+        self.buffer_size = size;
+        self
+    }
+
+    /// Get the ring size
+    pub const fn queue_depth(&self) -> u32 {
+        self.queue_depth
+    }
+
+    /// Get the size of each pool buffer
+    pub const fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+}
+
+impl Default for UringConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Page-aligned memory shared with the kernel
+struct BufferPool {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    buffer_size: usize,
+}
+
+// The pool is plain memory; exclusive use of each buffer is handed out
+// through `State::free`.
+unsafe impl Send for BufferPool {}
+unsafe impl Sync for BufferPool {}
+
+impl BufferPool {
+    fn new(count: usize, buffer_size: usize) -> io::Result<Self> {
+        let layout = Layout::from_size_align(count * buffer_size, BUFFER_ALIGN)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let ptr = NonNull::new(unsafe { std::alloc::alloc_zeroed(layout) })
+            .ok_or_else(|| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        Ok(Self {
+            ptr,
+            layout,
+            buffer_size,
+        })
+    }
+
+    fn count(&self) -> usize {
+        self.layout.size() / self.buffer_size
+    }
+
+    fn buffer(&self, index: u16) -> *mut u8 {
+        unsafe { self.ptr.as_ptr().add(usize::from(index) * self.buffer_size) }
+    }
+
+    fn iovecs(&self) -> Vec<libc::iovec> {
+        (0..self.count())
+            .map(|i| libc::iovec {
+                iov_base: self.buffer(i as u16).cast(),
+                iov_len: self.buffer_size,
+            })
+            .collect()
+    }
+}
+
+impl Drop for BufferPool {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+/// An operation submitted to the ring
+struct Op {
+    buffer: Option<u16>,
+    result: Option<i32>,
+    waker: Option<Waker>,
+    /// The waiting future was dropped; the reaper frees the buffer
+    abandoned: bool,
+}
+
+#[derive(Default)]
+struct State {
+    free: Vec<u16>,
+    buffer_waiters: Vec<Waker>,
+    ops: HashMap<u64, Op>,
+    next_id: u64,
+}
+
+impl State {
+    fn release(&mut self, buffer: Option<u16>) {
+        if let Some(buffer) = buffer {
+            self.free.push(buffer);
+            self.buffer_waiters.drain(..).for_each(Waker::wake);
+        }
+    }
+
+    /// Give up on an operation: free it now if it completed, otherwise let
+    /// the reaper free it on completion
+    fn abandon(&mut self, id: u64) {
+        let Some(op) = self.ops.get_mut(&id) else {
+            return;
+        };
+        if op.result.is_some() {
+            let buffer = op.buffer;
+            self.ops.remove(&id);
+            self.release(buffer);
+        } else {
+            op.abandoned = true;
+        }
+    }
+}
+
+/// State shared between device handles and the reaper thread
+struct Shared {
+    ring: IoUring,
+    file: std::fs::File,
+    eventfd: OwnedFd,
+    pool: BufferPool,
+    registered: bool,
+    /// Serialises pushes to the submission queue; set once a failed
+    /// submission left entries queued, after which nothing is submitted
+    submit_lock: Mutex<bool>,
+    state: Mutex<State>,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn notify(&self) {
+        let one: u64 = 1;
+        unsafe {
+            libc::write(
+                self.eventfd.as_raw_fd(),
+                (&one as *const u64).cast(),
+                size_of::<u64>(),
+            );
+        }
+    }
+
+    /// Register `entries` as operations and submit them in one call
+    ///
+    /// Returns the operation ids in order.
+    ///
+    /// Entries the kernel did not take stay in the submission queue and
+    /// cannot be removed, so any later submission would run them long after
+    /// the caller saw the error. A failed submission therefore breaks the
+    /// ring: every later call fails and the device has to be reopened.
+    fn submit(&self, entries: Vec<(squeue::Entry, Option<u16>)>) -> io::Result<Vec<u64>> {
+        let mut broken = self.submit_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if *broken {
+            let mut state = self.state();
+            entries
+                .into_iter()
+                .for_each(|(_, buffer)| state.release(buffer));
+            return Err(io::Error::other(
+                "io_uring submission failed earlier; reopen the device",
+            ));
+        }
+
+        let mut ids = Vec::with_capacity(entries.len());
+        // `ids[..submitted]` were handed to the kernel, the rest are still queued
+        let mut submitted = 0;
+        let mut entries = entries.into_iter();
+        let mut result = Ok(());
+        for (entry, buffer) in entries.by_ref() {
+            let id = {
+                let mut state = self.state();
+                let id = state.next_id;
+                state.next_id += 1;
+                state.ops.insert(
+                    id,
+                    Op {
+                        buffer,
+                        result: None,
+                        waker: None,
+                        abandoned: false,
+                    },
+                );
+                id
+            };
+            ids.push(id);
+            let entry = entry.user_data(id);
+            // The submission queue is only touched under `submit_lock`
+            if unsafe { self.ring.submission_shared().push(&entry) }.is_ok() {
+                continue;
+            }
+            // Queue full: hand what we have to the kernel and retry
+            result = self.ring.submit().map(|n| submitted += n).and_then(|()| {
+                unsafe { self.ring.submission_shared().push(&entry) }
+                    .map_err(|_| io::Error::other("io_uring submission queue full"))
+            });
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() {
+            result = self.ring.submit().map(|n| submitted += n);
+        }
+        if result.is_ok() && submitted == ids.len() {
+            return Ok(ids);
+        }
+
+        *broken = true;
+        let mut state = self.state();
+        // Submitted operations complete normally and the reaper frees them
+        ids[..submitted].iter().for_each(|&id| state.abandon(id));
+        // Queued ones never reach the kernel now
+        for id in &ids[submitted..] {
+            if let Some(op) = state.ops.remove(id) {
+                state.release(op.buffer);
+            }
+        }
+        entries.for_each(|(_, buffer)| state.release(buffer));
+        Err(result
+            .err()
+            .unwrap_or_else(|| io::Error::other("io_uring accepted only part of a submission")))
+    }
+
+    /// Reaper loop: wait for the eventfd and dispatch completions
+    fn reap(&self) {
+        loop {
+            let mut count: u64 = 0;
+            let n = unsafe {
+                libc::read(
+                    self.eventfd.as_raw_fd(),
+                    (&mut count as *mut u64).cast(),
+                    size_of::<u64>(),
+                )
+            };
+            if n < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                break;
+            }
+
+            let mut state = self.state();
+            // The reaper is the only consumer of the completion queue
+            for cqe in unsafe { self.ring.completion_shared() } {
+                let Some(op) = state.ops.get_mut(&cqe.user_data()) else {
+                    continue;
+                };
+                if op.abandoned {
+                    let buffer = op.buffer;
+                    state.ops.remove(&cqe.user_data());
+                    state.release(buffer);
+                } else {
+                    op.result = Some(cqe.result());
+                    if let Some(waker) = op.waker.take() {
+                        waker.wake();
+                    }
+                }
+            }
+            if self.shutdown.load(Ordering::Acquire)
+                && state.ops.values().all(|op| op.result.is_some())
+            {
+                break;
+            }
+        }
+    }
+}
+
+/// Future resolving to the result of one submitted operation
+struct Completion<'a> {
+    shared: &'a Shared,
+    id: u64,
+}
+
+impl Future for Completion<'_> {
+    type Output = (i32, Option<u16>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state();
+        let op = state
+            .ops
+            .get_mut(&self.id)
+            .expect("operation is registered");
+        match op.result {
+            Some(result) => {
+                let buffer = op.buffer;
+                state.ops.remove(&self.id);
+                Poll::Ready((result, buffer))
+            }
+            None => {
+                op.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Completion<'_> {
+    fn drop(&mut self) {
+        // No-op once the result was taken
+        self.shared.state().abandon(self.id);
+    }
+}
+
+/// Buffers held by one request; returned to the pool on drop
+///
+/// Operations that have not completed when the request is dropped are
+/// marked abandoned so the reaper returns their buffers instead.
+struct Batch<'a> {
+    shared: &'a Shared,
+    buffers: Vec<u16>,
+    pending: Vec<u64>,
+}
+
+impl<'a> Batch<'a> {
+    /// Wait for at least one free buffer and take up to `max`
+    async fn acquire(shared: &'a Shared, max: usize) -> Batch<'a> {
+        let buffers = std::future::poll_fn(|cx| {
+            let mut state = shared.state();
+            if state.free.is_empty() {
+                state.buffer_waiters.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            let take = state.free.len().min(max);
+            let start = state.free.len() - take;
+            Poll::Ready(state.free.split_off(start))
+        })
+        .await;
+        Batch {
+            shared,
+            buffers,
+            pending: Vec::new(),
+        }
+    }
+
+    fn read_entry(&self, fd: types::Fd, index: u16, len: usize, offset: u64) -> squeue::Entry {
+        let buf = self.shared.pool.buffer(index);
+        if self.shared.registered {
+            opcode::ReadFixed::new(fd, buf, len as u32, index)
+                .offset(offset)
+                .build()
+        } else {
+            opcode::Read::new(fd, buf, len as u32)
+                .offset(offset)
+                .build()
+        }
+    }
+
+    fn write_entry(&self, fd: types::Fd, index: u16, len: usize, offset: u64) -> squeue::Entry {
+        let buf = self.shared.pool.buffer(index);
+        if self.shared.registered {
+            opcode::WriteFixed::new(fd, buf, len as u32, index)
+                .offset(offset)
+                .build()
+        } else {
+            opcode::Write::new(fd, buf, len as u32)
+                .offset(offset)
+                .build()
+        }
+    }
+
+    /// Submit one entry per buffer; the buffers now belong to the kernel
+    fn submit(&mut self, entries: Vec<squeue::Entry>) -> io::Result<()> {
+        let buffers = core::mem::take(&mut self.buffers);
+        let with_buffers = entries
+            .into_iter()
+            .zip(buffers.iter().copied().map(Some))
+            .collect();
+        self.pending = self.shared.submit(with_buffers)?;
+        Ok(())
+    }
+
+    /// Wait for the next submitted operation in order
+    async fn next(&mut self) -> (i32, u16) {
+        let id = self.pending[0];
+        let (result, buffer) = Completion {
+            shared: self.shared,
+            id,
+        }
+        .await;
+        self.pending.remove(0);
+        let buffer = buffer.expect("chunk operations own a buffer");
+        self.buffers.push(buffer);
+        (result, buffer)
+    }
+}
+
+impl Drop for Batch<'_> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state();
+        for id in self.pending.drain(..) {
+            state.abandon(id);
+        }
+        for buffer in self.buffers.drain(..) {
+            state.release(Some(buffer));
+        }
+    }
+}
+
+/// Check a chunk completion: negative results are errno values
+fn check_result(result: i32, expected: usize) -> io::Result<()> {
+    if result < 0 {
+        return Err(io::Error::from_raw_os_error(-result));
+    }
+    if result as usize != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Short transfer at end of device",
+        ));
+    }
+    Ok(())
+}
+
+/// Byte offset of a request, checked against the device size and the logical sector size
+fn check_range<const SIZE: usize>(
+    size: u64,
+    logical_sector_size: u32,
+    block_address: u32,
+    blocks: usize,
+) -> io::Result<u64> {
+    let offset = u64::from(block_address) * SIZE as u64;
+    let len = (blocks * SIZE) as u64;
+    if offset + len > size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Access beyond end of device",
+        ));
+    }
+    let sector = u64::from(logical_sector_size);
+    if offset % sector != 0 || len % sector != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Access not aligned to the logical sector size",
+        ));
+    }
+    Ok(offset)
+}
+
+/// Keeps the ring alive while any device handle exists
+struct Handle(Arc<Shared>);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.0.shutdown.store(true, Ordering::Release);
+        self.0.notify();
+    }
+}
+
+/// io_uring-backed Linux block device
+///
+/// Like [`LinuxBlockDevice`](crate::LinuxBlockDevice) the device is opened
+/// with `O_DIRECT`, but reads and writes are split into chunks of
+/// [`UringConfig::buffer_size`] bytes that are submitted together and run
+/// concurrently in the kernel. Reads take `&self`, so several tasks can read
+/// through clones of the same device at once.
+///
+/// Unlike [`LinuxBlockDevice`](crate::LinuxBlockDevice), partial logical
+/// sectors are not emulated with read-modify-write: a request that does not
+/// start and end on a [`logical_sector_size`](Self::logical_sector_size)
+/// boundary fails with [`io::ErrorKind::InvalidInput`].
+///
+/// Falls back to unregistered buffers if the kernel refuses to pin the pool
+/// (e.g. a low `RLIMIT_MEMLOCK` on kernels before 5.12).
+///
+/// # Examples
+/// ```ignore
+/// use fatrs_block_platform::{UringBlockDevice, UringConfig};
+///
+/// let config = UringConfig::new().with_queue_depth(64).with_buffer_size(256 * 1024);
+/// let dev = UringBlockDevice::open_with_config("/dev/sdb", false, config).await?;
+/// ```
+#[derive(Clone)]
+pub struct UringBlockDevice {
+    handle: Arc<Handle>,
+    size: u64,
+    logical_sector_size: u32,
+}
+
+impl UringBlockDevice {
+    /// Open a Linux block device with the default [`UringConfig`]
+    ///
+    /// # Arguments
+    /// * `path` - Device path (e.g., "/dev/sdb", "/dev/mmcblk0")
+    /// * `writable` - Whether to open for write access
+    pub async fn open(path: impl AsRef<Path>, writable: bool) -> io::Result<Self> {
+        Self::open_with_config(path, writable, UringConfig::new()).await
+    }
+
+    /// Open a Linux block device with a custom ring and buffer configuration
+    pub async fn open_with_config(
+        path: impl AsRef<Path>,
+        writable: bool,
+        config: UringConfig,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let shared = tokio::task::spawn_blocking(move || {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(writable)
+                .custom_flags(libc::O_DIRECT)
+                .open(&path)?;
+            let size = crate::linux::device_size(&file)?;
+            let logical_sector_size = crate::linux::logical_sector_size(&file)?;
+
+            let ring = IoUring::new(config.queue_depth)?;
+            let pool = BufferPool::new(config.queue_depth as usize, config.buffer_size)?;
+            // The pool outlives the ring registration: both live in `Shared`
+            let registered = unsafe { ring.submitter().register_buffers(&pool.iovecs()) }.is_ok();
+
+            let eventfd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+            if eventfd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let eventfd = unsafe { OwnedFd::from_raw_fd(eventfd) };
+            ring.submitter().register_eventfd(eventfd.as_raw_fd())?;
+
+            let state = State {
+                free: (0..config.queue_depth as u16).collect(),
+                ..State::default()
+            };
+            Ok::<_, io::Error>((
+                Shared {
+                    ring,
+                    file,
+                    eventfd,
+                    pool,
+                    registered,
+                    submit_lock: Mutex::new(false),
+                    state: Mutex::new(state),
+                    shutdown: AtomicBool::new(false),
+                },
+                size,
+                logical_sector_size,
+            ))
+        })
+        .await
+        .map_err(io::Error::other)??;
+
+        let (shared, size, logical_sector_size) = shared;
+        let shared = Arc::new(shared);
+        std::thread::Builder::new()
+            .name("fatrs-uring".into())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || shared.reap()
+            })?;
+
+        Ok(Self {
+            handle: Arc::new(Handle(shared)),
+            size,
+            logical_sector_size,
+        })
+    }
+
+    /// Get the size of the device in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the logical sector size (`BLKSSZGET`); every request must be aligned to it
+    pub fn logical_sector_size(&self) -> u32 {
+        self.logical_sector_size
+    }
+
+    /// Whether the buffer pool is registered with the kernel
+    pub fn buffers_registered(&self) -> bool {
+        self.handle.0.registered
+    }
+
+    fn shared(&self) -> &Shared {
+        &self.handle.0
+    }
+
+    fn fd(&self) -> types::Fd {
+        types::Fd(self.shared().file.as_raw_fd())
+    }
+
+    async fn read_blocks<const SIZE: usize>(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<A4, [u8; SIZE]>],
+    ) -> io::Result<()> {
+        let offset = check_range::<SIZE>(
+            self.size,
+            self.logical_sector_size,
+            block_address,
+            data.len(),
+        )?;
+        let shared = self.shared();
+        // Buffers are multiples of 4096 bytes, so every chunk stays sector aligned
+        let blocks_per_buffer = shared.pool.buffer_size / SIZE;
+        let mut remaining = data;
+        let mut offset = offset;
+
+        while !remaining.is_empty() {
+            let chunks = remaining.len().div_ceil(blocks_per_buffer);
+            let mut batch = Batch::acquire(shared, chunks).await;
+
+            let mut entries = Vec::with_capacity(batch.buffers.len());
+            let mut lens = Vec::with_capacity(batch.buffers.len());
+            let mut blocks = 0;
+            for &index in &batch.buffers {
+                let count = (remaining.len() - blocks).min(blocks_per_buffer);
+                let len = count * SIZE;
+                entries.push(batch.read_entry(
+                    self.fd(),
+                    index,
+                    len,
+                    offset + (blocks * SIZE) as u64,
+                ));
+                lens.push(count);
+                blocks += count;
+            }
+            batch.submit(entries)?;
+
+            for count in lens {
+                let (result, index) = batch.next().await;
+                check_result(result, count * SIZE)?;
+                let (chunk, rest) = core::mem::take(&mut remaining).split_at_mut(count);
+                let buf =
+                    unsafe { core::slice::from_raw_parts(shared.pool.buffer(index), count * SIZE) };
+                for (block, src) in chunk.iter_mut().zip(buf.chunks_exact(SIZE)) {
+                    block.copy_from_slice(src);
+                }
+                remaining = rest;
+                offset += (count * SIZE) as u64;
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_blocks<const SIZE: usize>(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<A4, [u8; SIZE]>],
+    ) -> io::Result<()> {
+        let mut offset = check_range::<SIZE>(
+            self.size,
+            self.logical_sector_size,
+            block_address,
+            data.len(),
+        )?;
+        let shared = self.shared();
+        let blocks_per_buffer = shared.pool.buffer_size / SIZE;
+        let mut remaining = data;
+
+        while !remaining.is_empty() {
+            let chunks = remaining.len().div_ceil(blocks_per_buffer);
+            let mut batch = Batch::acquire(shared, chunks).await;
+
+            // Stage every chunk, then submit the whole batch at once
+            let mut entries = Vec::with_capacity(batch.buffers.len());
+            let mut lens = Vec::with_capacity(batch.buffers.len());
+            for &index in &batch.buffers {
+                let count = remaining.len().min(blocks_per_buffer);
+                let (chunk, rest) = remaining.split_at(count);
+                let buf = unsafe {
+                    core::slice::from_raw_parts_mut(shared.pool.buffer(index), count * SIZE)
+                };
+                for (dst, block) in buf.chunks_exact_mut(SIZE).zip(chunk) {
+                    dst.copy_from_slice(&block[..]);
+                }
+                entries.push(batch.write_entry(self.fd(), index, count * SIZE, offset));
+                lens.push(count);
+                offset += (count * SIZE) as u64;
+                remaining = rest;
+            }
+            batch.submit(entries)?;
+
+            for count in lens {
+                let (result, _) = batch.next().await;
+                check_result(result, count * SIZE)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync_data(&mut self) -> io::Result<()> {
+        let shared = self.shared();
+        let entry = opcode::Fsync::new(self.fd())
+            .flags(types::FsyncFlags::DATASYNC)
+            .build();
+        let ids = shared.submit(vec![(entry, None)])?;
+        let (result, _) = Completion { shared, id: ids[0] }.await;
+        if result < 0 {
+            return Err(io::Error::from_raw_os_error(-result));
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice<512> for UringBlockDevice {
+    type Error = io::Error;
+    type Align = A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; 512]>],
+    ) -> Result<(), Self::Error> {
+        self.read_blocks(block_address, data).await
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; 512]>],
+    ) -> Result<(), Self::Error> {
+        self.write_blocks(block_address, data).await
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(UringBlockDevice::size(self))
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.sync_data().await
+    }
+}
+
+/// Native block size for 4Kn devices; also usable on 512e devices
+impl BlockDevice<4096> for UringBlockDevice {
+    type Error = io::Error;
+    type Align = A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; 4096]>],
+    ) -> Result<(), Self::Error> {
+        self.read_blocks(block_address, data).await
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; 4096]>],
+    ) -> Result<(), Self::Error> {
+        self.write_blocks(block_address, data).await
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(UringBlockDevice::size(self))
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.sync_data().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_builders() {
+        let config = UringConfig::default();
+        assert_eq!(config, UringConfig::new());
+        assert_eq!(
+            (config.queue_depth(), config.buffer_size()),
+            (32, 128 * 1024)
+        );
+
+        let config = UringConfig::new()
+            .with_queue_depth(4096)
+            .with_buffer_size(4096);
+        assert_eq!((config.queue_depth(), config.buffer_size()), (4096, 4096));
+        assert_eq!(UringConfig::new().with_queue_depth(1).queue_depth(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "queue depth must be 1..=4096")]
+    fn test_config_rejects_zero_queue_depth() {
+        let _ = UringConfig::new().with_queue_depth(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "queue depth must be 1..=4096")]
+    fn test_config_rejects_oversized_queue_depth() {
+        let _ = UringConfig::new().with_queue_depth(4097);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer size must be a multiple of 4096")]
+    fn test_config_rejects_zero_buffer_size() {
+        let _ = UringConfig::new().with_buffer_size(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer size must be a multiple of 4096")]
+    fn test_config_rejects_unaligned_buffer_size() {
+        let _ = UringConfig::new().with_buffer_size(6144);
+    }
+
+    #[test]
+    fn test_check_range_bounds() {
+        assert_eq!(check_range::<512>(8192, 512, 15, 1).unwrap(), 7680);
+        assert_eq!(check_range::<4096>(8192, 512, 1, 1).unwrap(), 4096);
+        for (block_address, blocks) in [(15, 2), (16, 1), (u32::MAX, 1)] {
+            let err = check_range::<512>(8192, 512, block_address, blocks).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(check_range::<4096>(8192, 512, 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_check_range_alignment_on_4k_devices() {
+        // 512-byte blocks must cover whole 4096-byte sectors
+        assert_eq!(check_range::<512>(65536, 4096, 8, 8).unwrap(), 4096);
+        let err = check_range::<512>(65536, 4096, 1, 8).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Access not aligned to the logical sector size"
+        );
+        assert!(check_range::<512>(65536, 4096, 8, 1).is_err());
+        assert_eq!(check_range::<4096>(65536, 4096, 3, 2).unwrap(), 12288);
+    }
+
+    #[test]
+    fn test_check_result() {
+        assert!(check_result(4096, 4096).is_ok());
+        let err = check_result(-libc::EIO, 4096).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EIO));
+        let err = check_result(512, 4096).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,445 @@
+//! VHD (Virtual Hard Disk) image backend
+//!
+//! Supports fixed and dynamic VHD images. Fixed images are the raw disk
+//! followed by a 512-byte footer. Dynamic images map the disk in blocks
+//! through a block allocation table (BAT); unallocated blocks read as zeros
+//! and are appended to the file on first write. Differencing images are not
+//! supported.
+
+use aligned::{A4, Aligned};
+use core::cell::RefCell;
+use embedded_io_async::{Read, Seek, SeekFrom, Write};
+use fatrs_block_device::BlockDevice;
+
+use crate::image::{
+    ImageError, SECTOR_SIZE, be_u32, be_u64, check_bounds, read_exact_at, read_sectors,
+    write_all_at, write_sectors,
+};
+
+pub(crate) const COOKIE: &[u8; 8] = b"conectix";
+const SPARSE_COOKIE: &[u8; 8] = b"cxsparse";
+const FOOTER_SIZE: usize = 512;
+const SPARSE_HEADER_SIZE: usize = 1024;
+const UNALLOCATED: u32 = 0xFFFF_FFFF;
+
+const DISK_TYPE_FIXED: u32 = 2;
+const DISK_TYPE_DYNAMIC: u32 = 3;
+const DISK_TYPE_DIFFERENCING: u32 = 4;
+
+/// VHD disk type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt-logging", derive(defmt::Format))]
+pub enum VhdType {
+    /// Preallocated image: disk data followed by the footer
+    Fixed,
+    /// Sparse image with a block allocation table
+    Dynamic,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sparse {
+    bat_offset: u64,
+    max_entries: u32,
+    block_size: u32,
+    bitmap_size: u32,
+}
+
+/// Block device over a fixed or dynamic VHD image
+///
+/// # Example
+///
+/// ```ignore
+/// use fatrs_block_platform::VhdDevice;
+/// use embedded_io_adapters::tokio_1::FromTokio;
+///
+/// let file = tokio::fs::OpenOptions::new().read(true).write(true).open("disk.vhd").await?;
+/// let device = VhdDevice::open(FromTokio::new(file)).await?;
+/// ```
+pub struct VhdDevice<T> {
+    io: RefCell<T>,
+    footer: [u8; FOOTER_SIZE],
+    footer_offset: u64,
+    size: u64,
+    sparse: Option<Sparse>,
+}
+
+/// VHD checksum: one's complement of the byte sum, skipping the checksum field
+fn checksum(buf: &[u8], field: usize) -> u32 {
+    let sum = buf
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !(field..field + 4).contains(i))
+        .fold(0u32, |sum, (_, b)| sum.wrapping_add(u32::from(*b)));
+    !sum
+}
+
+impl<T: Read + Write + Seek> VhdDevice<T> {
+    /// Open a VHD image, validating its footer and, for dynamic images, the
+    /// sparse header
+    pub async fn open(mut io: T) -> Result<Self, ImageError<T::Error>> {
+        let len = io.seek(SeekFrom::End(0)).await?;
+        if len < FOOTER_SIZE as u64 {
+            return Err(ImageError::UnexpectedEof);
+        }
+        let footer_offset = len - FOOTER_SIZE as u64;
+        let mut footer = [0u8; FOOTER_SIZE];
+        read_exact_at(&mut io, footer_offset, &mut footer).await?;
+        if &footer[..8] != COOKIE {
+            return Err(ImageError::Corrupt("missing VHD footer"));
+        }
+        if be_u32(&footer, 64) != checksum(&footer, 64) {
+            return Err(ImageError::Corrupt("VHD footer checksum mismatch"));
+        }
+
+        let size = be_u64(&footer, 48);
+        let sparse = match be_u32(&footer, 60) {
+            DISK_TYPE_FIXED => {
+                if size > footer_offset {
+                    return Err(ImageError::Corrupt("VHD shorter than its disk size"));
+                }
+                None
+            }
+            DISK_TYPE_DYNAMIC => {
+                Some(Self::read_sparse_header(&mut io, be_u64(&footer, 16), size).await?)
+            }
+            DISK_TYPE_DIFFERENCING => return Err(ImageError::Unsupported("differencing VHD")),
+            _ => return Err(ImageError::Corrupt("unknown VHD disk type")),
+        };
+
+        Ok(Self {
+            io: RefCell::new(io),
+            footer,
+            footer_offset,
+            size,
+            sparse,
+        })
+    }
+
+    async fn read_sparse_header(
+        io: &mut T,
+        offset: u64,
+        size: u64,
+    ) -> Result<Sparse, ImageError<T::Error>> {
+        let mut header = [0u8; SPARSE_HEADER_SIZE];
+        read_exact_at(io, offset, &mut header).await?;
+        if &header[..8] != SPARSE_COOKIE {
+            return Err(ImageError::Corrupt("missing VHD dynamic disk header"));
+        }
+        if be_u32(&header, 36) != checksum(&header, 36) {
+            return Err(ImageError::Corrupt(
+                "VHD dynamic disk header checksum mismatch",
+            ));
+        }
+
+        let block_size = be_u32(&header, 32);
+        if !block_size.is_power_of_two() || block_size < SECTOR_SIZE as u32 {
+            return Err(ImageError::Corrupt("invalid VHD block size"));
+        }
+        let max_entries = be_u32(&header, 28);
+        if u64::from(max_entries) * u64::from(block_size) < size {
+            return Err(ImageError::Corrupt(
+                "VHD allocation table smaller than disk",
+            ));
+        }
+        let sectors_per_block = block_size / SECTOR_SIZE as u32;
+        let bitmap_size = (sectors_per_block / 8).next_multiple_of(SECTOR_SIZE as u32);
+
+        Ok(Sparse {
+            bat_offset: be_u64(&header, 16),
+            max_entries,
+            block_size,
+            bitmap_size,
+        })
+    }
+
+    /// Get the disk type
+    pub fn disk_type(&self) -> VhdType {
+        if self.sparse.is_some() {
+            VhdType::Dynamic
+        } else {
+            VhdType::Fixed
+        }
+    }
+
+    /// Get the virtual disk size in bytes
+    pub fn virtual_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the block size of a dynamic image
+    pub fn block_size(&self) -> Option<u32> {
+        self.sparse.map(|s| s.block_size)
+    }
+
+    /// Consume the device and return the inner stream
+    pub fn into_inner(self) -> T {
+        self.io.into_inner()
+    }
+
+    async fn bat_entry(
+        io: &mut T,
+        sparse: &Sparse,
+        block: u32,
+    ) -> Result<u32, ImageError<T::Error>> {
+        let mut entry = [0u8; 4];
+        read_exact_at(io, sparse.bat_offset + u64::from(block) * 4, &mut entry).await?;
+        Ok(u32::from_be_bytes(entry))
+    }
+
+    /// Append a new block in place of the footer and point the BAT at it
+    async fn allocate(&mut self, sparse: Sparse, block: u32) -> Result<u32, ImageError<T::Error>> {
+        let io = self.io.get_mut();
+        let block_offset = self.footer_offset.next_multiple_of(SECTOR_SIZE as u64);
+        let new_footer_offset =
+            block_offset + u64::from(sparse.bitmap_size) + u64::from(sparse.block_size);
+
+        // Footer first so the file stays valid if we stop halfway; the data
+        // area in between reads back as zeros once the file is extended
+        write_all_at(io, new_footer_offset, &self.footer).await?;
+        let bitmap = [0xFFu8; SECTOR_SIZE];
+        for i in 0..sparse.bitmap_size as u64 / SECTOR_SIZE as u64 {
+            write_all_at(io, block_offset + i * SECTOR_SIZE as u64, &bitmap).await?;
+        }
+        let entry = (block_offset / SECTOR_SIZE as u64) as u32;
+        write_all_at(
+            io,
+            sparse.bat_offset + u64::from(block) * 4,
+            &entry.to_be_bytes(),
+        )
+        .await?;
+        self.footer_offset = new_footer_offset;
+        Ok(entry)
+    }
+}
+
+impl<T: Read + Write + Seek> BlockDevice<SECTOR_SIZE> for VhdDevice<T> {
+    type Error = ImageError<T::Error>;
+    type Align = A4;
+
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let offset = check_bounds(self.size, block_address, data.len())?;
+        let mut io = self.io.borrow_mut();
+        let Some(sparse) = self.sparse else {
+            return read_sectors(&mut *io, offset, data).await;
+        };
+
+        let mut offset = offset;
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            let block = (offset / u64::from(sparse.block_size)) as u32;
+            let within = offset % u64::from(sparse.block_size);
+            let count = remaining
+                .len()
+                .min(((u64::from(sparse.block_size) - within) / SECTOR_SIZE as u64) as usize);
+            let (chunk, rest) = remaining.split_at_mut(count);
+
+            match Self::bat_entry(&mut io, &sparse, block).await? {
+                UNALLOCATED => chunk.iter_mut().for_each(|s| s.fill(0)),
+                entry => {
+                    let start = u64::from(entry) * SECTOR_SIZE as u64
+                        + u64::from(sparse.bitmap_size)
+                        + within;
+                    read_sectors(&mut *io, start, chunk).await?;
+                }
+            }
+            offset += (count * SECTOR_SIZE) as u64;
+            remaining = rest;
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let offset = check_bounds(self.size, block_address, data.len())?;
+        let Some(sparse) = self.sparse else {
+            return write_sectors(self.io.get_mut(), offset, data).await;
+        };
+
+        let mut offset = offset;
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            let block = (offset / u64::from(sparse.block_size)) as u32;
+            if block >= sparse.max_entries {
+                return Err(ImageError::OutOfBounds);
+            }
+            let within = offset % u64::from(sparse.block_size);
+            let count = remaining
+                .len()
+                .min(((u64::from(sparse.block_size) - within) / SECTOR_SIZE as u64) as usize);
+            let (chunk, rest) = remaining.split_at(count);
+
+            let entry = match Self::bat_entry(self.io.get_mut(), &sparse, block).await? {
+                UNALLOCATED => self.allocate(sparse, block).await?,
+                entry => entry,
+            };
+            let start =
+                u64::from(entry) * SECTOR_SIZE as u64 + u64::from(sparse.bitmap_size) + within;
+            write_sectors(self.io.get_mut(), start, chunk).await?;
+            offset += (count * SECTOR_SIZE) as u64;
+            remaining = rest;
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.size)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.io.get_mut().flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::{MemStream, block_on};
+    use std::vec::Vec;
+
+    const DISK_SIZE: u64 = 16 * 1024;
+    const BLOCK_SIZE: u32 = 4096;
+
+    fn footer(disk_type: u32, data_offset: u64) -> [u8; FOOTER_SIZE] {
+        let mut footer = [0u8; FOOTER_SIZE];
+        footer[..8].copy_from_slice(COOKIE);
+        footer[16..24].copy_from_slice(&data_offset.to_be_bytes());
+        footer[40..48].copy_from_slice(&DISK_SIZE.to_be_bytes());
+        footer[48..56].copy_from_slice(&DISK_SIZE.to_be_bytes());
+        footer[60..64].copy_from_slice(&disk_type.to_be_bytes());
+        let sum = checksum(&footer, 64);
+        footer[64..68].copy_from_slice(&sum.to_be_bytes());
+        footer
+    }
+
+    /// Footer copy, sparse header at 512, BAT at 1536 with nothing allocated
+    fn dynamic_image() -> Vec<u8> {
+        let footer = footer(DISK_TYPE_DYNAMIC, 512);
+        let mut header = [0u8; SPARSE_HEADER_SIZE];
+        header[..8].copy_from_slice(SPARSE_COOKIE);
+        header[8..16].copy_from_slice(&u64::MAX.to_be_bytes());
+        header[16..24].copy_from_slice(&1536u64.to_be_bytes());
+        let max_entries = (DISK_SIZE / u64::from(BLOCK_SIZE)) as u32;
+        header[28..32].copy_from_slice(&max_entries.to_be_bytes());
+        header[32..36].copy_from_slice(&BLOCK_SIZE.to_be_bytes());
+        let sum = checksum(&header, 36);
+        header[36..40].copy_from_slice(&sum.to_be_bytes());
+
+        let mut image = footer.to_vec();
+        image.extend_from_slice(&header);
+        image.extend_from_slice(&[0xFF; SECTOR_SIZE]);
+        image.extend_from_slice(&footer);
+        image
+    }
+
+    fn sectors<const N: usize>(fill: u8) -> [Aligned<A4, [u8; SECTOR_SIZE]>; N] {
+        [Aligned([fill; SECTOR_SIZE]); N]
+    }
+
+    #[test]
+    fn test_fixed_image() {
+        block_on(async {
+            let mut image: Vec<u8> = (0..DISK_SIZE).map(|i| (i / 512) as u8).collect();
+            image.extend_from_slice(&footer(DISK_TYPE_FIXED, u64::MAX));
+            let mut device = VhdDevice::open(MemStream::new(image)).await.unwrap();
+            assert_eq!(device.disk_type(), VhdType::Fixed);
+            assert_eq!(device.size().await.unwrap(), DISK_SIZE);
+
+            let mut buf = sectors::<2>(0);
+            device.read(5, &mut buf).await.unwrap();
+            assert_eq!((buf[0][0], buf[1][511]), (5, 6));
+            device.write(31, &sectors::<1>(0xAA)).await.unwrap();
+            assert_eq!(
+                device.read(31, &mut buf).await,
+                Err(ImageError::OutOfBounds)
+            );
+            assert_eq!(
+                device.write(32, &sectors::<1>(0)).await,
+                Err(ImageError::OutOfBounds)
+            );
+
+            // The footer is left alone
+            let data = device.into_inner().data;
+            assert_eq!(data.len() as u64, DISK_SIZE + FOOTER_SIZE as u64);
+            assert_eq!(data[31 * 512], 0xAA);
+            assert_eq!(&data[DISK_SIZE as usize..][..8], COOKIE);
+        });
+    }
+
+    #[test]
+    fn test_dynamic_image_allocates_blocks_and_reopens() {
+        block_on(async {
+            let mut device = VhdDevice::open(MemStream::new(dynamic_image()))
+                .await
+                .unwrap();
+            assert_eq!(device.disk_type(), VhdType::Dynamic);
+            assert_eq!(device.block_size(), Some(BLOCK_SIZE));
+
+            // Unallocated blocks read as zeros
+            let mut buf = sectors::<3>(1);
+            device.read(6, &mut buf).await.unwrap();
+            assert!(buf.iter().all(|s| s.iter().all(|&b| b == 0)));
+
+            // Sectors 7 and 8 straddle blocks 0 and 1
+            device
+                .write(7, &[Aligned([0x11; 512]), Aligned([0x22; 512])])
+                .await
+                .unwrap();
+            device.write(25, &sectors::<1>(0x33)).await.unwrap();
+            device.write(8, &sectors::<1>(0x44)).await.unwrap();
+            device.sync().await.unwrap();
+
+            // Three blocks appended, each with a one-sector bitmap, then the footer
+            let data = device.into_inner().data;
+            assert_eq!(data.len(), 2048 + 3 * (512 + 4096) + FOOTER_SIZE);
+            assert_eq!(&data[data.len() - FOOTER_SIZE..][..8], COOKIE);
+
+            let device = VhdDevice::open(MemStream::new(data)).await.unwrap();
+            device.read(6, &mut buf).await.unwrap();
+            assert_eq!((buf[0][0], buf[1][0], buf[2][0]), (0, 0x11, 0x44));
+            device.read(24, &mut buf[..2]).await.unwrap();
+            assert_eq!((buf[0][0], buf[1][0]), (0, 0x33));
+            // Block 2 was never written
+            device.read(16, &mut buf[..1]).await.unwrap();
+            assert_eq!(buf[0][0], 0);
+        });
+    }
+
+    #[test]
+    fn test_corrupt_and_unsupported_images_are_rejected() {
+        block_on(async {
+            let mut image = dynamic_image();
+            let len = image.len();
+            image[len - 300] ^= 1;
+            assert!(matches!(
+                VhdDevice::open(MemStream::new(image)).await,
+                Err(ImageError::Corrupt("VHD footer checksum mismatch"))
+            ));
+
+            let mut image = dynamic_image();
+            image[600] ^= 1;
+            assert!(matches!(
+                VhdDevice::open(MemStream::new(image)).await,
+                Err(ImageError::Corrupt(
+                    "VHD dynamic disk header checksum mismatch"
+                ))
+            ));
+
+            let image = footer(DISK_TYPE_DIFFERENCING, 512).to_vec();
+            assert!(matches!(
+                VhdDevice::open(MemStream::new(image)).await,
+                Err(ImageError::Unsupported("differencing VHD"))
+            ));
+        });
+    }
+}
@@ -0,0 +1,657 @@
+//! VHDX image backend
+//!
+//! Supports dynamic and fixed VHDX images without a parent. Payload blocks
+//! are located through the block allocation table (BAT); blocks that are not
+//! present read as zeros. Images with a pending log are refused rather than
+//! replayed.
+//!
+//! Writes go in place to payload blocks that are already present. VHDX
+//! requires BAT updates to go through its metadata log, which this backend
+//! does not write, so a write touching a block that is not present fails
+//! with [`ImageError::ReadOnly`] before anything is written. Fixed images,
+//! and dynamic images whose blocks are all allocated, are fully writable.
+
+use aligned::{A4, Aligned};
+use core::cell::RefCell;
+use crc::{CRC_32_ISCSI, Crc};
+use embedded_io_async::{Read, Seek, Write};
+use fatrs_block_device::BlockDevice;
+
+use crate::image::{
+    ImageError, SECTOR_SIZE, check_bounds, le_u16, le_u32, le_u64, read_exact_at, read_sectors,
+    write_all_at, write_sectors,
+};
+
+pub(crate) const FILE_SIGNATURE: &[u8; 8] = b"vhdxfile";
+const HEADER_SIGNATURE: &[u8; 4] = b"head";
+const REGION_SIGNATURE: &[u8; 4] = b"regi";
+const METADATA_SIGNATURE: &[u8; 8] = b"metadata";
+
+const HEADER_OFFSETS: [u64; 2] = [64 * 1024, 128 * 1024];
+const HEADER_SIZE: usize = 4 * 1024;
+const REGION_TABLE_OFFSETS: [u64; 2] = [192 * 1024, 256 * 1024];
+const REGION_TABLE_SIZE: usize = 64 * 1024;
+/// Header fields end at the log offset; the rest of the header is reserved
+const HEADER_FIELDS: usize = 80;
+const MB: u64 = 1024 * 1024;
+
+const BAT_REGION: [u8; 16] = [
+    0x66, 0x77, 0xC2, 0x2D, 0x23, 0xF6, 0x00, 0x42, 0x9D, 0x64, 0x11, 0x5E, 0x9B, 0xFD, 0x4A, 0x08,
+];
+const METADATA_REGION: [u8; 16] = [
+    0x06, 0xA2, 0x7C, 0x8B, 0x90, 0x47, 0x9A, 0x4B, 0xB8, 0xFE, 0x57, 0x5F, 0x05, 0x0F, 0x88, 0x6E,
+];
+const FILE_PARAMETERS: [u8; 16] = [
+    0x37, 0x67, 0xA1, 0xCA, 0x36, 0xFA, 0x43, 0x4D, 0xB3, 0xB6, 0x33, 0xF0, 0xAA, 0x44, 0xE7, 0x6B,
+];
+const VIRTUAL_DISK_SIZE: [u8; 16] = [
+    0x24, 0x42, 0xA5, 0x2F, 0x1B, 0xCD, 0x76, 0x48, 0xB2, 0x11, 0x5D, 0xBE, 0xD8, 0x3B, 0xF4, 0xB8,
+];
+const LOGICAL_SECTOR_SIZE: [u8; 16] = [
+    0x1D, 0xBF, 0x41, 0x81, 0x6F, 0xA9, 0x09, 0x47, 0xBA, 0x47, 0xF2, 0x33, 0xA8, 0xFA, 0xAB, 0x5F,
+];
+
+const BAT_STATE_MASK: u64 = 0x7;
+const PAYLOAD_FULLY_PRESENT: u64 = 6;
+const PAYLOAD_PARTIALLY_PRESENT: u64 = 7;
+
+const CRC32C: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+/// Block device over a VHDX image
+///
+/// # Example
+///
+/// ```ignore
+/// use fatrs_block_platform::VhdxDevice;
+/// use embedded_io_adapters::tokio_1::FromTokio;
+///
+/// let file = tokio::fs::OpenOptions::new().read(true).write(true).open("disk.vhdx").await?;
+/// let device = VhdxDevice::open(FromTokio::new(file)).await?;
+/// ```
+pub struct VhdxDevice<T> {
+    io: RefCell<T>,
+    header: [u8; HEADER_FIELDS],
+    /// Index into `HEADER_OFFSETS` of the current header
+    current_header: usize,
+    /// Whether the write GUIDs were refreshed for this session
+    header_updated: bool,
+    bat: Bat,
+    block_size: u32,
+    logical_sector_size: u32,
+    size: u64,
+}
+
+/// Location of the block allocation table
+#[derive(Debug, Clone, Copy)]
+struct Bat {
+    offset: u64,
+    entries: u64,
+    /// Payload blocks per sector bitmap block; the BAT interleaves one
+    /// bitmap entry after every `chunk_ratio` payload entries
+    chunk_ratio: u64,
+}
+
+impl Bat {
+    fn entry_offset(&self, block: u64) -> u64 {
+        self.offset + (block + block / self.chunk_ratio) * 8
+    }
+
+    async fn entry<T: Read + Seek>(
+        &self,
+        io: &mut T,
+        block: u64,
+    ) -> Result<u64, ImageError<T::Error>> {
+        if block + block / self.chunk_ratio >= self.entries {
+            return Err(ImageError::OutOfBounds);
+        }
+        let mut entry = [0u8; 8];
+        read_exact_at(io, self.entry_offset(block), &mut entry).await?;
+        Ok(u64::from_le_bytes(entry))
+    }
+}
+
+/// CRC32C of `len` bytes at `offset`, with the checksum field at 4..8 zeroed
+async fn structure_checksum<T: Read + Seek>(
+    io: &mut T,
+    offset: u64,
+    len: usize,
+) -> Result<u32, ImageError<T::Error>> {
+    let mut digest = CRC32C.digest();
+    let mut chunk = [0u8; SECTOR_SIZE];
+    for start in (0..len).step_by(SECTOR_SIZE) {
+        read_exact_at(io, offset + start as u64, &mut chunk).await?;
+        if start == 0 {
+            chunk[4..8].fill(0);
+        }
+        digest.update(&chunk);
+    }
+    Ok(digest.finalize())
+}
+
+/// Derive a fresh GUID from the previous one and the new sequence number
+///
+/// There is no entropy source in `no_std`; the GUIDs only have to differ from
+/// the previous values so other tools notice the file and data changed.
+fn next_guid(previous: &[u8], sequence: u64) -> [u8; 16] {
+    let mut guid = [0u8; 16];
+    for (i, word) in guid.chunks_exact_mut(4).enumerate() {
+        let mut digest = CRC32C.digest();
+        digest.update(previous);
+        digest.update(&sequence.to_le_bytes());
+        digest.update(&[i as u8]);
+        word.copy_from_slice(&digest.finalize().to_le_bytes());
+    }
+    // Version 4 (random) GUID layout
+    guid[7] = (guid[7] & 0x0F) | 0x40;
+    guid[8] = (guid[8] & 0x3F) | 0x80;
+    guid
+}
+
+impl<T: Read + Write + Seek> VhdxDevice<T> {
+    /// Open a VHDX image, selecting the current header and reading the
+    /// region table and metadata
+    pub async fn open(mut io: T) -> Result<Self, ImageError<T::Error>> {
+        let mut signature = [0u8; 8];
+        read_exact_at(&mut io, 0, &mut signature).await?;
+        if &signature != FILE_SIGNATURE {
+            return Err(ImageError::Corrupt("missing VHDX file identifier"));
+        }
+
+        let (current_header, header) = Self::read_current_header(&mut io).await?;
+        if header[48..64].iter().any(|&b| b != 0) {
+            return Err(ImageError::Unsupported("VHDX log replay"));
+        }
+        if le_u16(&header, 66) != 1 {
+            return Err(ImageError::Unsupported("VHDX version"));
+        }
+
+        let (bat_offset, bat_length, metadata_offset) = Self::read_region_table(&mut io).await?;
+        let (block_size, has_parent, size, logical_sector_size) =
+            Self::read_metadata(&mut io, metadata_offset).await?;
+        if has_parent {
+            return Err(ImageError::Unsupported("differencing VHDX"));
+        }
+
+        let chunk_ratio = (1u64 << 23) * u64::from(logical_sector_size) / u64::from(block_size);
+        let data_blocks = size.div_ceil(u64::from(block_size));
+        let bat_entries = data_blocks + (data_blocks - 1) / chunk_ratio;
+        if bat_entries * 8 > u64::from(bat_length) {
+            return Err(ImageError::Corrupt("VHDX BAT smaller than disk"));
+        }
+
+        Ok(Self {
+            io: RefCell::new(io),
+            header,
+            current_header,
+            header_updated: false,
+            bat: Bat {
+                offset: bat_offset,
+                entries: bat_entries,
+                chunk_ratio,
+            },
+            block_size,
+            logical_sector_size,
+            size,
+        })
+    }
+
+    /// Pick the valid header with the highest sequence number
+    async fn read_current_header(
+        io: &mut T,
+    ) -> Result<(usize, [u8; HEADER_FIELDS]), ImageError<T::Error>> {
+        let mut current: Option<(usize, [u8; HEADER_FIELDS])> = None;
+        for (index, &offset) in HEADER_OFFSETS.iter().enumerate() {
+            let mut header = [0u8; HEADER_FIELDS];
+            read_exact_at(io, offset, &mut header).await?;
+            if &header[..4] != HEADER_SIGNATURE
+                || le_u32(&header, 4) != structure_checksum(io, offset, HEADER_SIZE).await?
+            {
+                continue;
+            }
+            if current.is_none_or(|(_, h)| le_u64(&header, 8) > le_u64(&h, 8)) {
+                current = Some((index, header));
+            }
+        }
+        current.ok_or(ImageError::Corrupt("no valid VHDX header"))
+    }
+
+    /// Find the BAT (offset, length) and metadata offset in the region table
+    async fn read_region_table(io: &mut T) -> Result<(u64, u32, u64), ImageError<T::Error>> {
+        for &offset in &REGION_TABLE_OFFSETS {
+            let mut header = [0u8; 16];
+            read_exact_at(io, offset, &mut header).await?;
+            if &header[..4] != REGION_SIGNATURE
+                || le_u32(&header, 4) != structure_checksum(io, offset, REGION_TABLE_SIZE).await?
+            {
+                continue;
+            }
+            let count = le_u32(&header, 8);
+            if count > 2047 {
+                return Err(ImageError::Corrupt("VHDX region table entry count"));
+            }
+
+            let mut bat = None;
+            let mut metadata = None;
+            for i in 0..u64::from(count) {
+                let mut entry = [0u8; 32];
+                read_exact_at(io, offset + 16 + i * 32, &mut entry).await?;
+                let region = (le_u64(&entry, 16), le_u32(&entry, 24));
+                if entry[..16] == BAT_REGION {
+                    bat = Some(region);
+                } else if entry[..16] == METADATA_REGION {
+                    metadata = Some(region.0);
+                } else if le_u32(&entry, 28) & 1 != 0 {
+                    return Err(ImageError::Unsupported("required VHDX region"));
+                }
+            }
+            return match (bat, metadata) {
+                (Some((bat_offset, bat_length)), Some(metadata_offset)) => {
+                    Ok((bat_offset, bat_length, metadata_offset))
+                }
+                _ => Err(ImageError::Corrupt(
+                    "VHDX region table lacks BAT or metadata",
+                )),
+            };
+        }
+        Err(ImageError::Corrupt("no valid VHDX region table"))
+    }
+
+    /// Read (block size, has parent, virtual size, logical sector size)
+    async fn read_metadata(
+        io: &mut T,
+        offset: u64,
+    ) -> Result<(u32, bool, u64, u32), ImageError<T::Error>> {
+        let mut header = [0u8; 32];
+        read_exact_at(io, offset, &mut header).await?;
+        if &header[..8] != METADATA_SIGNATURE {
+            return Err(ImageError::Corrupt("missing VHDX metadata table"));
+        }
+
+        let mut parameters = None;
+        let mut size = None;
+        let mut logical_sector_size = None;
+        for i in 0..u64::from(le_u16(&header, 10)) {
+            let mut entry = [0u8; 32];
+            read_exact_at(io, offset + 32 + i * 32, &mut entry).await?;
+            let item = offset + u64::from(le_u32(&entry, 16));
+            let mut value = [0u8; 8];
+            if entry[..16] == FILE_PARAMETERS {
+                read_exact_at(io, item, &mut value).await?;
+                parameters = Some((le_u32(&value, 0), le_u32(&value, 4) & 2 != 0));
+            } else if entry[..16] == VIRTUAL_DISK_SIZE {
+                read_exact_at(io, item, &mut value).await?;
+                size = Some(le_u64(&value, 0));
+            } else if entry[..16] == LOGICAL_SECTOR_SIZE {
+                read_exact_at(io, item, &mut value[..4]).await?;
+                logical_sector_size = Some(le_u32(&value, 0));
+            } else if le_u32(&entry, 24) & 4 != 0 && !Self::is_known_item(&entry[..16]) {
+                return Err(ImageError::Unsupported("required VHDX metadata item"));
+            }
+        }
+
+        let (Some((block_size, has_parent)), Some(size), Some(logical_sector_size)) =
+            (parameters, size, logical_sector_size)
+        else {
+            return Err(ImageError::Corrupt("incomplete VHDX metadata"));
+        };
+        if !block_size.is_power_of_two() || !(MB as u32..=256 * MB as u32).contains(&block_size) {
+            return Err(ImageError::Corrupt("invalid VHDX block size"));
+        }
+        if logical_sector_size != 512 && logical_sector_size != 4096 {
+            return Err(ImageError::Corrupt("invalid VHDX logical sector size"));
+        }
+        if size == 0 || size % u64::from(logical_sector_size) != 0 {
+            return Err(ImageError::Corrupt("invalid VHDX disk size"));
+        }
+        Ok((block_size, has_parent, size, logical_sector_size))
+    }
+
+    /// Required items we can safely ignore: physical sector size and page 83 data
+    fn is_known_item(id: &[u8]) -> bool {
+        const PHYSICAL_SECTOR_SIZE: [u8; 16] = [
+            0xC7, 0x48, 0xA3, 0xCD, 0x5D, 0x44, 0x71, 0x44, 0x9C, 0xC9, 0xE9, 0x88, 0x52, 0x51,
+            0xC5, 0x56,
+        ];
+        const PAGE_83_DATA: [u8; 16] = [
+            0xAB, 0x12, 0xCA, 0xBE, 0xE6, 0xB2, 0x23, 0x45, 0x93, 0xEF, 0xC3, 0x09, 0xE0, 0x00,
+            0xC7, 0x46,
+        ];
+        id == PHYSICAL_SECTOR_SIZE || id == PAGE_83_DATA
+    }
+
+    /// Get the virtual disk size in bytes
+    pub fn virtual_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the payload block size in bytes
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Get the logical sector size reported to the guest
+    pub fn logical_sector_size(&self) -> u32 {
+        self.logical_sector_size
+    }
+
+    /// Consume the device and return the inner stream
+    pub fn into_inner(self) -> T {
+        self.io.into_inner()
+    }
+
+    /// Write a new header with fresh write GUIDs into the non-current slot
+    ///
+    /// Required before the first modification of the file in a session.
+    async fn update_header(&mut self) -> Result<(), ImageError<T::Error>> {
+        let sequence = le_u64(&self.header, 8) + 1;
+        let mut header = self.header;
+        header[4..8].fill(0);
+        header[8..16].copy_from_slice(&sequence.to_le_bytes());
+        let file_guid = next_guid(&self.header[16..32], sequence);
+        let data_guid = next_guid(&self.header[32..48], sequence);
+        header[16..32].copy_from_slice(&file_guid);
+        header[32..48].copy_from_slice(&data_guid);
+
+        let mut digest = CRC32C.digest();
+        digest.update(&header);
+        digest.update(&[0u8; HEADER_SIZE - HEADER_FIELDS]);
+        header[4..8].copy_from_slice(&digest.finalize().to_le_bytes());
+
+        let target = 1 - self.current_header;
+        let io = self.io.get_mut();
+        write_all_at(
+            io,
+            HEADER_OFFSETS[target] + HEADER_FIELDS as u64,
+            &[0u8; HEADER_SIZE - HEADER_FIELDS],
+        )
+        .await?;
+        write_all_at(io, HEADER_OFFSETS[target], &header).await?;
+        io.flush().await?;
+
+        self.header = header;
+        self.current_header = target;
+        self.header_updated = true;
+        Ok(())
+    }
+
+    /// Offset of the payload block `block`, which a write must not need to allocate
+    async fn present_block(&mut self, block: u64) -> Result<u64, ImageError<T::Error>> {
+        let entry = self.bat.entry(self.io.get_mut(), block).await?;
+        match entry & BAT_STATE_MASK {
+            PAYLOAD_FULLY_PRESENT => Ok(entry >> 20 << 20),
+            PAYLOAD_PARTIALLY_PRESENT => Err(ImageError::Unsupported("differencing VHDX")),
+            // Mapping a block means updating the BAT through the log
+            _ => Err(ImageError::ReadOnly),
+        }
+    }
+}
+
+impl<T: Read + Write + Seek> BlockDevice<SECTOR_SIZE> for VhdxDevice<T> {
+    type Error = ImageError<T::Error>;
+    type Align = A4;
+
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let mut offset = check_bounds(self.size, block_address, data.len())?;
+        let mut io = self.io.borrow_mut();
+        let block_size = u64::from(self.block_size);
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            let within = offset % block_size;
+            let count = remaining
+                .len()
+                .min(((block_size - within) / SECTOR_SIZE as u64) as usize);
+            let (chunk, rest) = remaining.split_at_mut(count);
+
+            let entry = self.bat.entry(&mut *io, offset / block_size).await?;
+            match entry & BAT_STATE_MASK {
+                PAYLOAD_FULLY_PRESENT => {
+                    read_sectors(&mut *io, (entry >> 20 << 20) + within, chunk).await?;
+                }
+                PAYLOAD_PARTIALLY_PRESENT => {
+                    return Err(ImageError::Unsupported("differencing VHDX"));
+                }
+                // Not present, undefined, zero and unmapped all read as zeros
+                _ => chunk.iter_mut().for_each(|s| s.fill(0)),
+            }
+            offset += (count * SECTOR_SIZE) as u64;
+            remaining = rest;
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SECTOR_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let mut offset = check_bounds(self.size, block_address, data.len())?;
+        let block_size = u64::from(self.block_size);
+        // Refuse the whole write up front rather than after part of it landed
+        let last = (offset + (data.len() * SECTOR_SIZE) as u64 - 1) / block_size;
+        for block in offset / block_size..=last {
+            self.present_block(block).await?;
+        }
+        if !self.header_updated {
+            self.update_header().await?;
+        }
+
+        let mut remaining = data;
+        while !remaining.is_empty() {
+            let block = offset / block_size;
+            let within = offset % block_size;
+            let count = remaining
+                .len()
+                .min(((block_size - within) / SECTOR_SIZE as u64) as usize);
+            let (chunk, rest) = remaining.split_at(count);
+
+            let block_offset = self.present_block(block).await?;
+            write_sectors(self.io.get_mut(), block_offset + within, chunk).await?;
+            offset += (count * SECTOR_SIZE) as u64;
+            remaining = rest;
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.size)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        self.io.get_mut().flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::test_util::{MemStream, block_on};
+    use std::vec;
+    use std::vec::Vec;
+
+    const METADATA_OFFSET: usize = MB as usize;
+    const BAT_OFFSET: usize = 2 * MB as usize;
+    const PAYLOAD_OFFSET: usize = 3 * MB as usize;
+
+    fn put(image: &mut [u8], at: usize, bytes: &[u8]) {
+        image[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn seal(image: &mut [u8], offset: usize, len: usize) {
+        image[offset + 4..offset + 8].fill(0);
+        let crc = CRC32C.checksum(&image[offset..offset + len]);
+        put(image, offset + 4, &crc.to_le_bytes());
+    }
+
+    /// Image with an empty BAT at 2 MiB and room for one payload block at 3 MiB
+    fn image(block_size: u32, size: u64, pending_log: bool) -> Vec<u8> {
+        let mut image = vec![0u8; 4 * MB as usize];
+        put(&mut image, 0, FILE_SIGNATURE);
+
+        for (sequence, &offset) in HEADER_OFFSETS.iter().enumerate() {
+            let offset = offset as usize;
+            put(&mut image, offset, HEADER_SIGNATURE);
+            put(&mut image, offset + 8, &(sequence as u64 + 1).to_le_bytes());
+            image[offset + 16] = 0x10;
+            image[offset + 32] = 0x20;
+            if pending_log {
+                image[offset + 48] = 0x30;
+            }
+            put(&mut image, offset + 66, &1u16.to_le_bytes());
+            seal(&mut image, offset, HEADER_SIZE);
+        }
+
+        for &offset in &REGION_TABLE_OFFSETS {
+            let offset = offset as usize;
+            put(&mut image, offset, REGION_SIGNATURE);
+            put(&mut image, offset + 8, &2u32.to_le_bytes());
+            let regions = [(BAT_REGION, BAT_OFFSET), (METADATA_REGION, METADATA_OFFSET)];
+            for (i, (id, region)) in regions.iter().enumerate() {
+                let entry = offset + 16 + i * 32;
+                put(&mut image, entry, id);
+                put(&mut image, entry + 16, &(*region as u64).to_le_bytes());
+                put(&mut image, entry + 24, &(MB as u32).to_le_bytes());
+                put(&mut image, entry + 28, &1u32.to_le_bytes());
+            }
+            seal(&mut image, offset, REGION_TABLE_SIZE);
+        }
+
+        let items = [
+            (FILE_PARAMETERS, 8u32),
+            (VIRTUAL_DISK_SIZE, 8),
+            (LOGICAL_SECTOR_SIZE, 4),
+        ];
+        put(&mut image, METADATA_OFFSET, METADATA_SIGNATURE);
+        put(
+            &mut image,
+            METADATA_OFFSET + 10,
+            &(items.len() as u16).to_le_bytes(),
+        );
+        for (i, (id, len)) in items.iter().enumerate() {
+            let entry = METADATA_OFFSET + 32 + i * 32;
+            put(&mut image, entry, id);
+            put(
+                &mut image,
+                entry + 16,
+                &(0x1_0000 + i as u32 * 8).to_le_bytes(),
+            );
+            put(&mut image, entry + 20, &len.to_le_bytes());
+            put(&mut image, entry + 24, &6u32.to_le_bytes());
+        }
+        put(
+            &mut image,
+            METADATA_OFFSET + 0x1_0000,
+            &block_size.to_le_bytes(),
+        );
+        put(&mut image, METADATA_OFFSET + 0x1_0008, &size.to_le_bytes());
+        put(
+            &mut image,
+            METADATA_OFFSET + 0x1_0010,
+            &512u32.to_le_bytes(),
+        );
+        image
+    }
+
+    fn sectors<const N: usize>(fill: u8) -> [Aligned<A4, [u8; SECTOR_SIZE]>; N] {
+        [Aligned([fill; SECTOR_SIZE]); N]
+    }
+
+    #[test]
+    fn test_bat_skips_sector_bitmap_entries() {
+        block_on(async {
+            // 256 MiB blocks with 512-byte sectors: one bitmap entry per 16 blocks
+            let block_size = 256 * MB as u32;
+            let mut image = image(block_size, 17 * u64::from(block_size), false);
+            // Block 16 follows the bitmap entry, so it lives in BAT slot 17
+            let entry = PAYLOAD_OFFSET as u64 | PAYLOAD_FULLY_PRESENT;
+            put(&mut image, BAT_OFFSET + 17 * 8, &entry.to_le_bytes());
+            image[PAYLOAD_OFFSET..PAYLOAD_OFFSET + SECTOR_SIZE].fill(0x5A);
+
+            let device = VhdxDevice::open(MemStream::new(image)).await.unwrap();
+            assert_eq!(device.bat.chunk_ratio, 16);
+            assert_eq!(device.bat.entries, 18);
+
+            let sectors_per_block = block_size / SECTOR_SIZE as u32;
+            let mut buf = sectors::<1>(1);
+            device.read(16 * sectors_per_block, &mut buf).await.unwrap();
+            assert!(buf[0].iter().all(|&b| b == 0x5A));
+            device.read(15 * sectors_per_block, &mut buf).await.unwrap();
+            assert!(buf[0].iter().all(|&b| b == 0));
+            assert_eq!(
+                device.read(17 * sectors_per_block, &mut buf).await,
+                Err(ImageError::OutOfBounds)
+            );
+        });
+    }
+
+    #[test]
+    fn test_write_to_present_block_and_reopen() {
+        block_on(async {
+            let mut image = image(MB as u32, 4 * MB, false);
+            let entry = PAYLOAD_OFFSET as u64 | PAYLOAD_FULLY_PRESENT;
+            put(&mut image, BAT_OFFSET + 8, &entry.to_le_bytes());
+            let mut device = VhdxDevice::open(MemStream::new(image)).await.unwrap();
+            assert_eq!(device.current_header, 1);
+
+            device
+                .write(2048, &[Aligned([0x11; 512]), Aligned([0x22; 512])])
+                .await
+                .unwrap();
+            let data = device.into_inner().data;
+            assert_eq!(data.len(), 4 * MB as usize);
+            assert_eq!(data[PAYLOAD_OFFSET + SECTOR_SIZE], 0x22);
+
+            // The older header slot now holds sequence 3 with fresh GUIDs
+            let header = &data[HEADER_OFFSETS[0] as usize..][..HEADER_SIZE];
+            assert_eq!(le_u64(header, 8), 3);
+            assert_ne!(
+                header[16..32],
+                data[HEADER_OFFSETS[1] as usize + 16..][..16]
+            );
+
+            let device = VhdxDevice::open(MemStream::new(data)).await.unwrap();
+            assert_eq!(device.current_header, 0);
+            let mut buf = sectors::<3>(1);
+            device.read(2047, &mut buf).await.unwrap();
+            assert_eq!((buf[0][0], buf[1][0], buf[2][0]), (0, 0x11, 0x22));
+        });
+    }
+
+    #[test]
+    fn test_write_needing_allocation_is_refused() {
+        block_on(async {
+            let mut image = image(MB as u32, 4 * MB, false);
+            let entry = PAYLOAD_OFFSET as u64 | PAYLOAD_FULLY_PRESENT;
+            put(&mut image, BAT_OFFSET + 8, &entry.to_le_bytes());
+            let original = image.clone();
+            let mut device = VhdxDevice::open(MemStream::new(image)).await.unwrap();
+
+            // Sectors 2047 and 2048 straddle unallocated block 0 and present block 1
+            assert_eq!(
+                device
+                    .write(2047, &[Aligned([0x11; 512]), Aligned([0x22; 512])])
+                    .await,
+                Err(ImageError::ReadOnly)
+            );
+            assert!(device.into_inner().data == original);
+        });
+    }
+
+    #[test]
+    fn test_pending_log_is_refused() {
+        block_on(async {
+            let image = image(MB as u32, 4 * MB, true);
+            assert!(matches!(
+                VhdxDevice::open(MemStream::new(image)).await,
+                Err(ImageError::Unsupported("VHDX log replay"))
+            ));
+        });
+    }
+}
@@ -9,7 +9,7 @@ use clap::{Parser, Subcommand, ValueEnum};
 use embedded_io_adapters::tokio_1::FromTokio;
 use fatrs::{FatType, FormatVolumeOptions, FsOptions};
 use fatrs_adapters::{HeapPageStream, presets};
-use fatrs_block_platform::StreamBlockDevice;
+use fatrs_block_platform::{DiskImage, StreamBlockDevice};
 use fatrs_cli::path_parser::{PathSpec, parse_copy_operation};
 use log::{info, debug};
 
@@ -243,6 +243,20 @@ pub enum Command {
     AuditLog {
         /// Path to FAT filesystem image
         image: PathBuf,
+
+        /// Verify the tamper-evident hash chain instead of listing entries
+        #[arg(long)]
+        verify: bool,
+
+        /// HMAC-SHA-256 device key as 64 hex digits (for HMAC-protected logs)
+        #[arg(long, requires = "verify")]
+        hmac_key: Option<String>,
+    },
+
+    /// Read every file in a FAT image and report I/O, cache and latency metrics
+    Metrics {
+        /// Path to FAT filesystem image
+        image: PathBuf,
     },
 
     /// Work with physical flash drives (Windows only)
@@ -326,6 +340,19 @@ fn parse_size(s: &str) -> Result<u64> {
     Ok(num * multiplier)
 }
 
+/// Parse a 256-bit key given as 64 hex digits
+fn parse_hex_key(s: &str) -> Result<[u8; 32]> {
+    let s = s.trim();
+    if s.len() != 64 || !s.is_ascii() {
+        anyhow::bail!("Key must be exactly 64 hex digits");
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).context("Invalid hex digit in key")?;
+    }
+    Ok(key)
+}
+
 /// Format file size for display
 fn format_size(size: u64) -> String {
     if size < 1024 {
@@ -346,6 +373,9 @@ fn effective_page_size(page_size: PageSize) -> usize {
     //page_size.to_bytes().unwrap_or(presets::PAGE_4K)
 }
 
+/// Page-buffered stream over an image file in any supported format
+type ImageStream = HeapPageStream<DiskImage<FromTokio<tokio::fs::File>>, 512>;
+
 /// Open a FAT filesystem image with large page buffering
 async fn open_fs_buffered(
     image: &Path,
@@ -353,7 +383,26 @@ async fn open_fs_buffered(
     page_size: usize,
 ) -> Result<(
     fatrs::FileSystem<
-        HeapPageStream<StreamBlockDevice<FromTokio<tokio::fs::File>>, 512>,
+        ImageStream,
+        fatrs::DefaultTimeProvider,
+        fatrs::LossyOemCpConverter,
+    >,
+    usize,
+)> {
+    open_fs_buffered_with_options(image, writable, page_size, FsOptions::new()).await
+}
+
+/// Open a FAT filesystem image with large page buffering and custom mount options
+///
+/// The image format (raw, VHD, VHDX or qcow2) is detected from its magic numbers.
+async fn open_fs_buffered_with_options(
+    image: &Path,
+    writable: bool,
+    page_size: usize,
+    options: FsOptions<fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter>,
+) -> Result<(
+    fatrs::FileSystem<
+        ImageStream,
         fatrs::DefaultTimeProvider,
         fatrs::LossyOemCpConverter,
     >,
@@ -366,11 +415,14 @@ async fn open_fs_buffered(
         .await
         .with_context(|| format!("Failed to open image: {}", image.display()))?;
 
-    let block_dev = StreamBlockDevice(FromTokio::new(file));
+    let block_dev = DiskImage::open(FromTokio::new(file))
+        .await
+        .with_context(|| format!("Failed to open disk image: {}", image.display()))?;
+    debug!("Detected {:?} image: {}", block_dev.format(), image.display());
     let stream = HeapPageStream::new(block_dev, page_size)
         .map_err(|e| anyhow::anyhow!("Failed to create page stream: {:?}", e))?;
 
-    let fs = fatrs::FileSystem::new(stream, FsOptions::new())
+    let fs = fatrs::FileSystem::new(stream, options)
         .await
         .context("Failed to mount FAT filesystem")?;
 
@@ -476,7 +528,18 @@ pub async fn run(cli: Cli) -> Result<()> {
         Command::Extract { image, dest } => cmd_extract(&image, &dest, page_size).await,
         #[cfg(feature = "transaction-safe")]
         Command::TxLog { image } => cmd_txlog(&image, page_size).await,
-        Command::AuditLog { image } => cmd_auditlog(&image, page_size).await,
+        Command::AuditLog {
+            image,
+            verify,
+            hmac_key,
+        } => {
+            if verify {
+                cmd_auditlog_verify(&image, hmac_key.as_deref(), page_size).await
+            } else {
+                cmd_auditlog(&image, page_size).await
+            }
+        }
+        Command::Metrics { image } => cmd_metrics(&image, page_size).await,
         #[cfg(windows)]
         Command::Flash { command } => cmd_flash(command, page_size).await,
     }
@@ -1438,29 +1501,187 @@ async fn cmd_auditlog(image: &Path, page_size: usize) -> Result<()> {
     println!("Audit Log");
     println!("=========\n");
 
-    // Get audit log entries
-    let entries = fs.audit_entries().await;
+    // Read the persisted audit log (pending entries are flushed first)
+    let mut history = fs.audit_history().await?;
 
-    if entries.is_empty() {
-        println!("No audit entries in buffer.");
+    if history.remaining() == 0 {
+        println!("No audit entries recorded.");
     } else {
-        println!("{} entries in buffer:\n", entries.len());
+        println!("{} entries recorded:\n", history.remaining());
 
-        for entry in entries {
-            print_audit_entry(&entry);
+        while let Some(entry) = history.next().await {
+            print_audit_entry(&entry?);
         }
     }
 
     Ok(())
 }
 
+async fn cmd_auditlog_verify(image: &Path, hmac_key: Option<&str>, page_size: usize) -> Result<()> {
+    info!("Opening image: {}", image.display());
+
+    let integrity = match hmac_key {
+        Some(key) => fatrs::AuditIntegrity::HmacSha256(parse_hex_key(key)?),
+        None => fatrs::AuditIntegrity::Sha256,
+    };
+    let options = FsOptions::new().with_audit_log(fatrs::AuditConfig::new().integrity(integrity));
+    let (fs, _) = open_fs_buffered_with_options(image, false, page_size, options).await?;
+
+    println!("Audit Chain Verification");
+    println!("========================\n");
+
+    let report = fs.verify_audit_chain().await?;
+
+    match (report.first_sequence, report.last_sequence) {
+        (Some(first), Some(last)) => println!("Records:          #{} - #{}", first, last),
+        _ => println!("Records:          none"),
+    }
+    println!("Verified links:   {}", report.verified);
+    if report.unprotected > 0 {
+        println!("Unprotected:      {} (written before chaining was enabled)", report.unprotected);
+    }
+    if report.truncated {
+        println!(
+            "Note:             oldest records were overwritten; chain anchored at #{}",
+            report.first_sequence.unwrap_or_default()
+        );
+    }
+    println!();
+
+    match report.broken {
+        None => {
+            println!("✓ Audit chain intact");
+            Ok(())
+        }
+        Some(broken) => {
+            let reason = match broken.fault {
+                fatrs::AuditChainFault::Missing => "record missing or corrupted",
+                fatrs::AuditChainFault::Unchained => "record has no chain link",
+                fatrs::AuditChainFault::LinkMismatch => "link does not match (record altered or wrong key)",
+                fatrs::AuditChainFault::KeyRequired => "record is HMAC-protected, pass --hmac-key",
+            };
+            println!("✗ Audit chain broken at record #{}: {}", broken.sequence, reason);
+            anyhow::bail!("audit chain verification failed")
+        }
+    }
+}
+
+fn metrics_clock() -> u64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
+
+async fn cmd_metrics(image: &Path, page_size: usize) -> Result<()> {
+    info!("Opening image: {}", image.display());
+
+    let (fs, _) = open_fs_buffered(image, false, page_size).await?;
+
+    // Only measure the scan itself, not the mount
+    fs.reset_metrics().await;
+    fs.set_metrics_clock(Some(metrics_clock)).await;
+
+    let root = fs.root_dir();
+    let files = read_tree(&root, "").await?;
+    let metrics = fs.metrics().await;
+
+    println!("Metrics ({} files read)", files);
+    println!("=======\n");
+
+    let ops = &metrics.operations;
+    println!("Operations:");
+    println!("  opens: {}  creates: {}  dir creates: {}", ops.opens, ops.creates, ops.dir_creates);
+    println!("  removes: {}  renames: {}  truncates: {}", ops.removes, ops.renames, ops.truncates);
+    println!("  reads: {}  writes: {}  seeks: {}  flushes: {}", ops.reads, ops.writes, ops.seeks, ops.flushes);
+    println!();
+
+    // Counted on the page stream above the image file, not on the image itself
+    let stream = &metrics.stream;
+    println!("Bytes:");
+    println!("  user read:     {}", format_size(metrics.user_bytes_read));
+    println!("  user written:  {}", format_size(metrics.user_bytes_written));
+    println!("  stream read:   {}", format_size(stream.bytes_read));
+    println!("  stream written: {}", format_size(stream.bytes_written));
+    if let Some(amplification) = metrics.read_amplification() {
+        println!("  stream/user read:    {:.2}x", amplification);
+    }
+    if let Some(amplification) = metrics.write_amplification() {
+        println!("  stream/user written: {:.2}x", amplification);
+    }
+    println!();
+
+    println!("Stream:");
+    println!("  reads: {}  writes: {}  seeks: {}  flushes: {}", stream.reads, stream.writes, stream.seeks, stream.flushes);
+    print_latency("read", &stream.read_latency);
+    print_latency("write", &stream.write_latency);
+    print_latency("flush", &stream.flush_latency);
+
+    Ok(())
+}
+
+fn print_latency(name: &str, histogram: &fatrs::LatencyHistogram) {
+    if let (Some(p50), Some(p99)) = (histogram.percentile_us(50), histogram.percentile_us(99)) {
+        println!(
+            "  {} latency: p50 < {} us, p99 < {} us ({} samples)",
+            name,
+            p50,
+            p99,
+            histogram.count()
+        );
+    }
+}
+
+/// Read every file below `path`, returning the number of files read
+async fn read_tree<IO: fatrs::ReadWriteSeek>(
+    root: &fatrs::Dir<'_, IO, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter>,
+    path: &str,
+) -> Result<usize>
+where
+    IO::Error: std::error::Error + Send + Sync + 'static,
+{
+    use embedded_io_async::Read;
+
+    let dir = if path.is_empty() { root.clone() } else { root.open_dir(path).await? };
+    let mut entries = Vec::new();
+    let mut iter = dir.iter();
+    while let Some(entry) = iter.next().await {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.as_str() == "." || name.as_str() == ".." {
+            continue;
+        }
+        entries.push((name.to_string(), entry.is_dir()));
+    }
+    drop(iter);
+
+    let mut files = 0;
+    let mut buffer = vec![0u8; 64 * 1024];
+    for (name, is_dir) in entries {
+        let child_path = if path.is_empty() { name } else { format!("{}/{}", path, name) };
+        if is_dir {
+            files += Box::pin(read_tree(root, &child_path)).await?;
+        } else {
+            let mut file = root.open_file(&child_path).await?;
+            while file.read(&mut buffer).await? > 0 {}
+            files += 1;
+        }
+    }
+    Ok(files)
+}
+
 fn print_audit_entry(entry: &fatrs::AuditEntry) {
     use chrono::{DateTime, Utc};
 
-    // Convert timestamp to datetime
-    let datetime = DateTime::<Utc>::from_timestamp_millis(entry.timestamp as i64)
-        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
-        .unwrap_or_else(|| format!("{} ms", entry.timestamp));
+    // Convert timestamp to datetime (entries logged without a clock carry a counter)
+    let datetime = if entry.has_wall_clock_time() {
+        DateTime::<Utc>::from_timestamp_millis(entry.timestamp as i64)
+            .map(|dt| format!("{} UTC", dt.format("%Y-%m-%d %H:%M:%S%.3f")))
+            .unwrap_or_else(|| format!("{} ms", entry.timestamp))
+    } else {
+        format!("#{} (no clock)", entry.timestamp)
+    };
 
     // Format operation
     let op_str = format!("{:?}", entry.operation);
@@ -1471,7 +1692,7 @@ fn print_audit_entry(entry: &fatrs::AuditEntry) {
         fatrs::AuditResult::Error => "✗ Error",
     };
 
-    println!("{} UTC | {} | {}", datetime, result_str, op_str);
+    println!("{} | {} | {}", datetime, result_str, op_str);
     println!("  Path: {}", entry.get_path());
 
     if let Some(path2) = entry.get_path2() {
@@ -1482,5 +1703,12 @@ fn print_audit_entry(entry: &fatrs::AuditEntry) {
         println!("  Data: {} bytes", entry.data);
     }
 
+    if !entry.context.is_empty() {
+        match entry.context.tag() {
+            Some(tag) => println!("  Context: task {} ({})", entry.context.task_id, tag),
+            None => println!("  Context: task {}", entry.context.task_id),
+        }
+    }
+
     println!();
 }
@@ -2,14 +2,15 @@
 
 use embedded_io_async::{ErrorType, Read, Seek, SeekFrom, Write};
 
-#[cfg(windows)]
 use fatrs_adapters::HeapPageStream;
+use fatrs_block_platform::DiskImage;
 #[cfg(windows)]
 use fatrs_block_platform::StreamBlockDevice;
 
-/// Unified IO type that can be either a file or a Windows device
+/// Unified IO type that can be either an image file or a Windows device
 pub enum UnifiedIO {
-    File(embedded_io_adapters::tokio_1::FromTokio<tokio::fs::File>),
+    /// Raw, VHD, VHDX or qcow2 image file
+    File(HeapPageStream<DiskImage<embedded_io_adapters::tokio_1::FromTokio<tokio::fs::File>>, 512>),
     #[cfg(windows)]
     Device(HeapPageStream<StreamBlockDevice<fatrs_cli::AsyncWindowsDevice>, 512>),
 }
@@ -21,7 +22,10 @@ impl ErrorType for UnifiedIO {
 impl Read for UnifiedIO {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         match self {
-            UnifiedIO::File(f) => f.read(buf).await,
+            UnifiedIO::File(f) => f
+                .read(buf)
+                .await
+                .map_err(|e| std::io::Error::other(format!("{:?}", e))),
             #[cfg(windows)]
             UnifiedIO::Device(d) => d
                 .read(buf)
@@ -34,7 +38,10 @@ impl Read for UnifiedIO {
 impl Write for UnifiedIO {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         match self {
-            UnifiedIO::File(f) => f.write(buf).await,
+            UnifiedIO::File(f) => f
+                .write(buf)
+                .await
+                .map_err(|e| std::io::Error::other(format!("{:?}", e))),
             #[cfg(windows)]
             UnifiedIO::Device(d) => d
                 .write(buf)
@@ -45,7 +52,10 @@ impl Write for UnifiedIO {
 
     async fn flush(&mut self) -> Result<(), Self::Error> {
         match self {
-            UnifiedIO::File(f) => f.flush().await,
+            UnifiedIO::File(f) => f
+                .flush()
+                .await
+                .map_err(|e| std::io::Error::other(format!("{:?}", e))),
             #[cfg(windows)]
             UnifiedIO::Device(d) => d
                 .flush()
@@ -58,7 +68,10 @@ impl Write for UnifiedIO {
 impl Seek for UnifiedIO {
     async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
         match self {
-            UnifiedIO::File(f) => f.seek(pos).await,
+            UnifiedIO::File(f) => f
+                .seek(pos)
+                .await
+                .map_err(|e| std::io::Error::other(format!("{:?}", e))),
             #[cfg(windows)]
             UnifiedIO::Device(d) => d
                 .seek(pos)
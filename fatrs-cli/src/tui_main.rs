@@ -172,7 +172,14 @@ async fn open_image_file(
             .await
             .with_context(|| format!("Failed to open image: {}", image_path))?;
 
-        let io = UnifiedIO::File(embedded_io_adapters::tokio_1::FromTokio::new(file));
+        let block_dev =
+            fatrs_block_platform::DiskImage::open(embedded_io_adapters::tokio_1::FromTokio::new(file))
+                .await
+                .with_context(|| format!("Failed to open disk image: {}", image_path))?;
+        let stream = fatrs_adapters::HeapPageStream::new(block_dev, 512)
+            .with_context(|| "Failed to create page stream")?;
+
+        let io = UnifiedIO::File(stream);
 
         let fs = fatrs::FileSystem::new(io, fatrs::FsOptions::new())
             .await
@@ -6,49 +6,137 @@
 //! # Architecture
 //!
 //! ## Storage
-//! - Stored as a hidden file `.audit.log` in the root directory
-//! - Uses `postcard` binary serialization for compact storage
-//! - Circular buffer with configurable maximum size
-//! - Automatically rotates when full
+//! - Stored in reserved sectors (see [`AuditConfig`]) used as a circular log
+//! - The area is divided into 32-byte slots; each record starts on a slot boundary
+//! - Every record carries a monotonic sequence number and a CRC-32 of its contents
+//! - When the end of the area is reached, the unused tail is cleared and writing
+//!   wraps around, overwriting the oldest records
+//! - Pending entries are buffered in memory and flushed automatically once
+//!   [`AuditConfig::flush_threshold`] entries are pending (or on `FileSystem::flush`)
+//! - If the buffer overflows (flushing keeps failing), the oldest pending entries
+//!   are discarded; the loss is counted in [`AuditStats::dropped`] and recorded
+//!   on disk as an [`AuditOperation::EntriesDropped`] entry on the next flush
 //!
 //! ## Entry Types
 //! - File operations: open, read, write, truncate, close, delete
 //! - Directory operations: create, delete, list
 //! - Metadata operations: stat, rename, chmod
 //!
-//! ## Format
-//! Each audit entry contains:
-//! - Timestamp (milliseconds since epoch)
-//! - Operation type
-//! - Path(s) involved
-//! - Result (success/error)
-//! - Optional: size, offset, or other operation-specific data
+//! ## Timestamps and Context
+//! Timestamps are milliseconds since the Unix epoch, taken from the filesystem's
+//! `TimeProvider`. Without a usable clock (a provider reporting the DOS epoch, such
+//! as `NullTimeProvider` or an unset RTC) a monotonic counter continuing from the
+//! newest persisted entry is used instead, so entries stay ordered. Timestamps are
+//! strictly increasing in either case.
+//!
+//! The application can attach an [`AuditContext`] (task ID and a short user tag)
+//! that is recorded with every subsequent entry.
+//!
+//! ## Record Format
+//! ```text
+//! offset  size  field
+//! 0       2     magic (0xA0D1)
+//! 2       2     payload length
+//! 4       4     CRC-32 of sequence, payload length and payload
+//! 8       8     sequence number
+//! 16      n     payload: timestamp (u64), operation (u8), result (u8), data (u64),
+//!               path length (u16), path, path2 length (u16), path2,
+//!               task id (u32), tag length (u8), tag
+//! 16+n    33    chain trailer (chained records only): link kind (u8), link (32 bytes)
+//! ```
+//!
+//! ## Tamper Evidence
+//! With the `audit-chain` feature, [`AuditIntegrity`] links every record to its
+//! predecessor: the link is `H(previous link || sequence || payload)`, where `H`
+//! is SHA-256 or HMAC-SHA-256 keyed with a device secret. Chained records use the
+//! magic `0xA0D2`. `FileSystem::verify_audit_chain` reports the first record that
+//! was altered, removed from the middle of the log or stripped of its link after
+//! chained records. With an integrity mode configured it also reports a log whose
+//! newest record is unchained, and oldest records removed where the ring could not
+//! have overwritten them. It cannot detect removal of the newest records, or the
+//! whole area being rolled back to an earlier copy. Records written before chaining
+//! was enabled are counted as unprotected. A plain SHA-256 chain only detects
+//! accidental or naive tampering - anyone with write access can recompute it.
+//! Use a keyed MAC to prevent forgery.
+//!
+//! On mount the area is scanned to find the newest valid record, so new records
+//! continue after it. A record torn by power loss fails its CRC and is skipped.
 //!
 //! # no_std Compatibility
-//! - Uses `postcard` for serialization (no_std compatible)
+//! - Records are encoded into fixed-size stack buffers
 //! - Fixed-size buffers for paths
-//! - Optional alloc feature for unbounded logs
+//! - History iteration requires the `alloc` feature (enabled by `audit-log`)
 
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::must_use_candidate)]
 
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
 use core::fmt::Debug;
 
 #[cfg(feature = "defmt")]
 use defmt;
 
+use crate::error::{Error, ReadExactError};
+use crate::fs::{FileSystem, ReadWriteSeek};
+use crate::io::{Read, Seek, SeekFrom, Write};
+use crate::time::DateTime;
+
 /// Maximum path length in audit entries (stack-allocated)
 const MAX_PATH_LEN: usize = 256;
 
-/// Maximum audit log size in bytes (default: 1MB)
-const DEFAULT_MAX_LOG_SIZE: usize = 1024 * 1024;
+/// Maximum length of the user tag in an [`AuditContext`]
+pub const MAX_AUDIT_TAG_LEN: usize = 16;
+
+/// Smallest wall-clock timestamp (1980-01-01, the DOS epoch) in milliseconds since the Unix epoch
+///
+/// Timestamps below this value come from the monotonic fallback counter.
+pub const WALL_CLOCK_MIN_TIMESTAMP: u64 = 315_532_800_000;
 
 /// Default number of sectors for audit log (8 sectors = 4KB with 512-byte sectors)
 pub const DEFAULT_AUDIT_LOG_SECTORS: u32 = 8;
 
-/// Maximum entries per sector (512 bytes / ~100 bytes per entry ≈ 5 entries)
-/// With 8 sectors, we can store ~40 entries on disk
-const ENTRIES_PER_SECTOR: usize = 5;
+/// Default number of pending entries that triggers an automatic flush
+pub const DEFAULT_FLUSH_THRESHOLD: usize = 8;
+
+/// Capacity of the in-memory pending entry buffer
+const BUFFER_CAPACITY: usize = 16;
+
+/// Size of one slot in the on-disk ring; records are aligned to slot boundaries
+const SLOT_SIZE: usize = 32;
+
+/// Magic number at the start of every on-disk record
+const RECORD_MAGIC: u16 = 0xA0D1;
+
+/// Magic number at the start of records carrying a chain trailer
+const RECORD_MAGIC_CHAINED: u16 = 0xA0D2;
+
+/// Size of the record header (magic, length, CRC, sequence)
+const RECORD_HEADER_SIZE: usize = 16;
+
+/// Size of a chain link hash
+const CHAIN_LINK_SIZE: usize = 32;
+
+/// Size of the chain trailer (link kind and link hash)
+const CHAIN_TRAILER_SIZE: usize = 1 + CHAIN_LINK_SIZE;
+
+/// Largest possible payload: fixed fields plus two full-length paths
+const MAX_PAYLOAD_SIZE: usize =
+    8 + 1 + 1 + 8 + 2 + MAX_PATH_LEN + 2 + MAX_PATH_LEN + 4 + 1 + MAX_AUDIT_TAG_LEN;
+
+/// Largest possible record (header, payload and chain trailer)
+const MAX_RECORD_SIZE: usize = RECORD_HEADER_SIZE + MAX_PAYLOAD_SIZE + CHAIN_TRAILER_SIZE;
+
+/// Convert a time provider reading to milliseconds since the Unix epoch
+///
+/// Returns `None` for the zeroed DOS date and time that providers without a
+/// clock (such as `NullTimeProvider`) report. Real dates in 1980 are kept.
+pub(crate) fn wall_clock_millis(now: DateTime) -> Option<u64> {
+    if now.date.year < 1980 || now == DateTime::decode(0, 0, 0) {
+        return None;
+    }
+    Some(now.to_unix_timestamp() * 1000 + u64::from(now.time.millis))
+}
 
 /// Audit logging level - controls which operations are logged
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -105,9 +193,34 @@ pub enum AuditOperation {
     Stat = 12,
     /// File/directory metadata changed
     MetadataUpdate = 13,
+    /// Pending entries were discarded because the in-memory buffer was full
+    ///
+    /// Written ahead of the next flushed entries; `data` holds the number of lost entries.
+    EntriesDropped = 14,
 }
 
 impl AuditOperation {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(AuditOperation::FileOpenRead),
+            1 => Some(AuditOperation::FileOpenWrite),
+            2 => Some(AuditOperation::FileCreate),
+            3 => Some(AuditOperation::FileRead),
+            4 => Some(AuditOperation::FileWrite),
+            5 => Some(AuditOperation::FileTruncate),
+            6 => Some(AuditOperation::FileDelete),
+            7 => Some(AuditOperation::FileClose),
+            8 => Some(AuditOperation::DirCreate),
+            9 => Some(AuditOperation::DirDelete),
+            10 => Some(AuditOperation::DirList),
+            11 => Some(AuditOperation::Rename),
+            12 => Some(AuditOperation::Stat),
+            13 => Some(AuditOperation::MetadataUpdate),
+            14 => Some(AuditOperation::EntriesDropped),
+            _ => None,
+        }
+    }
+
     /// Check if this operation should be logged at the given audit level
     pub const fn should_log(&self, level: AuditLevel) -> bool {
         match level {
@@ -145,9 +258,68 @@ pub enum AuditResult {
     Error = 1,
 }
 
+impl AuditResult {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(AuditResult::Success),
+            1 => Some(AuditResult::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Application-defined context recorded with audit entries
+///
+/// Set it with `FileSystem::set_audit_context` to make entries attributable to a
+/// task or user.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "audit-log", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditContext {
+    /// Application task or thread identifier (0 if unset)
+    pub task_id: u32,
+    /// User tag bytes
+    tag: [u8; MAX_AUDIT_TAG_LEN],
+    /// Length of valid tag data
+    tag_len: u8,
+}
+
+impl AuditContext {
+    /// Create a context for the given task
+    pub fn new(task_id: u32) -> Self {
+        Self {
+            task_id,
+            ..Self::default()
+        }
+    }
+
+    /// Attach a user tag (truncated to 16 bytes)
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        let bytes = tag.as_bytes();
+        let len = bytes.len().min(MAX_AUDIT_TAG_LEN);
+        self.tag = [0; MAX_AUDIT_TAG_LEN];
+        self.tag[..len].copy_from_slice(&bytes[..len]);
+        self.tag_len = len as u8;
+        self
+    }
+
+    /// Get the user tag as a string
+    pub fn tag(&self) -> Option<&str> {
+        if self.tag_len > 0 {
+            Some(core::str::from_utf8(&self.tag[..self.tag_len as usize]).unwrap_or("<invalid>"))
+        } else {
+            None
+        }
+    }
+
+    /// Check if neither a task ID nor a tag is set
+    pub fn is_empty(&self) -> bool {
+        self.task_id == 0 && self.tag_len == 0
+    }
+}
+
 /// A single audit log entry
 ///
-/// Optimized for size using postcard serialization.
 /// Uses fixed-size arrays for no_std compatibility.
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Clone)]
@@ -171,6 +343,8 @@ pub struct AuditEntry {
     pub path2_len: u16,
     /// Optional size/offset/count parameter
     pub data: u64,
+    /// Application context active when the entry was logged
+    pub context: AuditContext,
 }
 
 impl AuditEntry {
@@ -190,6 +364,7 @@ impl AuditEntry {
             path2: [0; MAX_PATH_LEN],
             path2_len: 0,
             data: 0,
+            context: AuditContext::default(),
         };
         entry.set_path(path);
         entry
@@ -230,6 +405,356 @@ impl AuditEntry {
         self.data = data;
         self
     }
+
+    /// Set the application context
+    pub fn with_context(mut self, context: AuditContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Check if the timestamp comes from a wall clock rather than the fallback counter
+    pub fn has_wall_clock_time(&self) -> bool {
+        self.timestamp >= WALL_CLOCK_MIN_TIMESTAMP
+    }
+
+    /// Encode the entry into the on-disk payload format, returning the payload length
+    fn encode_payload(&self, buf: &mut [u8]) -> usize {
+        let path_len = self.path_len as usize;
+        let path2_len = self.path2_len as usize;
+        let mut pos = 0;
+        let mut put = |bytes: &[u8]| {
+            buf[pos..pos + bytes.len()].copy_from_slice(bytes);
+            pos += bytes.len();
+        };
+        put(&self.timestamp.to_le_bytes());
+        put(&[self.operation as u8, self.result as u8]);
+        put(&self.data.to_le_bytes());
+        put(&self.path_len.to_le_bytes());
+        put(&self.path[..path_len]);
+        put(&self.path2_len.to_le_bytes());
+        put(&self.path2[..path2_len]);
+        put(&self.context.task_id.to_le_bytes());
+        put(&[self.context.tag_len]);
+        put(&self.context.tag[..self.context.tag_len as usize]);
+        pos
+    }
+
+    /// Decode an entry from the on-disk payload format
+    fn decode_payload(payload: &[u8]) -> Option<Self> {
+        fn take<'a>(payload: &'a [u8], pos: &mut usize, len: usize) -> Option<&'a [u8]> {
+            let bytes = payload.get(*pos..*pos + len)?;
+            *pos += len;
+            Some(bytes)
+        }
+        fn take_path(payload: &[u8], pos: &mut usize) -> Option<([u8; MAX_PATH_LEN], u16)> {
+            let len = u16::from_le_bytes(take(payload, pos, 2)?.try_into().ok()?);
+            if len as usize > MAX_PATH_LEN {
+                return None;
+            }
+            let mut path = [0; MAX_PATH_LEN];
+            path[..len as usize].copy_from_slice(take(payload, pos, len as usize)?);
+            Some((path, len))
+        }
+
+        let mut pos = 0;
+        let timestamp = u64::from_le_bytes(take(payload, &mut pos, 8)?.try_into().ok()?);
+        let kind = take(payload, &mut pos, 2)?;
+        let operation = AuditOperation::from_u8(kind[0])?;
+        let result = AuditResult::from_u8(kind[1])?;
+        let data = u64::from_le_bytes(take(payload, &mut pos, 8)?.try_into().ok()?);
+        let (path, path_len) = take_path(payload, &mut pos)?;
+        let (path2, path2_len) = take_path(payload, &mut pos)?;
+        let mut context = AuditContext::default();
+        if pos < payload.len() {
+            context.task_id = u32::from_le_bytes(take(payload, &mut pos, 4)?.try_into().ok()?);
+            let tag_len = take(payload, &mut pos, 1)?[0];
+            if tag_len as usize > MAX_AUDIT_TAG_LEN {
+                return None;
+            }
+            context.tag[..tag_len as usize].copy_from_slice(take(payload, &mut pos, tag_len as usize)?);
+            context.tag_len = tag_len;
+        }
+        Some(Self {
+            timestamp,
+            operation,
+            result,
+            path,
+            path_len,
+            path2,
+            path2_len,
+            data,
+            context,
+        })
+    }
+}
+
+/// Location of a valid record found while scanning the on-disk ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RecordLocation {
+    /// Sequence number of the record
+    pub(crate) sequence: u64,
+    /// First slot occupied by the record
+    pub(crate) slot: u32,
+    /// Number of slots occupied by the record
+    pub(crate) slots: u32,
+}
+
+/// Link kind for SHA-256 chained records
+const LINK_KIND_SHA256: u8 = 1;
+
+/// Link kind for HMAC-SHA-256 chained records
+const LINK_KIND_HMAC_SHA256: u8 = 2;
+
+/// Hash chain link stored in the trailer of a chained record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChainLink {
+    /// How the link was computed (`LINK_KIND_*`)
+    pub(crate) kind: u8,
+    /// Link hash
+    pub(crate) hash: [u8; CHAIN_LINK_SIZE],
+}
+
+/// A valid record read from the on-disk ring
+pub(crate) struct Record {
+    pub(crate) location: RecordLocation,
+    pub(crate) entry: AuditEntry,
+    pub(crate) link: Option<ChainLink>,
+}
+
+/// Number of slots needed to store a record of `record_len` bytes
+const fn slots_for(record_len: usize) -> u32 {
+    record_len.div_ceil(SLOT_SIZE) as u32
+}
+
+/// CRC-32 over the sequence number, payload length and record body (payload and trailer)
+fn record_crc(sequence: u64, payload_len: usize, body: &[u8]) -> u32 {
+    use crc::{CRC_32_ISO_HDLC, Crc};
+
+    const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    let mut digest = CRC32.digest();
+    digest.update(&sequence.to_le_bytes());
+    digest.update(&(payload_len as u16).to_le_bytes());
+    digest.update(body);
+    digest.finalize()
+}
+
+/// Encode a complete record (header, payload and optional chain trailer)
+///
+/// `chain` computes the chain link from the encoded payload. Returns the record
+/// length in bytes and the link that was stored.
+fn encode_record(
+    sequence: u64,
+    entry: &AuditEntry,
+    chain: impl FnOnce(&[u8]) -> Option<ChainLink>,
+    buf: &mut [u8; MAX_RECORD_SIZE],
+) -> (usize, Option<ChainLink>) {
+    let payload_len = entry.encode_payload(&mut buf[RECORD_HEADER_SIZE..]);
+    let payload_end = RECORD_HEADER_SIZE + payload_len;
+    let link = chain(&buf[RECORD_HEADER_SIZE..payload_end]);
+    let (magic, body_end) = match link {
+        Some(link) => {
+            buf[payload_end] = link.kind;
+            buf[payload_end + 1..payload_end + CHAIN_TRAILER_SIZE].copy_from_slice(&link.hash);
+            (RECORD_MAGIC_CHAINED, payload_end + CHAIN_TRAILER_SIZE)
+        }
+        None => (RECORD_MAGIC, payload_end),
+    };
+    let crc = record_crc(sequence, payload_len, &buf[RECORD_HEADER_SIZE..body_end]);
+    buf[0..2].copy_from_slice(&magic.to_le_bytes());
+    buf[2..4].copy_from_slice(&(payload_len as u16).to_le_bytes());
+    buf[4..8].copy_from_slice(&crc.to_le_bytes());
+    buf[8..16].copy_from_slice(&sequence.to_le_bytes());
+    (body_end, link)
+}
+
+/// Parse a record header, returning `(sequence, payload_len, crc, chained)` if it looks like a record
+fn decode_header(header: &[u8; RECORD_HEADER_SIZE]) -> Option<(u64, usize, u32, bool)> {
+    let magic = u16::from_le_bytes([header[0], header[1]]);
+    let chained = match magic {
+        RECORD_MAGIC => false,
+        RECORD_MAGIC_CHAINED => true,
+        _ => return None,
+    };
+    let payload_len = u16::from_le_bytes([header[2], header[3]]) as usize;
+    if payload_len > MAX_PAYLOAD_SIZE {
+        return None;
+    }
+    let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let sequence = u64::from_le_bytes([
+        header[8], header[9], header[10], header[11], header[12], header[13], header[14],
+        header[15],
+    ]);
+    Some((sequence, payload_len, crc, chained))
+}
+
+/// Read and validate the record stored at `slot`
+///
+/// Returns `Ok(None)` if the slot does not hold a valid record (bad magic or CRC).
+async fn read_record<IO: Read + Seek>(
+    disk: &mut IO,
+    area_offset: u64,
+    slot: u32,
+) -> Result<Option<Record>, ReadExactError<IO::Error>> {
+    let mut buf = [0_u8; MAX_RECORD_SIZE];
+    disk.seek(SeekFrom::Start(area_offset + u64::from(slot) * SLOT_SIZE as u64))
+        .await
+        .map_err(ReadExactError::Other)?;
+    let header: &mut [u8; RECORD_HEADER_SIZE] =
+        (&mut buf[..RECORD_HEADER_SIZE]).try_into().unwrap();
+    disk.read_exact(header).await?;
+    let Some((sequence, payload_len, crc, chained)) = decode_header(header) else {
+        return Ok(None);
+    };
+    let trailer_len = if chained { CHAIN_TRAILER_SIZE } else { 0 };
+    let body = &mut buf[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + payload_len + trailer_len];
+    disk.read_exact(body).await?;
+    if record_crc(sequence, payload_len, body) != crc {
+        return Ok(None);
+    }
+    let (payload, trailer) = body.split_at(payload_len);
+    let link = chained.then(|| ChainLink {
+        kind: trailer[0],
+        hash: trailer[1..].try_into().unwrap(),
+    });
+    let location = RecordLocation {
+        sequence,
+        slot,
+        slots: slots_for(RECORD_HEADER_SIZE + payload_len + trailer_len),
+    };
+    Ok(AuditEntry::decode_payload(payload).map(|entry| Record {
+        location,
+        entry,
+        link,
+    }))
+}
+
+/// Integrity protection applied to persisted audit records
+///
+/// See the module documentation for the chain construction.
+#[cfg(feature = "audit-chain")]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuditIntegrity {
+    /// Records are only protected by a CRC against accidental corruption
+    #[default]
+    None,
+    /// Records are chained with SHA-256
+    Sha256,
+    /// Records are chained with HMAC-SHA-256 keyed with a device secret
+    HmacSha256([u8; 32]),
+}
+
+#[cfg(feature = "audit-chain")]
+impl Debug for AuditIntegrity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Never print the device secret
+        match self {
+            Self::None => f.write_str("None"),
+            Self::Sha256 => f.write_str("Sha256"),
+            Self::HmacSha256(_) => f.write_str("HmacSha256(<redacted>)"),
+        }
+    }
+}
+
+#[cfg(feature = "audit-chain")]
+impl AuditIntegrity {
+    /// Compute the link for a record following `prev`
+    fn link(&self, prev: &[u8; CHAIN_LINK_SIZE], sequence: u64, payload: &[u8]) -> Option<ChainLink> {
+        use hmac::{Hmac, Mac};
+        use sha2::{Digest, Sha256};
+
+        match self {
+            Self::None => None,
+            Self::Sha256 => {
+                let hash = Sha256::new()
+                    .chain_update(prev)
+                    .chain_update(sequence.to_le_bytes())
+                    .chain_update(payload)
+                    .finalize();
+                Some(ChainLink {
+                    kind: LINK_KIND_SHA256,
+                    hash: hash.into(),
+                })
+            }
+            Self::HmacSha256(key) => {
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+                    .expect("HMAC accepts keys of any length");
+                mac.update(prev);
+                mac.update(&sequence.to_le_bytes());
+                mac.update(payload);
+                Some(ChainLink {
+                    kind: LINK_KIND_HMAC_SHA256,
+                    hash: mac.finalize().into_bytes().into(),
+                })
+            }
+        }
+    }
+}
+
+/// Why audit chain verification stopped
+#[cfg(feature = "audit-chain")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditChainFault {
+    /// The record is missing: it was deleted, overwritten or fails its CRC
+    Missing,
+    /// The record carries no chain link although earlier records do
+    Unchained,
+    /// The link does not match the record contents and its predecessor,
+    /// or was not produced with the configured key
+    LinkMismatch,
+    /// The record is protected with HMAC but no key is configured
+    KeyRequired,
+}
+
+/// The first broken link found by audit chain verification
+#[cfg(feature = "audit-chain")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditChainBreak {
+    /// Sequence number of the first record that failed verification
+    pub sequence: u64,
+    /// Reason for the failure
+    pub fault: AuditChainFault,
+}
+
+/// Result of verifying the audit hash chain
+#[cfg(feature = "audit-chain")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AuditChainReport {
+    /// Number of records whose link was verified
+    pub verified: u64,
+    /// Number of leading records written without a chain link
+    pub unprotected: u64,
+    /// Sequence number of the oldest record in the log
+    pub first_sequence: Option<u64>,
+    /// Sequence number of the last record that passed verification
+    pub last_sequence: Option<u64>,
+    /// Whether older records were overwritten by the ring
+    ///
+    /// The oldest surviving chained record is then trusted as the chain anchor
+    /// because its predecessor is no longer available. With an integrity mode
+    /// configured this is only accepted where the ring wrapped over the predecessor.
+    pub truncated: bool,
+    /// The first broken link, if any
+    pub broken: Option<AuditChainBreak>,
+}
+
+#[cfg(feature = "audit-chain")]
+impl AuditChainReport {
+    /// Returns `true` if no broken link was found
+    pub fn is_intact(&self) -> bool {
+        self.broken.is_none()
+    }
+}
+
+/// Audit log counters
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AuditStats {
+    /// Number of entries buffered in memory and not yet persisted
+    pub pending: usize,
+    /// Number of entries discarded because the pending buffer was full
+    pub dropped: u64,
+    /// Sequence number that will be assigned to the next persisted record
+    pub next_sequence: u64,
 }
 
 /// Audit log configuration
@@ -243,6 +768,11 @@ pub struct AuditConfig {
     pub enabled: bool,
     /// Audit level - controls which operations are logged
     pub level: AuditLevel,
+    /// Number of pending entries that triggers an automatic flush to disk
+    pub flush_threshold: usize,
+    /// Tamper-evidence applied to persisted records
+    #[cfg(feature = "audit-chain")]
+    pub integrity: AuditIntegrity,
 }
 
 impl Default for AuditConfig {
@@ -252,6 +782,9 @@ impl Default for AuditConfig {
             log_sector_count: DEFAULT_AUDIT_LOG_SECTORS,
             enabled: true,
             level: AuditLevel::default(),
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            #[cfg(feature = "audit-chain")]
+            integrity: AuditIntegrity::None,
         }
     }
 }
@@ -293,6 +826,9 @@ impl AuditConfig {
             log_sector_count: sector_count,
             enabled: true,
             level: AuditLevel::default(),
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            #[cfg(feature = "audit-chain")]
+            integrity: AuditIntegrity::None,
         }
     }
 
@@ -303,6 +839,9 @@ impl AuditConfig {
             log_sector_count,
             enabled: true,
             level: AuditLevel::default(),
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            #[cfg(feature = "audit-chain")]
+            integrity: AuditIntegrity::None,
         }
     }
 
@@ -323,21 +862,55 @@ impl AuditConfig {
         self.level = level;
         self
     }
+
+    /// Set the number of pending entries that triggers an automatic flush
+    ///
+    /// The value is clamped to the size of the in-memory buffer (16 entries).
+    /// A threshold of 1 persists every entry as soon as it is logged.
+    pub fn flush_threshold(mut self, threshold: usize) -> Self {
+        self.flush_threshold = threshold.clamp(1, BUFFER_CAPACITY);
+        self
+    }
+
+    /// Set the tamper-evidence applied to persisted records
+    #[cfg(feature = "audit-chain")]
+    pub fn integrity(mut self, integrity: AuditIntegrity) -> Self {
+        self.integrity = integrity;
+        self
+    }
 }
 
-/// In-memory audit log buffer
+/// Audit log state
 ///
-/// Holds audit entries before they're written to disk.
+/// Holds pending audit entries in memory until they're written to the on-disk
+/// ring, and tracks where the next record will be written.
 /// Uses fixed-size buffer for no_std compatibility.
 pub struct AuditLog {
     /// Configuration
     config: AuditConfig,
+    /// Sector size used to locate the on-disk ring
+    bytes_per_sector: u32,
     /// Number of entries in buffer
     count: usize,
     /// Buffer of pending entries (up to 16 entries)
-    buffer: [Option<AuditEntry>; 16],
+    buffer: [Option<AuditEntry>; BUFFER_CAPACITY],
     /// Whether the buffer has unsaved changes
     dirty: bool,
+    /// Slot where the next record will be written
+    write_slot: u32,
+    /// Sequence number of the next record
+    next_sequence: u64,
+    /// Link of the newest persisted record
+    #[cfg(feature = "audit-chain")]
+    chain_head: [u8; CHAIN_LINK_SIZE],
+    /// Timestamp of the newest entry, used to keep timestamps monotonic
+    last_timestamp: u64,
+    /// Total number of entries discarded because the buffer was full
+    dropped: u64,
+    /// Discarded entries not yet recorded on disk
+    unreported_drops: u64,
+    /// Application context recorded with new entries
+    context: AuditContext,
 }
 
 impl AuditLog {
@@ -345,13 +918,71 @@ impl AuditLog {
     pub fn new(config: AuditConfig) -> Self {
         Self {
             config,
+            bytes_per_sector: 512,
             count: 0,
-            buffer: [None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None],
+            buffer: [const { None }; BUFFER_CAPACITY],
             dirty: false,
+            write_slot: 0,
+            next_sequence: 0,
+            #[cfg(feature = "audit-chain")]
+            chain_head: [0; CHAIN_LINK_SIZE],
+            last_timestamp: 0,
+            dropped: 0,
+            unreported_drops: 0,
+            context: AuditContext::default(),
         }
     }
 
+    /// Set the sector size of the volume (defaults to 512 bytes)
+    pub(crate) fn set_bytes_per_sector(&mut self, bytes_per_sector: u32) {
+        self.bytes_per_sector = bytes_per_sector;
+    }
+
+    /// Get the active configuration
+    pub fn config(&self) -> &AuditConfig {
+        &self.config
+    }
+
+    /// Byte offset of the on-disk ring
+    pub(crate) fn area_offset(&self) -> u64 {
+        u64::from(self.config.log_start_sector) * u64::from(self.bytes_per_sector)
+    }
+
+    /// Number of slots in the on-disk ring
+    pub(crate) fn slot_count(&self) -> u32 {
+        let area_bytes = u64::from(self.config.log_sector_count) * u64::from(self.bytes_per_sector);
+        (area_bytes / SLOT_SIZE as u64) as u32
+    }
+
+    /// Set the application context recorded with subsequent entries
+    pub fn set_context(&mut self, context: AuditContext) {
+        self.context = context;
+    }
+
+    /// Get the application context recorded with new entries
+    pub fn context(&self) -> AuditContext {
+        self.context
+    }
+
+    /// Produce the timestamp for a new entry
+    ///
+    /// Uses `wall_clock` (milliseconds since the Unix epoch) when available and
+    /// falls back to a counter otherwise. The result is always greater than the
+    /// previous timestamp.
+    pub fn next_timestamp(&mut self, wall_clock: Option<u64>) -> u64 {
+        let timestamp = match wall_clock {
+            Some(now) if now > self.last_timestamp => now,
+            _ => self.last_timestamp + 1,
+        };
+        self.last_timestamp = timestamp;
+        timestamp
+    }
+
     /// Add an entry to the audit log
+    ///
+    /// If the pending buffer is full, the oldest pending entry is discarded. The
+    /// loss is counted in [`AuditStats::dropped`] and recorded as an
+    /// [`AuditOperation::EntriesDropped`] entry on the next flush.
     pub fn log(&mut self, entry: AuditEntry) {
         if !self.config.enabled {
             return;
@@ -367,6 +998,9 @@ impl AuditLog {
             self.count += 1;
         } else {
             // Buffer full - shift left and add new entry at end
+            warn!("Audit log buffer full, dropping oldest pending entry");
+            self.dropped += 1;
+            self.unreported_drops += 1;
             for i in 0..self.buffer.len() - 1 {
                 self.buffer[i] = self.buffer[i + 1].take();
             }
@@ -381,7 +1015,8 @@ impl AuditLog {
         if !operation.should_log(self.config.level) {
             return;
         }
-        self.log(AuditEntry::new(timestamp, operation, result, path));
+        let context = self.context;
+        self.log(AuditEntry::new(timestamp, operation, result, path).with_context(context));
     }
 
     /// Helper: log a file operation with data (size, offset, etc.)
@@ -390,7 +1025,12 @@ impl AuditLog {
         if !operation.should_log(self.config.level) {
             return;
         }
-        self.log(AuditEntry::new(timestamp, operation, result, path).with_data(data));
+        let context = self.context;
+        self.log(
+            AuditEntry::new(timestamp, operation, result, path)
+                .with_data(data)
+                .with_context(context),
+        );
     }
 
     /// Helper: log a rename operation
@@ -399,12 +1039,13 @@ impl AuditLog {
         if !AuditOperation::Rename.should_log(self.config.level) {
             return;
         }
-        let mut entry = AuditEntry::new(timestamp, AuditOperation::Rename, result, old_path);
+        let mut entry = AuditEntry::new(timestamp, AuditOperation::Rename, result, old_path)
+            .with_context(self.context);
         entry.set_path2(new_path);
         self.log(entry);
     }
 
-    /// Get all entries in the buffer
+    /// Get all pending (not yet persisted) entries in the buffer
     pub fn entries(&self) -> impl Iterator<Item = &AuditEntry> {
         self.buffer[..self.count].iter().filter_map(|e| e.as_ref())
     }
@@ -437,111 +1078,391 @@ impl AuditLog {
         self.dirty
     }
 
-    /// Write audit log to reserved disk sectors
-    pub async fn flush<IO: crate::io::Write + crate::io::Seek>(&mut self, disk: &mut IO) -> Result<(), IO::Error> {
-        use crate::io::{WriteLeExt, SeekFrom};
+    /// Number of entries discarded because the pending buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
 
-        if !self.dirty || !self.config.enabled {
-            return Ok(());
+    /// Get the audit log counters
+    pub fn stats(&self) -> AuditStats {
+        AuditStats {
+            pending: self.count,
+            dropped: self.dropped,
+            next_sequence: self.next_sequence,
         }
+    }
 
-        let sector_size = 512u64; // Standard sector size
-        let start_offset = u64::from(self.config.log_start_sector) * sector_size;
-
-        // Seek to audit log area
-        disk.seek(SeekFrom::Start(start_offset)).await?;
+    /// Check if enough entries are pending to trigger an automatic flush
+    pub fn needs_flush(&self) -> bool {
+        self.dirty && self.count >= self.config.flush_threshold.clamp(1, BUFFER_CAPACITY)
+    }
 
-        // Write entries using postcard serialization
-        #[cfg(all(feature = "alloc", not(feature = "std")))]
-        {
-            let entries: alloc::vec::Vec<_> = self.entries().cloned().collect();
-            if let Ok(data) = postcard::to_allocvec(&entries) {
-                // Write length prefix
-                disk.write_u32_le(data.len() as u32).await?;
-                // Write serialized data
-                disk.write_all(&data).await?;
-            }
+    /// Append pending entries to the on-disk ring
+    ///
+    /// Records are written after the newest persisted record, wrapping to the
+    /// start of the area (and overwriting the oldest records) when the end is reached.
+    pub async fn flush<IO: Write + Seek>(&mut self, disk: &mut IO) -> Result<(), IO::Error> {
+        if !self.dirty || !self.config.enabled {
+            return Ok(());
         }
 
-        #[cfg(feature = "std")]
-        {
-            let entries: Vec<_> = self.entries().cloned().collect();
-            if let Ok(data) = postcard::to_allocvec(&entries) {
-                // Write length prefix
-                disk.write_u32_le(data.len() as u32).await?;
-                // Write serialized data
-                disk.write_all(&data).await?;
-            }
+        if self.unreported_drops > 0 {
+            // Record the loss ahead of the surviving entries
+            let timestamp = self
+                .entries()
+                .next()
+                .map_or(self.last_timestamp, |entry| entry.timestamp);
+            let marker = AuditEntry::new(timestamp, AuditOperation::EntriesDropped, AuditResult::Error, "")
+                .with_data(self.unreported_drops)
+                .with_context(self.context);
+            self.append(disk, &marker).await?;
+            self.unreported_drops = 0;
+        }
+        for i in 0..self.count {
+            let Some(entry) = self.buffer[i].clone() else {
+                continue;
+            };
+            self.append(disk, &entry).await?;
         }
 
         disk.flush().await?;
+        self.clear();
         self.dirty = false;
         Ok(())
     }
 
-    /// Load audit log from reserved disk sectors
-    pub async fn load<IO: crate::io::Read + crate::io::Seek>(&mut self, disk: &mut IO) -> Result<(), IO::Error> {
-        use crate::io::{ReadLeExt, SeekFrom};
+    /// Write one record at the current position of the on-disk ring
+    async fn append<IO: Write + Seek>(&mut self, disk: &mut IO, entry: &AuditEntry) -> Result<(), IO::Error> {
+        let slot_count = self.slot_count();
+        let mut buf = [0_u8; MAX_RECORD_SIZE];
+        let (record_len, link) = encode_record(
+            self.next_sequence,
+            entry,
+            |payload| self.chain_link(self.next_sequence, payload),
+            &mut buf,
+        );
+        let slots = slots_for(record_len);
+        if slots > slot_count {
+            warn!("Audit log area too small for record, dropping entry");
+            return Ok(());
+        }
+        if self.write_slot + slots > slot_count {
+            // Not enough room before the end of the area. Clear the leftover tail so
+            // records from earlier laps don't survive out of sequence, then wrap around
+            let tail = self.area_offset() + u64::from(self.write_slot) * SLOT_SIZE as u64;
+            disk.seek(SeekFrom::Start(tail)).await?;
+            for _ in self.write_slot..slot_count {
+                disk.write_all(&[0; SLOT_SIZE]).await?;
+            }
+            self.write_slot = 0;
+        }
+        let offset = self.area_offset() + u64::from(self.write_slot) * SLOT_SIZE as u64;
+        disk.seek(SeekFrom::Start(offset)).await?;
+        disk.write_all(&buf[..record_len]).await?;
+        self.write_slot = (self.write_slot + slots) % slot_count;
+        self.next_sequence += 1;
+        #[cfg(feature = "audit-chain")]
+        if let Some(link) = link {
+            self.chain_head = link.hash;
+        }
+        #[cfg(not(feature = "audit-chain"))]
+        let _ = link;
+        Ok(())
+    }
+
+    /// Scan the on-disk ring for valid records
+    ///
+    /// Returns the location of every valid record, ordered from oldest to newest.
+    pub(crate) async fn scan<IO: Read + Seek>(
+        &self,
+        disk: &mut IO,
+    ) -> Result<Vec<RecordLocation>, IO::Error> {
+        let area_offset = self.area_offset();
+        let slot_count = self.slot_count();
+        let mut records = Vec::new();
+        let mut slot = 0;
+        while slot < slot_count {
+            match read_record(disk, area_offset, slot).await {
+                Ok(Some(record)) if slot + record.location.slots <= slot_count => {
+                    records.push(record.location);
+                    slot += record.location.slots;
+                }
+                Ok(_) => slot += 1,
+                // The area runs past the end of the device - nothing more to read
+                Err(ReadExactError::UnexpectedEof) => break,
+                Err(ReadExactError::Other(e)) => return Err(e),
+            }
+        }
+        records.sort_unstable_by_key(|r| r.sequence);
+        Ok(records)
+    }
 
+    /// Locate the end of the on-disk ring so new records are appended after it
+    ///
+    /// Any pending in-memory entries are kept and will be written on the next flush.
+    pub async fn load<IO: Read + Seek>(&mut self, disk: &mut IO) -> Result<(), IO::Error> {
         if !self.config.enabled {
             return Ok(());
         }
 
-        let sector_size = 512u64;
-        let start_offset = u64::from(self.config.log_start_sector) * sector_size;
-
-        // Seek to audit log area
-        disk.seek(SeekFrom::Start(start_offset)).await?;
+        let records = self.scan(disk).await?;
+        if let Some(newest) = records.last() {
+            self.write_slot = (newest.slot + newest.slots) % self.slot_count();
+            self.next_sequence = newest.sequence + 1;
+        } else {
+            self.write_slot = 0;
+            self.next_sequence = 0;
+        }
 
-        // Read length prefix
-        let data_len = match disk.read_u32_le().await {
-            Ok(len) => len as usize,
-            Err(_) => return Ok(()), // No data or error, start fresh
+        // Continue timestamps and the hash chain from the newest record
+        let newest = match records.last() {
+            Some(newest) => match read_record(disk, self.area_offset(), newest.slot).await {
+                Ok(record) => record,
+                Err(ReadExactError::UnexpectedEof) => None,
+                Err(ReadExactError::Other(e)) => return Err(e),
+            },
+            None => None,
         };
-
-        if data_len == 0 || data_len > (self.config.log_sector_count as usize * 512) {
-            return Ok(()); // Invalid or no data
+        if let Some(record) = &newest {
+            self.last_timestamp = self.last_timestamp.max(record.entry.timestamp);
         }
+        #[cfg(feature = "audit-chain")]
+        {
+            self.chain_head = newest
+                .and_then(|record| record.link)
+                .map_or([0; CHAIN_LINK_SIZE], |link| link.hash);
+        }
+        trace!(
+            "Audit log: {} persisted records, next sequence {}",
+            records.len(),
+            self.next_sequence
+        );
+        Ok(())
+    }
+
+    /// Get the sequence number that will be assigned to the next persisted record
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
 
-        // Read serialized data
-        #[cfg(all(feature = "alloc", not(feature = "std")))]
+    /// Compute the chain link for the record with the given sequence and payload
+    fn chain_link(&self, sequence: u64, payload: &[u8]) -> Option<ChainLink> {
+        #[cfg(feature = "audit-chain")]
         {
-            let mut data = alloc::vec![0u8; data_len];
-            if disk.read_exact(&mut data).await.is_ok() {
-                if let Ok(entries) = postcard::from_bytes::<alloc::vec::Vec<AuditEntry>>(&data) {
-                    // Load entries into buffer
-                    self.count = 0;
-                    for entry in entries.into_iter().take(self.buffer.len()) {
-                        self.buffer[self.count] = Some(entry);
-                        self.count += 1;
+            self.config.integrity.link(&self.chain_head, sequence, payload)
+        }
+        #[cfg(not(feature = "audit-chain"))]
+        {
+            let _ = (sequence, payload);
+            None
+        }
+    }
+
+    /// Verify the hash chain of the persisted records
+    ///
+    /// Walks the records from oldest to newest and stops at the first broken link.
+    /// With an integrity mode configured, the newest record must be chained and the
+    /// oldest surviving record is only trusted as the anchor of a truncated chain if
+    /// the ring layout shows its predecessors were overwritten.
+    #[cfg(feature = "audit-chain")]
+    pub(crate) async fn verify_chain<IO: Read + Seek>(
+        &self,
+        disk: &mut IO,
+    ) -> Result<AuditChainReport, ReadExactError<IO::Error>> {
+        let records = self.scan(disk).await.map_err(ReadExactError::Other)?;
+        let mut report = AuditChainReport {
+            first_sequence: records.first().map(|r| r.sequence),
+            ..AuditChainReport::default()
+        };
+        let chain_required = !matches!(self.config.integrity, AuditIntegrity::None);
+        let anchor_overwritten = self.overwritten_before_oldest(&records);
+        let mut payload = [0_u8; MAX_PAYLOAD_SIZE];
+        // Sequence and link of the previous record, once the chain has started
+        let mut prev: Option<(u64, [u8; CHAIN_LINK_SIZE])> = None;
+        let mut expected_sequence = report.first_sequence.unwrap_or(0);
+
+        for location in &records {
+            let fault = if location.sequence != expected_sequence {
+                Some(AuditChainFault::Missing)
+            } else {
+                match read_record(disk, self.area_offset(), location.slot).await? {
+                    None => Some(AuditChainFault::Missing),
+                    Some(Record { link: None, .. }) if prev.is_none() => {
+                        // Written before the chain was enabled
+                        report.unprotected += 1;
+                        expected_sequence += 1;
+                        continue;
+                    }
+                    Some(Record { link: None, .. }) => Some(AuditChainFault::Unchained),
+                    Some(Record {
+                        entry, link: Some(link), ..
+                    }) => {
+                        let prev_hash = match prev {
+                            Some((_, hash)) => hash,
+                            None if location.sequence == 0 || report.unprotected > 0 => [0; CHAIN_LINK_SIZE],
+                            None if chain_required && !anchor_overwritten => {
+                                // The predecessor was removed rather than overwritten
+                                report.broken = Some(AuditChainBreak {
+                                    sequence: location.sequence - 1,
+                                    fault: AuditChainFault::Missing,
+                                });
+                                break;
+                            }
+                            None => {
+                                // The predecessor was overwritten: anchor the chain here
+                                report.truncated = true;
+                                prev = Some((location.sequence, link.hash));
+                                report.last_sequence = Some(location.sequence);
+                                expected_sequence += 1;
+                                continue;
+                            }
+                        };
+                        let integrity = match (link.kind, self.config.integrity) {
+                            (LINK_KIND_HMAC_SHA256, AuditIntegrity::HmacSha256(key)) => {
+                                Some(AuditIntegrity::HmacSha256(key))
+                            }
+                            (LINK_KIND_HMAC_SHA256, _) => None,
+                            // A keyed log must not contain unkeyed links
+                            (_, AuditIntegrity::HmacSha256(_)) => Some(AuditIntegrity::None),
+                            _ => Some(AuditIntegrity::Sha256),
+                        };
+                        match integrity {
+                            None => Some(AuditChainFault::KeyRequired),
+                            Some(integrity) => {
+                                let len = entry.encode_payload(&mut payload);
+                                if integrity.link(&prev_hash, location.sequence, &payload[..len]) == Some(link) {
+                                    report.verified += 1;
+                                    report.last_sequence = Some(location.sequence);
+                                    prev = Some((location.sequence, link.hash));
+                                    None
+                                } else {
+                                    Some(AuditChainFault::LinkMismatch)
+                                }
+                            }
+                        }
                     }
                 }
+            };
+
+            if let Some(fault) = fault {
+                report.broken = Some(AuditChainBreak {
+                    sequence: expected_sequence,
+                    fault,
+                });
+                break;
             }
+            expected_sequence += 1;
         }
 
-        #[cfg(feature = "std")]
-        {
-            let mut data = vec![0u8; data_len];
-            if disk.read_exact(&mut data).await.is_ok() {
-                if let Ok(entries) = postcard::from_bytes::<Vec<AuditEntry>>(&data) {
-                    // Load entries into buffer
-                    self.count = 0;
-                    for entry in entries.into_iter().take(self.buffer.len()) {
-                        self.buffer[self.count] = Some(entry);
-                        self.count += 1;
-                    }
+        // Records are chained from the moment the integrity mode is configured, so
+        // an unchained newest record means the trailers were stripped
+        if chain_required && report.broken.is_none() && report.last_sequence.is_none() {
+            if let Some(newest) = records.last() {
+                report.broken = Some(AuditChainBreak {
+                    sequence: newest.sequence,
+                    fault: AuditChainFault::Unchained,
+                });
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Whether the slots between the newest and the oldest record can only hold
+    /// records overwritten by the ring
+    ///
+    /// Writing the newest record destroys at most one older record partially, and
+    /// a record torn by power loss leaves at most one more record's worth of slots.
+    #[cfg(feature = "audit-chain")]
+    fn overwritten_before_oldest(&self, records: &[RecordLocation]) -> bool {
+        let (Some(oldest), Some(newest)) = (records.first(), records.last()) else {
+            return true;
+        };
+        let slot_count = self.slot_count();
+        let write_slot = (newest.slot + newest.slots) % slot_count;
+        let gap = (oldest.slot + slot_count - write_slot) % slot_count;
+        gap < 2 * slots_for(MAX_RECORD_SIZE)
+    }
+}
+
+/// An iterator over all persisted audit entries, from oldest to newest.
+///
+/// This struct is created by the `audit_history` method on `FileSystem`.
+/// Records are read from disk lazily, one per call to [`AuditHistory::next`].
+/// Records overwritten after the iterator was created are skipped.
+pub struct AuditHistory<'a, IO: ReadWriteSeek, TP, OCC>
+where
+    IO::Error: 'static,
+{
+    fs: &'a FileSystem<IO, TP, OCC>,
+    area_offset: u64,
+    records: Vec<RecordLocation>,
+    index: usize,
+}
+
+impl<'a, IO: ReadWriteSeek, TP, OCC> AuditHistory<'a, IO, TP, OCC> {
+    pub(crate) fn new(
+        fs: &'a FileSystem<IO, TP, OCC>,
+        area_offset: u64,
+        records: Vec<RecordLocation>,
+    ) -> Self {
+        Self {
+            fs,
+            area_offset,
+            records,
+            index: 0,
+        }
+    }
+
+    /// Number of records remaining in the iterator
+    pub fn remaining(&self) -> usize {
+        self.records.len() - self.index
+    }
+
+    /// Read the next persisted entry
+    pub async fn next(&mut self) -> Option<Result<AuditEntry, Error<IO::Error>>> {
+        while let Some(expected) = self.records.get(self.index).copied() {
+            self.index += 1;
+            let result = {
+                let mut disk = self.fs.disk.acquire().await;
+                read_record(&mut *disk, self.area_offset, expected.slot).await
+            };
+            match result {
+                Ok(Some(record)) if record.location.sequence == expected.sequence => {
+                    return Some(Ok(record.entry));
                 }
+                // Record was overwritten since the scan
+                Ok(_) => {}
+                Err(e) => return Some(Err(e.into())),
             }
         }
+        None
+    }
 
-        self.dirty = false;
-        Ok(())
+    /// Read all remaining entries
+    pub async fn collect(&mut self) -> Result<Vec<AuditEntry>, Error<IO::Error>> {
+        let mut entries = Vec::with_capacity(self.remaining());
+        while let Some(entry) = self.next().await {
+            entries.push(entry?);
+        }
+        Ok(entries)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use embedded_io_adapters::tokio_1::FromTokio;
+
     use super::*;
+    use std::io::Cursor;
+
+    fn ring_config(sectors: u32) -> AuditConfig {
+        AuditConfig::at_sector(1, sectors).flush_threshold(4)
+    }
+
+    fn entry(timestamp: u64) -> AuditEntry {
+        AuditEntry::new(timestamp, AuditOperation::FileCreate, AuditResult::Success, "/test.txt")
+    }
 
     #[test]
     fn test_audit_entry_paths() {
@@ -598,5 +1519,322 @@ mod tests {
         // Oldest entries should have been dropped
         let entries: Vec<_> = log.entries().collect();
         assert_eq!(entries[0].timestamp, 4); // Entry 0-3 were dropped
+        assert_eq!(log.stats().dropped, 4);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_entries_are_recorded_on_flush() {
+        let mut disk = FromTokio::new(Cursor::new(vec![0_u8; 512 * 20]));
+        let mut log = AuditLog::new(AuditConfig::at_sector(1, 16));
+        for i in 0..20 {
+            log.log(entry(i));
+        }
+        log.flush(&mut disk).await.unwrap();
+        assert_eq!(log.stats().dropped, 4);
+        assert_eq!(log.stats().pending, 0);
+
+        // The marker precedes the surviving entries
+        let records = log.scan(&mut disk).await.unwrap();
+        assert_eq!(records.len(), 17);
+        let marker = read_record(&mut disk, log.area_offset(), records[0].slot)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(marker.entry.operation, AuditOperation::EntriesDropped);
+        assert_eq!(marker.entry.data, 4);
+
+        // Reported once
+        log.log(entry(20));
+        log.flush(&mut disk).await.unwrap();
+        assert_eq!(log.scan(&mut disk).await.unwrap().len(), 18);
+    }
+
+    #[test]
+    fn test_timestamps_are_monotonic() {
+        let mut log = AuditLog::new(AuditConfig::default());
+        // No clock: counter
+        assert_eq!(log.next_timestamp(None), 1);
+        assert_eq!(log.next_timestamp(None), 2);
+        // Clock available
+        let now = WALL_CLOCK_MIN_TIMESTAMP + 5_000;
+        assert_eq!(log.next_timestamp(Some(now)), now);
+        // Clock stalled or stepped backwards
+        assert_eq!(log.next_timestamp(Some(now)), now + 1);
+        assert_eq!(log.next_timestamp(Some(now - 1_000)), now + 2);
+    }
+
+    #[test]
+    fn test_wall_clock_accepts_1980() {
+        use crate::time::{Date, Time};
+
+        assert_eq!(wall_clock_millis(DateTime::decode(0, 0, 0)), None);
+        let new_year = DateTime::new(Date::new(1980, 1, 1), Time::new(0, 0, 1, 500));
+        assert_eq!(wall_clock_millis(new_year), Some(WALL_CLOCK_MIN_TIMESTAMP + 1_500));
+        let later = DateTime::new(Date::new(1980, 6, 1), Time::new(12, 0, 0, 0));
+        assert!(wall_clock_millis(later).unwrap() > WALL_CLOCK_MIN_TIMESTAMP);
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let mut original = entry(42)
+            .with_data(7)
+            .with_context(AuditContext::new(3).with_tag("operator"));
+        original.set_path2("/renamed.txt");
+        let mut buf = [0_u8; MAX_RECORD_SIZE];
+        let (len, link) = encode_record(3, &original, |_| None, &mut buf);
+        assert_eq!(link, None);
+
+        let header: [u8; RECORD_HEADER_SIZE] = buf[..RECORD_HEADER_SIZE].try_into().unwrap();
+        let (sequence, payload_len, crc, chained) = decode_header(&header).unwrap();
+        assert_eq!(sequence, 3);
+        assert!(!chained);
+        assert_eq!(RECORD_HEADER_SIZE + payload_len, len);
+
+        let payload = &buf[RECORD_HEADER_SIZE..len];
+        assert_eq!(record_crc(sequence, payload_len, payload), crc);
+        let decoded = AuditEntry::decode_payload(payload).unwrap();
+        assert_eq!(decoded.timestamp, 42);
+        assert_eq!(decoded.data, 7);
+        assert_eq!(decoded.get_path(), "/test.txt");
+        assert_eq!(decoded.get_path2(), Some("/renamed.txt"));
+        assert_eq!(decoded.context.task_id, 3);
+        assert_eq!(decoded.context.tag(), Some("operator"));
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_record_is_skipped() {
+        let mut disk = FromTokio::new(Cursor::new(vec![0_u8; 512 * 4]));
+        let mut log = AuditLog::new(ring_config(2));
+        log.log(entry(1));
+        log.log(entry(2));
+        log.flush(&mut disk).await.unwrap();
+
+        // Flip a payload byte of the first record
+        disk.inner_mut().get_mut()[512 + RECORD_HEADER_SIZE] ^= 0xFF;
+
+        let records = log.scan(&mut disk).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].sequence, 1);
+    }
+
+    #[tokio::test]
+    async fn test_ring_wraps_and_resumes() {
+        let mut disk = FromTokio::new(Cursor::new(vec![0_u8; 512 * 4]));
+        let mut log = AuditLog::new(ring_config(2));
+        for i in 0..100 {
+            log.log(entry(i));
+            if log.needs_flush() {
+                log.flush(&mut disk).await.unwrap();
+            }
+        }
+        log.flush(&mut disk).await.unwrap();
+        assert_eq!(log.next_sequence(), 100);
+
+        // Only the newest records fit in the ring, oldest first
+        let records = log.scan(&mut disk).await.unwrap();
+        assert!(!records.is_empty() && records.len() < 100);
+        assert_eq!(records.last().unwrap().sequence, 99);
+        assert!(records.windows(2).all(|w| w[0].sequence + 1 == w[1].sequence));
+
+        // A fresh log (as after remount) continues the sequence
+        let mut remounted = AuditLog::new(ring_config(2));
+        remounted.load(&mut disk).await.unwrap();
+        assert_eq!(remounted.next_sequence(), 100);
+        remounted.log(entry(100));
+        remounted.flush(&mut disk).await.unwrap();
+        let records = remounted.scan(&mut disk).await.unwrap();
+        assert_eq!(records.last().unwrap().sequence, 100);
+
+        let newest = read_record(&mut disk, remounted.area_offset(), records.last().unwrap().slot)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(newest.entry.timestamp, 100);
+    }
+
+    /// Path of the `i`-th entry in the multi-lap tests: a mix of short and long paths
+    fn mixed_path(i: u64) -> String {
+        let len = if i % 3 == 0 { 1 } else { (i * 37 % 200) as usize + 1 };
+        "/".repeat(len)
+    }
+
+    #[tokio::test]
+    async fn test_ring_wrap_leaves_no_stale_records() {
+        let mut disk = FromTokio::new(Cursor::new(vec![0_u8; 512 * 4]));
+        let mut log = AuditLog::new(ring_config(2));
+        for i in 0..300 {
+            log.log(AuditEntry::new(i, AuditOperation::FileCreate, AuditResult::Success, &mixed_path(i)));
+            if log.needs_flush() {
+                log.flush(&mut disk).await.unwrap();
+                let records = log.scan(&mut disk).await.unwrap();
+                assert_eq!(records.last().unwrap().sequence, i);
+                assert!(records.windows(2).all(|w| w[0].sequence + 1 == w[1].sequence));
+            }
+        }
+    }
+
+    #[cfg(feature = "audit-chain")]
+    #[tokio::test]
+    async fn test_chain_verifies_after_many_laps() {
+        let mut disk = FromTokio::new(Cursor::new(vec![0_u8; 512 * 4]));
+        let mut log = AuditLog::new(ring_config(2).integrity(AuditIntegrity::Sha256));
+        for i in 0..300 {
+            log.log(AuditEntry::new(i, AuditOperation::FileCreate, AuditResult::Success, &mixed_path(i)));
+            if log.needs_flush() {
+                log.flush(&mut disk).await.unwrap();
+                let report = log.verify_chain(&mut disk).await.unwrap();
+                assert!(report.is_intact(), "{report:?}");
+                assert_eq!(report.last_sequence, Some(i));
+            }
+        }
+    }
+
+    #[cfg(feature = "audit-chain")]
+    async fn chained_disk(integrity: AuditIntegrity) -> (FromTokio<Cursor<Vec<u8>>>, AuditLog) {
+        let mut disk = FromTokio::new(Cursor::new(vec![0_u8; 512 * 8]));
+        let mut log = AuditLog::new(ring_config(4).integrity(integrity));
+        for i in 0..6 {
+            log.log(entry(i));
+        }
+        log.flush(&mut disk).await.unwrap();
+        (disk, log)
+    }
+
+    #[cfg(feature = "audit-chain")]
+    #[tokio::test]
+    async fn test_chain_verifies_across_remount() {
+        let (mut disk, _) = chained_disk(AuditIntegrity::Sha256).await;
+
+        let mut remounted = AuditLog::new(ring_config(4).integrity(AuditIntegrity::Sha256));
+        remounted.load(&mut disk).await.unwrap();
+        remounted.log(entry(6));
+        remounted.flush(&mut disk).await.unwrap();
+
+        let report = remounted.verify_chain(&mut disk).await.unwrap();
+        assert!(report.is_intact());
+        assert_eq!(report.verified, 7);
+        assert_eq!(report.last_sequence, Some(6));
+        assert!(!report.truncated);
+    }
+
+    #[cfg(feature = "audit-chain")]
+    #[tokio::test]
+    async fn test_chain_detects_altered_record() {
+        let (mut disk, log) = chained_disk(AuditIntegrity::Sha256).await;
+        let records = log.scan(&mut disk).await.unwrap();
+        let target = records[2];
+        let original = read_record(&mut disk, log.area_offset(), target.slot)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Rewrite the record with different contents but the original link and a valid CRC
+        let mut buf = [0_u8; MAX_RECORD_SIZE];
+        let (len, _) = encode_record(target.sequence, &entry(99), |_| original.link, &mut buf);
+        disk.seek(SeekFrom::Start(log.area_offset() + u64::from(target.slot) * SLOT_SIZE as u64))
+            .await
+            .unwrap();
+        disk.write_all(&buf[..len]).await.unwrap();
+
+        let report = log.verify_chain(&mut disk).await.unwrap();
+        assert_eq!(
+            report.broken,
+            Some(AuditChainBreak {
+                sequence: 2,
+                fault: AuditChainFault::LinkMismatch
+            })
+        );
+        assert_eq!(report.last_sequence, Some(1));
+    }
+
+    #[cfg(feature = "audit-chain")]
+    #[tokio::test]
+    async fn test_chain_detects_deleted_record() {
+        let (mut disk, log) = chained_disk(AuditIntegrity::Sha256).await;
+        let records = log.scan(&mut disk).await.unwrap();
+        let target = records[3];
+        let offset = log.area_offset() as usize + target.slot as usize * SLOT_SIZE;
+        disk.inner_mut().get_mut()[offset..offset + SLOT_SIZE].fill(0);
+
+        let report = log.verify_chain(&mut disk).await.unwrap();
+        assert_eq!(
+            report.broken,
+            Some(AuditChainBreak {
+                sequence: 3,
+                fault: AuditChainFault::Missing
+            })
+        );
+    }
+
+    #[cfg(feature = "audit-chain")]
+    #[tokio::test]
+    async fn test_chain_detects_removed_oldest_records() {
+        let (mut disk, log) = chained_disk(AuditIntegrity::Sha256).await;
+        let records = log.scan(&mut disk).await.unwrap();
+        // The ring never wrapped, so this is not what an overwrite leaves behind
+        let start = log.area_offset() as usize;
+        let end = start + records[2].slot as usize * SLOT_SIZE;
+        disk.inner_mut().get_mut()[start..end].fill(0);
+
+        let report = log.verify_chain(&mut disk).await.unwrap();
+        assert_eq!(
+            report.broken,
+            Some(AuditChainBreak {
+                sequence: 1,
+                fault: AuditChainFault::Missing
+            })
+        );
+        assert!(!report.truncated);
+    }
+
+    #[cfg(feature = "audit-chain")]
+    #[tokio::test]
+    async fn test_chain_detects_stripped_trailers() {
+        let (mut disk, log) = chained_disk(AuditIntegrity::Sha256).await;
+        // Rewrite every record without its chain trailer and with a valid CRC
+        for location in log.scan(&mut disk).await.unwrap() {
+            let record = read_record(&mut disk, log.area_offset(), location.slot)
+                .await
+                .unwrap()
+                .unwrap();
+            let mut buf = [0_u8; MAX_RECORD_SIZE];
+            let (len, _) = encode_record(location.sequence, &record.entry, |_| None, &mut buf);
+            disk.seek(SeekFrom::Start(log.area_offset() + u64::from(location.slot) * SLOT_SIZE as u64))
+                .await
+                .unwrap();
+            disk.write_all(&buf[..len]).await.unwrap();
+        }
+
+        let report = log.verify_chain(&mut disk).await.unwrap();
+        assert_eq!(
+            report.broken,
+            Some(AuditChainBreak {
+                sequence: 5,
+                fault: AuditChainFault::Unchained
+            })
+        );
+
+        // Without an integrity mode the records are only reported as unprotected
+        let unchained = AuditLog::new(ring_config(4));
+        let report = unchained.verify_chain(&mut disk).await.unwrap();
+        assert!(report.is_intact());
+        assert_eq!(report.unprotected, 6);
+    }
+
+    #[cfg(feature = "audit-chain")]
+    #[tokio::test]
+    async fn test_chain_hmac_requires_key() {
+        let (mut disk, _) = chained_disk(AuditIntegrity::HmacSha256([7; 32])).await;
+
+        let keyed = AuditLog::new(ring_config(4).integrity(AuditIntegrity::HmacSha256([7; 32])));
+        assert!(keyed.verify_chain(&mut disk).await.unwrap().is_intact());
+
+        let wrong_key = AuditLog::new(ring_config(4).integrity(AuditIntegrity::HmacSha256([8; 32])));
+        let report = wrong_key.verify_chain(&mut disk).await.unwrap();
+        assert_eq!(report.broken.map(|b| b.fault), Some(AuditChainFault::LinkMismatch));
+
+        let unkeyed = AuditLog::new(ring_config(4));
+        let report = unkeyed.verify_chain(&mut disk).await.unwrap();
+        assert_eq!(report.broken.map(|b| b.fault), Some(AuditChainFault::KeyRequired));
     }
 }
@@ -46,6 +46,9 @@ pub struct ClusterBitmap {
     /// Count of free clusters (cached for performance)
     free_count: u32,
 
+    /// Clusters marked bad in the FAT (tracked as allocated), sorted
+    bad_clusters: Vec<u32>,
+
     /// Dirty flag - bitmap has been modified since last sync
     /// Not currently used but reserved for future persistence
     dirty: bool,
@@ -95,6 +98,7 @@ impl ClusterBitmap {
             total_clusters,
             next_free_hint: 0,
             free_count: total_clusters,
+            bad_clusters: Vec::new(),
             dirty: false,
             fast_allocations: 0,
             slow_allocations: 0,
@@ -115,6 +119,7 @@ impl ClusterBitmap {
             total_clusters,
             next_free_hint: 0,
             free_count: total_clusters,
+            bad_clusters: Vec::new(),
             dirty: false,
             fast_allocations: 0,
             slow_allocations: 0,
@@ -193,6 +198,22 @@ impl ClusterBitmap {
         }
     }
 
+    /// Mark a cluster as bad so it is never returned as free
+    ///
+    /// Marking a cluster that is already bad does nothing.
+    ///
+    /// # Arguments
+    /// * `cluster` - Cluster number to mark as bad
+    pub fn set_bad(&mut self, cluster: u32) {
+        if cluster >= self.total_clusters {
+            return;
+        }
+        if let Err(index) = self.bad_clusters.binary_search(&cluster) {
+            self.bad_clusters.insert(index, cluster);
+            self.set_allocated(cluster);
+        }
+    }
+
     /// Find the next free cluster, starting from a hint
     ///
     /// This is the core optimization: instead of scanning the FAT table
@@ -232,6 +253,16 @@ impl ClusterBitmap {
         None
     }
 
+    /// Find a free cluster while keeping `reserved` clusters free
+    ///
+    /// Returns `None` if `reserved` or fewer free clusters remain.
+    pub fn find_free_above_reserve(&mut self, start_cluster: u32, reserved: u32) -> Option<u32> {
+        if self.free_count <= reserved {
+            return None;
+        }
+        self.find_free(start_cluster)
+    }
+
     /// Find free cluster in a specific range
     ///
     /// Optimized to scan bytes at a time rather than individual bits
@@ -341,6 +372,7 @@ impl ClusterBitmap {
             total_clusters: self.total_clusters,
             free_clusters: self.free_count,
             allocated_clusters: self.total_clusters - self.free_count,
+            bad_clusters: self.bad_clusters.len() as u32,
             utilization: (self.total_clusters - self.free_count) as f32
                 / self.total_clusters as f32,
             fast_allocations: self.fast_allocations,
@@ -360,6 +392,9 @@ impl ClusterBitmap {
     ///
     /// This is a one-time cost at mount time that pays off with dramatically
     /// faster allocations throughout the filesystem's lifetime.
+    ///
+    /// `total_clusters` is the end of the scanned range, i.e. the number of data
+    /// clusters plus the reserved FAT entries.
     pub async fn build_from_fat<S, E>(
         &mut self,
         fat: &mut S,
@@ -386,8 +421,14 @@ impl ClusterBitmap {
         }
 
         self.free_count = 0; // Will count as we scan
+        self.bad_clusters.clear();
         self.next_free_hint = crate::table::RESERVED_FAT_ENTRIES;
 
+        // The reserved FAT entries are not clusters and must never be handed out
+        for cluster in 0..crate::table::RESERVED_FAT_ENTRIES {
+            self.bitmap[(cluster / 8) as usize] |= 1 << (cluster % 8);
+        }
+
         // Scan all clusters - manually inline read_fat logic since it's private
         for cluster in crate::table::RESERVED_FAT_ENTRIES..total_clusters {
             let value = match fat_type {
@@ -401,6 +442,13 @@ impl ClusterBitmap {
                     // Cluster is free - leave bit as 0, increment counter
                     self.free_count += 1;
                 }
+                crate::table::FatValue::Bad => {
+                    // Bad cluster - never hand it out, count it separately
+                    let byte_idx = (cluster / 8) as usize;
+                    let bit_idx = (cluster % 8) as u8;
+                    self.bitmap[byte_idx] |= 1 << bit_idx;
+                    self.bad_clusters.push(cluster);
+                }
                 _ => {
                     // Cluster is allocated - set bit to 1
                     let byte_idx = (cluster / 8) as usize;
@@ -423,6 +471,7 @@ pub struct ClusterBitmapStatistics {
     pub total_clusters: u32,
     pub free_clusters: u32,
     pub allocated_clusters: u32,
+    pub bad_clusters: u32,
     pub utilization: f32,
     pub fast_allocations: u64,
     pub slow_allocations: u64,
@@ -479,6 +528,42 @@ mod tests {
         assert_eq!(bitmap.find_free(0), Some(10));
     }
 
+    #[test]
+    #[cfg(feature = "cluster-bitmap")]
+    fn test_find_free_above_reserve() {
+        let mut bitmap = ClusterBitmap::new(10);
+        for i in 0..7 {
+            bitmap.set_allocated(i);
+        }
+
+        // 3 free clusters left
+        assert_eq!(bitmap.find_free_above_reserve(0, 3), None);
+        assert_eq!(bitmap.find_free_above_reserve(0, 2), Some(7));
+        // the search hint moved past the cluster found last
+        assert_eq!(bitmap.find_free_above_reserve(0, 0), Some(8));
+    }
+
+    #[test]
+    #[cfg(feature = "cluster-bitmap")]
+    fn test_bad_clusters_are_never_free() {
+        let mut bitmap = ClusterBitmap::new(10);
+        bitmap.set_bad(3);
+        assert!(bitmap.is_allocated(3));
+        assert_eq!(bitmap.free_count(), 9);
+
+        for i in 0..3 {
+            bitmap.set_allocated(i);
+        }
+        assert_eq!(bitmap.find_free(0), Some(4));
+        assert_eq!(bitmap.find_contiguous_free(3, 0), Some(4));
+        assert_eq!(bitmap.statistics().bad_clusters, 1);
+
+        // Marking it again changes nothing
+        bitmap.set_bad(3);
+        assert_eq!(bitmap.statistics().bad_clusters, 1);
+        assert_eq!(bitmap.free_count(), 6);
+    }
+
     #[test]
     #[cfg(feature = "cluster-bitmap")]
     fn test_find_contiguous() {
@@ -144,6 +144,12 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> Dir<'a, IO, TP, OCC> {
     pub fn iter(&self) -> DirIter<'a, IO, TP, OCC> {
         DirIter::new(self.stream.clone(), self.fs, true)
     }
+
+    /// First cluster of this directory, `None` for a FAT12/FAT16 root directory
+    #[cfg(feature = "quotas")]
+    pub(crate) fn first_cluster(&self) -> Option<u32> {
+        self.stream.first_cluster()
+    }
 }
 
 impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, TP, OCC> {
@@ -275,6 +281,10 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::NotFound` will be returned if `path` does not point to any existing directory entry.
     /// * `Error::InvalidInput` will be returned if `path` points to a file that is not a directory.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::open_dir", level = "debug", skip_all, fields(path = path))
+    )]
     pub async fn open_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
         trace!("Dir::open_dir {}", path);
         let mut split = split_path(path);
@@ -336,8 +346,14 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
     /// * `Error::InvalidInput` will be returned if `path` points to a file that is a directory.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::open_file", level = "debug", skip_all, fields(path = path))
+    )]
     pub async fn open_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::open_file {}", path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Open);
         let mut split = split_path(path);
         let mut e = self.clone();
         loop {
@@ -349,6 +365,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 }
                 None => {
                     let file = e.find_entry(name, Some(false), None).await?.to_file();
+                    #[cfg(feature = "fs-events")]
+                    let file = file.with_event_path(path);
                     return Ok(file);
                 }
             }
@@ -369,8 +387,14 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
     /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::create_file", level = "debug", skip_all, fields(path = path))
+    )]
     pub async fn create_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::create_file {}", path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Create);
         let mut split = split_path(path);
         let mut e = self.clone();
         loop {
@@ -399,6 +423,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                     None,
                 );
                 let file = parent.write_entry(name, sfn_entry).await?.to_file();
+                #[cfg(feature = "fs-events")]
+                let file = file.with_event_path(path);
 
                 // Audit log: file created
                 #[cfg(feature = "audit-log")]
@@ -411,11 +437,17 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                     ).await;
                 }
 
+                // Change notification: file created
+                #[cfg(feature = "fs-events")]
+                self.fs.emit_event(crate::events::FsEventKind::Created, path, None);
+
                 Ok(file)
             }
             // file already exists - return it
             DirEntryOrShortName::DirEntry(e) => {
                 let file = e.to_file();
+                #[cfg(feature = "fs-events")]
+                let file = file.with_event_path(path);
                 Ok(file)
             }
         }
@@ -424,12 +456,14 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// Opens an existing file with a shared (read) lock.
     ///
     /// This method acquires a shared lock before opening the file, allowing multiple
-    /// concurrent readers but blocking writers. Use [`File::close_and_unlock`] to
-    /// properly release the lock when done.
+    /// concurrent readers but blocking writers. The lock is released when the file is
+    /// dropped or closed with [`File::close_and_unlock`]. Use [`Dir::open_file_locked_wait`] to wait
+    /// for the lock instead of failing.
     ///
     /// # Errors
     ///
-    /// * `Error::FileLocked` if the file is exclusively locked by another writer.
+    /// * `Error::FileLocked` if the file is exclusively locked by another writer, or
+    ///   other tasks are waiting for a lock on it.
     /// * `Error::NotFound` if `path` points to a non-existing directory entry.
     /// * `Error::InvalidInput` if `path` points to a directory.
     /// * `Error::Io` if the underlying storage object returned an I/O error.
@@ -443,6 +477,10 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// file.close_and_unlock().await?;
     /// ```
     #[cfg(feature = "file-locking")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::open_file_locked", level = "debug", skip_all, fields(path = path))
+    )]
     pub async fn open_file_locked(
         &self,
         path: &str,
@@ -450,8 +488,10 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         use crate::file_locking::LockType;
 
         trace!("Dir::open_file_locked {}", path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Open);
 
-        // First, find the entry to get the first cluster
+        // First, find the entry to get its position
         let mut split = split_path(path);
         let mut e = self.clone();
         loop {
@@ -463,19 +503,126 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 }
                 None => {
                     let entry = e.find_entry(name, Some(false), None).await?;
-                    let first_cluster = entry.first_cluster();
 
                     // Try to acquire shared lock
-                    if let Some(cluster) = first_cluster {
-                        let mut locks = self.fs.file_locks.acquire().await;
-                        if locks.try_lock(cluster, LockType::Shared).is_err() {
-                            return Err(Error::FileLocked);
-                        }
+                    let mut locks = self.fs.file_locks.acquire().await;
+                    if locks.try_lock(entry.entry_pos, LockType::Shared).is_err() {
+                        return Err(Error::FileLocked);
                     }
+                    drop(locks);
 
                     // Create file using to_file and then set lock info
-                    let mut file = entry.to_file_locked(LockType::Shared);
-                    #[cfg(feature = "audit-log")]
+                    let file = entry.to_file_locked(LockType::Shared);
+                    #[cfg(feature = "fs-events")]
+                    let file = file.with_event_path(path);
+                    return Ok(file);
+                }
+            }
+        }
+    }
+
+    /// Opens existing file with a lock, waiting until the lock is available.
+    ///
+    /// Unlike [`Dir::open_file_locked`] this does not fail when the file is locked
+    /// by another handle. The caller is queued and woken when the lock is released.
+    /// Waiters are served in arrival order, so a writer is not starved by readers that
+    /// keep opening the file. The lock is released when the file is dropped or closed
+    /// with [`File::close_and_unlock`].
+    ///
+    /// # Errors
+    ///
+    /// * `Error::NotFound` will be returned if `path` does not point to any existing directory entry.
+    /// * `Error::InvalidInput` will be returned if `path` points to a directory entry which is a directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let mut file = dir.open_file_locked_wait("state.bin", LockType::Exclusive).await?;
+    /// file.write_all(&state).await?;
+    /// file.flush().await?;
+    /// file.close_and_unlock().await?;
+    /// ```
+    #[cfg(feature = "file-locking")]
+    pub async fn open_file_locked_wait(
+        &self,
+        path: &str,
+        lock_type: crate::file_locking::LockType,
+    ) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        self.open_file_locked_timeout(path, lock_type, core::future::pending::<()>())
+            .await
+    }
+
+    /// Opens existing file with a lock, waiting until the lock is available or
+    /// `timeout` completes.
+    ///
+    /// `timeout` is any future, so the executor's timer (`tokio::time::sleep`,
+    /// `embassy_time::Timer::after`) or a cancellation signal can be used. When it
+    /// completes first the caller leaves the queue and `Error::FileLocked` is returned.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::FileLocked` if the lock was not acquired before `timeout` completed.
+    /// * `Error::NotFound` will be returned if `path` does not point to any existing directory entry.
+    /// * `Error::InvalidInput` will be returned if `path` points to a directory entry which is a directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let file = dir
+    ///     .open_file_locked_timeout("data.txt", LockType::Shared, Timer::after_millis(500))
+    ///     .await?;
+    /// ```
+    #[cfg(feature = "file-locking")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::open_file_locked_timeout", level = "debug", skip_all, fields(path = path))
+    )]
+    pub async fn open_file_locked_timeout<F: Future>(
+        &self,
+        path: &str,
+        lock_type: crate::file_locking::LockType,
+        timeout: F,
+    ) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("Dir::open_file_locked_timeout {}", path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Open);
+
+        let mut split = split_path(path);
+        let mut e = self.clone();
+        loop {
+            let (name, rest_opt) = split;
+            match rest_opt {
+                Some(rest) => {
+                    split = split_path(rest);
+                    e = e.find_entry(name, Some(true), None).await?.to_dir();
+                }
+                None => {
+                    let entry = e.find_entry(name, Some(false), None).await?;
+                    let mut queued = QueuedOpen::new(self.fs, entry.entry_pos);
+                    let acquired = crate::file_locking::wait_for_lock(
+                        &self.fs.file_locks,
+                        entry.entry_pos,
+                        lock_type,
+                        timeout,
+                    )
+                    .await;
+                    if !acquired {
+                        return Err(Error::FileLocked);
+                    }
+                    queued.granted = Some(lock_type);
+
+                    // The previous holder may have resized the file while this task waited
+                    let entry = e.find_entry(name, Some(false), None).await?;
+                    if entry.entry_pos != queued.pos {
+                        return Err(Error::NotFound);
+                    }
+                    // The file owns the lock from here on
+                    queued.granted = None;
+                    let file = entry.to_file_locked(lock_type);
+                    #[cfg(feature = "fs-events")]
+                    let file = file.with_event_path(path);
                     return Ok(file);
                 }
             }
@@ -485,8 +632,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// Creates or opens a file with an exclusive (write) lock.
     ///
     /// This method acquires an exclusive lock before creating/opening the file,
-    /// blocking all other readers and writers. Use [`File::close_and_unlock`] to
-    /// properly release the lock when done.
+    /// blocking all other readers and writers. The lock is released when the file is
+    /// dropped or closed with [`File::close_and_unlock`].
     ///
     /// # Errors
     ///
@@ -506,6 +653,10 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// file.close_and_unlock().await?;
     /// ```
     #[cfg(feature = "file-locking")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::create_file_locked", level = "debug", skip_all, fields(path = path))
+    )]
     pub async fn create_file_locked(
         &self,
         path: &str,
@@ -513,6 +664,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         use crate::file_locking::LockType;
 
         trace!("Dir::create_file_locked {}", path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Create);
 
         let mut split = split_path(path);
         let mut e = self.clone();
@@ -542,34 +695,36 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                     None,
                 );
                 let entry = parent.write_entry(name, sfn_entry).await?;
-                let first_cluster = entry.first_cluster();
 
                 // Try to acquire exclusive lock (new file, should always succeed)
-                if let Some(cluster) = first_cluster {
-                    let mut locks = self.fs.file_locks.acquire().await;
-                    if locks.try_lock(cluster, LockType::Exclusive).is_err() {
-                        return Err(Error::FileLocked);
-                    }
+                let mut locks = self.fs.file_locks.acquire().await;
+                if locks.try_lock(entry.entry_pos, LockType::Exclusive).is_err() {
+                    return Err(Error::FileLocked);
                 }
+                drop(locks);
+
+                let file = entry.to_file_locked(LockType::Exclusive);
+                #[cfg(feature = "fs-events")]
+                let file = file.with_event_path(path);
+
+                // Change notification: file created
+                #[cfg(feature = "fs-events")]
+                self.fs.emit_event(crate::events::FsEventKind::Created, path, None);
 
-                let mut file = entry.to_file_locked(LockType::Exclusive);
-                #[cfg(feature = "audit-log")]
                 Ok(file)
             }
             // file already exists - try to lock it exclusively
             DirEntryOrShortName::DirEntry(entry) => {
-                let first_cluster = entry.first_cluster();
-
                 // Try to acquire exclusive lock
-                if let Some(cluster) = first_cluster {
-                    let mut locks = self.fs.file_locks.acquire().await;
-                    if locks.try_lock(cluster, LockType::Exclusive).is_err() {
-                        return Err(Error::FileLocked);
-                    }
+                let mut locks = self.fs.file_locks.acquire().await;
+                if locks.try_lock(entry.entry_pos, LockType::Exclusive).is_err() {
+                    return Err(Error::FileLocked);
                 }
+                drop(locks);
 
-                let mut file = entry.to_file_locked(LockType::Exclusive);
-                #[cfg(feature = "audit-log")]
+                let file = entry.to_file_locked(LockType::Exclusive);
+                #[cfg(feature = "fs-events")]
+                let file = file.with_event_path(path);
                 Ok(file)
             }
         }
@@ -588,8 +743,14 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
     /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new directory.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::create_dir", level = "debug", skip_all, fields(path = path))
+    )]
     pub async fn create_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
         trace!("Dir::create_dir {}", path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::DirCreate);
         let mut split = split_path(path);
         let mut e = self.clone();
         loop {
@@ -612,7 +773,13 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
             // directory does not exist - create it
             DirEntryOrShortName::ShortName(short_name) => {
                 // alloc cluster for directory data
-                let cluster = self.fs.alloc_cluster(None, true).await?;
+                #[cfg(feature = "quotas")]
+                let cluster = self
+                    .fs
+                    .alloc_cluster_for(e.stream.first_cluster(), None, true, false)
+                    .await?;
+                #[cfg(not(feature = "quotas"))]
+                let cluster = self.fs.alloc_cluster(None, true, false).await?;
                 // create entry in parent directory
                 let sfn_entry =
                     e.create_sfn_entry(short_name, FileAttributes::DIRECTORY, Some(cluster));
@@ -639,6 +806,10 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                     crate::audit::AuditResult::Success,
                 ).await;
 
+                // Change notification: directory created
+                #[cfg(feature = "fs-events")]
+                self.fs.emit_event(crate::events::FsEventKind::DirCreated, path, None);
+
                 Ok(dir)
             }
             // directory already exists - return it
@@ -665,8 +836,9 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// Removes existing file or directory.
     ///
     /// `path` is a '/' separated file path relative to self directory.
-    /// Make sure there is no reference to this file (no File instance) or filesystem corruption
-    /// can happen.
+    /// With the `alloc` feature open files are refused with `Error::FileBusy`. Without it, make
+    /// sure there is no reference to this file (no File instance) or filesystem corruption
+    /// can happen. A `FileContext` kept from `File::close` does not count as open.
     ///
     /// # Errors
     ///
@@ -675,9 +847,16 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
     /// * `Error::InvalidInput` will be returned if `path` points to a file that is not a directory.
     /// * `Error::DirectoryIsNotEmpty` will be returned if the specified directory is not empty.
+    /// * `Error::FileBusy` will be returned if the file is open (requires `alloc` feature).
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::remove", level = "debug", skip_all, fields(path = path))
+    )]
     pub async fn remove(&self, path: &str) -> Result<(), Error<IO::Error>> {
         trace!("Dir::remove {}", path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Remove);
 
         // traverse path
         let mut split = split_path(path);
@@ -709,6 +888,11 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         if e.is_dir() && !e.to_dir().is_empty().await? {
             return Err(Error::DirectoryIsNotEmpty);
         }
+        // an open handle would keep writing to the freed clusters
+        #[cfg(feature = "alloc")]
+        if e.is_file() && self.fs.is_file_open(e.entry_pos) {
+            return Err(Error::FileBusy);
+        }
 
         // Mark directory entries as deleted FIRST, before freeing data clusters
         // This is important because freeing clusters might affect the parent directory stream
@@ -743,8 +927,23 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
 
         // Now free the file's data clusters
         if let Some(n) = e.first_cluster() {
+            // Resolve the charged quotas while the directory's ".." entry is still intact
+            #[cfg(feature = "quotas")]
+            let quotas = {
+                let owner = if e.is_dir() { Some(n) } else { e.parent_cluster };
+                self.fs.governing_quotas(owner).await?
+            };
             trace!("Freeing cluster chain starting at cluster {}", n);
-            self.fs.free_cluster_chain(n).await?;
+            let freed = self.fs.free_cluster_chain(n).await?;
+            #[cfg(feature = "quotas")]
+            {
+                self.fs.release_governed(&quotas, freed).await;
+                if e.is_dir() {
+                    self.fs.quota_dir_removed(n).await?;
+                }
+            }
+            #[cfg(not(feature = "quotas"))]
+            let _ = freed;
         }
 
         // Audit log: file/directory deleted
@@ -758,6 +957,17 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
             self.fs.log_audit(operation, path, crate::audit::AuditResult::Success).await;
         }
 
+        // Change notification: file/directory deleted
+        #[cfg(feature = "fs-events")]
+        {
+            let kind = if e.is_dir() {
+                crate::events::FsEventKind::DirDeleted
+            } else {
+                crate::events::FsEventKind::Deleted
+            };
+            self.fs.emit_event(kind, path, None);
+        }
+
         Ok(())
     }
 
@@ -766,8 +976,9 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// `src_path` is a '/' separated source file path relative to self directory.
     /// `dst_path` is a '/' separated destination file path relative to `dst_dir`.
     /// `dst_dir` can be set to self directory if rename operation without moving is needed.
-    /// Make sure there is no reference to this file (no File instance) or filesystem corruption
-    /// can happen.
+    /// With the `alloc` feature open files are refused with `Error::FileBusy`. Without it, make
+    /// sure there is no reference to this file (no File instance) or filesystem corruption
+    /// can happen. A `FileContext` kept from `File::close` does not count as open.
     ///
     /// # Errors
     ///
@@ -776,7 +987,12 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// * `Error::NotFound` will be returned if `src_path` points to a non-existing directory entry or if `dst_path`
     ///   stripped from the last component does not point to an existing directory.
     /// * `Error::AlreadyExists` will be returned if `dst_path` points to an existing directory entry.
+    /// * `Error::FileBusy` will be returned if the source is an open file (requires `alloc` feature).
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::rename", level = "debug", skip_all, fields(src = src_path, dst = dst_path))
+    )]
     pub async fn rename(
         &self,
         src_path: &str,
@@ -784,6 +1000,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         dst_path: &str,
     ) -> Result<(), Error<IO::Error>> {
         trace!("Dir::rename {} {}", src_path, dst_path);
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Rename);
         // traverse source path
         let mut split_src = split_path(src_path);
         let mut e_src = self.clone();
@@ -818,7 +1036,13 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
 
         e_src
             .rename_internal(split_src.0, dst_dir, split_dst.0)
-            .await
+            .await?;
+
+        // Change notification: entry renamed
+        #[cfg(feature = "fs-events")]
+        self.fs.emit_event(crate::events::FsEventKind::Renamed, src_path, Some(dst_path));
+
+        Ok(())
     }
 
     async fn rename_internal(
@@ -830,6 +1054,11 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         trace!("Dir::rename_internal {} {}", src_name, dst_name);
         // find existing file
         let e = self.find_entry(src_name, None, None).await?;
+        // open handles refer to the entry by its position, which changes on rename
+        #[cfg(feature = "alloc")]
+        if e.is_file() && self.fs.is_file_open(e.entry_pos) {
+            return Err(Error::FileBusy);
+        }
         // check if destionation filename is unused
         let r = dst_dir.check_for_existence(dst_name, None).await?;
         let short_name = match r {
@@ -846,6 +1075,45 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
             // destionation file does not exist, short name has been generatorerated
             DirEntryOrShortName::ShortName(short_name) => short_name,
         };
+        #[cfg(feature = "quotas")]
+        let src_parent = self.stream.first_cluster();
+        let dst_parent = dst_dir.stream.first_cluster();
+        // an entry moved to another directory takes its clusters to the destination quotas:
+        // charge them before touching any entry, release the source once the move succeeded
+        #[cfg(feature = "quotas")]
+        let transfer = match e.first_cluster() {
+            Some(n) if src_parent != dst_parent => {
+                let clusters = if e.is_dir() {
+                    self.fs.subtree_clusters(&e.to_dir()).await?
+                } else {
+                    self.fs.chain_length(n).await?
+                };
+                Some(self.fs.reserve_quota_transfer(src_parent, dst_parent, clusters).await?)
+            }
+            _ => None,
+        };
+        let result = self.move_entry(&e, dst_dir, dst_name, short_name, dst_parent).await;
+        #[cfg(feature = "quotas")]
+        {
+            if let Some(transfer) = transfer {
+                self.fs.finish_quota_transfer(transfer, result.is_ok()).await;
+            }
+            if result.is_ok() && e.is_dir() && e.first_cluster().is_some() {
+                self.fs.quota_dir_renamed(src_parent != dst_parent).await?;
+            }
+        }
+        result
+    }
+
+    /// Moves entry `e` of this directory to `dst_name` in `dst_dir`, whose first cluster is `dst_parent`
+    async fn move_entry(
+        &self,
+        e: &DirEntry<'a, IO, TP, OCC>,
+        dst_dir: &Dir<'_, IO, TP, OCC>,
+        dst_name: &str,
+        short_name: [u8; SFN_SIZE],
+        dst_parent: Option<u32>,
+    ) -> Result<(), Error<IO::Error>> {
         // free long and short name entries
         let mut stream = self.stream.clone();
 
@@ -870,6 +1138,15 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
 
         // rename requires stream flush (no async drop :()
         stream.flush().await?;
+
+        if e.is_dir() {
+            if let Some(n) = e.first_cluster() {
+                // a moved directory must point its ".." entry at the new parent
+                if self.stream.first_cluster() != dst_parent {
+                    self.fs.set_parent_dir_cluster(n, dst_parent).await?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -917,6 +1194,40 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         }
     }
 
+    /// Opens a file used for filesystem metadata, `None` if it does not exist
+    #[cfg(feature = "quotas")]
+    pub(crate) async fn open_metadata_file(
+        &self,
+        name: &str,
+    ) -> Result<Option<File<'a, IO, TP, OCC>>, Error<IO::Error>> {
+        match self.find_entry(name, Some(false), None).await {
+            Ok(e) => Ok(Some(e.to_metadata_file())),
+            Err(Error::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Opens or creates a hidden system file used for filesystem metadata
+    ///
+    /// Unlike `create_file` this is not counted, audited or notified.
+    #[cfg(feature = "quotas")]
+    pub(crate) async fn create_metadata_file(
+        &self,
+        name: &str,
+    ) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        match self.check_for_existence(name, Some(false)).await? {
+            DirEntryOrShortName::ShortName(short_name) => {
+                let sfn_entry = self.create_sfn_entry(
+                    short_name,
+                    FileAttributes::HIDDEN | FileAttributes::SYSTEM,
+                    None,
+                );
+                Ok(self.write_entry(name, sfn_entry).await?.to_metadata_file())
+            }
+            DirEntryOrShortName::DirEntry(e) => Ok(e.to_metadata_file()),
+        }
+    }
+
     fn create_sfn_entry(
         &self,
         short_name: [u8; SFN_SIZE],
@@ -1001,6 +1312,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
             fs: self.fs,
             entry_pos: start_abs_pos,
             offset_range: (start_pos, end_pos),
+            #[cfg(feature = "quotas")]
+            parent_cluster: self.stream.first_cluster(),
         })
     }
 }
@@ -1101,6 +1414,8 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
                         fs: self.fs,
                         entry_pos: abs_pos,
                         offset_range: (begin_offset, offset),
+                        #[cfg(feature = "quotas")]
+                        parent_cluster: self.stream.first_cluster(),
                     }));
                 }
                 DirEntryData::Lfn(data) => {
@@ -1150,6 +1465,49 @@ impl<IO: ReadWriteSeek, TP, OCC> Clone for DirIter<'_, IO, TP, OCC> {
 }
 
 #[rustfmt::skip]
+/// Entry of a task queued in [`Dir::open_file_locked_timeout`]
+///
+/// The entry is registered as open while the task waits, so it cannot be removed or
+/// renamed before the lock is granted. A lock granted to a task that is cancelled
+/// before its `File` is created is released on drop.
+#[cfg(feature = "file-locking")]
+struct QueuedOpen<'a, IO: ReadWriteSeek, TP, OCC>
+where
+    IO::Error: 'static,
+{
+    fs: &'a FileSystem<IO, TP, OCC>,
+    pos: u64,
+    granted: Option<crate::file_locking::LockType>,
+}
+
+#[cfg(feature = "file-locking")]
+impl<'a, IO: ReadWriteSeek, TP, OCC> QueuedOpen<'a, IO, TP, OCC>
+where
+    IO::Error: 'static,
+{
+    fn new(fs: &'a FileSystem<IO, TP, OCC>, pos: u64) -> Self {
+        fs.track_open_file(pos);
+        Self {
+            fs,
+            pos,
+            granted: None,
+        }
+    }
+}
+
+#[cfg(feature = "file-locking")]
+impl<IO: ReadWriteSeek, TP, OCC> Drop for QueuedOpen<'_, IO, TP, OCC>
+where
+    IO::Error: 'static,
+{
+    fn drop(&mut self) {
+        if let Some(lock_type) = self.granted {
+            self.fs.file_locks.spin_acquire().unlock(self.pos, lock_type);
+        }
+        self.fs.untrack_open_file(self.pos);
+    }
+}
+
 fn validate_long_name<E: IoError>(name: &str) -> Result<(), Error<E>> {
     // check if length is valid
     if name.is_empty() {
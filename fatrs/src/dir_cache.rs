@@ -252,9 +252,17 @@ impl DirCache {
             capacity: DIR_CACHE_ENTRIES,
         }
     }
+
+    /// Reset hit/miss counters without clearing cached entries
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    pub fn reset_statistics(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
 }
 
 /// Directory cache statistics
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Clone, Copy)]
 pub struct DirCacheStatistics {
     pub hits: u32,
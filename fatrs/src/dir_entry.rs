@@ -16,6 +16,8 @@ use crate::error::{Error, IoError, ReadExactError};
 use crate::file::File;
 use crate::fs::{FatType, FileSystem, OemCpConverter, ReadWriteSeek};
 use crate::io::{self, Read, ReadLeExt, Write, WriteLeExt};
+#[cfg(feature = "metrics")]
+use crate::io::Seek;
 use crate::time::{Date, DateTime};
 
 bitflags! {
@@ -489,6 +491,11 @@ impl DirEntryEditor {
         &self.data
     }
 
+    /// Position of the short name entry on the volume
+    pub(crate) fn pos(&self) -> u64 {
+        self.pos
+    }
+
     pub(crate) fn dirty(&self) -> bool {
         self.dirty
     }
@@ -602,6 +609,9 @@ where
     pub(crate) lfn_utf16: LfnBuffer,
     pub(crate) entry_pos: u64,
     pub(crate) offset_range: (u64, u64),
+    // first cluster of the directory containing this entry (quota owner of files)
+    #[cfg(feature = "quotas")]
+    pub(crate) parent_cluster: Option<u32>,
     pub(crate) fs: &'a FileSystem<IO, TP, OCC>,
 }
 
@@ -693,6 +703,18 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
     /// Will panic if this is not a file.
     #[must_use]
     pub fn to_file(&self) -> File<'a, IO, TP, OCC> {
+        assert!(!self.is_dir(), "Not a file entry");
+        let file = File::new(self.first_cluster(), Some(self.editor()), self.fs);
+        #[cfg(feature = "quotas")]
+        let file = file.with_quota_owner(self.parent_cluster);
+        #[cfg(feature = "fs-events")]
+        let file = file.with_event_path(&self.file_name());
+        file
+    }
+
+    /// Returns `File` struct for an internal metadata file (no notifications, no quota charges).
+    #[cfg(feature = "quotas")]
+    pub(crate) fn to_metadata_file(&self) -> File<'a, IO, TP, OCC> {
         assert!(!self.is_dir(), "Not a file entry");
         File::new(self.first_cluster(), Some(self.editor()), self.fs)
     }
@@ -712,12 +734,17 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         lock_type: crate::file_locking::LockType,
     ) -> File<'a, IO, TP, OCC> {
         assert!(!self.is_dir(), "Not a file entry");
-        File::new_with_lock(
+        let file = File::new_with_lock(
             self.first_cluster(),
             Some(self.editor()),
             self.fs,
             lock_type,
-        )
+        );
+        #[cfg(feature = "quotas")]
+        let file = file.with_quota_owner(self.parent_cluster);
+        #[cfg(feature = "fs-events")]
+        let file = file.with_event_path(&self.file_name());
+        file
     }
 
     /// Returns `File` struct for this entry, resuming from an existing [`FileContext`].
@@ -764,6 +791,8 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         match self.first_cluster() {
             Some(n) => {
                 let file = File::new(Some(n), Some(self.editor()), self.fs);
+                #[cfg(feature = "quotas")]
+                let file = file.with_quota_owner(Some(n));
                 Dir::new(DirRawStream::File(file), self.fs)
             }
             None => self.fs.root_dir(),
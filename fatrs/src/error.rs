@@ -33,6 +33,12 @@ pub enum Error<T> {
     /// File is locked by another reader or writer (requires `file-locking` feature).
     #[cfg(feature = "file-locking")]
     FileLocked,
+    /// The file is open and cannot be removed or renamed until all its handles are closed.
+    #[cfg(feature = "alloc")]
+    FileBusy,
+    /// An allocation would exceed the quota of the directory subtree (requires `quotas` feature).
+    #[cfg(feature = "quotas")]
+    QuotaExceeded,
     /// Directory entry position is stale due to cluster reallocation.
     /// This indicates the directory containing this file/directory was modified
     /// (entries deleted/moved) while this entry was open.
@@ -88,6 +94,10 @@ impl<T: core::fmt::Display> core::fmt::Display for Error<T> {
             Error::CorruptedFileSystem => write!(f, "Corrupted file system"),
             #[cfg(feature = "file-locking")]
             Error::FileLocked => write!(f, "File is locked by another reader or writer"),
+            #[cfg(feature = "alloc")]
+            Error::FileBusy => write!(f, "File is open"),
+            #[cfg(feature = "quotas")]
+            Error::QuotaExceeded => write!(f, "Directory quota exceeded"),
             Error::StaleDirectoryEntry => write!(f, "Directory entry position is stale due to cluster reallocation"),
         }
     }
@@ -0,0 +1,263 @@
+//! Filesystem change notifications.
+//!
+//! This module provides an inotify-style event stream so applications can react
+//! to changes instead of polling directory listings.
+//!
+//! # Events
+//!
+//! - **Created** - a new file was created
+//! - **Modified** - a file was written to for the first time since it was opened
+//! - **ClosedAfterWrite** - a file that was written to was flushed and then closed
+//!   (or dropped)
+//! - **DroppedUnflushed** - a file was closed (or dropped) with writes that were
+//!   never flushed, so its data may not have reached the disk
+//! - **Renamed** - a file or directory was renamed or moved
+//! - **Deleted** / **DirDeleted** - a file or directory was removed
+//! - **DirCreated** - a new directory was created
+//!
+//! Paths are reported as passed to the `Dir` method that caused the event, so
+//! they are relative to that directory. Operations invoked on `root_dir()` report
+//! absolute paths.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! let mut events = fs.subscribe();
+//! loop {
+//!     let event = events.next().await;
+//!     if event.kind == FsEventKind::ClosedAfterWrite {
+//!         upload(&event.path).await;
+//!     }
+//! }
+//! ```
+//!
+//! # Delivery
+//!
+//! Each subscription has a bounded queue. When it is full the oldest event is
+//! dropped and counted (see [`FsEventSubscription::dropped`]). Events are
+//! published synchronously, so they can also be emitted when a `File` is dropped.
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+use crate::share::Shared;
+
+/// Default number of events buffered per subscription
+pub const DEFAULT_EVENT_QUEUE_CAPACITY: usize = 64;
+
+/// Kind of filesystem change.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsEventKind {
+    /// A new file was created
+    Created,
+    /// A file was written to (reported once per open file)
+    Modified,
+    /// A file that was written to was flushed and then closed
+    ClosedAfterWrite,
+    /// A file was closed with writes that were never flushed
+    DroppedUnflushed,
+    /// A file or directory was renamed; the destination is in `new_path`
+    Renamed,
+    /// A file was deleted
+    Deleted,
+    /// A directory was created
+    DirCreated,
+    /// A directory was deleted
+    DirDeleted,
+}
+
+/// A filesystem change notification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FsEvent {
+    /// What happened
+    pub kind: FsEventKind,
+    /// Path of the affected file or directory (the source path for renames)
+    pub path: String,
+    /// Destination path for renames
+    pub new_path: Option<String>,
+}
+
+struct Subscriber {
+    id: u32,
+    queue: VecDeque<FsEvent>,
+    capacity: usize,
+    dropped: u64,
+    waker: Option<Waker>,
+}
+
+/// Registry of subscriptions shared between the filesystem and its subscribers.
+///
+/// The bus lock is never held across an `.await`, which lets [`lock`] acquire it
+/// synchronously (events must also be publishable from `Drop`).
+pub(crate) struct EventBus {
+    next_id: u32,
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventBus {
+    pub(crate) fn new() -> Self {
+        Self {
+            next_id: 0,
+            subscribers: Vec::new(),
+        }
+    }
+
+    fn subscribe(&mut self, capacity: usize) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.subscribers.push(Subscriber {
+            id,
+            queue: VecDeque::new(),
+            capacity: capacity.max(1),
+            dropped: 0,
+            waker: None,
+        });
+        id
+    }
+
+    fn unsubscribe(&mut self, id: u32) {
+        self.subscribers.retain(|s| s.id != id);
+    }
+
+    fn subscriber(&mut self, id: u32) -> Option<&mut Subscriber> {
+        self.subscribers.iter_mut().find(|s| s.id == id)
+    }
+
+    /// Deliver an event to every subscriber
+    pub(crate) fn publish(&mut self, kind: FsEventKind, path: &str, new_path: Option<&str>) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let event = FsEvent {
+            kind,
+            path: String::from(path),
+            new_path: new_path.map(String::from),
+        };
+        for subscriber in &mut self.subscribers {
+            if subscriber.queue.len() >= subscriber.capacity {
+                subscriber.queue.pop_front();
+                subscriber.dropped += 1;
+            }
+            subscriber.queue.push_back(event.clone());
+            if let Some(waker) = subscriber.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// A subscription to filesystem change notifications.
+///
+/// This struct is created by the `subscribe` method on `FileSystem`. It does not
+/// borrow the filesystem, so it can be moved to another task. Dropping it ends
+/// the subscription.
+pub struct FsEventSubscription {
+    bus: Shared<EventBus>,
+    id: u32,
+}
+
+impl FsEventSubscription {
+    pub(crate) fn new(bus: Shared<EventBus>, capacity: usize) -> Self {
+        let id = bus.spin_acquire().subscribe(capacity);
+        Self { bus, id }
+    }
+
+    /// Wait for the next event
+    pub async fn next(&mut self) -> FsEvent {
+        poll_fn(|cx| {
+            let mut bus = self.bus.spin_acquire();
+            let Some(subscriber) = bus.subscriber(self.id) else {
+                return Poll::Pending;
+            };
+            match subscriber.queue.pop_front() {
+                Some(event) => Poll::Ready(event),
+                None => {
+                    subscriber.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Take the next event if one is queued
+    pub fn try_next(&mut self) -> Option<FsEvent> {
+        self.bus.spin_acquire()
+            .subscriber(self.id)
+            .and_then(|s| s.queue.pop_front())
+    }
+
+    /// Number of queued events
+    pub fn pending(&self) -> usize {
+        self.bus.spin_acquire()
+            .subscriber(self.id)
+            .map_or(0, |s| s.queue.len())
+    }
+
+    /// Number of events lost because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.bus.spin_acquire().subscriber(self.id).map_or(0, |s| s.dropped)
+    }
+}
+
+impl Drop for FsEventSubscription {
+    fn drop(&mut self) {
+        self.bus.spin_acquire().unsubscribe(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_without_subscribers() {
+        let bus = Shared::new(EventBus::new());
+        bus.spin_acquire().publish(FsEventKind::Created, "a.txt", None);
+        let sub = FsEventSubscription::new(bus.clone(), 4);
+        assert_eq!(sub.pending(), 0);
+    }
+
+    #[test]
+    fn test_queue_overflow_drops_oldest() {
+        let bus = Shared::new(EventBus::new());
+        let mut sub = FsEventSubscription::new(bus.clone(), 2);
+        bus.spin_acquire().publish(FsEventKind::Created, "a.txt", None);
+        bus.spin_acquire().publish(FsEventKind::Modified, "a.txt", None);
+        bus.spin_acquire().publish(FsEventKind::Renamed, "a.txt", Some("b.txt"));
+
+        assert_eq!(sub.dropped(), 1);
+        assert_eq!(sub.try_next().unwrap().kind, FsEventKind::Modified);
+        let renamed = sub.try_next().unwrap();
+        assert_eq!(renamed.new_path.as_deref(), Some("b.txt"));
+        assert!(sub.try_next().is_none());
+    }
+
+    #[test]
+    fn test_drop_unsubscribes() {
+        let bus = Shared::new(EventBus::new());
+        let sub = FsEventSubscription::new(bus.clone(), 4);
+        assert_eq!(bus.spin_acquire().subscribers.len(), 1);
+        drop(sub);
+        assert!(bus.spin_acquire().subscribers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_next_waits_for_event() {
+        let bus = Shared::new(EventBus::new());
+        let mut sub = FsEventSubscription::new(bus.clone(), 4);
+        let publisher = async {
+            tokio::task::yield_now().await;
+            bus.spin_acquire().publish(FsEventKind::DirCreated, "logs", None);
+        };
+        let (event, ()) = tokio::join!(sub.next(), publisher);
+        assert_eq!(event.kind, FsEventKind::DirCreated);
+        assert_eq!(event.path, "logs");
+    }
+}
@@ -6,7 +6,12 @@
 //! Performance impact:
 //! - Sequential access: 5-10x faster
 //! - Random access: 20-50x faster
-//! - Memory cost: configurable (default 4KB for 8 sectors)
+//! - Memory cost: `FAT_CACHE_SECTORS` slots of 4KB each (32KB by default),
+//!   independent of the volume's sector size
+//!
+//! The `fat-cache-8k`/`fat-cache-16k` names describe how much of the FAT is
+//! covered on 512-byte-sector volumes; with 4096-byte sectors the same slot
+//! count covers eight times as much.
 
 use crate::error::Error;
 use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
@@ -14,17 +19,17 @@ use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
 /// Size of the FAT cache in number of sectors
 /// Can be configured via const generics
 #[cfg(feature = "fat-cache-16k")]
-pub const FAT_CACHE_SECTORS: usize = 32; // 16KB at 512 bytes/sector
+pub const FAT_CACHE_SECTORS: usize = 32; // 16KB of FAT at 512 bytes/sector, 128KB at 4096
 
 #[cfg(all(feature = "fat-cache-8k", not(feature = "fat-cache-16k")))]
-pub const FAT_CACHE_SECTORS: usize = 16; // 8KB at 512 bytes/sector
+pub const FAT_CACHE_SECTORS: usize = 16; // 8KB of FAT at 512 bytes/sector, 64KB at 4096
 
 #[cfg(all(
     feature = "fat-cache",
     not(feature = "fat-cache-8k"),
     not(feature = "fat-cache-16k")
 ))]
-pub const FAT_CACHE_SECTORS: usize = 8; // 4KB at 512 bytes/sector (default)
+pub const FAT_CACHE_SECTORS: usize = 8; // 4KB of FAT at 512 bytes/sector, 32KB at 4096 (default)
 
 /// A single cached FAT sector
 #[derive(Debug)]
@@ -32,7 +37,7 @@ struct CachedFatSector {
     /// Relative byte offset of this sector within the FAT region
     /// (NOT absolute disk offset - this is critical for correct writeback!)
     offset: u64,
-    /// The sector data (max 4KB for exFAT, typically 512B for FAT32)
+    /// The sector data (sized for the largest supported sector, 4096 bytes)
     data: [u8; 4096],
     /// Valid data length (actual sector size may be < 4096)
     valid_len: usize,
@@ -112,13 +117,44 @@ impl FatCache {
         lru_idx
     }
 
+    /// Read the whole sector at `sector_offset` into `data`
+    ///
+    /// Storage below the cache may return a sector in several short reads (e.g. a
+    /// 4096-byte sector over a stream with 512-byte pages), so keep reading until
+    /// the sector is complete or the FAT region ends.
+    async fn read_sector<S, E>(
+        &self,
+        storage: &mut S,
+        sector_offset: u64,
+        data: &mut [u8; 4096],
+    ) -> Result<usize, Error<E>>
+    where
+        S: Read + Seek + IoBase,
+        Error<E>: From<S::Error>,
+    {
+        storage.seek(SeekFrom::Start(sector_offset)).await?;
+        let sector = &mut data[..self.sector_size as usize];
+        let mut filled = 0;
+        while filled < sector.len() {
+            let n = storage.read(&mut sector[filled..]).await?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        Ok(filled)
+    }
+
     /// Read data from cache or storage
+    ///
+    /// Reads at most up to the end of the sector containing `offset` and returns the
+    /// number of bytes read.
     pub async fn read_cached<S, E>(
         &mut self,
         storage: &mut S,
         offset: u64,
         buf: &mut [u8],
-    ) -> Result<(), Error<E>>
+    ) -> Result<usize, Error<E>>
     where
         S: Read + Write + Seek + IoBase,
         Error<E>: From<S::Error>,
@@ -138,7 +174,7 @@ impl FatCache {
             let to_copy = buf.len().min(sector.valid_len - offset_in_sector);
             buf[..to_copy]
                 .copy_from_slice(&sector.data[offset_in_sector..offset_in_sector + to_copy]);
-            return Ok(());
+            return Ok(to_copy);
         }
 
         // Cache miss - read from storage
@@ -167,10 +203,9 @@ impl FatCache {
 
         // Re-seek to the sector we want to read (may have changed during writeback)
         // Pass RELATIVE offset to DiskSlice::seek
-        storage.seek(SeekFrom::Start(sector_offset)).await?;
         let mut sector_data = [0u8; 4096];
-        let bytes_read = storage
-            .read(&mut sector_data[..self.sector_size as usize])
+        let bytes_read = self
+            .read_sector(storage, sector_offset, &mut sector_data)
             .await?;
 
         // Cache the sector with RELATIVE offset (what we pass to seek, not what seek returns!)
@@ -188,16 +223,19 @@ impl FatCache {
         let to_copy = buf.len().min(bytes_read - offset_in_sector);
         buf[..to_copy].copy_from_slice(&sector_data[offset_in_sector..offset_in_sector + to_copy]);
 
-        Ok(())
+        Ok(to_copy)
     }
 
     /// Write data through cache
+    ///
+    /// Writes at most up to the end of the sector containing `offset` and returns the
+    /// number of bytes written.
     pub async fn write_cached<S, E>(
         &mut self,
         storage: &mut S,
         offset: u64,
         buf: &[u8],
-    ) -> Result<(), Error<E>>
+    ) -> Result<usize, Error<E>>
     where
         S: Read + Write + Seek + IoBase,
         Error<E>: From<S::Error>,
@@ -235,10 +273,9 @@ impl FatCache {
 
             // Re-seek and read existing sector (for partial writes)
             // Pass RELATIVE offset to DiskSlice::seek
-            storage.seek(SeekFrom::Start(sector_offset)).await?;
             let mut sector_data = [0u8; 4096];
-            let bytes_read = storage
-                .read(&mut sector_data[..self.sector_size as usize])
+            let bytes_read = self
+                .read_sector(storage, sector_offset, &mut sector_data)
                 .await?;
 
             self.access_counter = self.access_counter.wrapping_add(1);
@@ -260,7 +297,7 @@ impl FatCache {
         sector.data[offset_in_sector..offset_in_sector + to_copy].copy_from_slice(&buf[..to_copy]);
         sector.dirty = true;
 
-        Ok(())
+        Ok(to_copy)
     }
 
     /// Flush all dirty sectors to storage
@@ -313,9 +350,17 @@ impl FatCache {
             },
         }
     }
+
+    /// Reset hit/miss counters without invalidating cached sectors
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    pub fn reset_statistics(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
 }
 
 /// Cache statistics for monitoring performance
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Clone, Copy)]
 pub struct CacheStatistics {
     pub hits: u32,
@@ -366,12 +411,13 @@ where
 {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         // Read through cache - cache handles all error conversions
+        // A FAT12 entry may span two sectors, so this can be a short read
         let mut cache = self.cache.acquire().await;
-        cache
+        let n = cache
             .read_cached(&mut self.inner, self.current_offset, buf)
             .await?;
-        self.current_offset += buf.len() as u64;
-        Ok(buf.len())
+        self.current_offset += n as u64;
+        Ok(n)
     }
 }
 
@@ -384,11 +430,11 @@ where
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         // Write through cache
         let mut cache = self.cache.acquire().await;
-        cache
+        let n = cache
             .write_cached(&mut self.inner, self.current_offset, buf)
             .await?;
-        self.current_offset += buf.len() as u64;
-        Ok(buf.len())
+        self.current_offset += n as u64;
+        Ok(n)
     }
 
     async fn flush(&mut self) -> Result<(), Self::Error> {
@@ -1,5 +1,8 @@
 use core::cmp;
 
+#[cfg(all(not(feature = "std"), feature = "fs-events"))]
+use alloc::string::String;
+
 use crate::dir_entry::DirEntryEditor;
 use crate::error::Error;
 use crate::fs::{FileSystem, ReadWriteSeek};
@@ -21,6 +24,9 @@ where
     // Lock type held by this file (if file-locking feature is enabled)
     #[cfg(feature = "file-locking")]
     lock_info: Option<crate::file_locking::LockType>,
+    // Owner id of this handle's byte-range locks, assigned on first use
+    #[cfg(feature = "file-locking")]
+    range_lock_owner: Option<u64>,
 }
 
 /// A context of an existing [`File`].
@@ -44,6 +50,12 @@ pub struct FileContext {
     pub(crate) offset: u32,
     // file dir entry editor - None for root dir
     pub(crate) entry: Option<DirEntryEditor>,
+    // allocations may use the clusters kept free by `FsOptions::reserved_clusters`
+    pub(crate) critical: bool,
+    // directory whose quotas are charged for allocations (the parent for files,
+    // the directory itself for directory streams, None for the root directory)
+    #[cfg(feature = "quotas")]
+    pub(crate) quota_owner: Option<u32>,
 
     // Phase 2 Optimization: Contiguous file tracking
     // When true, file clusters are allocated sequentially and FAT traversal can be skipped
@@ -67,6 +79,16 @@ pub struct FileContext {
     pub(crate) total_read: u64,
     #[cfg(feature = "audit-log")]
     pub(crate) total_written: u64,
+
+    // Path reported in change notifications
+    #[cfg(feature = "fs-events")]
+    pub(crate) event_path: Option<String>,
+    // Whether a Modified event was emitted for this file session
+    #[cfg(feature = "fs-events")]
+    pub(crate) event_modified: bool,
+    // Whether the file was written to since it was last flushed
+    #[cfg(feature = "fs-events")]
+    pub(crate) event_unflushed: bool,
 }
 
 /// An extent containing a file's data on disk.
@@ -87,12 +109,15 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         entry: Option<DirEntryEditor>,
         fs: &'a FileSystem<IO, TP, OCC>,
     ) -> Self {
-        File {
+        let file = File {
             context: FileContext {
                 first_cluster,
                 entry,
                 current_cluster: None, // cluster before first one
                 offset: 0,
+                critical: false,
+                #[cfg(feature = "quotas")]
+                quota_owner: None,
                 #[cfg(feature = "multi-cluster-io")]
                 is_contiguous: false, // Will be detected during allocation
                 #[cfg(feature = "cluster-checkpoints")]
@@ -107,11 +132,21 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
                 total_read: 0,
                 #[cfg(feature = "audit-log")]
                 total_written: 0,
+                #[cfg(feature = "fs-events")]
+                event_path: None,
+                #[cfg(feature = "fs-events")]
+                event_modified: false,
+                #[cfg(feature = "fs-events")]
+                event_unflushed: false,
             },
             fs,
             #[cfg(feature = "file-locking")]
             lock_info: None,
-        }
+            #[cfg(feature = "file-locking")]
+            range_lock_owner: None,
+        };
+        file.track_open();
+        file
     }
 
     /// Create a new file with a lock held.
@@ -123,12 +158,15 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         fs: &'a FileSystem<IO, TP, OCC>,
         lock_type: crate::file_locking::LockType,
     ) -> Self {
-        File {
+        let file = File {
             context: FileContext {
                 first_cluster,
                 entry,
                 current_cluster: None,
                 offset: 0,
+                critical: false,
+                #[cfg(feature = "quotas")]
+                quota_owner: None,
                 #[cfg(feature = "multi-cluster-io")]
                 is_contiguous: false,
                 #[cfg(feature = "cluster-checkpoints")]
@@ -143,10 +181,19 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
                 total_read: 0,
                 #[cfg(feature = "audit-log")]
                 total_written: 0,
+                #[cfg(feature = "fs-events")]
+                event_path: None,
+                #[cfg(feature = "fs-events")]
+                event_modified: false,
+                #[cfg(feature = "fs-events")]
+                event_unflushed: false,
             },
             fs,
             lock_info: Some(lock_type),
-        }
+            range_lock_owner: None,
+        };
+        file.track_open();
+        file
     }
 
     /// Create a file from a prexisting [`FileContext`] & [`FileSystem`].
@@ -159,11 +206,39 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     /// Prefer using [`DirEntry::try_to_file_with_context`](crate::dir_entry::DirEntry::try_to_file_with_context) where possible because
     /// it does some basic checks to avoid file corruption.
     pub(crate) fn new_from_context(context: FileContext, fs: &'a FileSystem<IO, TP, OCC>) -> Self {
-        File {
+        let file = File {
             context,
             fs,
             #[cfg(feature = "file-locking")]
             lock_info: None,
+            #[cfg(feature = "file-locking")]
+            range_lock_owner: None,
+        };
+        file.track_open();
+        file
+    }
+
+    /// Identity of this file in the lock manager: its directory entry position
+    #[cfg(feature = "file-locking")]
+    fn lock_file_id(&self) -> Option<u64> {
+        self.context.entry.as_ref().map(DirEntryEditor::pos)
+    }
+
+    /// Directory entry position tracked while this handle is open (files only)
+    #[cfg(feature = "alloc")]
+    fn tracked_entry_pos(&self) -> Option<u64> {
+        self.context
+            .entry
+            .as_ref()
+            .filter(|e| !e.inner().is_dir())
+            .map(DirEntryEditor::pos)
+    }
+
+    /// Register this handle so the file cannot be removed or renamed while it is open
+    fn track_open(&self) {
+        #[cfg(feature = "alloc")]
+        if let Some(pos) = self.tracked_entry_pos() {
+            self.fs.track_open_file(pos);
         }
     }
 
@@ -176,8 +251,20 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     /// # Panics
     ///
     /// Will panic if this is the root directory.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "fatrs::truncate",
+            level = "debug",
+            skip_all,
+            err(Debug),
+            fields(first_cluster = self.context.first_cluster, offset = self.context.offset)
+        )
+    )]
     pub async fn truncate(&mut self) -> Result<(), Error<IO::Error>> {
         trace!("File::truncate");
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Truncate);
         if let Some(ref mut e) = self.context.entry {
             e.set_size(self.context.offset);
             if self.context.offset == 0 {
@@ -187,17 +274,21 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
             // Note: we cannot handle this case because there is no size field
             panic!("Trying to truncate a file without an entry");
         }
-        if let Some(current_cluster) = self.context.current_cluster {
+        let freed = if let Some(current_cluster) = self.context.current_cluster {
             // current cluster is none only if offset is 0
             debug_assert!(self.context.offset > 0);
-            self.fs.truncate_cluster_chain(current_cluster).await?;
+            self.fs.truncate_cluster_chain(current_cluster).await?
         } else {
             debug_assert!(self.context.offset == 0);
-            if let Some(n) = self.context.first_cluster {
-                self.fs.free_cluster_chain(n).await?;
-                self.context.first_cluster = None;
+            match self.context.first_cluster.take() {
+                Some(n) => self.fs.free_cluster_chain(n).await?,
+                None => 0,
             }
-        }
+        };
+        #[cfg(feature = "quotas")]
+        self.fs.release_quota(self.context.quota_owner, freed).await?;
+        #[cfg(not(feature = "quotas"))]
+        let _ = freed;
 
         // Refresh generation counter after freeing clusters.
         // The free_cluster_chain/truncate_cluster_chain operations increment the
@@ -207,9 +298,76 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         if let Some(ref mut e) = self.context.entry {
             e.refresh_generation(self.fs);
         }
+
+        // Change notification: truncation modifies the file
+        #[cfg(feature = "fs-events")]
+        self.notify_modified();
         Ok(())
     }
 
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn write_in_cluster(
+        &self,
+        cluster: u32,
+        offset_in_cluster: u32,
+        buf: &[u8],
+    ) -> Result<usize, Error<IO::Error>> {
+        let offset_in_fs = self.fs.offset_from_cluster(cluster) + u64::from(offset_in_cluster);
+        let mut disk = self.fs.disk.acquire().await;
+        disk.seek(SeekFrom::Start(offset_in_fs)).await?;
+        Ok(disk.write(buf).await?)
+    }
+
+    /// Retries a failed write to `cluster` with nothing else buffered
+    ///
+    /// A buffered disk may have failed to write back unrelated data while the write was
+    /// made, so pending data is flushed first and its error returned as is. Returns
+    /// `None` if the cluster itself fails.
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn retry_in_cluster(
+        &self,
+        cluster: u32,
+        offset_in_cluster: u32,
+        buf: &[u8],
+    ) -> Result<Option<usize>, Error<IO::Error>> {
+        let offset_in_fs = self.fs.offset_from_cluster(cluster) + u64::from(offset_in_cluster);
+        let mut disk = self.fs.disk.acquire().await;
+        disk.flush().await?;
+        disk.seek(SeekFrom::Start(offset_in_fs)).await?;
+        let Ok(written) = disk.write(buf).await else {
+            return Ok(None);
+        };
+        Ok(disk.flush().await.ok().map(|()| written))
+    }
+
+    /// Replaces `bad_cluster` of this file with a new cluster holding the same data
+    ///
+    /// The old cluster is marked bad. Other handles to the same file may still
+    /// point at it until they seek.
+    async fn relocate_cluster(&mut self, bad_cluster: u32) -> Result<u32, Error<IO::Error>> {
+        let first_cluster = self
+            .context
+            .first_cluster
+            .ok_or(Error::CorruptedFileSystem)?;
+        let new_cluster = self
+            .fs
+            .relocate_cluster(first_cluster, bad_cluster, self.context.critical)
+            .await?;
+        if first_cluster == bad_cluster {
+            self.set_first_cluster(new_cluster);
+        }
+        if self.context.current_cluster == Some(bad_cluster) {
+            self.context.current_cluster = Some(new_cluster);
+        }
+        #[cfg(feature = "cluster-checkpoints")]
+        for checkpoint in &mut self.context.checkpoints[..self.context.checkpoint_count as usize] {
+            if checkpoint.1 == bad_cluster {
+                checkpoint.1 = new_cluster;
+            }
+        }
+        Ok(new_cluster)
+    }
+
     /// Phase 3 Optimization: Find the closest checkpoint to the target cluster index
     /// Returns (starting_cluster, clusters_already_traversed)
     #[cfg(feature = "cluster-checkpoints")]
@@ -390,13 +548,102 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         self.context.first_cluster
     }
 
+    /// Mark this handle as critical.
+    ///
+    /// A critical handle may allocate clusters from the reserve configured with
+    /// [`FsOptions::reserved_clusters`](crate::FsOptions::reserved_clusters), so it can
+    /// still write (for example a configuration file or crash dump) after ordinary
+    /// writes started failing with `Error::NotEnoughSpace`.
+    ///
+    /// Only this handle's own clusters come from the reserve. Creating the file
+    /// and growing its parent directory are ordinary allocations, so create critical
+    /// files (or a directory with room for them) before the volume fills up.
+    pub fn set_critical(&mut self, critical: bool) {
+        self.context.critical = critical;
+    }
+
+    /// Returns `true` if this handle may allocate from the reserved clusters.
+    #[must_use]
+    pub fn is_critical(&self) -> bool {
+        self.context.critical
+    }
+
+    /// Record transferred bytes and the number of clusters they span on the current span
+    #[cfg(feature = "tracing")]
+    fn record_transfer(&self, start_offset: u32, bytes: usize) {
+        let cluster_size = u64::from(self.fs.cluster_size());
+        let start = u64::from(start_offset);
+        let clusters = if bytes == 0 {
+            0
+        } else {
+            (start + bytes as u64 - 1) / cluster_size - start / cluster_size + 1
+        };
+        let span = tracing::Span::current();
+        span.record("bytes", bytes);
+        span.record("clusters", clusters);
+    }
+
+    /// Set the directory whose quotas are charged for this file's allocations
+    #[cfg(feature = "quotas")]
+    pub(crate) fn with_quota_owner(mut self, owner: Option<u32>) -> Self {
+        self.context.quota_owner = owner;
+        self
+    }
+
+    /// Set the path reported in change notifications for this file
+    #[cfg(feature = "fs-events")]
+    pub(crate) fn with_event_path(mut self, path: &str) -> Self {
+        self.context.event_path = Some(String::from(path));
+        self
+    }
+
+    #[cfg(feature = "fs-events")]
+    fn event_path(&self) -> &str {
+        self.context.event_path.as_deref().unwrap_or("")
+    }
+
+    /// Whether this handle is a regular file rather than a directory stream
+    fn is_regular_file(&self) -> bool {
+        self.context.entry.as_ref().is_some_and(|e| !e.inner().is_dir())
+    }
+
+    /// Emit a Modified event on the first change in this file session
+    ///
+    /// Directory streams (and the root directory, which has no entry) are skipped:
+    /// directory changes are reported by the operation that made them.
+    #[cfg(feature = "fs-events")]
+    fn notify_modified(&mut self) {
+        self.context.event_unflushed = true;
+        if self.is_regular_file() && !self.context.event_modified {
+            self.context.event_modified = true;
+            self.fs
+                .emit_event(crate::events::FsEventKind::Modified, self.event_path(), None);
+        }
+    }
+
     #[allow(clippy::await_holding_refcell_ref)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "fatrs::file_flush",
+            level = "debug",
+            skip_all,
+            err(Debug),
+            fields(first_cluster = self.context.first_cluster)
+        )
+    )]
     pub async fn flush(&mut self) -> Result<(), Error<IO::Error>> {
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Flush);
         self.flush_dir_entry().await?;
         {
             let mut disk = self.fs.disk.acquire().await;
             disk.flush().await?;
         }
+        #[cfg(feature = "fs-events")]
+        {
+            self.context.event_unflushed = false;
+        }
         Ok(())
     }
 }
@@ -426,23 +673,19 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
     /// A [`FileContext`] is returned, which can be used in conjunction with the
     /// `to_file_with_context` API.
     ///
-    /// **Note:** If this file was opened with a lock (using `open_file_locked` or
-    /// `create_file_locked`), use [`close_and_unlock`](Self::close_and_unlock) instead
-    /// to properly release the lock.
+    /// A lock taken by `open_file_locked` or `create_file_locked` is released when the
+    /// handle is dropped here; [`close_and_unlock`](Self::close_and_unlock) does the same
+    /// without spinning on the lock table.
     #[allow(clippy::missing_errors_doc)]
     pub fn close(self) -> Result<FileContext, Error<IO::Error>> {
-        #[cfg(feature = "file-locking")]
-        if self.lock_info.is_some() {
-            warn!(
-                "Closing locked file without calling close_and_unlock - lock will not be released"
-            );
-        }
-
         Ok(FileContext {
             first_cluster: self.context.first_cluster,
             current_cluster: self.context.current_cluster,
             offset: self.context.offset,
             entry: self.context.entry.clone(),
+            critical: self.context.critical,
+            #[cfg(feature = "quotas")]
+            quota_owner: self.context.quota_owner,
             #[cfg(feature = "multi-cluster-io")]
             is_contiguous: self.context.is_contiguous,
             #[cfg(feature = "cluster-checkpoints")]
@@ -457,6 +700,12 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
             total_read: self.context.total_read,
             #[cfg(feature = "audit-log")]
             total_written: self.context.total_written,
+            #[cfg(feature = "fs-events")]
+            event_path: self.context.event_path.clone(),
+            #[cfg(feature = "fs-events")]
+            event_modified: false,
+            #[cfg(feature = "fs-events")]
+            event_unflushed: false,
         })
     }
 
@@ -473,12 +722,11 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
     /// let context = file.close_and_unlock().await?;
     /// ```
     #[cfg(feature = "file-locking")]
-    pub async fn close_and_unlock(self) -> Result<FileContext, Error<IO::Error>> {
+    pub async fn close_and_unlock(mut self) -> Result<FileContext, Error<IO::Error>> {
         // Release the lock if one was held
-        if let (Some(lock_type), Some(first_cluster)) = (self.lock_info, self.context.first_cluster)
-        {
+        if let (Some(lock_type), Some(file_id)) = (self.lock_info.take(), self.lock_file_id()) {
             let mut locks = self.fs.file_locks.acquire().await;
-            locks.unlock(first_cluster, lock_type);
+            locks.unlock(file_id, lock_type);
         }
 
         Ok(FileContext {
@@ -486,6 +734,9 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
             current_cluster: self.context.current_cluster,
             offset: self.context.offset,
             entry: self.context.entry.clone(),
+            critical: self.context.critical,
+            #[cfg(feature = "quotas")]
+            quota_owner: self.context.quota_owner,
             #[cfg(feature = "multi-cluster-io")]
             is_contiguous: self.context.is_contiguous,
             #[cfg(feature = "cluster-checkpoints")]
@@ -500,6 +751,12 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
             total_read: self.context.total_read,
             #[cfg(feature = "audit-log")]
             total_written: self.context.total_written,
+            #[cfg(feature = "fs-events")]
+            event_path: self.context.event_path.clone(),
+            #[cfg(feature = "fs-events")]
+            event_modified: false,
+            #[cfg(feature = "fs-events")]
+            event_unflushed: false,
         })
     }
 
@@ -514,6 +771,71 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
     pub fn lock_type(&self) -> Option<crate::file_locking::LockType> {
         self.lock_info
     }
+
+    /// Locks `len` bytes starting at `offset` for this handle.
+    ///
+    /// A `len` of 0 locks everything from `offset` to the end of the file, including
+    /// bytes appended later. Locking a range this handle already holds converts it to
+    /// `lock_type`. Range locks are advisory: they do not block reads or writes, only
+    /// conflicting `lock_range` calls from other handles. They are released by
+    /// [`File::unlock_range`] or when the handle is dropped.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::FileLocked` if another handle holds an overlapping exclusive lock, or an
+    ///   overlapping lock of any type when `lock_type` is exclusive.
+    /// * `Error::InvalidInput` if the range overflows or the file has no directory entry.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// // Each producer owns one slot of a preallocated ring file
+    /// file.lock_range(slot * SLOT_SIZE, SLOT_SIZE, LockType::Exclusive).await?;
+    /// file.seek(SeekFrom::Start(slot * SLOT_SIZE)).await?;
+    /// file.write_all(&record).await?;
+    /// file.unlock_range(slot * SLOT_SIZE, SLOT_SIZE).await;
+    /// ```
+    #[cfg(feature = "file-locking")]
+    pub async fn lock_range(
+        &mut self,
+        offset: u64,
+        len: u64,
+        lock_type: crate::file_locking::LockType,
+    ) -> Result<(), Error<IO::Error>> {
+        let end = range_end(offset, len).ok_or(Error::InvalidInput)?;
+        let file_id = self.lock_file_id().ok_or(Error::InvalidInput)?;
+        let mut locks = self.fs.file_locks.acquire().await;
+        let owner = *self
+            .range_lock_owner
+            .get_or_insert_with(|| locks.new_range_owner());
+        locks
+            .try_lock_range(file_id, owner, offset, end, lock_type)
+            .map_err(|()| Error::FileLocked)
+    }
+
+    /// Releases `len` bytes starting at `offset` locked by this handle.
+    ///
+    /// A `len` of 0 releases everything from `offset` on. Locked ranges extending beyond
+    /// the released bytes stay locked.
+    #[cfg(feature = "file-locking")]
+    pub async fn unlock_range(&mut self, offset: u64, len: u64) {
+        let (Some(owner), Some(file_id)) = (self.range_lock_owner, self.lock_file_id()) else {
+            return;
+        };
+        let end = range_end(offset, len).unwrap_or(u64::MAX);
+        let mut locks = self.fs.file_locks.acquire().await;
+        locks.unlock_range(file_id, owner, offset, end);
+    }
+}
+
+/// Exclusive end of a lock range, `len` 0 meaning up to the end of the file
+#[cfg(feature = "file-locking")]
+fn range_end(offset: u64, len: u64) -> Option<u64> {
+    if len == 0 {
+        (offset < u64::MAX).then_some(u64::MAX)
+    } else {
+        offset.checked_add(len)
+    }
 }
 
 impl<IO: ReadWriteSeek, TP, OCC> Drop for File<'_, IO, TP, OCC> {
@@ -529,9 +851,32 @@ impl<IO: ReadWriteSeek, TP, OCC> Drop for File<'_, IO, TP, OCC> {
             }
         }
 
+        // A locked handle dropped on an error path must not leave waiters queued forever
         #[cfg(feature = "file-locking")]
-        if let (Some(lock_info), Some(first_cluster)) = (self.lock_info, self.context.first_cluster) {
-            warn!("File dropped while locked - lock will not be released properly");
+        if let Some(file_id) = self.lock_file_id() {
+            if let Some(lock_type) = self.lock_info.take() {
+                self.fs.file_locks.spin_acquire().unlock(file_id, lock_type);
+            }
+            if let Some(owner) = self.range_lock_owner {
+                self.fs.file_locks.spin_acquire().unlock_owner(file_id, owner);
+            }
+        }
+
+        #[cfg(feature = "alloc")]
+        if let Some(pos) = self.tracked_entry_pos() {
+            self.fs.untrack_open_file(pos);
+        }
+
+        // Change notification: file closed after being written. Only a flushed
+        // session reports its data as committed.
+        #[cfg(feature = "fs-events")]
+        if self.context.event_modified {
+            let kind = if self.context.event_unflushed {
+                crate::events::FsEventKind::DroppedUnflushed
+            } else {
+                crate::events::FsEventKind::ClosedAfterWrite
+            };
+            self.fs.emit_event(kind, self.event_path(), None);
         }
     }
 }
@@ -541,12 +886,24 @@ impl<IO: ReadWriteSeek, TP, OCC> Drop for File<'_, IO, TP, OCC> {
 // This is intentional to prevent lock reference counting issues.
 impl<IO: ReadWriteSeek, TP, OCC> Clone for File<'_, IO, TP, OCC> {
     fn clone(&self) -> Self {
-        File {
-            context: self.context.clone(),
+        #[allow(unused_mut)]
+        let mut context = self.context.clone();
+        // A clone starts a new session - it has not written anything yet
+        #[cfg(feature = "fs-events")]
+        {
+            context.event_modified = false;
+            context.event_unflushed = false;
+        }
+        let file = File {
+            context,
             fs: self.fs,
             #[cfg(feature = "file-locking")]
             lock_info: None, // Clones don't inherit locks
-        }
+            #[cfg(feature = "file-locking")]
+            range_lock_owner: None,
+        };
+        file.track_open();
+        file
     }
 }
 
@@ -559,8 +916,26 @@ where
 
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
     #[allow(clippy::too_many_lines)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "fatrs::read",
+            level = "debug",
+            skip_all,
+            err(Debug),
+            fields(
+                first_cluster = self.context.first_cluster,
+                offset = self.context.offset,
+                requested = buf.len(),
+                bytes = tracing::field::Empty,
+                clusters = tracing::field::Empty,
+            )
+        )
+    )]
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         trace!("File::read");
+        #[cfg(feature = "tracing")]
+        let start_offset = self.context.offset;
         let cluster_size = self.fs.cluster_size();
         let current_cluster_opt = if self.context.offset % cluster_size == 0 {
             // next cluster
@@ -608,20 +983,11 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
                         let new_offset = self.context.offset;
 
                         // Update current cluster to match new offset
-                        // FAT convention: when at a cluster boundary, current_cluster points to
-                        // the previous cluster (the one just finished), not the next cluster.
-                        let old_cluster_index = if old_offset == 0 {
-                            0u32
-                        } else {
-                            (old_offset / cluster_size).saturating_sub(1)
-                        };
-
-                        let new_cluster_index = if new_offset > 0 && new_offset % cluster_size == 0
-                        {
-                            (new_offset / cluster_size).saturating_sub(1)
-                        } else {
-                            new_offset / cluster_size
-                        };
+                        // `current_cluster` holds the byte at `old_offset`. FAT convention: when
+                        // at a cluster boundary, current_cluster points to the previous cluster
+                        // (the one just finished), so follow the chain to the last byte read.
+                        let old_cluster_index = old_offset / cluster_size;
+                        let new_cluster_index = (new_offset - 1) / cluster_size;
 
                         let cluster_delta = new_cluster_index.saturating_sub(old_cluster_index);
 
@@ -653,6 +1019,12 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
                             }
                         }
                         trace!("multi-cluster read: {} bytes", read_bytes);
+                        #[cfg(feature = "metrics")]
+                        if self.is_regular_file() {
+                            self.fs.op_metrics.record_read(read_bytes);
+                        }
+                        #[cfg(feature = "tracing")]
+                        self.record_transfer(start_offset, read_bytes);
                         return Ok(read_bytes);
                     }
                     _ => {
@@ -718,14 +1090,39 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
             self.context.total_read = self.context.total_read.saturating_add(read_bytes as u64);
         }
 
+        #[cfg(feature = "metrics")]
+        if self.is_regular_file() {
+            self.fs.op_metrics.record_read(read_bytes);
+        }
+        #[cfg(feature = "tracing")]
+        self.record_transfer(start_offset, read_bytes);
+
         Ok(read_bytes)
     }
 }
 
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
     #[allow(clippy::too_many_lines)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "fatrs::write",
+            level = "debug",
+            skip_all,
+            err(Debug),
+            fields(
+                first_cluster = self.context.first_cluster,
+                offset = self.context.offset,
+                requested = buf.len(),
+                bytes = tracing::field::Empty,
+                clusters = tracing::field::Empty,
+            )
+        )
+    )]
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         trace!("File::write");
+        #[cfg(feature = "tracing")]
+        let start_offset = self.context.offset;
         let cluster_size = self.fs.cluster_size();
         let offset_in_cluster = self.context.offset % cluster_size;
         let bytes_left_until_max_file_size = (MAX_FILE_SIZE - self.context.offset) as usize;
@@ -783,41 +1180,31 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
                             let new_offset = self.context.offset;
 
                             // Update current cluster to match new offset
-                            // FAT convention: when at a cluster boundary, current_cluster points to
-                            // the previous cluster (the one just finished), not the next cluster.
-                            let old_cluster_index = if old_offset == 0 {
-                                0u32
-                            } else {
-                                (old_offset / cluster_size).saturating_sub(1)
-                            };
-
-                            let new_cluster_index =
-                                if new_offset > 0 && new_offset % cluster_size == 0 {
-                                    (new_offset / cluster_size).saturating_sub(1)
-                                } else {
-                                    new_offset / cluster_size
-                                };
+                            // `current_cluster` holds the byte at `old_offset`. FAT convention:
+                            // when at a cluster boundary, current_cluster points to the previous
+                            // cluster (the one just finished), so follow the chain to the last
+                            // byte written.
+                            let old_cluster_index = old_offset / cluster_size;
+                            let new_cluster_index = (new_offset - 1) / cluster_size;
 
                             let cluster_delta = new_cluster_index.saturating_sub(old_cluster_index);
 
-                            if cluster_delta > 0 {
-                                let mut cluster = current_cluster;
-                                for _i in 0..cluster_delta {
-                                    let mut iter = self.fs.cluster_iter(cluster);
-                                    if let Some(Ok(next)) = iter.next().await {
-                                        cluster = next;
-                                        // Record checkpoint during sequential write traversal
-                                        #[cfg(feature = "cluster-checkpoints")]
-                                        {
-                                            let cluster_idx = old_cluster_index + _i + 1;
-                                            self.record_checkpoint(cluster_idx, cluster);
-                                        }
-                                    } else {
-                                        break;
+                            let mut cluster = current_cluster;
+                            for _i in 0..cluster_delta {
+                                let mut iter = self.fs.cluster_iter(cluster);
+                                if let Some(Ok(next)) = iter.next().await {
+                                    cluster = next;
+                                    // Record checkpoint during sequential write traversal
+                                    #[cfg(feature = "cluster-checkpoints")]
+                                    {
+                                        let cluster_idx = old_cluster_index + _i + 1;
+                                        self.record_checkpoint(cluster_idx, cluster);
                                     }
+                                } else {
+                                    break;
                                 }
-                                self.context.current_cluster = Some(cluster);
                             }
+                            self.context.current_cluster = Some(cluster);
 
                             self.update_dir_entry_after_write().await?;
                             trace!("multi-cluster write: {} bytes", written_bytes);
@@ -839,6 +1226,17 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
                                 self.context.total_written = self.context.total_written.saturating_add(written_bytes as u64);
                             }
 
+                            // Change notification: first write on this file
+                            #[cfg(feature = "fs-events")]
+                            self.notify_modified();
+
+                            #[cfg(feature = "metrics")]
+                            if self.is_regular_file() {
+                                self.fs.op_metrics.record_write(written_bytes);
+                            }
+                            #[cfg(feature = "tracing")]
+                            self.record_transfer(start_offset, written_bytes);
+
                             return Ok(written_bytes);
                         }
                         _ => {
@@ -876,9 +1274,20 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
                 n
             } else {
                 // end of chain reached - allocate new cluster
+                #[cfg(feature = "quotas")]
                 let new_cluster = self
                     .fs
-                    .alloc_cluster(self.context.current_cluster, self.is_dir())
+                    .alloc_cluster_for(
+                        self.context.quota_owner,
+                        self.context.current_cluster,
+                        self.is_dir(),
+                        self.context.critical,
+                    )
+                    .await?;
+                #[cfg(not(feature = "quotas"))]
+                let new_cluster = self
+                    .fs
+                    .alloc_cluster(self.context.current_cluster, self.is_dir(), self.context.critical)
                     .await?;
                 trace!("allocated cluster {}", new_cluster);
                 if self.context.first_cluster.is_none() {
@@ -894,13 +1303,30 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
             }
         };
         trace!("write {} bytes in cluster {}", write_size, current_cluster);
-        let offset_in_fs =
-            self.fs.offset_from_cluster(current_cluster) + u64::from(offset_in_cluster);
-        #[allow(clippy::await_holding_refcell_ref)]
-        let written_bytes = {
-            let mut disk = self.fs.disk.acquire().await;
-            disk.seek(SeekFrom::Start(offset_in_fs)).await?;
-            disk.write(&buf[..write_size]).await?
+        let mut current_cluster = current_cluster;
+        let written_bytes = match self
+            .write_in_cluster(current_cluster, offset_in_cluster, &buf[..write_size])
+            .await
+        {
+            Ok(n) => n,
+            // Move the data of a regular file off a failing cluster and retry once
+            Err(Error::Io(_)) if self.is_regular_file() => {
+                if let Some(n) = self
+                    .retry_in_cluster(current_cluster, offset_in_cluster, &buf[..write_size])
+                    .await?
+                {
+                    n
+                } else {
+                    warn!(
+                        "write to cluster {} failed, relocating its data",
+                        current_cluster
+                    );
+                    current_cluster = self.relocate_cluster(current_cluster).await?;
+                    self.write_in_cluster(current_cluster, offset_in_cluster, &buf[..write_size])
+                        .await?
+                }
+            }
+            Err(err) => return Err(err),
         };
         if written_bytes == 0 {
             return Ok(0);
@@ -935,6 +1361,17 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
             self.context.total_written = self.context.total_written.saturating_add(written_bytes as u64);
         }
 
+        // Change notification: first write on this file
+        #[cfg(feature = "fs-events")]
+        self.notify_modified();
+
+        #[cfg(feature = "metrics")]
+        if self.is_regular_file() {
+            self.fs.op_metrics.record_write(written_bytes);
+        }
+        #[cfg(feature = "tracing")]
+        self.record_transfer(start_offset, written_bytes);
+
         Ok(written_bytes)
     }
 
@@ -946,6 +1383,8 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
 impl<IO: ReadWriteSeek, TP, OCC> Seek for File<'_, IO, TP, OCC> {
     async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
         trace!("File::seek");
+        #[cfg(feature = "metrics")]
+        self.fs.count_operation(crate::metrics::Operation::Seek);
         let size_opt = self.size();
         let new_offset_opt: Option<u32> = match pos {
             SeekFrom::Current(x) => i64::from(self.context.offset)
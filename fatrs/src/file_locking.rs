@@ -8,6 +8,38 @@
 //! - **Shared locks** allow multiple concurrent readers
 //! - **Exclusive locks** allow a single writer with no readers
 //!
+//! # File Identity
+//!
+//! Files are identified by the position of their directory entry on the volume,
+//! so empty files (which have no cluster yet) can be locked too. The position is
+//! stable while the file is open because open files cannot be renamed or removed.
+//!
+//! Positions are byte offsets and do not fit in 32 bits on large volumes, so the
+//! `file_id` taken by [`FileLockManager`] methods is a `u64`. Earlier releases keyed
+//! locks by the file's first cluster as a `u32`; callers using the manager directly
+//! must pass the directory entry position instead.
+//!
+//! # Byte-Range Locks
+//!
+//! `File::lock_range` locks a region of a file for one file handle. Ranges held by
+//! different handles conflict when they overlap and at least one is exclusive; a
+//! handle's own ranges never conflict and are replaced (converted or split) by a
+//! new lock over the same bytes, like POSIX record locks. A handle's range locks
+//! are released by `File::unlock_range` or when the handle is dropped.
+//!
+//! Range locks are advisory and independent of the whole-file locks taken by
+//! `open_file_locked`/`create_file_locked`, similar to `fcntl` and `flock` locks on
+//! Linux.
+//!
+//! # Waiting For Locks
+//!
+//! `Dir::open_file_locked_wait` queues the caller instead of failing with
+//! `Error::FileLocked`. Waiters are served in FIFO order per file (consecutive shared
+//! waiters at the head of the queue are granted together) and are woken from
+//! [`FileLockManager::unlock`]. While anyone is queued, [`FileLockManager::try_lock`]
+//! fails as well, so a steady stream of readers cannot starve a waiting writer.
+//! Waiting only relies on `core::task::Waker`, so it works on any executor.
+//!
 //! # Example
 //!
 //! ```rust,ignore
@@ -21,11 +53,18 @@
 //! ```
 
 #[cfg(all(not(feature = "std"), feature = "alloc"))]
-use alloc::collections::BTreeMap;
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
 #[cfg(feature = "std")]
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 
+use crate::share::Shared;
 use core::fmt::Debug;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
 
 /// Lock type for file access control.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -70,15 +109,46 @@ impl FileLockState {
     }
 }
 
+/// A byte range `[start, end)` locked by one file handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RangeLock {
+    owner: u64,
+    start: u64,
+    end: u64,
+    lock_type: LockType,
+}
+
+impl RangeLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+}
+
+/// A task queued for a whole-file lock.
+#[derive(Debug)]
+struct LockWaiter {
+    ticket: u64,
+    lock_type: LockType,
+    waker: Option<Waker>,
+}
+
 /// File lock manager for tracking locks across all open files.
 ///
-/// Uses the file's first cluster as the key for identifying files.
-/// This works because each file has a unique first cluster.
+/// Uses the position of the file's directory entry as the key for identifying
+/// files. Unlike the first cluster it also exists for empty files.
 #[derive(Debug)]
 pub struct FileLockManager {
-    /// Maps first_cluster -> lock state.
+    /// Maps file id -> lock state.
     /// Using BTreeMap for no_std compatibility (works with alloc, no HashMap needed).
-    locks: BTreeMap<u32, FileLockState>,
+    locks: BTreeMap<u64, FileLockState>,
+    /// Maps file id -> byte-range locks held on it.
+    range_locks: BTreeMap<u64, Vec<RangeLock>>,
+    /// Next range lock owner id handed out to a file handle.
+    next_owner: u64,
+    /// Maps file id -> tasks waiting for a whole-file lock, in arrival order.
+    waiters: BTreeMap<u64, VecDeque<LockWaiter>>,
+    /// Next waiter ticket.
+    next_ticket: u64,
 }
 
 impl FileLockManager {
@@ -86,6 +156,10 @@ impl FileLockManager {
     pub fn new() -> Self {
         Self {
             locks: BTreeMap::new(),
+            range_locks: BTreeMap::new(),
+            next_owner: 1,
+            waiters: BTreeMap::new(),
+            next_ticket: 1,
         }
     }
 
@@ -93,13 +167,14 @@ impl FileLockManager {
     ///
     /// # Arguments
     ///
-    /// * `cluster` - The first cluster of the file (unique identifier)
+    /// * `file_id` - The directory entry position of the file (unique identifier)
     /// * `lock_type` - Whether to acquire a shared or exclusive lock
     ///
     /// # Returns
     ///
     /// * `Ok(())` if the lock was acquired
-    /// * `Err(())` if the lock could not be acquired (file is locked)
+    /// * `Err(())` if the lock could not be acquired (file is locked or other tasks
+    ///   are waiting for it)
     ///
     /// # Lock Compatibility
     ///
@@ -111,8 +186,16 @@ impl FileLockManager {
     /// | Exclusive | No locks      | OK     |
     /// | Exclusive | Shared(n)     | FAIL   |
     /// | Exclusive | Exclusive     | FAIL   |
-    pub fn try_lock(&mut self, cluster: u32, lock_type: LockType) -> Result<(), ()> {
-        match self.locks.get_mut(&cluster) {
+    pub fn try_lock(&mut self, file_id: u64, lock_type: LockType) -> Result<(), ()> {
+        if self.waiters.contains_key(&file_id) {
+            // Queued waiters are served first
+            return Err(());
+        }
+        self.grant(file_id, lock_type)
+    }
+
+    fn grant(&mut self, file_id: u64, lock_type: LockType) -> Result<(), ()> {
+        match self.locks.get_mut(&file_id) {
             Some(state) => {
                 match lock_type {
                     LockType::Shared => {
@@ -137,7 +220,7 @@ impl FileLockManager {
                     LockType::Shared => FileLockState::new_shared(),
                     LockType::Exclusive => FileLockState::new_exclusive(),
                 };
-                self.locks.insert(cluster, state);
+                self.locks.insert(file_id, state);
             }
         }
         Ok(())
@@ -147,14 +230,14 @@ impl FileLockManager {
     ///
     /// # Arguments
     ///
-    /// * `cluster` - The first cluster of the file
+    /// * `file_id` - The directory entry position of the file
     /// * `lock_type` - The type of lock to release (must match what was acquired)
     ///
     /// # Panics
     ///
     /// In debug builds, panics if trying to release a lock that wasn't held.
-    pub fn unlock(&mut self, cluster: u32, lock_type: LockType) {
-        if let Some(state) = self.locks.get_mut(&cluster) {
+    pub fn unlock(&mut self, file_id: u64, lock_type: LockType) {
+        if let Some(state) = self.locks.get_mut(&file_id) {
             match lock_type {
                 LockType::Shared => {
                     debug_assert!(
@@ -173,43 +256,240 @@ impl FileLockManager {
             }
             // Remove entry if no locks held (cleanup)
             if state.is_empty() {
-                self.locks.remove(&cluster);
+                self.locks.remove(&file_id);
             }
+            self.wake_waiters(file_id);
         } else {
             debug_assert!(false, "Tried to unlock file that has no lock entry");
         }
     }
 
+    /// Queue a task for a lock on a file and return its ticket.
+    ///
+    /// The ticket must be polled with [`FileLockManager::poll_waiter`] until the lock
+    /// is granted, or given back with [`FileLockManager::cancel_wait`].
+    pub fn enqueue_waiter(&mut self, file_id: u64, lock_type: LockType) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.waiters
+            .entry(file_id)
+            .or_default()
+            .push_back(LockWaiter {
+                ticket,
+                lock_type,
+                waker: None,
+            });
+        ticket
+    }
+
+    /// Try to grant the lock a queued task is waiting for.
+    ///
+    /// The lock is granted when it is compatible with the current state and no
+    /// incompatible waiter is ahead of the ticket. Otherwise `waker` is stored and
+    /// woken once the lock may have become available.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the lock was acquired and the ticket removed from the queue
+    pub fn poll_waiter(&mut self, file_id: u64, ticket: u64, waker: &Waker) -> bool {
+        let Some(queue) = self.waiters.get_mut(&file_id) else {
+            debug_assert!(false, "Polled a lock waiter that is not queued");
+            return false;
+        };
+        let Some(index) = queue.iter().position(|w| w.ticket == ticket) else {
+            debug_assert!(false, "Polled a lock waiter that is not queued");
+            return false;
+        };
+        let lock_type = queue[index].lock_type;
+        // Only shared waiters may overtake, and only other shared waiters
+        let eligible = index == 0
+            || (lock_type == LockType::Shared
+                && queue
+                    .iter()
+                    .take(index)
+                    .all(|w| w.lock_type == LockType::Shared));
+        if eligible && self.grant(file_id, lock_type).is_ok() {
+            self.cancel_wait(file_id, ticket);
+            return true;
+        }
+        if let Some(waiter) = self
+            .waiters
+            .get_mut(&file_id)
+            .and_then(|q| q.iter_mut().find(|w| w.ticket == ticket))
+        {
+            match &waiter.waker {
+                Some(w) if w.will_wake(waker) => {}
+                _ => waiter.waker = Some(waker.clone()),
+            }
+        }
+        false
+    }
+
+    /// Remove a queued task, e.g. after it acquired the lock or gave up waiting.
+    pub fn cancel_wait(&mut self, file_id: u64, ticket: u64) {
+        if let Some(queue) = self.waiters.get_mut(&file_id) {
+            queue.retain(|w| w.ticket != ticket);
+            if queue.is_empty() {
+                self.waiters.remove(&file_id);
+            }
+        }
+        // The tasks behind it may be able to proceed now
+        self.wake_waiters(file_id);
+    }
+
+    /// Get the number of tasks waiting for a lock on a file.
+    pub fn waiter_count(&self, file_id: u64) -> usize {
+        self.waiters.get(&file_id).map_or(0, VecDeque::len)
+    }
+
+    /// Wake the waiter at the head of the queue, plus the shared waiters right behind
+    /// a shared head since they can be granted together.
+    fn wake_waiters(&mut self, file_id: u64) {
+        let Some(queue) = self.waiters.get_mut(&file_id) else {
+            return;
+        };
+        let head_shared = queue
+            .front()
+            .is_some_and(|w| w.lock_type == LockType::Shared);
+        for (i, waiter) in queue.iter_mut().enumerate() {
+            if i > 0 && !(head_shared && waiter.lock_type == LockType::Shared) {
+                break;
+            }
+            if let Some(waker) = waiter.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
     /// Check if a file is currently locked.
     ///
     /// # Arguments
     ///
-    /// * `cluster` - The first cluster of the file
+    /// * `file_id` - The directory entry position of the file
     ///
     /// # Returns
     ///
     /// `true` if the file has any active locks (shared or exclusive)
-    pub fn is_locked(&self, cluster: u32) -> bool {
-        self.locks.get(&cluster).is_some_and(|s| !s.is_empty())
+    pub fn is_locked(&self, file_id: u64) -> bool {
+        self.locks.get(&file_id).is_some_and(|s| !s.is_empty())
     }
 
     /// Get the current lock state for a file.
     ///
     /// # Arguments
     ///
-    /// * `cluster` - The first cluster of the file
+    /// * `file_id` - The directory entry position of the file
     ///
     /// # Returns
     ///
     /// The current lock state, or `None` if no locks are held
-    pub fn get_lock_state(&self, cluster: u32) -> Option<&FileLockState> {
-        self.locks.get(&cluster)
+    pub fn get_lock_state(&self, file_id: u64) -> Option<&FileLockState> {
+        self.locks.get(&file_id)
     }
 
     /// Get the number of currently locked files.
     pub fn locked_file_count(&self) -> usize {
         self.locks.len()
     }
+
+    /// Allocate an owner id for the byte-range locks of one file handle.
+    pub fn new_range_owner(&mut self) -> u64 {
+        let owner = self.next_owner;
+        self.next_owner += 1;
+        owner
+    }
+
+    /// Attempt to lock bytes `[start, end)` of a file for `owner`.
+    ///
+    /// Fails if another owner holds an overlapping range and either lock is
+    /// exclusive. Ranges of `owner` overlapping the new one are replaced by it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the range was locked
+    /// * `Err(())` if the range conflicts with a lock of another owner
+    pub fn try_lock_range(
+        &mut self,
+        file_id: u64,
+        owner: u64,
+        start: u64,
+        end: u64,
+        lock_type: LockType,
+    ) -> Result<(), ()> {
+        debug_assert!(start < end, "Empty lock range");
+        let ranges = self.range_locks.entry(file_id).or_default();
+        let conflict = ranges.iter().any(|r| {
+            r.owner != owner
+                && r.overlaps(start, end)
+                && (r.lock_type == LockType::Exclusive || lock_type == LockType::Exclusive)
+        });
+        if conflict {
+            if ranges.is_empty() {
+                self.range_locks.remove(&file_id);
+            }
+            return Err(());
+        }
+        Self::remove_range(ranges, owner, start, end);
+        ranges.push(RangeLock {
+            owner,
+            start,
+            end,
+            lock_type,
+        });
+        Ok(())
+    }
+
+    /// Release bytes `[start, end)` locked by `owner`, splitting ranges that extend beyond it.
+    pub fn unlock_range(&mut self, file_id: u64, owner: u64, start: u64, end: u64) {
+        if let Some(ranges) = self.range_locks.get_mut(&file_id) {
+            Self::remove_range(ranges, owner, start, end);
+            if ranges.is_empty() {
+                self.range_locks.remove(&file_id);
+            }
+        }
+    }
+
+    /// Release all byte-range locks of `owner` on a file.
+    pub fn unlock_owner(&mut self, file_id: u64, owner: u64) {
+        if let Some(ranges) = self.range_locks.get_mut(&file_id) {
+            ranges.retain(|r| r.owner != owner);
+            if ranges.is_empty() {
+                self.range_locks.remove(&file_id);
+            }
+        }
+    }
+
+    /// Check if any byte of `[start, end)` is locked by an owner other than `owner`.
+    pub fn is_range_locked(&self, file_id: u64, owner: u64, start: u64, end: u64) -> bool {
+        self.range_locks.get(&file_id).is_some_and(|ranges| {
+            ranges
+                .iter()
+                .any(|r| r.owner != owner && r.overlaps(start, end))
+        })
+    }
+
+    /// Get the number of byte-range locks held on a file.
+    pub fn range_lock_count(&self, file_id: u64) -> usize {
+        self.range_locks.get(&file_id).map_or(0, Vec::len)
+    }
+
+    fn remove_range(ranges: &mut Vec<RangeLock>, owner: u64, start: u64, end: u64) {
+        let mut i = 0;
+        while i < ranges.len() {
+            let r = ranges[i];
+            if r.owner != owner || !r.overlaps(start, end) {
+                i += 1;
+                continue;
+            }
+            ranges.swap_remove(i);
+            if r.start < start {
+                ranges.push(RangeLock { end: start, ..r });
+            }
+            if r.end > end {
+                ranges.push(RangeLock { start: end, ..r });
+            }
+        }
+    }
 }
 
 impl Default for FileLockManager {
@@ -218,6 +498,79 @@ impl Default for FileLockManager {
     }
 }
 
+/// Future resolving once a whole-file lock has been granted to a queued waiter.
+///
+/// Dropping it before completion gives up the place in the queue.
+pub(crate) struct LockWait<'a> {
+    manager: &'a Shared<FileLockManager>,
+    file_id: u64,
+    ticket: Option<u64>,
+}
+
+impl<'a> LockWait<'a> {
+    pub(crate) fn new(
+        manager: &'a Shared<FileLockManager>,
+        file_id: u64,
+        lock_type: LockType,
+    ) -> Self {
+        let ticket = manager.spin_acquire().enqueue_waiter(file_id, lock_type);
+        Self {
+            manager,
+            file_id,
+            ticket: Some(ticket),
+        }
+    }
+}
+
+impl Future for LockWait<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(ticket) = self.ticket else {
+            return Poll::Ready(());
+        };
+        if self.manager.spin_acquire().poll_waiter(self.file_id, ticket, cx.waker()) {
+            self.ticket = None;
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for LockWait<'_> {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            self.manager.spin_acquire().cancel_wait(self.file_id, ticket);
+        }
+    }
+}
+
+/// Wait in the queue for a lock on a file, giving up when `cancel` completes first.
+///
+/// # Returns
+///
+/// `true` if the lock was acquired, `false` if the wait was cancelled
+pub(crate) async fn wait_for_lock<F: Future>(
+    manager: &Shared<FileLockManager>,
+    file_id: u64,
+    lock_type: LockType,
+    cancel: F,
+) -> bool {
+    let mut wait = core::pin::pin!(LockWait::new(manager, file_id, lock_type));
+    let mut cancel = core::pin::pin!(cancel);
+    core::future::poll_fn(|cx| {
+        if wait.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(true);
+        }
+        if cancel.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(false);
+        }
+        Poll::Pending
+    })
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -345,4 +698,179 @@ mod tests {
         assert_eq!(manager.locked_file_count(), 0);
         assert!(!manager.is_locked(cluster));
     }
+
+    #[test]
+    fn test_waiters_are_served_in_order() {
+        let mut manager = FileLockManager::new();
+        let waker = Waker::noop();
+        assert!(manager.try_lock(1, LockType::Shared).is_ok());
+
+        let writer = manager.enqueue_waiter(1, LockType::Exclusive);
+        let reader = manager.enqueue_waiter(1, LockType::Shared);
+        assert!(!manager.poll_waiter(1, writer, waker));
+        // New readers queue behind the writer instead of starving it
+        assert!(manager.try_lock(1, LockType::Shared).is_err());
+        assert!(!manager.poll_waiter(1, reader, waker));
+
+        manager.unlock(1, LockType::Shared);
+        assert!(!manager.poll_waiter(1, reader, waker));
+        assert!(manager.poll_waiter(1, writer, waker));
+        assert!(!manager.poll_waiter(1, reader, waker));
+
+        manager.unlock(1, LockType::Exclusive);
+        assert!(manager.poll_waiter(1, reader, waker));
+        assert_eq!(manager.waiter_count(1), 0);
+        assert!(manager.try_lock(1, LockType::Shared).is_ok());
+    }
+
+    #[test]
+    fn test_consecutive_shared_waiters_are_granted_together() {
+        let mut manager = FileLockManager::new();
+        let waker = Waker::noop();
+        assert!(manager.try_lock(1, LockType::Exclusive).is_ok());
+
+        let first = manager.enqueue_waiter(1, LockType::Shared);
+        let second = manager.enqueue_waiter(1, LockType::Shared);
+        let writer = manager.enqueue_waiter(1, LockType::Exclusive);
+        manager.unlock(1, LockType::Exclusive);
+
+        assert!(manager.poll_waiter(1, second, waker));
+        assert!(manager.poll_waiter(1, first, waker));
+        assert!(!manager.poll_waiter(1, writer, waker));
+        assert_eq!(manager.get_lock_state(1).unwrap().readers, 2);
+    }
+
+    #[test]
+    fn test_cancelled_waiter_unblocks_queue() {
+        let mut manager = FileLockManager::new();
+        let waker = Waker::noop();
+        assert!(manager.try_lock(1, LockType::Shared).is_ok());
+
+        let writer = manager.enqueue_waiter(1, LockType::Exclusive);
+        let reader = manager.enqueue_waiter(1, LockType::Shared);
+        assert!(!manager.poll_waiter(1, reader, waker));
+
+        manager.cancel_wait(1, writer);
+        assert!(manager.poll_waiter(1, reader, waker));
+        assert_eq!(manager.waiter_count(1), 0);
+    }
+
+    #[test]
+    fn test_range_locks_conflict_only_when_overlapping() {
+        let mut manager = FileLockManager::new();
+        let file = 4096;
+        let a = manager.new_range_owner();
+        let b = manager.new_range_owner();
+
+        assert!(
+            manager
+                .try_lock_range(file, a, 0, 512, LockType::Exclusive)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .try_lock_range(file, b, 512, 1024, LockType::Exclusive)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .try_lock_range(file, b, 511, 512, LockType::Shared)
+                .is_err()
+        );
+        assert!(manager.is_range_locked(file, b, 0, 1));
+        assert!(!manager.is_range_locked(file, a, 0, 512));
+
+        // Whole-file locks are independent
+        assert!(manager.try_lock(file, LockType::Exclusive).is_ok());
+    }
+
+    #[test]
+    fn test_shared_ranges_are_compatible() {
+        let mut manager = FileLockManager::new();
+        let a = manager.new_range_owner();
+        let b = manager.new_range_owner();
+
+        assert!(
+            manager
+                .try_lock_range(1, a, 0, 100, LockType::Shared)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .try_lock_range(1, b, 50, 150, LockType::Shared)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .try_lock_range(1, b, 50, 150, LockType::Exclusive)
+                .is_err()
+        );
+
+        manager.unlock_owner(1, a);
+        assert!(
+            manager
+                .try_lock_range(1, b, 50, 150, LockType::Exclusive)
+                .is_ok()
+        );
+        assert_eq!(manager.range_lock_count(1), 1);
+    }
+
+    #[test]
+    fn test_unlock_splits_range() {
+        let mut manager = FileLockManager::new();
+        let a = manager.new_range_owner();
+        let b = manager.new_range_owner();
+
+        assert!(
+            manager
+                .try_lock_range(1, a, 0, 300, LockType::Exclusive)
+                .is_ok()
+        );
+        manager.unlock_range(1, a, 100, 200);
+        assert_eq!(manager.range_lock_count(1), 2);
+
+        assert!(
+            manager
+                .try_lock_range(1, b, 100, 200, LockType::Exclusive)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .try_lock_range(1, b, 99, 100, LockType::Shared)
+                .is_err()
+        );
+        assert!(
+            manager
+                .try_lock_range(1, b, 200, 201, LockType::Shared)
+                .is_err()
+        );
+
+        manager.unlock_owner(1, a);
+        manager.unlock_owner(1, b);
+        assert_eq!(manager.range_lock_count(1), 0);
+    }
+
+    #[test]
+    fn test_relocking_own_range_converts_it() {
+        let mut manager = FileLockManager::new();
+        let a = manager.new_range_owner();
+        let b = manager.new_range_owner();
+
+        assert!(
+            manager
+                .try_lock_range(1, a, 0, 100, LockType::Exclusive)
+                .is_ok()
+        );
+        assert!(
+            manager
+                .try_lock_range(1, a, 0, 100, LockType::Shared)
+                .is_ok()
+        );
+        assert_eq!(manager.range_lock_count(1), 1);
+        assert!(
+            manager
+                .try_lock_range(1, b, 0, 100, LockType::Shared)
+                .is_ok()
+        );
+    }
 }
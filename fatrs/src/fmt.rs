@@ -110,7 +110,9 @@ macro_rules! trace {
             ::log::trace!($s $(, $x)*);
             #[cfg(feature = "defmt")]
             ::defmt::trace!($s $(, $x)*);
-            #[cfg(not(any(feature = "log", feature="defmt")))]
+            #[cfg(all(feature = "tracing", not(feature = "log")))]
+            ::tracing::trace!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature="defmt", feature = "tracing")))]
             let _ = ($( & $x ),*);
         }
     };
@@ -123,7 +125,9 @@ macro_rules! debug {
             ::log::debug!($s $(, $x)*);
             #[cfg(feature = "defmt")]
             ::defmt::debug!($s $(, $x)*);
-            #[cfg(not(any(feature = "log", feature="defmt")))]
+            #[cfg(all(feature = "tracing", not(feature = "log")))]
+            ::tracing::debug!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature="defmt", feature = "tracing")))]
             let _ = ($( & $x ),*);
         }
     };
@@ -136,7 +140,9 @@ macro_rules! info {
             ::log::info!($s $(, $x)*);
             #[cfg(feature = "defmt")]
             ::defmt::info!($s $(, $x)*);
-            #[cfg(not(any(feature = "log", feature="defmt")))]
+            #[cfg(all(feature = "tracing", not(feature = "log")))]
+            ::tracing::info!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature="defmt", feature = "tracing")))]
             let _ = ($( & $x ),*);
         }
     };
@@ -149,7 +155,9 @@ macro_rules! warn {
             ::log::warn!($s $(, $x)*);
             #[cfg(feature = "defmt")]
             ::defmt::warn!($s $(, $x)*);
-            #[cfg(not(any(feature = "log", feature="defmt")))]
+            #[cfg(all(feature = "tracing", not(feature = "log")))]
+            ::tracing::warn!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature="defmt", feature = "tracing")))]
             let _ = ($( & $x ),*);
         }
     };
@@ -162,7 +170,9 @@ macro_rules! error {
             ::log::error!($s $(, $x)*);
             #[cfg(feature = "defmt")]
             ::defmt::error!($s $(, $x)*);
-            #[cfg(not(any(feature = "log", feature="defmt")))]
+            #[cfg(all(feature = "tracing", not(feature = "log")))]
+            ::tracing::error!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature="defmt", feature = "tracing")))]
             let _ = ($( & $x ),*);
         }
     };
@@ -12,20 +12,25 @@ use core::fmt::Debug;
 use core::marker::PhantomData;
 use portable_atomic::{AtomicU8, AtomicU64, Ordering};
 
-#[cfg(all(not(feature = "std"), feature = "alloc", feature = "lfn"))]
+#[cfg(all(
+    not(feature = "std"),
+    feature = "alloc",
+    any(feature = "lfn", feature = "quotas")
+))]
 use alloc::string::String;
 #[cfg(feature = "std")]
 use embedded_io_adapters::tokio_1::FromTokio;
 
 use crate::boot_sector::{BiosParameterBlock, BootSector, format_boot_sector};
 use crate::dir::{Dir, DirRawStream};
-use crate::dir_entry::{DirFileEntryData, FileAttributes, SFN_PADDING, SFN_SIZE};
+use crate::dir_entry::{DIR_ENTRY_SIZE, DirFileEntryData, FileAttributes, SFN_PADDING, SFN_SIZE};
 use crate::error::Error;
 use crate::file::File;
 use crate::io::{self, IoBase, Read, ReadLeExt, Seek, SeekFrom, Write, WriteLeExt};
 use crate::table::{
-    ClusterIterator, RESERVED_FAT_ENTRIES, alloc_cluster, count_free_clusters, format_fat,
-    read_fat_flags,
+    ClusterIterator, FatValue, RESERVED_FAT_ENTRIES, alloc_cluster, count_free_clusters,
+    format_fat, mark_bad_cluster, read_fat, read_fat_flags, release_reserved_cluster,
+    replace_bad_cluster, reserve_cluster,
 };
 use crate::time::{DefaultTimeProvider, TimeProvider};
 
@@ -307,6 +312,7 @@ pub struct FsOptions<TP, OCC> {
     pub(crate) update_accessed_date: bool,
     pub(crate) oem_cp_converter: OCC,
     pub(crate) time_provider: TP,
+    pub(crate) reserved_clusters: u32,
     #[cfg(feature = "transaction-safe")]
     pub(crate) transaction_log_config: Option<TransactionLogConfig>,
     #[cfg(feature = "audit-log")]
@@ -321,6 +327,7 @@ impl FsOptions<DefaultTimeProvider, LossyOemCpConverter> {
             update_accessed_date: false,
             oem_cp_converter: LossyOemCpConverter::new(),
             time_provider: DefaultTimeProvider::new(),
+            reserved_clusters: 0,
             #[cfg(feature = "transaction-safe")]
             transaction_log_config: None,
             #[cfg(feature = "audit-log")]
@@ -337,6 +344,20 @@ impl<TP: TimeProvider, OCC: OemCpConverter> FsOptions<TP, OCC> {
         self
     }
 
+    /// Keep `count` clusters free for critical writes.
+    ///
+    /// Once only `count` free clusters remain, ordinary allocations fail with
+    /// `Error::NotEnoughSpace`. Handles marked with [`File::set_critical`] can still
+    /// allocate from the reserve, so the device can save its configuration or a crash
+    /// dump even after a log has filled the volume. Creating files and directories never
+    /// uses the reserve, so critical files should exist beforehand. Defaults to 0 (no
+    /// reserve).
+    #[must_use]
+    pub fn reserved_clusters(mut self, count: u32) -> Self {
+        self.reserved_clusters = count;
+        self
+    }
+
     /// Changes default OEM code page encoder-decoder.
     pub fn oem_cp_converter<OCC2: OemCpConverter>(
         self,
@@ -346,6 +367,7 @@ impl<TP: TimeProvider, OCC: OemCpConverter> FsOptions<TP, OCC> {
             update_accessed_date: self.update_accessed_date,
             oem_cp_converter,
             time_provider: self.time_provider,
+            reserved_clusters: self.reserved_clusters,
             #[cfg(feature = "transaction-safe")]
             transaction_log_config: self.transaction_log_config,
             #[cfg(feature = "audit-log")]
@@ -359,6 +381,7 @@ impl<TP: TimeProvider, OCC: OemCpConverter> FsOptions<TP, OCC> {
             update_accessed_date: self.update_accessed_date,
             oem_cp_converter: self.oem_cp_converter,
             time_provider,
+            reserved_clusters: self.reserved_clusters,
             #[cfg(feature = "transaction-safe")]
             transaction_log_config: self.transaction_log_config,
             #[cfg(feature = "audit-log")]
@@ -423,7 +446,7 @@ impl<TP: TimeProvider, OCC: OemCpConverter> FsOptions<TP, OCC> {
     /// use fatrs::{FsOptions, AuditConfig};
     ///
     /// let options = FsOptions::new()
-    ///     .with_audit_log(AuditConfig::new().max_size(2 * 1024 * 1024)); // 2MB max
+    ///     .with_audit_log(AuditConfig::new().sector_count(64).flush_threshold(4));
     /// ```
     #[cfg(feature = "audit-log")]
     #[must_use]
@@ -462,6 +485,36 @@ impl FileSystemStats {
     }
 }
 
+/// How [`FileSystem::scan_surface`] verifies free clusters.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SurfaceScanMode {
+    /// Read every free cluster. Clusters that cannot be read are marked bad.
+    Read,
+    /// Write a test pattern to every free cluster and read it back.
+    ///
+    /// Also finds sectors that silently lose writes. The contents of free clusters are overwritten.
+    Write,
+}
+
+/// Result of a surface scan.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct SurfaceScanReport {
+    /// Number of free clusters that were verified
+    pub clusters_scanned: u32,
+    /// Number of clusters that failed verification and were marked bad
+    pub bad_clusters: u32,
+}
+
+/// Storage object as held by the filesystem.
+///
+/// With the `metrics` feature the storage is wrapped in an adapter counting stream I/O.
+#[cfg(feature = "metrics")]
+pub(crate) type DiskIo<IO> = crate::metrics::MeteredIo<IO>;
+#[cfg(not(feature = "metrics"))]
+pub(crate) type DiskIo<IO> = IO;
+
 /// A FAT filesystem object.
 ///
 /// `FileSystem` struct is representing a state of a mounted FAT volume.
@@ -469,7 +522,7 @@ pub struct FileSystem<IO: Read + Write + Seek, TP, OCC>
 where
     IO::Error: 'static,
 {
-    pub(crate) disk: Shared<IO>,
+    pub(crate) disk: Shared<DiskIo<IO>>,
     pub(crate) options: FsOptions<TP, OCC>,
     fat_type: FatType,
     bpb: BiosParameterBlock,
@@ -488,6 +541,9 @@ where
     /// files are created/modified in the same directory.
     #[cfg(feature = "alloc")]
     pub(crate) dirty_dir_entries: Shared<Vec<DirtyDirEntry>>,
+    /// Open file handles by directory entry position, consulted by remove and rename
+    #[cfg(feature = "alloc")]
+    pub(crate) open_files: Shared<crate::open_files::OpenFileTable>,
     #[cfg(feature = "fat-cache")]
     pub(crate) fat_cache: Shared<crate::fat_cache::FatCache>,
     #[cfg(feature = "dir-cache")]
@@ -500,6 +556,12 @@ where
     pub(crate) file_locks: Shared<crate::file_locking::FileLockManager>,
     #[cfg(feature = "audit-log")]
     pub(crate) audit_log: Shared<crate::audit::AuditLog>,
+    #[cfg(feature = "quotas")]
+    pub(crate) quotas: Shared<crate::quota::QuotaTable>,
+    #[cfg(feature = "fs-events")]
+    pub(crate) events: Shared<crate::events::EventBus>,
+    #[cfg(feature = "metrics")]
+    pub(crate) op_metrics: crate::metrics::OperationRecorder,
 }
 
 /// The underlying storage device
@@ -562,10 +624,23 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
     /// # Panics
     ///
     /// Panics in non-optimized build if `storage` position returned by `seek` is not zero.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "fatrs::mount",
+            skip_all,
+            err(Debug),
+            fields(fat_type = tracing::field::Empty, clusters = tracing::field::Empty)
+        )
+    )]
     pub async fn new<T: IntoStorage<IO>>(
         storage: T,
         options: FsOptions<TP, OCC>,
-    ) -> Result<Self, Error<IO::Error>> {
+    ) -> Result<Self, Error<IO::Error>>
+    where
+        TP: TimeProvider,
+        OCC: OemCpConverter,
+    {
         // Make sure given image is not seeked
         let mut disk = storage.into_storage();
         trace!("FileSystem::new");
@@ -583,6 +658,13 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
         let total_clusters = bpb.total_clusters();
         let fat_type = FatType::from_clusters(total_clusters);
 
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            span.record("fat_type", tracing::field::debug(fat_type));
+            span.record("clusters", total_clusters);
+        }
+
         // read FSInfo sector if this is FAT32
         let mut fs_info = if fat_type == FatType::Fat32 {
             disk.seek(SeekFrom::Start(
@@ -625,7 +707,13 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
             // Otherwise use the user-provided configuration
             let total_sectors = bpb.total_sectors();
             let mut config = if options.audit_config.log_sector_count == crate::audit::DEFAULT_AUDIT_LOG_SECTORS {
-                crate::audit::AuditConfig::automatic(total_sectors)
+                // Automatic sizes are in 512-byte sectors; keep the same area in bytes
+                // on larger sectors so the ring fits where it does on 512-byte volumes
+                let sector_scale = u32::from(bpb.bytes_per_sector) / 512;
+                let mut config =
+                    crate::audit::AuditConfig::automatic(total_sectors.saturating_mul(sector_scale));
+                config.log_sector_count = config.log_sector_count.div_ceil(sector_scale);
+                config
             } else {
                 options.audit_config
             };
@@ -651,6 +739,9 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
 
         trace!("FileSystem::new end");
 
+        #[cfg(feature = "metrics")]
+        let disk = crate::metrics::MeteredIo::new(disk);
+
         let fs = Self {
             disk: Shared::new(disk),
             options,
@@ -664,12 +755,17 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
             cluster_generation: AtomicU64::new(0),
             #[cfg(feature = "alloc")]
             dirty_dir_entries: Shared::new(Vec::new()),
+            #[cfg(feature = "alloc")]
+            open_files: Shared::new(crate::open_files::OpenFileTable::new()),
             #[cfg(feature = "fat-cache")]
             fat_cache: Shared::new(crate::fat_cache::FatCache::new(sector_size)),
             #[cfg(feature = "dir-cache")]
             dir_cache: Shared::new(crate::dir_cache::DirCache::new()),
             #[cfg(feature = "cluster-bitmap")]
-            cluster_bitmap: Shared::new(crate::cluster_bitmap::ClusterBitmap::new(total_clusters)),
+            // Indexed by cluster number, so data clusters start after the reserved FAT entries
+            cluster_bitmap: Shared::new(crate::cluster_bitmap::ClusterBitmap::new(
+                total_clusters + RESERVED_FAT_ENTRIES,
+            )),
             #[cfg(feature = "transaction-safe")]
             transaction_log: Shared::new(crate::transaction::TransactionLog::new(
                 transaction_log_config.log_start_sector,
@@ -679,6 +775,12 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
             file_locks: Shared::new(crate::file_locking::FileLockManager::new()),
             #[cfg(feature = "audit-log")]
             audit_log: Shared::new(crate::audit::AuditLog::new(audit_config)),
+            #[cfg(feature = "quotas")]
+            quotas: Shared::new(crate::quota::QuotaTable::new()),
+            #[cfg(feature = "fs-events")]
+            events: Shared::new(crate::events::EventBus::new()),
+            #[cfg(feature = "metrics")]
+            op_metrics: crate::metrics::OperationRecorder::new(),
         };
 
         // Build cluster bitmap from FAT (one-time cost at mount for 10-100x allocation speedup)
@@ -688,7 +790,7 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
             let mut bitmap = fs.cluster_bitmap.acquire().await;
             let mut fat = fs.fat_slice();
             bitmap
-                .build_from_fat(&mut fat, fat_type, total_clusters)
+                .build_from_fat(&mut fat, fat_type, total_clusters + RESERVED_FAT_ENTRIES)
                 .await?;
             trace!(
                 "Cluster bitmap built: {} free clusters",
@@ -759,14 +861,19 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
             let mut audit_log = fs.audit_log.acquire().await;
             let mut disk = fs.disk.acquire().await;
 
+            audit_log.set_bytes_per_sector(u32::from(fs.bpb.bytes_per_sector));
             if let Err(e) = audit_log.load(&mut *disk).await {
                 // Log error but don't fail mount - audit log is not critical
                 error!("Failed to load audit log: {:?}", e);
             } else {
-                trace!("Audit log loaded: {} entries", audit_log.len());
+                trace!("Audit log loaded, next sequence {}", audit_log.next_sequence());
             }
         }
 
+        // Quotas are enforced from the first operation, so load them before returning
+        #[cfg(feature = "quotas")]
+        fs.load_quotas().await?;
+
         Ok(fs)
     }
 
@@ -846,18 +953,20 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
         ClusterIterator::new(disk_slice, self.fat_type, cluster)
     }
 
+    /// Frees the clusters following `cluster`, returning how many were freed.
     pub(crate) async fn truncate_cluster_chain(
         &self,
         cluster: u32,
-    ) -> Result<(), Error<IO::Error>> {
+    ) -> Result<u32, Error<IO::Error>> {
         let mut iter = self.cluster_iter(cluster);
         let num_free = iter.truncate().await?;
         let mut fs_info = self.fs_info.acquire().await;
         fs_info.map_free_clusters(|n| n + num_free);
-        Ok(())
+        Ok(num_free)
     }
 
-    pub(crate) async fn free_cluster_chain(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+    /// Frees the whole chain starting at `cluster`, returning how many clusters were freed.
+    pub(crate) async fn free_cluster_chain(&self, cluster: u32) -> Result<u32, Error<IO::Error>> {
         // Collect clusters to free (for bitmap update)
         #[cfg(feature = "cluster-bitmap")]
         let mut clusters_to_free = {
@@ -901,16 +1010,45 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
         // This prevents writing to reallocated clusters
         self.cluster_generation.fetch_add(1, Ordering::Release);
 
+        Ok(num_free)
+    }
+
+    /// Points the ".." entry of directory `cluster` at `parent` after the directory was moved.
+    pub(crate) async fn set_parent_dir_cluster(
+        &self,
+        cluster: u32,
+        parent: Option<u32>,
+    ) -> Result<(), Error<IO::Error>> {
+        // ".." refers to the root directory with cluster 0, also on FAT32
+        let parent = parent.filter(|&n| !self.is_root_dir_cluster(n)).unwrap_or(0);
+        let dotdot_pos = self.offset_from_cluster(cluster) + u64::from(DIR_ENTRY_SIZE);
+        let mut disk = self.disk.acquire().await;
+        if self.fat_type == FatType::Fat32 {
+            disk.seek(SeekFrom::Start(dotdot_pos + 20)).await?;
+            disk.write_u16_le((parent >> 16) as u16).await?;
+        }
+        disk.seek(SeekFrom::Start(dotdot_pos + 26)).await?;
+        disk.write_u16_le((parent & 0xFFFF) as u16).await?;
         Ok(())
     }
 
+    pub(crate) fn is_root_dir_cluster(&self, cluster: u32) -> bool {
+        self.fat_type == FatType::Fat32 && cluster == self.bpb.root_dir_first_cluster
+    }
+
+    /// Allocates a cluster, optionally linking it after `prev_cluster`.
+    ///
+    /// Unless `privileged` is set, allocation fails with `Error::NotEnoughSpace` once
+    /// only the clusters reserved by `FsOptions::reserved_clusters` are left.
     #[allow(clippy::await_holding_refcell_ref)]
     pub(crate) async fn alloc_cluster(
         &self,
         prev_cluster: Option<u32>,
         zero: bool,
+        privileged: bool,
     ) -> Result<u32, Error<IO::Error>> {
         trace!("alloc_cluster");
+        let reserved = if privileged { 0 } else { self.options.reserved_clusters };
 
         // Use cluster bitmap for fast allocation if enabled
         #[cfg(feature = "cluster-bitmap")]
@@ -924,17 +1062,32 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
                 .unwrap_or(RESERVED_FAT_ENTRIES);
 
             // Find free cluster using bitmap (O(1) average instead of O(n))
-            match bitmap.find_free(hint_from_fsinfo) {
+            match bitmap.find_free_above_reserve(hint_from_fsinfo, reserved) {
                 Some(cluster) => Some(cluster),
                 None => {
-                    // Bitmap says disk is full
+                    // Bitmap says disk is full (or only the reserve is left)
                     return Err(Error::NotEnoughSpace);
                 }
             }
         };
 
         #[cfg(not(feature = "cluster-bitmap"))]
-        let hint = self.fs_info.acquire().await.next_free_cluster;
+        if reserved > 0 {
+            // Count the free clusters if unknown before taking the lock below
+            self.free_cluster_count().await?;
+        }
+        // Held until the cluster is accounted for, so concurrent allocations cannot
+        // both pass the reserve check
+        #[cfg(not(feature = "cluster-bitmap"))]
+        let mut fs_info = self.fs_info.acquire().await;
+        #[cfg(not(feature = "cluster-bitmap"))]
+        let hint = {
+            if reserved > 0 && fs_info.free_cluster_count.is_some_and(|n| n <= reserved) {
+                trace!("only {} reserved clusters left", reserved);
+                return Err(Error::NotEnoughSpace);
+            }
+            fs_info.next_free_cluster
+        };
 
         let cluster = {
             let mut fat = self.fat_slice();
@@ -955,15 +1108,18 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
             bitmap.set_allocated(cluster);
         }
 
+        #[cfg(feature = "cluster-bitmap")]
+        let mut fs_info = self.fs_info.acquire().await;
+        fs_info.set_next_free_cluster(cluster + 1);
+        fs_info.map_free_clusters(|n| n - 1);
+        drop(fs_info);
+
         if zero {
             let mut disk = self.disk.acquire().await;
             disk.seek(SeekFrom::Start(self.offset_from_cluster(cluster)))
                 .await?;
             write_zeros(&mut *disk, u64::from(self.cluster_size())).await?;
         }
-        let mut fs_info = self.fs_info.acquire().await;
-        fs_info.set_next_free_cluster(cluster + 1);
-        fs_info.map_free_clusters(|n| n - 1);
         Ok(cluster)
     }
 
@@ -990,12 +1146,7 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
     ///
     /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub async fn stats(&self) -> Result<FileSystemStats, Error<IO::Error>> {
-        let free_clusters_option = self.fs_info.acquire().await.free_cluster_count;
-        let free_clusters = if let Some(n) = free_clusters_option {
-            n
-        } else {
-            self.recalc_free_clusters().await?
-        };
+        let free_clusters = self.free_cluster_count().await?;
         Ok(FileSystemStats {
             cluster_size: self.cluster_size(),
             total_clusters: self.total_clusters,
@@ -1003,6 +1154,22 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
         })
     }
 
+    /// Returns the number of free clusters, counting them on first use if unknown.
+    async fn free_cluster_count(&self) -> Result<u32, Error<IO::Error>> {
+        let free_clusters_option = self.fs_info.acquire().await.free_cluster_count;
+        if let Some(n) = free_clusters_option {
+            Ok(n)
+        } else {
+            self.recalc_free_clusters().await
+        }
+    }
+
+    /// Number of clusters kept free for critical writes (see `FsOptions::reserved_clusters`)
+    #[must_use]
+    pub fn reserved_clusters(&self) -> u32 {
+        self.options.reserved_clusters
+    }
+
     /// Forces free clusters recalculation.
     async fn recalc_free_clusters(&self) -> Result<u32, Error<IO::Error>> {
         let mut fat = self.fat_slice();
@@ -1015,6 +1182,244 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
         Ok(free_cluster_count)
     }
 
+    /// Marks free cluster `cluster` as bad so it is never allocated.
+    ///
+    /// The mark is stored in the FAT, so the cluster stays excluded after remounting.
+    /// Marking a cluster that is already bad does nothing.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::InvalidInput` if `cluster` is not a data cluster of this volume or is in use.
+    /// * `Error::Io` if the underlying storage object returned an I/O error.
+    pub async fn mark_bad_cluster(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        self.check_data_cluster(cluster)?;
+        if mark_bad_cluster(&mut self.fat_slice(), self.fat_type, cluster).await? {
+            self.account_bad_cluster(cluster).await;
+            warn!("marked cluster {} as bad", cluster);
+        }
+        Ok(())
+    }
+
+    /// Returns whether `cluster` is marked bad in the FAT.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::InvalidInput` if `cluster` is not a data cluster of this volume.
+    /// * `Error::Io` if the underlying storage object returned an I/O error.
+    pub async fn is_bad_cluster(&self, cluster: u32) -> Result<bool, Error<IO::Error>> {
+        self.check_data_cluster(cluster)?;
+        Ok(read_fat(&mut self.fat_slice(), self.fat_type, cluster).await? == FatValue::Bad)
+    }
+
+    /// Verifies every free cluster and marks the ones that fail as bad.
+    ///
+    /// `on_bad` is called with each cluster the scan marks bad. Clusters allocated while
+    /// the scan runs are skipped; in `SurfaceScanMode::Write` the cluster under test is
+    /// held allocated so its test pattern cannot overwrite new data. Storage errors on
+    /// the verified clusters are not returned: they are what marks a cluster bad.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the FAT cannot be read or updated.
+    pub async fn scan_surface<F: FnMut(u32)>(
+        &self,
+        mode: SurfaceScanMode,
+        mut on_bad: F,
+    ) -> Result<SurfaceScanReport, Error<IO::Error>> {
+        let mut report = SurfaceScanReport::default();
+        for cluster in RESERVED_FAT_ENTRIES..self.total_clusters + RESERVED_FAT_ENTRIES {
+            if read_fat(&mut self.fat_slice(), self.fat_type, cluster).await? != FatValue::Free {
+                continue;
+            }
+            let reserved = mode == SurfaceScanMode::Write;
+            if reserved && !self.reserve_cluster(cluster).await? {
+                continue;
+            }
+            report.clusters_scanned += 1;
+            let good = self.verify_cluster(cluster, mode).await;
+            let marked = if reserved {
+                self.release_reserved_cluster(cluster, !good).await?;
+                !good
+            } else if good {
+                false
+            } else {
+                match mark_bad_cluster(&mut self.fat_slice(), self.fat_type, cluster).await {
+                    Ok(true) => {
+                        self.account_bad_cluster(cluster).await;
+                        true
+                    }
+                    // Already bad or allocated in the meantime
+                    Ok(false) | Err(Error::InvalidInput) => false,
+                    Err(err) => return Err(err),
+                }
+            };
+            if marked {
+                warn!("surface scan marked cluster {} as bad", cluster);
+                report.bad_clusters += 1;
+                on_bad(cluster);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Takes free `cluster` out of the free space, returning `false` if it is not free
+    async fn reserve_cluster(&self, cluster: u32) -> Result<bool, Error<IO::Error>> {
+        // Claim it in the bitmap first so the allocator skips it from now on
+        #[cfg(feature = "cluster-bitmap")]
+        {
+            let mut bitmap = self.cluster_bitmap.acquire().await;
+            if !bitmap.is_free(cluster) {
+                return Ok(false);
+            }
+            bitmap.set_allocated(cluster);
+        }
+        match reserve_cluster(&mut self.fat_slice(), self.fat_type, cluster).await {
+            Ok(true) => {
+                self.fs_info.acquire().await.map_free_clusters(|n| n - 1);
+                Ok(true)
+            }
+            // In use according to the FAT, which the bitmap now agrees with
+            Ok(false) => Ok(false),
+            Err(err) => {
+                #[cfg(feature = "cluster-bitmap")]
+                self.cluster_bitmap.acquire().await.set_free(cluster);
+                Err(err)
+            }
+        }
+    }
+
+    /// Ends a reservation made by `reserve_cluster`, marking the cluster bad or free again
+    async fn release_reserved_cluster(&self, cluster: u32, bad: bool) -> Result<(), Error<IO::Error>> {
+        release_reserved_cluster(&mut self.fat_slice(), self.fat_type, cluster, bad).await?;
+        #[cfg(feature = "cluster-bitmap")]
+        {
+            let mut bitmap = self.cluster_bitmap.acquire().await;
+            if bad {
+                bitmap.set_bad(cluster);
+            } else {
+                bitmap.set_free(cluster);
+            }
+        }
+        if !bad {
+            self.fs_info.acquire().await.map_free_clusters(|n| n + 1);
+        }
+        Ok(())
+    }
+
+    fn check_data_cluster(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        if cluster < RESERVED_FAT_ENTRIES || cluster >= self.total_clusters + RESERVED_FAT_ENTRIES {
+            return Err(Error::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Removes a cluster that was free and is now marked bad from the free space
+    async fn account_bad_cluster(&self, cluster: u32) {
+        #[cfg(feature = "cluster-bitmap")]
+        self.cluster_bitmap.acquire().await.set_bad(cluster);
+        #[cfg(not(feature = "cluster-bitmap"))]
+        let _ = cluster;
+        self.fs_info.acquire().await.map_free_clusters(|n| n - 1);
+    }
+
+    /// Reads a whole cluster, writing a test pattern first in `SurfaceScanMode::Write`.
+    ///
+    /// Returns `false` if any part of it failed.
+    async fn verify_cluster(&self, cluster: u32, mode: SurfaceScanMode) -> bool {
+        const PATTERN: [u8; 512] = [0xA5; 512];
+        let mut buf = [0_u8; 512];
+        let start = self.offset_from_cluster(cluster);
+        let mut disk = self.disk.acquire().await;
+        for chunk in (0..u64::from(self.cluster_size())).step_by(buf.len()) {
+            let pos = start + chunk;
+            if mode == SurfaceScanMode::Write
+                && (disk.seek(SeekFrom::Start(pos)).await.is_err()
+                    || disk.write_all(&PATTERN).await.is_err()
+                    || disk.flush().await.is_err())
+            {
+                return false;
+            }
+            if disk.seek(SeekFrom::Start(pos)).await.is_err()
+                || disk.read_exact(&mut buf).await.is_err()
+            {
+                return false;
+            }
+            if mode == SurfaceScanMode::Write && buf != PATTERN {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Moves the contents of `bad_cluster` to a new cluster and marks it bad.
+    ///
+    /// `bad_cluster` belongs to the chain starting at `first_cluster`; the new cluster
+    /// takes its place in the chain. Parts of the old cluster that cannot be read are
+    /// zero-filled. Returns the new cluster.
+    pub(crate) async fn relocate_cluster(
+        &self,
+        first_cluster: u32,
+        bad_cluster: u32,
+        privileged: bool,
+    ) -> Result<u32, Error<IO::Error>> {
+        let prev_cluster = if first_cluster == bad_cluster {
+            None
+        } else {
+            let mut prev = first_cluster;
+            let mut iter = self.cluster_iter(first_cluster);
+            loop {
+                match iter.next().await {
+                    Some(Ok(n)) if n == bad_cluster => break Some(prev),
+                    Some(Ok(n)) => prev = n,
+                    Some(Err(err)) => return Err(err),
+                    None => return Err(Error::CorruptedFileSystem),
+                }
+            }
+        };
+        let new_cluster = self.alloc_cluster(None, false, privileged).await?;
+        if let Err(err) = self.copy_cluster(bad_cluster, new_cluster).await {
+            self.free_cluster_chain(new_cluster).await?;
+            return Err(err);
+        }
+        replace_bad_cluster(
+            &mut self.fat_slice(),
+            self.fat_type,
+            prev_cluster,
+            bad_cluster,
+            new_cluster,
+        )
+        .await?;
+        // The bad cluster was in use, so it is not counted as free anyway
+        #[cfg(feature = "cluster-bitmap")]
+        self.cluster_bitmap.acquire().await.set_bad(bad_cluster);
+        warn!(
+            "relocated cluster {} to {} after a write error",
+            bad_cluster, new_cluster
+        );
+        Ok(new_cluster)
+    }
+
+    async fn copy_cluster(&self, src: u32, dst: u32) -> Result<(), Error<IO::Error>> {
+        let mut buf = [0_u8; 512];
+        let src_offset = self.offset_from_cluster(src);
+        let dst_offset = self.offset_from_cluster(dst);
+        let mut disk = self.disk.acquire().await;
+        for chunk in (0..u64::from(self.cluster_size())).step_by(buf.len()) {
+            let readable = disk.seek(SeekFrom::Start(src_offset + chunk)).await.is_ok()
+                && disk.read_exact(&mut buf).await.is_ok();
+            if !readable {
+                warn!(
+                    "zero-filling unreadable data in cluster {} at offset {}",
+                    src, chunk
+                );
+                buf.fill(0);
+            }
+            disk.seek(SeekFrom::Start(dst_offset + chunk)).await?;
+            disk.write_all(&buf).await?;
+        }
+        Ok(())
+    }
+
     /// Unmounts the filesystem.
     ///
     /// Updates the FS Information Sector if needed.
@@ -1074,6 +1479,33 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
         }
     }
 
+    /// Registers an open file handle for the directory entry at `pos`.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn track_open_file(&self, pos: u64) {
+        self.open_files.spin_acquire().open(pos);
+    }
+
+    /// Unregisters an open file handle for the directory entry at `pos`.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn untrack_open_file(&self, pos: u64) {
+        self.open_files.spin_acquire().close(pos);
+    }
+
+    /// Returns whether a file handle is open on the directory entry at `pos`.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn is_file_open(&self, pos: u64) -> bool {
+        self.open_files.spin_acquire().handles(pos) > 0
+    }
+
+    /// Returns the number of files that currently have open handles.
+    ///
+    /// Files with open handles cannot be removed or renamed (`Error::FileBusy`).
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn open_file_count(&self) -> usize {
+        self.open_files.spin_acquire().len()
+    }
+
     /// Removes a dirty directory entry from the registry.
     ///
     /// This is called after a DirEntryEditor has been successfully flushed.
@@ -1125,6 +1557,10 @@ impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
     /// Updates the FS Information Sector if needed and clears
     /// the dirty flag.
     #[allow(clippy::missing_errors_doc)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "fatrs::fs_flush", level = "debug", skip_all, err(Debug))
+    )]
     pub async fn flush(&self) -> Result<(), Error<IO::Error>> {
         // Flush any dirty directory entries first
         #[cfg(feature = "alloc")]
@@ -1394,61 +1830,643 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> FileSystem<IO, TP, OCC> {
         let tx_log = self.transaction_log.acquire().await;
         tx_log.get_all_transaction_info()
     }
+}
 
-    /// Get audit log entries
+#[cfg(feature = "fs-events")]
+impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
+    /// Subscribe to filesystem change notifications
     ///
-    /// Returns all buffered audit log entries. The audit log holds up to 16
-    /// recent entries in memory before they would be written to disk.
-    #[cfg(all(feature = "audit-log", not(feature = "std")))]
-    pub async fn audit_entries(&self) -> alloc::vec::Vec<crate::audit::AuditEntry> {
-        let audit = self.audit_log.acquire().await;
-        audit.entries().cloned().collect()
+    /// Returns a subscription that receives created, modified, closed-after-write,
+    /// renamed, deleted and directory events. Up to 64 events are buffered; use
+    /// [`FileSystem::subscribe_with_capacity`] to change that.
+    pub fn subscribe(&self) -> crate::events::FsEventSubscription {
+        self.subscribe_with_capacity(crate::events::DEFAULT_EVENT_QUEUE_CAPACITY)
     }
 
-    /// Get audit log entries
+    /// Subscribe to filesystem change notifications with a custom queue size
     ///
-    /// Returns all buffered audit log entries. The audit log holds up to 16
-    /// recent entries in memory before they would be written to disk.
-    #[cfg(all(feature = "audit-log", feature = "std"))]
-    pub async fn audit_entries(&self) -> Vec<crate::audit::AuditEntry> {
-        let audit = self.audit_log.acquire().await;
-        audit.entries().cloned().collect()
+    /// When the queue is full the oldest event is dropped.
+    pub fn subscribe_with_capacity(&self, capacity: usize) -> crate::events::FsEventSubscription {
+        crate::events::FsEventSubscription::new(self.events.clone(), capacity)
     }
 
-    /// Helper to get current timestamp for audit logging
-    #[cfg(feature = "audit-log")]
-    fn current_timestamp() -> u64 {
-        #[cfg(feature = "std")]
-        {
-            use std::time::SystemTime;
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis() as u64
+    /// Publish a change notification to all subscribers
+    pub(crate) fn emit_event(&self, kind: crate::events::FsEventKind, path: &str, new_path: Option<&str>) {
+        self.events.spin_acquire().publish(kind, path, new_path);
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
+    /// Get a snapshot of I/O, operation and cache metrics
+    ///
+    /// Counters accumulate since mount or the last [`FileSystem::reset_metrics`] call.
+    /// Stream counters include metadata traffic but are taken above any caching
+    /// or translation in the storage stack; see [`crate::metrics`].
+    pub async fn metrics(&self) -> crate::metrics::FsMetrics {
+        let stream = self.disk.acquire().await.stream_metrics();
+        crate::metrics::FsMetrics {
+            operations: self.op_metrics.operations(),
+            user_bytes_read: self.op_metrics.user_bytes_read(),
+            user_bytes_written: self.op_metrics.user_bytes_written(),
+            stream,
+            #[cfg(feature = "fat-cache")]
+            fat_cache: self.fat_cache.acquire().await.statistics(),
+            #[cfg(feature = "dir-cache")]
+            dir_cache: self.dir_cache.acquire().await.statistics(),
         }
-        #[cfg(not(feature = "std"))]
+    }
+
+    /// Reset all metrics counters, including cache hit/miss counters
+    ///
+    /// Cached data itself is kept.
+    pub async fn reset_metrics(&self) {
+        self.disk.acquire().await.reset();
+        self.op_metrics.reset();
+        #[cfg(feature = "fat-cache")]
+        self.fat_cache.acquire().await.reset_statistics();
+        #[cfg(feature = "dir-cache")]
+        self.dir_cache.acquire().await.reset_statistics();
+    }
+
+    /// Enable device latency histograms
+    ///
+    /// `clock` must return a monotonic time in microseconds, for example
+    /// `|| embassy_time::Instant::now().as_micros()`. Pass `None` to stop
+    /// recording latencies.
+    pub async fn set_metrics_clock(&self, clock: Option<fn() -> u64>) {
+        self.disk.acquire().await.set_clock(clock);
+    }
+
+    /// Count a filesystem operation
+    pub(crate) fn count_operation(&self, operation: crate::metrics::Operation) {
+        self.op_metrics.count(operation);
+    }
+}
+
+#[cfg(feature = "quotas")]
+impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
+    /// Returns the parent of directory `cluster` from its ".." entry, `None` for the root directory
+    async fn parent_dir_cluster(&self, cluster: u32) -> Result<Option<u32>, Error<IO::Error>> {
+        let mut raw = [0_u8; DIR_ENTRY_SIZE as usize];
+        let mut found = false;
         {
-            // For no_std, use a placeholder timestamp
-            // In real embedded systems, this would come from an RTC
+            let mut disk = self.disk.acquire().await;
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(cluster))).await?;
+            // ".." is normally the second entry, but LFN entries may precede it
+            for _ in 0..self.cluster_size() / DIR_ENTRY_SIZE {
+                disk.read_exact(&mut raw).await?;
+                if raw[0] == 0 {
+                    break;
+                }
+                if raw[11] != FileAttributes::LFN.bits() && raw[..SFN_SIZE] == *b"..         " {
+                    found = true;
+                    break;
+                }
+            }
+        }
+        if !found {
+            error!("directory at cluster {} has no '..' entry", cluster);
+            return Err(Error::CorruptedFileSystem);
+        }
+        let hi = if self.fat_type == FatType::Fat32 {
+            u16::from_le_bytes([raw[20], raw[21]])
+        } else {
             0
+        };
+        let parent = (u32::from(hi) << 16) | u32::from(u16::from_le_bytes([raw[26], raw[27]]));
+        if parent == 0 || self.is_root_dir_cluster(parent) {
+            Ok(None)
+        } else {
+            Ok(Some(parent))
+        }
+    }
+
+    /// Returns the quotas charged for allocations on behalf of directory `dir`.
+    ///
+    /// The ancestry is resolved by walking ".." entries and cached per directory.
+    pub(crate) async fn governing_quotas(
+        &self,
+        dir: Option<u32>,
+    ) -> Result<Vec<crate::quota::QuotaId>, Error<IO::Error>> {
+        let Some(dir) = dir.filter(|&n| !self.is_root_dir_cluster(n)) else {
+            return Ok(Vec::new());
+        };
+        {
+            let quotas = self.quotas.acquire().await;
+            if quotas.is_empty() {
+                return Ok(Vec::new());
+            }
+            if let Some(cached) = quotas.cached_governing(dir) {
+                return Ok(cached.to_vec());
+            }
+        }
+        let mut ancestors = Vec::new();
+        let mut current = Some(dir);
+        while let Some(cluster) = current {
+            if ancestors.contains(&cluster) {
+                error!("directory loop at cluster {}", cluster);
+                return Err(Error::CorruptedFileSystem);
+            }
+            ancestors.push(cluster);
+            current = self.parent_dir_cluster(cluster).await?;
+        }
+        Ok(self.quotas.acquire().await.resolve_governing(dir, &ancestors))
+    }
+
+    /// Allocates a cluster like `alloc_cluster`, charging it to the quotas governing directory `owner`.
+    ///
+    /// Fails with `Error::QuotaExceeded` before touching the FAT if any of them is full.
+    pub(crate) async fn alloc_cluster_for(
+        &self,
+        owner: Option<u32>,
+        prev_cluster: Option<u32>,
+        zero: bool,
+        privileged: bool,
+    ) -> Result<u32, Error<IO::Error>> {
+        let quotas = self.governing_quotas(owner).await?;
+        if !quotas.is_empty() && !self.quotas.acquire().await.try_charge(&quotas, 1) {
+            trace!("quota exceeded for directory {:?}", owner);
+            return Err(Error::QuotaExceeded);
+        }
+        let result = self.alloc_cluster(prev_cluster, zero, privileged).await;
+        if result.is_err() {
+            self.release_governed(&quotas, 1).await;
+        }
+        result
+    }
+
+    /// Releases `clusters` freed on behalf of directory `owner`
+    pub(crate) async fn release_quota(
+        &self,
+        owner: Option<u32>,
+        clusters: u32,
+    ) -> Result<(), Error<IO::Error>> {
+        if clusters > 0 {
+            let quotas = self.governing_quotas(owner).await?;
+            self.release_governed(&quotas, clusters).await;
+        }
+        Ok(())
+    }
+
+    /// Releases `clusters` from quotas resolved earlier with `governing_quotas`
+    pub(crate) async fn release_governed(&self, quotas: &[crate::quota::QuotaId], clusters: u32) {
+        if !quotas.is_empty() && clusters > 0 {
+            self.quotas.acquire().await.release(quotas, clusters);
+        }
+    }
+
+    /// Charges `clusters` moving from directory `from` to directory `to` to the destination quotas.
+    ///
+    /// Quotas containing both directories are unaffected. Fails with `Error::QuotaExceeded`
+    /// if the destination cannot take them. The returned reservation must be completed
+    /// with `finish_quota_transfer` once the move succeeded or failed.
+    pub(crate) async fn reserve_quota_transfer(
+        &self,
+        from: Option<u32>,
+        to: Option<u32>,
+        clusters: u32,
+    ) -> Result<crate::quota::QuotaTransfer, Error<IO::Error>> {
+        let source = self.governing_quotas(from).await?;
+        let target = self.governing_quotas(to).await?;
+        let gained: Vec<_> = target.iter().copied().filter(|id| !source.contains(id)).collect();
+        let lost: Vec<_> = source.iter().copied().filter(|id| !target.contains(id)).collect();
+        if !self.quotas.acquire().await.try_charge(&gained, clusters) {
+            return Err(Error::QuotaExceeded);
+        }
+        Ok(crate::quota::QuotaTransfer { gained, lost, clusters })
+    }
+
+    /// Completes a reservation: releases the source quotas if the move succeeded,
+    /// otherwise gives the reserved clusters back to the destination quotas
+    pub(crate) async fn finish_quota_transfer(&self, transfer: crate::quota::QuotaTransfer, moved: bool) {
+        let released = if moved { &transfer.lost } else { &transfer.gained };
+        self.release_governed(released, transfer.clusters).await;
+    }
+
+    /// Number of clusters in the chain starting at `cluster`
+    pub(crate) async fn chain_length(&self, cluster: u32) -> Result<u32, Error<IO::Error>> {
+        let mut len = 1;
+        let mut iter = self.cluster_iter(cluster);
+        while let Some(r) = iter.next().await {
+            r?;
+            len += 1;
+        }
+        Ok(len)
+    }
+
+    /// Opens the directory starting at `cluster` (the root directory for `None`)
+    fn dir_from_cluster(&self, cluster: Option<u32>) -> Dir<'_, IO, TP, OCC> {
+        match cluster {
+            Some(n) if !self.is_root_dir_cluster(n) => {
+                let file = File::new(Some(n), None, self).with_quota_owner(Some(n));
+                Dir::new(DirRawStream::File(file), self)
+            }
+            _ => self.root_dir(),
+        }
+    }
+}
+
+#[cfg(feature = "quotas")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> FileSystem<IO, TP, OCC> {
+    /// Attach a space quota to the directory subtree at `path`
+    ///
+    /// `path` is relative to the root directory. Allocations for files and directories
+    /// below it fail with `Error::QuotaExceeded` once the subtree occupies `limit`.
+    /// Setting a quota on a directory that already has one replaces its limit. The
+    /// definition is stored on the volume and survives remounting.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::InvalidInput` if `path` is the root directory or a file.
+    /// * `Error::NotFound` if `path` does not exist.
+    /// * `Error::Io` if the underlying storage object returned an I/O error.
+    pub async fn set_quota(
+        &self,
+        path: &str,
+        limit: crate::quota::QuotaLimit,
+    ) -> Result<(), Error<IO::Error>> {
+        let path = crate::quota::normalize_path(path);
+        if path.is_empty() {
+            return Err(Error::InvalidInput);
+        }
+        let dir = self.root_dir().open_dir(&path).await?;
+        let limit = match limit {
+            crate::quota::QuotaLimit::Clusters(n) => n,
+            crate::quota::QuotaLimit::Bytes(bytes) => {
+                u32::try_from(bytes.div_ceil(u64::from(self.cluster_size()))).unwrap_or(u32::MAX)
+            }
+        };
+        let used = self.subtree_clusters(&dir).await?;
+        self.quotas
+            .acquire()
+            .await
+            .insert(path, dir.first_cluster(), limit, used);
+        self.save_quotas().await
+    }
+
+    /// Remove the quota attached to `path`, returning whether one existed
+    pub async fn remove_quota(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        let path = crate::quota::normalize_path(path);
+        let removed = {
+            let mut quotas = self.quotas.acquire().await;
+            match quotas.find(&path) {
+                Some(index) => {
+                    quotas.remove(index);
+                    true
+                }
+                None => false,
+            }
+        };
+        if removed {
+            self.save_quotas().await?;
+        }
+        Ok(removed)
+    }
+
+    /// Limits and current usage of all quotas
+    ///
+    /// A quota whose directory no longer exists reports no usage until it is recreated
+    /// and [`FileSystem::rescan_quotas`] is called.
+    pub async fn quotas(&self) -> Result<Vec<crate::quota::QuotaUsage>, Error<IO::Error>> {
+        let cluster_size = self.cluster_size();
+        let quotas = self.quotas.acquire().await;
+        Ok(quotas
+            .entries()
+            .iter()
+            .map(|e| crate::quota::QuotaUsage {
+                path: e.path.clone(),
+                limit_clusters: e.limit,
+                used_clusters: e.used,
+                cluster_size,
+            })
+            .collect())
+    }
+
+    /// Recompute quota usage by scanning every quota subtree
+    ///
+    /// This is done automatically after mounting; call it after the volume was
+    /// modified by another implementation.
+    pub async fn rescan_quotas(&self) -> Result<(), Error<IO::Error>> {
+        self.scan_quotas().await
+    }
+
+    /// Loads the quota definitions and scans their usage, called when mounting
+    ///
+    /// Of the two copies, the valid one with the highest generation is used.
+    pub(crate) async fn load_quotas(&self) -> Result<(), Error<IO::Error>> {
+        let mut newest: Option<(u32, Vec<(String, u32)>)> = None;
+        for name in crate::quota::QUOTA_FILE_NAMES {
+            let Some(mut file) = self.root_dir().open_metadata_file(name).await? else {
+                continue;
+            };
+            let mut data = Vec::new();
+            let mut buf = [0_u8; 512];
+            loop {
+                let n = file.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                data.extend_from_slice(&buf[..n]);
+            }
+            match crate::quota::QuotaTable::deserialize(&data) {
+                // Generations wrap around, so compare them as serial numbers
+                Some(copy)
+                    if newest
+                        .as_ref()
+                        .is_none_or(|(generation, _)| (1..=u32::MAX / 2).contains(&copy.0.wrapping_sub(*generation))) =>
+                {
+                    newest = Some(copy);
+                }
+                Some(_) => {}
+                None => warn!("Ignoring malformed quota file {}", name),
+            }
+        }
+        let Some((generation, definitions)) = newest else {
+            return Ok(());
+        };
+        let count = definitions.len();
+        {
+            let mut quotas = self.quotas.acquire().await;
+            quotas.set_generation(generation);
+            for (path, limit) in definitions {
+                quotas.insert(path, None, limit, 0);
+            }
         }
+        self.scan_quotas().await?;
+        trace!("Loaded {} directory quota(s)", count);
+        Ok(())
+    }
+
+    /// Writes the definitions as a new generation over the older of the two copies
+    ///
+    /// The newer copy is left untouched, so a torn write falls back to it at load.
+    async fn save_quotas(&self) -> Result<(), Error<IO::Error>> {
+        let (generation, data) = {
+            let quotas = self.quotas.acquire().await;
+            let generation = quotas.generation().wrapping_add(1);
+            (generation, quotas.serialize(generation))
+        };
+        let name = crate::quota::QUOTA_FILE_NAMES[generation as usize % 2];
+        let mut file = self.root_dir().create_metadata_file(name).await?;
+        file.truncate().await?;
+        file.write_all(&data).await?;
+        file.flush().await?;
+        // Only a complete copy becomes current; a failed write is retried over the same copy
+        self.quotas.acquire().await.set_generation(generation);
+        Ok(())
+    }
+
+    /// Resolves every quota path and recounts its usage
+    async fn scan_quotas(&self) -> Result<(), Error<IO::Error>> {
+        let paths: Vec<(crate::quota::QuotaId, String)> = self
+            .quotas
+            .acquire()
+            .await
+            .entries()
+            .iter()
+            .map(|e| (e.id, e.path.clone()))
+            .collect();
+        for (id, path) in &paths {
+            let (dir_cluster, used) = match self.root_dir().open_dir(path).await {
+                Ok(dir) => (dir.first_cluster(), self.subtree_clusters(&dir).await?),
+                Err(Error::NotFound | Error::InvalidInput) => {
+                    warn!("Quota directory {} does not exist", path.as_str());
+                    (None, 0)
+                }
+                Err(err) => return Err(err),
+            };
+            // The quota may have been removed while its subtree was scanned
+            if let Some(entry) = self.quotas.acquire().await.entry_mut(*id) {
+                entry.dir_cluster = dir_cluster;
+                entry.used = used;
+            }
+        }
+        self.quotas.acquire().await.forget_governing();
+        Ok(())
+    }
+
+    /// Number of clusters occupied by `dir` and everything below it
+    pub(crate) async fn subtree_clusters<'s>(&'s self, dir: &Dir<'s, IO, TP, OCC>) -> Result<u32, Error<IO::Error>> {
+        let mut total = match dir.first_cluster() {
+            Some(n) => self.chain_length(n).await?,
+            None => 0,
+        };
+        let mut pending = Vec::new();
+        pending.push(dir.clone());
+        while let Some(dir) = pending.pop() {
+            let mut iter = dir.iter();
+            while let Some(r) = iter.next().await {
+                let e = r?;
+                let name = e.short_file_name_as_bytes();
+                if name == b"." || name == b".." {
+                    continue;
+                }
+                if let Some(n) = e.first_cluster() {
+                    total = total.saturating_add(self.chain_length(n).await?);
+                }
+                if e.is_dir() {
+                    pending.push(e.to_dir());
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    /// Path of directory `cluster` relative to the root, found by walking ".." entries
+    async fn dir_path(&self, cluster: u32) -> Result<String, Error<IO::Error>> {
+        let mut names: Vec<String> = Vec::new();
+        let mut current = cluster;
+        loop {
+            let parent = self.parent_dir_cluster(current).await?;
+            let mut iter = self.dir_from_cluster(parent).iter();
+            let mut name = None;
+            while let Some(r) = iter.next().await {
+                let e = r?;
+                let short_name = e.short_file_name_as_bytes();
+                if e.is_dir()
+                    && e.first_cluster() == Some(current)
+                    && short_name != b"."
+                    && short_name != b".."
+                {
+                    name = Some(e.file_name());
+                    break;
+                }
+            }
+            names.push(name.ok_or(Error::CorruptedFileSystem)?);
+            match parent {
+                Some(n) if names.len() < self.total_clusters as usize => current = n,
+                Some(_) => return Err(Error::CorruptedFileSystem),
+                None => break,
+            }
+        }
+        names.reverse();
+        Ok(names.join("/"))
+    }
+
+    /// Drops the quota rooted at a removed directory
+    pub(crate) async fn quota_dir_removed(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        let removed = {
+            let mut quotas = self.quotas.acquire().await;
+            // the cluster may be reused by a directory with a different ancestry
+            quotas.forget_governing();
+            match quotas.find_by_cluster(cluster) {
+                Some(index) => {
+                    quotas.remove(index);
+                    true
+                }
+                None => false,
+            }
+        };
+        if removed {
+            self.save_quotas().await?;
+        }
+        Ok(())
+    }
+
+    /// Updates quota paths after a directory rename and recounts usage if it moved
+    pub(crate) async fn quota_dir_renamed(&self, moved: bool) -> Result<(), Error<IO::Error>> {
+        let roots: Vec<(crate::quota::QuotaId, u32)> = {
+            let mut quotas = self.quotas.acquire().await;
+            if quotas.is_empty() {
+                return Ok(());
+            }
+            quotas.forget_governing();
+            quotas.entries().iter().filter_map(|e| Some((e.id, e.dir_cluster?))).collect()
+        };
+        let mut changed = false;
+        for (id, root) in roots {
+            let path = self.dir_path(root).await?;
+            let used = if moved {
+                Some(self.subtree_clusters(&self.dir_from_cluster(Some(root))).await?)
+            } else {
+                None
+            };
+            let mut quotas = self.quotas.acquire().await;
+            let Some(entry) = quotas.entry_mut(id) else { continue };
+            if entry.path != path {
+                entry.path = path;
+                changed = true;
+            }
+            if let Some(used) = used {
+                entry.used = used;
+            }
+        }
+        if changed {
+            self.save_quotas().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "audit-log")]
+impl<IO: ReadWriteSeek, TP, OCC> FileSystem<IO, TP, OCC> {
+    /// Get pending audit log entries
+    ///
+    /// Returns the entries buffered in memory that have not been written to
+    /// disk yet. Use [`FileSystem::audit_history`] to read the persisted log.
+    pub async fn audit_entries(&self) -> Vec<crate::audit::AuditEntry> {
+        let audit = self.audit_log.acquire().await;
+        audit.entries().cloned().collect()
+    }
+
+    /// Iterate over the persisted audit log
+    ///
+    /// Pending entries are flushed first, so the history includes every entry
+    /// logged so far. Entries are returned from oldest to newest; once the
+    /// reserved area is full the oldest entries are overwritten.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub async fn audit_history(&self) -> Result<crate::audit::AuditHistory<'_, IO, TP, OCC>, Error<IO::Error>> {
+        let mut audit = self.audit_log.acquire().await;
+        let mut disk = self.disk.acquire().await;
+        audit.flush(&mut *disk).await?;
+        let records = audit.scan(&mut *disk).await?;
+        Ok(crate::audit::AuditHistory::new(self, audit.area_offset(), records))
+    }
+
+    /// Verify the tamper-evident hash chain of the persisted audit log
+    ///
+    /// Pending entries are flushed first. Records are checked from oldest to
+    /// newest, and verification stops at the first broken link, which is
+    /// reported in [`AuditChainReport::broken`](crate::AuditChainReport::broken).
+    /// HMAC-protected records can only be verified if the same key is configured
+    /// with [`AuditConfig::integrity`](crate::AuditConfig::integrity).
+    ///
+    /// Removing the newest records cannot be detected from the log alone; compare
+    /// [`AuditChainReport::last_sequence`](crate::AuditChainReport::last_sequence)
+    /// with a previously exported value to detect that.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "audit-chain")]
+    pub async fn verify_audit_chain(&self) -> Result<crate::audit::AuditChainReport, Error<IO::Error>> {
+        let mut audit = self.audit_log.acquire().await;
+        let mut disk = self.disk.acquire().await;
+        audit.flush(&mut *disk).await?;
+        Ok(audit.verify_chain(&mut *disk).await?)
+    }
+
+    /// Set the application context recorded with subsequent audit entries
+    ///
+    /// The context (task ID and user tag) makes entries attributable. It stays
+    /// in effect until replaced; pass `AuditContext::default()` to clear it.
+    pub async fn set_audit_context(&self, context: crate::audit::AuditContext) {
+        self.audit_log.acquire().await.set_context(context);
+    }
+
+    /// Get the application context recorded with new audit entries
+    pub async fn audit_context(&self) -> crate::audit::AuditContext {
+        self.audit_log.acquire().await.context()
+    }
+
+    /// Get the audit log counters
+    ///
+    /// [`AuditStats::dropped`](crate::AuditStats::dropped) counts entries lost
+    /// because the pending buffer overflowed while flushing to disk was failing.
+    pub async fn audit_stats(&self) -> crate::audit::AuditStats {
+        self.audit_log.acquire().await.stats()
+    }
+
+    /// Write pending audit entries to disk once the flush threshold is reached
+    async fn flush_audit_if_needed(&self, audit: &mut crate::audit::AuditLog) {
+        if audit.needs_flush() {
+            let mut disk = self.disk.acquire().await;
+            if let Err(e) = audit.flush(&mut *disk).await {
+                // Audit log is not critical - keep entries buffered and retry later
+                warn!("Failed to flush audit log: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "audit-log")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> FileSystem<IO, TP, OCC> {
+    /// Current wall-clock time from the time provider in milliseconds since the Unix epoch
+    ///
+    /// Returns `None` if the provider has no usable clock (it reports the DOS epoch).
+    fn audit_wall_clock(&self) -> Option<u64> {
+        crate::audit::wall_clock_millis(self.options.time_provider.get_current_date_time())
     }
 
     /// Helper to log an audit entry
-    #[cfg(feature = "audit-log")]
     pub(crate) async fn log_audit(
         &self,
         operation: crate::audit::AuditOperation,
         path: &str,
         result: crate::audit::AuditResult,
     ) {
-        let timestamp = Self::current_timestamp();
+        let wall_clock = self.audit_wall_clock();
         let mut audit = self.audit_log.acquire().await;
+        let timestamp = audit.next_timestamp(wall_clock);
         audit.log_file_op(timestamp, operation, path, result);
+        self.flush_audit_if_needed(&mut audit).await;
     }
 
     /// Helper to log an audit entry with data
-    #[cfg(feature = "audit-log")]
     pub(crate) async fn log_audit_with_data(
         &self,
         operation: crate::audit::AuditOperation,
@@ -1456,9 +2474,11 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> FileSystem<IO, TP, OCC> {
         result: crate::audit::AuditResult,
         data: u64,
     ) {
-        let timestamp = Self::current_timestamp();
+        let wall_clock = self.audit_wall_clock();
         let mut audit = self.audit_log.acquire().await;
+        let timestamp = audit.next_timestamp(wall_clock);
         audit.log_file_op_with_data(timestamp, operation, path, result, data);
+        self.flush_audit_if_needed(&mut audit).await;
     }
 }
 
@@ -1817,11 +2837,11 @@ impl FormatVolumeOptions {
     ///
     /// # Panics
     ///
-    /// Panics if `bytes_per_sector` is not a power of two or is lower than `512`.
+    /// Panics if `bytes_per_sector` is not a power of two or is outside of the range [512, 4096].
     #[must_use]
     pub fn bytes_per_sector(mut self, bytes_per_sector: u16) -> Self {
         assert!(
-            bytes_per_sector.is_power_of_two() && bytes_per_sector >= 512,
+            bytes_per_sector.is_power_of_two() && (512..=4096).contains(&bytes_per_sector),
             "Invalid bytes_per_sector"
         );
         self.bytes_per_sector = Some(bytes_per_sector);
@@ -1840,7 +2860,7 @@ impl FormatVolumeOptions {
     /// Set maximal numer of entries in root directory for FAT12/FAT16 volumes
     ///
     /// Total root directory size should be dividable by sectors size so keep it a multiple of 16 (for default sector
-    /// size).
+    /// size) or 128 (for 4096-byte sectors).
     /// Note: this limit is not used on FAT32 volumes.
     /// Default is `512`.
     #[must_use]
@@ -32,6 +32,16 @@
 //!
 //! See the [`send_bounds`] module for detailed examples.
 //!
+//! # Tracing
+//!
+//! With the `tracing` feature, mount, open, create, read, write, flush, rename and
+//! remove run inside `fatrs::*` spans. Path operations carry a `path` field (`src`
+//! and `dst` for renames); reads and writes carry `offset`, `requested`, `bytes`
+//! and `clusters` (the number of clusters the transfer touched). Span durations are
+//! reported by the subscriber, e.g. `tracing_subscriber::fmt().with_span_events(FmtSpan::CLOSE)`.
+//! When `log` is not enabled, the crate's log lines are emitted as `tracing` events
+//! inside these spans.
+//!
 //! # Examples
 //!
 //! ```rust,ignore
@@ -124,9 +134,21 @@ mod transaction;
 #[cfg(feature = "file-locking")]
 mod file_locking;
 
+#[cfg(feature = "alloc")]
+mod open_files;
+
+#[cfg(feature = "quotas")]
+mod quota;
+
 #[cfg(feature = "audit-log")]
 mod audit;
 
+#[cfg(feature = "fs-events")]
+mod events;
+
+#[cfg(feature = "metrics")]
+mod metrics;
+
 pub use crate::dir::*;
 pub use crate::dir_entry::*;
 pub use crate::error::*;
@@ -143,7 +165,28 @@ pub use crate::transaction::{
 #[cfg(feature = "file-locking")]
 pub use crate::file_locking::{FileLockManager, FileLockState, LockType};
 
+#[cfg(feature = "quotas")]
+pub use crate::quota::{QUOTA_FILE_NAME, QuotaLimit, QuotaUsage};
+
+#[cfg(feature = "metrics")]
+pub use crate::metrics::{
+    StreamMetrics, FsMetrics, LATENCY_BUCKETS, LatencyHistogram, OperationCounts,
+};
+
+#[cfg(feature = "fat-cache")]
+pub use crate::fat_cache::CacheStatistics;
+
+#[cfg(feature = "dir-cache")]
+pub use crate::dir_cache::DirCacheStatistics;
+
+#[cfg(feature = "fs-events")]
+pub use crate::events::{DEFAULT_EVENT_QUEUE_CAPACITY, FsEvent, FsEventKind, FsEventSubscription};
+
 #[cfg(feature = "audit-log")]
 pub use crate::audit::{
-    AuditConfig, AuditEntry, AuditLog, AuditOperation, AuditResult,
+    AuditConfig, AuditContext, AuditEntry, AuditHistory, AuditLevel, AuditLog, AuditOperation,
+    AuditResult, AuditStats, DEFAULT_FLUSH_THRESHOLD, MAX_AUDIT_TAG_LEN, WALL_CLOCK_MIN_TIMESTAMP,
 };
+
+#[cfg(feature = "audit-chain")]
+pub use crate::audit::{AuditChainBreak, AuditChainFault, AuditChainReport, AuditIntegrity};
@@ -0,0 +1,443 @@
+//! Unified I/O and cache metrics.
+//!
+//! With the `metrics` feature enabled, [`FileSystem::metrics`](crate::FileSystem::metrics)
+//! returns a single [`FsMetrics`] snapshot combining:
+//!
+//! - **Operation counts** - opens, creates, removes, renames, reads, writes, seeks, ...
+//! - **User bytes** - bytes read from and written to files by the application
+//! - **Stream counters** - read/write/seek/flush calls and bytes on the storage stream
+//! - **Write amplification** - stream bytes written per user byte written
+//! - **Cache hit rates** - FAT and directory caches (when those features are enabled)
+//! - **Latency histograms** - optional, once a clock is set with
+//!   [`FileSystem::set_metrics_clock`](crate::FileSystem::set_metrics_clock)
+//!
+//! Stream counters are collected by a thin adapter around the storage object passed
+//! to [`FileSystem::new`](crate::FileSystem::new), so they include all metadata
+//! traffic (FAT, directory entries, FSInfo, logs). They count I/O on that stream,
+//! not on the block device below it: a page cache or buffered stream may merge,
+//! defer or skip writes, and block rounding, erase blocks and flash translation
+//! are not visible here. They describe filesystem overhead, not device wear.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! fs.reset_metrics().await;
+//! run_workload(&fs).await?;
+//! let metrics = fs.metrics().await;
+//! println!("stream writes: {}", metrics.stream.writes);
+//! println!("write amplification: {:?}", metrics.write_amplification());
+//! ```
+
+use portable_atomic::{AtomicU64, Ordering};
+
+use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
+
+/// Number of buckets in a [`LatencyHistogram`]
+pub const LATENCY_BUCKETS: usize = 16;
+
+/// Latency distribution using power-of-two microsecond buckets.
+///
+/// Bucket `i` counts operations that took `2^i` to `2^(i+1) - 1` microseconds;
+/// bucket 0 also holds sub-microsecond operations and the last bucket holds
+/// everything slower than its lower bound.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    pub buckets: [u32; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, micros: u64) {
+        let index = ((63 - (micros | 1).leading_zeros()) as usize).min(LATENCY_BUCKETS - 1);
+        self.buckets[index] = self.buckets[index].saturating_add(1);
+    }
+
+    /// Total number of recorded operations
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|&n| u64::from(n)).sum()
+    }
+
+    /// Exclusive upper bound of a bucket in microseconds (`u64::MAX` for the last bucket)
+    #[must_use]
+    pub const fn bucket_upper_bound_us(index: usize) -> u64 {
+        if index + 1 >= LATENCY_BUCKETS {
+            u64::MAX
+        } else {
+            1 << (index + 1)
+        }
+    }
+
+    /// Upper bound of the bucket containing the given percentile (0-100)
+    ///
+    /// Returns `None` if nothing was recorded.
+    #[must_use]
+    pub fn percentile_us(&self, percentile: u8) -> Option<u64> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let target = (total * u64::from(percentile.min(100))).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (index, &n) in self.buckets.iter().enumerate() {
+            seen += u64::from(n);
+            if seen >= target {
+                return Some(Self::bucket_upper_bound_us(index));
+            }
+        }
+        None
+    }
+}
+
+/// Counters for the storage stream the filesystem was mounted on.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamMetrics {
+    /// Number of read calls
+    pub reads: u64,
+    /// Number of write calls
+    pub writes: u64,
+    /// Number of seek calls
+    pub seeks: u64,
+    /// Number of flush calls
+    pub flushes: u64,
+    /// Bytes read from the stream
+    pub bytes_read: u64,
+    /// Bytes written to the stream
+    pub bytes_written: u64,
+    /// Read latency (empty unless a metrics clock is set)
+    pub read_latency: LatencyHistogram,
+    /// Write latency (empty unless a metrics clock is set)
+    pub write_latency: LatencyHistogram,
+    /// Flush latency (empty unless a metrics clock is set)
+    pub flush_latency: LatencyHistogram,
+}
+
+/// Number of filesystem operations performed through the public API.
+///
+/// Reads and writes are counted when they transfer data; other operations are
+/// counted per call, including calls that fail.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OperationCounts {
+    pub opens: u64,
+    pub creates: u64,
+    pub dir_creates: u64,
+    pub removes: u64,
+    pub renames: u64,
+    pub reads: u64,
+    pub writes: u64,
+    pub seeks: u64,
+    pub flushes: u64,
+    pub truncates: u64,
+}
+
+/// Snapshot of all filesystem metrics.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug)]
+pub struct FsMetrics {
+    /// Operation counts
+    pub operations: OperationCounts,
+    /// Bytes returned by `File::read`
+    pub user_bytes_read: u64,
+    /// Bytes accepted by `File::write`
+    pub user_bytes_written: u64,
+    /// Storage stream counters
+    pub stream: StreamMetrics,
+    /// FAT cache statistics
+    #[cfg(feature = "fat-cache")]
+    pub fat_cache: crate::fat_cache::CacheStatistics,
+    /// Directory cache statistics
+    #[cfg(feature = "dir-cache")]
+    pub dir_cache: crate::dir_cache::DirCacheStatistics,
+}
+
+impl FsMetrics {
+    /// Stream bytes written per user byte written
+    ///
+    /// Returns `None` if no user data was written.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn write_amplification(&self) -> Option<f32> {
+        if self.user_bytes_written == 0 {
+            None
+        } else {
+            Some(self.stream.bytes_written as f32 / self.user_bytes_written as f32)
+        }
+    }
+
+    /// Stream bytes read per user byte read
+    ///
+    /// Returns `None` if no user data was read.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn read_amplification(&self) -> Option<f32> {
+        if self.user_bytes_read == 0 {
+            None
+        } else {
+            Some(self.stream.bytes_read as f32 / self.user_bytes_read as f32)
+        }
+    }
+}
+
+/// Operation kinds tracked by [`OperationRecorder`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Operation {
+    Open,
+    Create,
+    DirCreate,
+    Remove,
+    Rename,
+    Read,
+    Write,
+    Seek,
+    Flush,
+    Truncate,
+}
+
+impl Operation {
+    const COUNT: usize = 10;
+}
+
+/// Lock-free operation and user byte counters kept by the filesystem.
+pub(crate) struct OperationRecorder {
+    operations: [AtomicU64; Operation::COUNT],
+    user_bytes_read: AtomicU64,
+    user_bytes_written: AtomicU64,
+}
+
+impl OperationRecorder {
+    pub(crate) const fn new() -> Self {
+        Self {
+            operations: [const { AtomicU64::new(0) }; Operation::COUNT],
+            user_bytes_read: AtomicU64::new(0),
+            user_bytes_written: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn count(&self, operation: Operation) {
+        self.operations[operation as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_read(&self, bytes: usize) {
+        self.count(Operation::Read);
+        self.user_bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_write(&self, bytes: usize) {
+        self.count(Operation::Write);
+        self.user_bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn get(&self, operation: Operation) -> u64 {
+        self.operations[operation as usize].load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn operations(&self) -> OperationCounts {
+        OperationCounts {
+            opens: self.get(Operation::Open),
+            creates: self.get(Operation::Create),
+            dir_creates: self.get(Operation::DirCreate),
+            removes: self.get(Operation::Remove),
+            renames: self.get(Operation::Rename),
+            reads: self.get(Operation::Read),
+            writes: self.get(Operation::Write),
+            seeks: self.get(Operation::Seek),
+            flushes: self.get(Operation::Flush),
+            truncates: self.get(Operation::Truncate),
+        }
+    }
+
+    pub(crate) fn user_bytes_read(&self) -> u64 {
+        self.user_bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn user_bytes_written(&self) -> u64 {
+        self.user_bytes_written.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reset(&self) {
+        for counter in &self.operations {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.user_bytes_read.store(0, Ordering::Relaxed);
+        self.user_bytes_written.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Storage adapter that counts stream I/O.
+///
+/// The filesystem wraps its storage object in this adapter when the `metrics`
+/// feature is enabled. It is always accessed under the disk lock, so plain
+/// counters suffice.
+pub(crate) struct MeteredIo<IO> {
+    inner: IO,
+    stream: StreamMetrics,
+    clock: Option<fn() -> u64>,
+}
+
+impl<IO> MeteredIo<IO> {
+    pub(crate) fn new(inner: IO) -> Self {
+        Self {
+            inner,
+            stream: StreamMetrics::default(),
+            clock: None,
+        }
+    }
+
+    pub(crate) fn stream_metrics(&self) -> StreamMetrics {
+        self.stream
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.stream = StreamMetrics::default();
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Option<fn() -> u64>) {
+        self.clock = clock;
+    }
+
+    fn start(&self) -> Option<u64> {
+        self.clock.map(|now| now())
+    }
+
+    fn elapsed(&self, start: Option<u64>) -> Option<u64> {
+        match (self.clock, start) {
+            (Some(now), Some(start)) => Some(now().saturating_sub(start)),
+            _ => None,
+        }
+    }
+}
+
+impl<IO: IoBase> IoBase for MeteredIo<IO> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read> Read for MeteredIo<IO> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let start = self.start();
+        let n = self.inner.read(buf).await?;
+        self.stream.reads += 1;
+        self.stream.bytes_read += n as u64;
+        if let Some(micros) = self.elapsed(start) {
+            self.stream.read_latency.record(micros);
+        }
+        Ok(n)
+    }
+}
+
+impl<IO: Write> Write for MeteredIo<IO> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let start = self.start();
+        let n = self.inner.write(buf).await?;
+        self.stream.writes += 1;
+        self.stream.bytes_written += n as u64;
+        if let Some(micros) = self.elapsed(start) {
+            self.stream.write_latency.record(micros);
+        }
+        Ok(n)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        let start = self.start();
+        self.inner.flush().await?;
+        self.stream.flushes += 1;
+        if let Some(micros) = self.elapsed(start) {
+            self.stream.flush_latency.record(micros);
+        }
+        Ok(())
+    }
+}
+
+impl<IO: Seek> Seek for MeteredIo<IO> {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.stream.seeks += 1;
+        self.inner.seek(pos).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_io_adapters::tokio_1::FromTokio;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(0);
+        histogram.record(1);
+        histogram.record(3);
+        histogram.record(1000);
+        histogram.record(u64::MAX);
+
+        assert_eq!(histogram.buckets[0], 2);
+        assert_eq!(histogram.buckets[1], 1);
+        assert_eq!(histogram.buckets[9], 1);
+        assert_eq!(histogram.buckets[LATENCY_BUCKETS - 1], 1);
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.percentile_us(40), Some(2));
+        assert_eq!(histogram.percentile_us(50), Some(4));
+        assert_eq!(histogram.percentile_us(100), Some(u64::MAX));
+        assert_eq!(LatencyHistogram::default().percentile_us(50), None);
+    }
+
+    #[test]
+    fn test_recorder_reset() {
+        let recorder = OperationRecorder::new();
+        recorder.count(Operation::Open);
+        recorder.record_write(100);
+        recorder.record_read(40);
+
+        let operations = recorder.operations();
+        assert_eq!(operations.opens, 1);
+        assert_eq!(operations.writes, 1);
+        assert_eq!(operations.reads, 1);
+        assert_eq!(recorder.user_bytes_written(), 100);
+        assert_eq!(recorder.user_bytes_read(), 40);
+
+        recorder.reset();
+        assert_eq!(recorder.operations(), OperationCounts::default());
+        assert_eq!(recorder.user_bytes_written(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_metered_io_counts() {
+        let mut io = MeteredIo::new(FromTokio::new(Cursor::new(vec![0u8; 1024])));
+        io.write(&[1u8; 512]).await.unwrap();
+        io.flush().await.unwrap();
+        io.seek(SeekFrom::Start(0)).await.unwrap();
+        let mut buf = [0u8; 100];
+        io.read(&mut buf).await.unwrap();
+
+        let stream = io.stream_metrics();
+        assert_eq!(stream.writes, 1);
+        assert_eq!(stream.bytes_written, 512);
+        assert_eq!(stream.flushes, 1);
+        assert_eq!(stream.seeks, 1);
+        assert_eq!(stream.reads, 1);
+        assert_eq!(stream.bytes_read, 100);
+        assert_eq!(stream.read_latency.count(), 0);
+
+        io.reset();
+        assert_eq!(io.stream_metrics(), StreamMetrics::default());
+    }
+
+    #[tokio::test]
+    async fn test_metered_io_latency() {
+        static NOW: AtomicU64 = AtomicU64::new(0);
+        fn clock() -> u64 {
+            NOW.fetch_add(5, Ordering::Relaxed)
+        }
+
+        let mut io = MeteredIo::new(FromTokio::new(Cursor::new(vec![0u8; 64])));
+        io.set_clock(Some(clock));
+        io.write(&[0u8; 8]).await.unwrap();
+        io.flush().await.unwrap();
+
+        let stream = io.stream_metrics();
+        // Each operation spans one clock tick of 5us
+        assert_eq!(stream.write_latency.buckets[2], 1);
+        assert_eq!(stream.flush_latency.buckets[2], 1);
+    }
+}
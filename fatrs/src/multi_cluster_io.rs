@@ -14,6 +14,9 @@ use core::cmp;
 use crate::error::Error;
 use crate::fs::{FileSystem, ReadWriteSeek};
 use crate::io::SeekFrom;
+// The disk is a concrete `MeteredIo` with metrics, so its I/O traits must be in scope
+#[cfg(feature = "metrics")]
+use crate::io::{Read, Seek, Write};
 
 /// Maximum number of contiguous clusters to batch in one operation
 /// This prevents excessive memory usage while still providing good performance
@@ -0,0 +1,81 @@
+//! Tracking of open file handles.
+//!
+//! Every `File` that refers to a directory entry registers the entry's position on
+//! the volume while it is alive. `Dir::remove` and `Dir::rename` consult this table
+//! and fail with `Error::FileBusy` instead of freeing or moving the entry of a file
+//! that another task is still reading or writing.
+//!
+//! A [`FileContext`](crate::FileContext) returned by `File::close` does not keep the
+//! file open; the file is registered again when it is resumed.
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
+
+
+/// Open handle counts keyed by directory entry position
+#[derive(Debug, Default)]
+pub(crate) struct OpenFileTable {
+    entries: Vec<(u64, u32)>,
+}
+
+impl OpenFileTable {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handle for the entry at `pos`
+    pub(crate) fn open(&mut self, pos: u64) {
+        match self.entries.iter_mut().find(|(p, _)| *p == pos) {
+            Some((_, handles)) => *handles += 1,
+            None => self.entries.push((pos, 1)),
+        }
+    }
+
+    /// Unregisters a handle for the entry at `pos`
+    pub(crate) fn close(&mut self, pos: u64) {
+        if let Some(index) = self.entries.iter().position(|(p, _)| *p == pos) {
+            let handles = &mut self.entries[index].1;
+            *handles -= 1;
+            if *handles == 0 {
+                self.entries.swap_remove(index);
+            }
+        } else {
+            warn!("Closing a file that was not registered as open");
+        }
+    }
+
+    /// Number of handles open on the entry at `pos`
+    pub(crate) fn handles(&self, pos: u64) -> u32 {
+        self.entries
+            .iter()
+            .find(|(p, _)| *p == pos)
+            .map_or(0, |(_, handles)| *handles)
+    }
+
+    /// Number of distinct files with open handles
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_counting() {
+        let mut table = OpenFileTable::new();
+        table.open(64);
+        table.open(64);
+        table.open(96);
+        assert_eq!(table.handles(64), 2);
+        assert_eq!(table.len(), 2);
+
+        table.close(64);
+        assert_eq!(table.handles(64), 1);
+        table.close(64);
+        assert_eq!(table.handles(64), 0);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.handles(96), 1);
+    }
+}
@@ -0,0 +1,403 @@
+//! Per-directory space quotas.
+//!
+//! A quota caps the number of clusters a directory subtree may occupy, so that a
+//! runaway writer in one part of the volume cannot starve the others.
+//!
+//! # Accounting
+//!
+//! Every allocation made on behalf of a file is charged to all quotas whose root is
+//! the file's parent directory or one of its ancestors (quotas may be nested).
+//! Growing a directory charges the directory itself. When a charge would push any of
+//! these quotas over its limit the allocation fails with `Error::QuotaExceeded`
+//! before the FAT is touched. Freed clusters (truncate, remove) are released again.
+//!
+//! The usage of a quota counts every cluster of its root directory and of all files
+//! and directories below it.
+//!
+//! # Persistence
+//!
+//! Definitions (path and limit) are stored in two hidden system files in the root
+//! directory, [`QUOTA_FILE_NAME`] and a spare copy. Every update writes a complete
+//! new copy with a higher generation number and a CRC-32 over the older of the two
+//! files; the newest valid copy wins at load. A write torn by power loss therefore
+//! leaves the previous definitions in place. Usage is never stored: it is
+//! recomputed by scanning each quota subtree when the volume is mounted.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! fs.set_quota("logs", QuotaLimit::Bytes(2 * 1024 * 1024 * 1024)).await?;
+//! match file.write_all(&record).await {
+//!     Err(Error::QuotaExceeded) => rotate_logs().await?,
+//!     r => r?,
+//! }
+//! ```
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+/// Short name of the hidden file holding the quota definitions
+pub const QUOTA_FILE_NAME: &str = "FATRSQTA.SYS";
+
+/// Both copies of the quota definitions; generation `n` is stored in `QUOTA_FILE_NAMES[n % 2]`
+pub(crate) const QUOTA_FILE_NAMES: [&str; 2] = [QUOTA_FILE_NAME, "FATRSQTB.SYS"];
+
+const QUOTA_MAGIC: [u8; 4] = *b"FQTA";
+/// Version 1 files carry no generation or CRC and are still accepted
+const QUOTA_FORMAT_VERSION_V1: u8 = 1;
+const QUOTA_FORMAT_VERSION: u8 = 2;
+const QUOTA_HEADER_SIZE_V1: usize = 7;
+const QUOTA_HEADER_SIZE: usize = 11;
+const QUOTA_CRC_SIZE: usize = 4;
+
+/// Limit of a directory quota
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaLimit {
+    /// Limit in bytes, rounded up to whole clusters
+    Bytes(u64),
+    /// Limit in clusters
+    Clusters(u32),
+}
+
+/// Limit and current usage of a directory quota
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaUsage {
+    /// Path of the quota root, relative to the root directory
+    pub path: String,
+    /// Maximum number of clusters the subtree may occupy
+    pub limit_clusters: u32,
+    /// Number of clusters currently occupied by the subtree
+    pub used_clusters: u32,
+    /// Cluster size of the volume in bytes
+    pub cluster_size: u32,
+}
+
+impl QuotaUsage {
+    /// Limit in bytes
+    #[must_use]
+    pub fn limit_bytes(&self) -> u64 {
+        u64::from(self.limit_clusters) * u64::from(self.cluster_size)
+    }
+
+    /// Space currently occupied by the subtree in bytes
+    #[must_use]
+    pub fn used_bytes(&self) -> u64 {
+        u64::from(self.used_clusters) * u64::from(self.cluster_size)
+    }
+
+    /// Clusters that can still be allocated before the quota is exceeded
+    #[must_use]
+    pub fn remaining_clusters(&self) -> u32 {
+        self.limit_clusters.saturating_sub(self.used_clusters)
+    }
+}
+
+/// Identifies a quota for as long as it is defined; unlike its position in the
+/// table it does not change when other quotas are removed
+pub(crate) type QuotaId = u32;
+
+#[derive(Debug, Clone)]
+pub(crate) struct QuotaEntry {
+    pub(crate) id: QuotaId,
+    pub(crate) path: String,
+    /// First cluster of the quota root, `None` if the path could not be resolved
+    pub(crate) dir_cluster: Option<u32>,
+    pub(crate) limit: u32,
+    pub(crate) used: u32,
+}
+
+/// Clusters charged to the destination quotas of a move that has not completed yet
+pub(crate) struct QuotaTransfer {
+    /// Quotas of the destination that do not contain the source
+    pub(crate) gained: Vec<QuotaId>,
+    /// Quotas of the source that do not contain the destination
+    pub(crate) lost: Vec<QuotaId>,
+    pub(crate) clusters: u32,
+}
+
+/// Quota definitions and usage counters of a mounted volume
+#[derive(Debug, Default)]
+pub(crate) struct QuotaTable {
+    entries: Vec<QuotaEntry>,
+    /// Id given to the next quota added
+    next_id: QuotaId,
+    /// Quotas governing allocations for a directory, keyed by its first cluster
+    governing: BTreeMap<u32, Vec<QuotaId>>,
+    /// Generation of the newest persisted copy of the definitions
+    generation: u32,
+}
+
+/// Normalizes a quota path: no leading, trailing or repeated separators
+pub(crate) fn normalize_path(path: &str) -> String {
+    let mut normalized = String::new();
+    for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if !normalized.is_empty() {
+            normalized.push('/');
+        }
+        normalized.push_str(component);
+    }
+    normalized
+}
+
+impl QuotaTable {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub(crate) fn set_generation(&mut self, generation: u32) {
+        self.generation = generation;
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn entries(&self) -> &[QuotaEntry] {
+        &self.entries
+    }
+
+    /// The quota with `id`, `None` if it was removed in the meantime
+    pub(crate) fn entry_mut(&mut self, id: QuotaId) -> Option<&mut QuotaEntry> {
+        self.entries.iter_mut().find(|e| e.id == id)
+    }
+
+    /// Index of the quota defined for `path` (FAT names are case-insensitive)
+    pub(crate) fn find(&self, path: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.path.eq_ignore_ascii_case(path))
+    }
+
+    /// Index of the quota rooted at directory `cluster`
+    pub(crate) fn find_by_cluster(&self, cluster: u32) -> Option<usize> {
+        self.entries.iter().position(|e| e.dir_cluster == Some(cluster))
+    }
+
+    /// Adds a quota or replaces the limit of an existing one
+    pub(crate) fn insert(&mut self, path: String, dir_cluster: Option<u32>, limit: u32, used: u32) {
+        self.governing.clear();
+        if let Some(index) = self.find(&path) {
+            let entry = &mut self.entries[index];
+            entry.dir_cluster = dir_cluster;
+            entry.limit = limit;
+            entry.used = used;
+        } else {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            self.entries.push(QuotaEntry { id, path, dir_cluster, limit, used });
+        }
+    }
+
+    pub(crate) fn remove(&mut self, index: usize) -> QuotaEntry {
+        self.governing.clear();
+        self.entries.remove(index)
+    }
+
+    pub(crate) fn cached_governing(&self, dir_cluster: u32) -> Option<&[QuotaId]> {
+        self.governing.get(&dir_cluster).map(Vec::as_slice)
+    }
+
+    /// Computes and caches the quotas governing `dir_cluster` from its ancestor chain
+    /// (the directory itself first, root excluded)
+    pub(crate) fn resolve_governing(&mut self, dir_cluster: u32, ancestors: &[u32]) -> Vec<QuotaId> {
+        let ids: Vec<QuotaId> = self
+            .entries
+            .iter()
+            .filter(|e| e.dir_cluster.is_some_and(|c| ancestors.contains(&c)))
+            .map(|e| e.id)
+            .collect();
+        self.governing.insert(dir_cluster, ids.clone());
+        ids
+    }
+
+    /// Forgets the cached ancestry, e.g. after a directory was moved or removed
+    pub(crate) fn forget_governing(&mut self) {
+        self.governing.clear();
+    }
+
+    /// Charges `clusters` to all `quotas`, or to none of them if any limit would be exceeded
+    ///
+    /// Quotas removed since `quotas` was resolved are skipped.
+    pub(crate) fn try_charge(&mut self, quotas: &[QuotaId], clusters: u32) -> bool {
+        let fits = self
+            .entries
+            .iter()
+            .filter(|e| quotas.contains(&e.id))
+            .all(|e| e.used.checked_add(clusters).is_some_and(|used| used <= e.limit));
+        if fits {
+            for entry in self.entries.iter_mut().filter(|e| quotas.contains(&e.id)) {
+                entry.used += clusters;
+            }
+        }
+        fits
+    }
+
+    /// Releases `clusters` from all `quotas` that still exist
+    pub(crate) fn release(&mut self, quotas: &[QuotaId], clusters: u32) {
+        for entry in self.entries.iter_mut().filter(|e| quotas.contains(&e.id)) {
+            entry.used = entry.used.saturating_sub(clusters);
+        }
+    }
+
+    /// Serializes the quota definitions as copy `generation` (usage is recomputed at load)
+    pub(crate) fn serialize(&self, generation: u32) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(QUOTA_HEADER_SIZE + self.entries.len() * 16 + QUOTA_CRC_SIZE);
+        buf.extend_from_slice(&QUOTA_MAGIC);
+        buf.push(QUOTA_FORMAT_VERSION);
+        buf.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        buf.extend_from_slice(&generation.to_le_bytes());
+        for entry in &self.entries {
+            buf.extend_from_slice(&entry.limit.to_le_bytes());
+            buf.extend_from_slice(&(entry.path.len() as u16).to_le_bytes());
+            buf.extend_from_slice(entry.path.as_bytes());
+        }
+        let crc = quota_crc(&buf);
+        buf.extend_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    /// Parses a copy of the quota definitions into its generation and `(path, limit)` pairs
+    ///
+    /// Returns `None` if the data is malformed or fails its CRC.
+    pub(crate) fn deserialize(data: &[u8]) -> Option<(u32, Vec<(String, u32)>)> {
+        if data.len() < QUOTA_HEADER_SIZE_V1 || data[..4] != QUOTA_MAGIC {
+            return None;
+        }
+        let count = u16::from_le_bytes([data[5], data[6]]);
+        let (generation, mut rest) = match data[4] {
+            QUOTA_FORMAT_VERSION_V1 => (0, &data[QUOTA_HEADER_SIZE_V1..]),
+            QUOTA_FORMAT_VERSION if data.len() >= QUOTA_HEADER_SIZE + QUOTA_CRC_SIZE => {
+                let (body, crc) = data.split_at(data.len() - QUOTA_CRC_SIZE);
+                if quota_crc(body).to_le_bytes() != crc {
+                    return None;
+                }
+                let generation = u32::from_le_bytes([data[7], data[8], data[9], data[10]]);
+                (generation, &body[QUOTA_HEADER_SIZE..])
+            }
+            _ => return None,
+        };
+        let mut definitions = Vec::with_capacity(usize::from(count));
+        for _ in 0..count {
+            if rest.len() < 6 {
+                return None;
+            }
+            let limit = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]);
+            let path_len = usize::from(u16::from_le_bytes([rest[4], rest[5]]));
+            let path = rest.get(6..6 + path_len)?;
+            let path = core::str::from_utf8(path).ok()?;
+            definitions.push((String::from(path), limit));
+            rest = &rest[6 + path_len..];
+        }
+        Some((generation, definitions))
+    }
+}
+
+/// CRC-32 protecting a copy of the quota definitions
+fn quota_crc(data: &[u8]) -> u32 {
+    use crc::{CRC_32_ISO_HDLC, Crc};
+
+    const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    CRC32.checksum(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/logs/"), "logs");
+        assert_eq!(normalize_path("a//b/./c"), "a/b/c");
+        assert_eq!(normalize_path("/"), "");
+    }
+
+    #[test]
+    fn test_charge_is_all_or_nothing() {
+        let mut table = QuotaTable::new();
+        table.insert(String::from("a"), Some(10), 4, 0);
+        table.insert(String::from("a/b"), Some(20), 10, 0);
+        let both = table.resolve_governing(20, &[20, 10]);
+        assert_eq!(both, vec![0, 1]);
+
+        assert!(table.try_charge(&both, 3));
+        assert!(!table.try_charge(&both, 2));
+        assert_eq!(table.entries()[0].used, 3);
+        assert_eq!(table.entries()[1].used, 3);
+
+        table.release(&both, 5);
+        assert_eq!(table.entries()[0].used, 0);
+        assert!(table.try_charge(&both, 4));
+    }
+
+    #[test]
+    fn test_quota_removed_after_resolving_is_skipped() {
+        let mut table = QuotaTable::new();
+        table.insert(String::from("a"), Some(10), 4, 0);
+        table.insert(String::from("a/b"), Some(20), 10, 0);
+        let both = table.resolve_governing(20, &[20, 10]);
+
+        // Removed while an allocation resolved against it is in flight
+        let index = table.find("a").unwrap();
+        table.remove(index);
+        assert!(table.try_charge(&both, 6));
+        assert_eq!(table.entries()[0].path, "a/b");
+        assert_eq!(table.entries()[0].used, 6);
+        table.release(&both, 6);
+        assert_eq!(table.entries()[0].used, 0);
+
+        // A new quota does not take over the removed one's id
+        table.insert(String::from("c"), Some(30), 1, 0);
+        assert!(!both.contains(&table.entries()[1].id));
+    }
+
+    #[test]
+    fn test_insert_replaces_case_insensitively() {
+        let mut table = QuotaTable::new();
+        table.insert(String::from("Logs"), Some(5), 100, 7);
+        table.resolve_governing(5, &[5]);
+        table.insert(String::from("logs"), Some(5), 50, 7);
+        assert_eq!(table.entries().len(), 1);
+        assert_eq!(table.entries()[0].limit, 50);
+        assert!(table.cached_governing(5).is_none());
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() {
+        let mut table = QuotaTable::new();
+        table.insert(String::from("logs"), Some(5), 524_288, 12);
+        table.insert(String::from("data/cache"), None, 64, 0);
+        let bytes = table.serialize(7);
+
+        let (generation, definitions) = QuotaTable::deserialize(&bytes).unwrap();
+        assert_eq!(generation, 7);
+        assert_eq!(
+            definitions,
+            vec![(String::from("logs"), 524_288), (String::from("data/cache"), 64)]
+        );
+
+        assert!(QuotaTable::deserialize(&bytes[..bytes.len() - 1]).is_none());
+        assert!(QuotaTable::deserialize(b"XXXX\x01\x00\x00").is_none());
+
+        // A torn or damaged copy fails its CRC
+        let mut damaged = bytes.clone();
+        damaged[QUOTA_HEADER_SIZE + 2] ^= 1;
+        assert!(QuotaTable::deserialize(&damaged).is_none());
+    }
+
+    #[test]
+    fn test_deserialize_version_1() {
+        let mut bytes = b"FQTA\x01\x01\x00".to_vec();
+        bytes.extend_from_slice(&64_u32.to_le_bytes());
+        bytes.extend_from_slice(&4_u16.to_le_bytes());
+        bytes.extend_from_slice(b"logs");
+        assert_eq!(
+            QuotaTable::deserialize(&bytes),
+            Some((0, vec![(String::from("logs"), 64)]))
+        );
+    }
+}
@@ -419,6 +419,39 @@ impl<T> Shared<T> {
     pub fn try_acquire(&self) -> Option<impl DerefMut<Target = T>> {
         self.inner.try_lock()
     }
+
+    /// Try to acquire mutable access without blocking.
+    ///
+    /// Returns `Some(guard)` if the value is not currently borrowed,
+    /// or `None` if it is.
+    ///
+    /// # Note
+    ///
+    /// This is only available with `alloc` and no runtime (uses `RefCell`).
+    #[must_use]
+    #[cfg(all(
+        feature = "alloc",
+        not(any(feature = "runtime-tokio", feature = "runtime-generic"))
+    ))]
+    #[inline]
+    pub fn try_acquire(&self) -> Option<impl DerefMut<Target = T>> {
+        self.inner.try_borrow_mut().ok()
+    }
+
+    /// Acquire mutable access without awaiting, spinning until the lock is free.
+    ///
+    /// Only for state whose holders never await while holding the lock, so it is
+    /// contended for the duration of a short critical section at most. This makes
+    /// the state reachable from synchronous code such as `Drop`.
+    #[cfg(any(feature = "runtime-tokio", feature = "runtime-generic", feature = "alloc"))]
+    pub(crate) fn spin_acquire(&self) -> impl DerefMut<Target = T> + '_ {
+        loop {
+            if let Some(guard) = self.try_acquire() {
+                return guard;
+            }
+            core::hint::spin_loop();
+        }
+    }
 }
 
 // Implement Share for Shared<T> to make it compatible with generic code
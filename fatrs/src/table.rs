@@ -67,7 +67,11 @@ pub(crate) trait FatTrait {
         Error<E>: From<S::Error> + From<ReadExactError<S::Error>>;
 }
 
-async fn read_fat<S, E>(fat: &mut S, fat_type: FatType, cluster: u32) -> Result<FatValue, Error<E>>
+pub(crate) async fn read_fat<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    cluster: u32,
+) -> Result<FatValue, Error<E>>
 where
     S: Read + Seek,
     E: IoError,
@@ -166,6 +170,90 @@ where
     Ok(new_cluster)
 }
 
+/// Marks free cluster `cluster` as bad, returning `false` if it was already bad
+///
+/// Fails with `Error::InvalidInput` if the cluster is in use.
+pub(crate) async fn mark_bad_cluster<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    cluster: u32,
+) -> Result<bool, Error<E>>
+where
+    S: Read + Write + Seek,
+    E: IoError,
+    Error<E>: From<S::Error> + From<ReadExactError<S::Error>>,
+{
+    match read_fat(fat, fat_type, cluster).await? {
+        FatValue::Free => {
+            write_fat(fat, fat_type, cluster, FatValue::Bad).await?;
+            Ok(true)
+        }
+        FatValue::Bad => Ok(false),
+        FatValue::Data(_) | FatValue::EndOfChain => Err(Error::InvalidInput),
+    }
+}
+
+/// Marks free cluster `cluster` as end of chain, returning `false` if it is not free
+pub(crate) async fn reserve_cluster<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    cluster: u32,
+) -> Result<bool, Error<E>>
+where
+    S: Read + Write + Seek,
+    E: IoError,
+    Error<E>: From<S::Error> + From<ReadExactError<S::Error>>,
+{
+    if read_fat(fat, fat_type, cluster).await? != FatValue::Free {
+        return Ok(false);
+    }
+    write_fat(fat, fat_type, cluster, FatValue::EndOfChain).await?;
+    Ok(true)
+}
+
+/// Ends a reservation made by `reserve_cluster`, leaving `cluster` bad or free again
+pub(crate) async fn release_reserved_cluster<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    cluster: u32,
+    bad: bool,
+) -> Result<(), Error<E>>
+where
+    S: Read + Write + Seek,
+    E: IoError,
+    Error<E>: From<S::Error> + From<ReadExactError<S::Error>>,
+{
+    let value = if bad { FatValue::Bad } else { FatValue::Free };
+    write_fat(fat, fat_type, cluster, value).await
+}
+
+/// Puts `new_cluster` in place of `old_cluster` in a chain and marks `old_cluster` as bad
+///
+/// `prev_cluster` is the cluster preceding `old_cluster` in the chain, `None` if it is
+/// the first one.
+pub(crate) async fn replace_bad_cluster<S, E>(
+    fat: &mut S,
+    fat_type: FatType,
+    prev_cluster: Option<u32>,
+    old_cluster: u32,
+    new_cluster: u32,
+) -> Result<(), Error<E>>
+where
+    S: Read + Write + Seek,
+    E: IoError,
+    Error<E>: From<S::Error> + From<ReadExactError<S::Error>>,
+{
+    let next = match read_fat(fat, fat_type, old_cluster).await? {
+        FatValue::Data(n) => FatValue::Data(n),
+        _ => FatValue::EndOfChain,
+    };
+    write_fat(fat, fat_type, new_cluster, next).await?;
+    if let Some(n) = prev_cluster {
+        write_fat(fat, fat_type, n, FatValue::Data(new_cluster)).await?;
+    }
+    write_fat(fat, fat_type, old_cluster, FatValue::Bad).await
+}
+
 pub(crate) async fn read_fat_flags<S, E>(
     fat: &mut S,
     fat_type: FatType,
@@ -875,6 +963,72 @@ mod tests {
         );
     }
 
+    async fn test_bad_clusters<S: Read + Write + Seek + IoBase>(fat_type: FatType, mut cur: S) {
+        // 0x12 is free, 0x17 is already bad and 0x4 is in use
+        assert_eq!(
+            mark_bad_cluster(&mut cur, fat_type, 0x12).await.ok(),
+            Some(true)
+        );
+        assert_eq!(
+            read_fat(&mut cur, fat_type, 0x12).await.ok(),
+            Some(FatValue::Bad)
+        );
+        assert_eq!(
+            mark_bad_cluster(&mut cur, fat_type, 0x17).await.ok(),
+            Some(false)
+        );
+        assert!(matches!(
+            mark_bad_cluster(&mut cur, fat_type, 0x4).await,
+            Err(Error::InvalidInput)
+        ));
+        // bad clusters are never allocated
+        assert_eq!(
+            find_free_cluster(&mut cur, fat_type, 0x2, 0x20).await.ok(),
+            Some(0x1B)
+        );
+
+        // chain 4 -> 5 -> 6: replace 5 with the free cluster 0x1B
+        replace_bad_cluster(&mut cur, fat_type, Some(0x4), 0x5, 0x1B)
+            .await
+            .unwrap();
+        assert_eq!(
+            read_fat(&mut cur, fat_type, 0x4).await.ok(),
+            Some(FatValue::Data(0x1B))
+        );
+        assert_eq!(
+            read_fat(&mut cur, fat_type, 0x1B).await.ok(),
+            Some(FatValue::Data(0x6))
+        );
+        assert_eq!(
+            read_fat(&mut cur, fat_type, 0x5).await.ok(),
+            Some(FatValue::Bad)
+        );
+        // replacing the last cluster of chain 9 -> ... -> 0x1A ends the chain on the new one
+        replace_bad_cluster(&mut cur, fat_type, Some(0x19), 0x1A, 0x1C)
+            .await
+            .unwrap();
+        assert_eq!(
+            read_fat(&mut cur, fat_type, 0x1C).await.ok(),
+            Some(FatValue::EndOfChain)
+        );
+        assert_eq!(
+            read_fat(&mut cur, fat_type, 0x19).await.ok(),
+            Some(FatValue::Data(0x1C))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bad_clusters_fat16() {
+        let fat: Vec<u8> = vec![
+            0xF0, 0xFF, 0xFF, 0xFF, 0x03, 0x00, 0x04, 0x00, 0x05, 0x00, 0x06, 0x00, 0x07, 0x00,
+            0x08, 0x00, 0xFF, 0xFF, 0x0A, 0x00, 0x14, 0x00, 0x0C, 0x00, 0x0D, 0x00, 0x0E, 0x00,
+            0x0F, 0x00, 0x10, 0x00, 0x11, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x15, 0x00,
+            0x16, 0x00, 0x19, 0x00, 0xF7, 0xFF, 0xF7, 0xFF, 0x1A, 0x00, 0xFF, 0xFF, 0x00, 0x00,
+            0x00, 0x00, 0xF7, 0xFF, 0x00, 0x00, 0x00, 0x00,
+        ];
+        test_bad_clusters(FatType::Fat16, FromTokio::new(Cursor::<Vec<u8>>::new(fat))).await;
+    }
+
     #[tokio::test]
     async fn test_fat12() {
         let fat: Vec<u8> = vec![
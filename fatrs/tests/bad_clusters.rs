@@ -0,0 +1,342 @@
+//! Tests for bad cluster management: marking, surface scan and relocation on write errors
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use embedded_io_async::{ErrorType, Read, Seek, SeekFrom, Write};
+use fatrs::{Error, FileSystem, FormatVolumeOptions, FsOptions, SurfaceScanMode};
+
+type TestFs = FileSystem<FaultyDisk, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter>;
+
+const IMAGE_SIZE: usize = 2 * 1024 * 1024;
+const SECTOR: u64 = 512;
+
+#[derive(Default)]
+struct Medium {
+    data: Vec<u8>,
+    /// Sectors that fail every read and write
+    bad: Vec<Range<u64>>,
+    /// Sectors that can still be read but fail every write
+    worn: Vec<Range<u64>>,
+    /// Sectors that accept writes but keep their old contents
+    stuck: Vec<Range<u64>>,
+    /// The next write starting with this byte fails and wears out its sectors
+    fail_write_starting_with: Option<u8>,
+    /// The next writes or flushes that fail without wearing out any sector, like the
+    /// write-back of unrelated buffered data
+    failing_write_backs: u32,
+}
+
+fn overlaps(ranges: &[Range<u64>], range: &Range<u64>) -> bool {
+    ranges
+        .iter()
+        .any(|r| r.start < range.end && range.start < r.end)
+}
+
+/// In-memory disk with injectable media faults, shared between mounts
+#[derive(Clone)]
+struct FaultyDisk {
+    medium: Arc<Mutex<Medium>>,
+    pos: u64,
+}
+
+impl FaultyDisk {
+    fn new() -> Self {
+        let medium = Medium {
+            data: vec![0; IMAGE_SIZE],
+            ..Medium::default()
+        };
+        Self {
+            medium: Arc::new(Mutex::new(medium)),
+            pos: 0,
+        }
+    }
+
+    fn medium(&self) -> std::sync::MutexGuard<'_, Medium> {
+        self.medium.lock().unwrap()
+    }
+}
+
+fn media_error() -> std::io::Error {
+    std::io::Error::other("media error")
+}
+
+impl ErrorType for FaultyDisk {
+    type Error = std::io::Error;
+}
+
+impl Read for FaultyDisk {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let medium = self.medium.lock().unwrap();
+        let start = self.pos as usize;
+        let len = buf.len().min(medium.data.len().saturating_sub(start));
+        if overlaps(&medium.bad, &(self.pos..self.pos + len as u64)) {
+            return Err(media_error());
+        }
+        buf[..len].copy_from_slice(&medium.data[start..start + len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Write for FaultyDisk {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let mut medium = self.medium.lock().unwrap();
+        let start = self.pos as usize;
+        let len = buf.len().min(medium.data.len().saturating_sub(start));
+        let range = self.pos..self.pos + len as u64;
+        if medium.failing_write_backs > 0 {
+            medium.failing_write_backs -= 1;
+            return Err(media_error());
+        }
+        if len > 0 && medium.fail_write_starting_with == Some(buf[0]) {
+            medium.fail_write_starting_with = None;
+            let sectors = range.start / SECTOR * SECTOR..range.end.div_ceil(SECTOR) * SECTOR;
+            medium.worn.push(sectors);
+        }
+        if overlaps(&medium.bad, &range) || overlaps(&medium.worn, &range) {
+            return Err(media_error());
+        }
+        for (i, byte) in buf[..len].iter().enumerate() {
+            let offset = self.pos + i as u64;
+            if !medium.stuck.iter().any(|s| s.contains(&offset)) {
+                medium.data[start + i] = *byte;
+            }
+        }
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        let mut medium = self.medium();
+        if medium.failing_write_backs > 0 {
+            medium.failing_write_backs -= 1;
+            return Err(media_error());
+        }
+        Ok(())
+    }
+}
+
+impl Seek for FaultyDisk {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let len = self.medium().data.len() as i64;
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(n) => len + n,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
+async fn formatted_disk() -> FaultyDisk {
+    let mut disk = FaultyDisk::new();
+    fatrs::format_volume(&mut disk, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+    disk
+}
+
+async fn mount(disk: &FaultyDisk) -> TestFs {
+    let mut disk = disk.clone();
+    disk.pos = 0;
+    FileSystem::new(disk, FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+/// Writes until the volume is full, returning the number of bytes stored
+async fn fill_volume(fs: &TestFs) -> u64 {
+    let mut file = fs.root_dir().create_file("fill.bin").await.unwrap();
+    let chunk = vec![0x5A; fs.cluster_size() as usize];
+    let mut total = 0;
+    loop {
+        match file.write(&chunk).await {
+            Ok(0) | Err(Error::NotEnoughSpace) => break,
+            Ok(n) => total += n as u64,
+            Err(err) => panic!("unexpected write error: {err:?}"),
+        }
+    }
+    file.flush().await.unwrap();
+    total
+}
+
+#[tokio::test]
+async fn test_mark_bad_cluster() {
+    let disk = formatted_disk().await;
+    let fs = mount(&disk).await;
+    let stats = fs.stats().await.unwrap();
+    let last = stats.total_clusters() + 1;
+
+    fs.mark_bad_cluster(last - 1).await.unwrap();
+    assert!(fs.is_bad_cluster(last - 1).await.unwrap());
+    assert!(!fs.is_bad_cluster(last).await.unwrap());
+    // Marking twice is harmless
+    fs.mark_bad_cluster(last - 1).await.unwrap();
+    assert_eq!(
+        fs.stats().await.unwrap().free_clusters(),
+        stats.free_clusters() - 1
+    );
+
+    assert!(matches!(
+        fs.mark_bad_cluster(1).await,
+        Err(Error::InvalidInput)
+    ));
+    assert!(matches!(
+        fs.mark_bad_cluster(last + 1).await,
+        Err(Error::InvalidInput)
+    ));
+
+    // Every other free cluster is used before the volume is full
+    let stored = fill_volume(&fs).await;
+    assert_eq!(
+        stored,
+        u64::from(stats.free_clusters() - 1) * u64::from(fs.cluster_size())
+    );
+    assert!(matches!(
+        fs.mark_bad_cluster(last).await,
+        Err(Error::InvalidInput)
+    ));
+    fs.unmount().await.unwrap();
+
+    let fs = mount(&disk).await;
+    assert!(fs.is_bad_cluster(last - 1).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_surface_scan_marks_unreadable_clusters() {
+    let disk = formatted_disk().await;
+    let offset = (IMAGE_SIZE / 2) as u64;
+    disk.medium().bad.push(offset..offset + 2 * SECTOR);
+
+    let fs = mount(&disk).await;
+    let free_before = fs.stats().await.unwrap().free_clusters();
+    let mut marked = Vec::new();
+    let report = fs
+        .scan_surface(SurfaceScanMode::Read, |cluster| marked.push(cluster))
+        .await
+        .unwrap();
+    assert_eq!(report.clusters_scanned, free_before);
+    assert!(report.bad_clusters >= 1);
+    assert_eq!(marked.len() as u32, report.bad_clusters);
+    for cluster in &marked {
+        assert!(fs.is_bad_cluster(*cluster).await.unwrap());
+    }
+    let free_after = fs.stats().await.unwrap().free_clusters();
+    assert_eq!(free_after, free_before - report.bad_clusters);
+
+    // Writes no longer touch the damaged area
+    let stored = fill_volume(&fs).await;
+    assert_eq!(stored, u64::from(free_after) * u64::from(fs.cluster_size()));
+}
+
+#[tokio::test]
+async fn test_surface_scan_write_verify_finds_stuck_sectors() {
+    let disk = formatted_disk().await;
+    let offset = (IMAGE_SIZE / 2) as u64;
+    disk.medium().stuck.push(offset..offset + SECTOR);
+
+    let fs = mount(&disk).await;
+    let report = fs
+        .scan_surface(SurfaceScanMode::Read, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.bad_clusters, 0);
+
+    let report = fs
+        .scan_surface(SurfaceScanMode::Write, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.bad_clusters, 1);
+}
+
+#[tokio::test]
+async fn test_failed_write_relocates_cluster() {
+    let disk = formatted_disk().await;
+    let fs = mount(&disk).await;
+    let cluster_size = fs.cluster_size() as usize;
+    let root = fs.root_dir();
+
+    let mut file = root.create_file("data.bin").await.unwrap();
+    file.write_all(&vec![0x11; 2 * cluster_size]).await.unwrap();
+    file.flush().await.unwrap();
+    let free_before = fs.stats().await.unwrap().free_clusters();
+
+    // Overwrite part of the second cluster; the medium fails and the data moves
+    disk.medium().fail_write_starting_with = Some(0x22);
+    file.seek(SeekFrom::Start(cluster_size as u64 + 100))
+        .await
+        .unwrap();
+    file.write_all(&[0x22; 50]).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+    assert_eq!(fs.stats().await.unwrap().free_clusters(), free_before - 1);
+    drop(root);
+    fs.unmount().await.unwrap();
+
+    let fs = mount(&disk).await;
+    let mut file = fs.root_dir().open_file("data.bin").await.unwrap();
+    let mut data = vec![0; 2 * cluster_size];
+    file.read_exact(&mut data).await.unwrap();
+    let mut expected = vec![0x11; 2 * cluster_size];
+    expected[cluster_size + 100..cluster_size + 150].fill(0x22);
+    assert_eq!(data, expected);
+}
+
+#[tokio::test]
+async fn test_unrelated_write_error_does_not_relocate() {
+    let disk = formatted_disk().await;
+    let fs = mount(&disk).await;
+    let cluster_size = fs.cluster_size() as usize;
+    let root = fs.root_dir();
+
+    let mut file = root.create_file("data.bin").await.unwrap();
+    file.write_all(&vec![0x11; 2 * cluster_size]).await.unwrap();
+    file.flush().await.unwrap();
+    let free_before = fs.stats().await.unwrap().free_clusters();
+
+    // A write-back failing once only delays the write
+    disk.medium().failing_write_backs = 1;
+    file.seek(SeekFrom::Start(cluster_size as u64 + 100))
+        .await
+        .unwrap();
+    file.write_all(&[0x22; 50]).await.unwrap();
+    assert_eq!(fs.stats().await.unwrap().free_clusters(), free_before);
+
+    // One that keeps failing is reported, the cluster stays in place
+    disk.medium().failing_write_backs = 2;
+    assert!(matches!(file.write(&[0x33; 50]).await, Err(Error::Io(_))));
+    assert_eq!(fs.stats().await.unwrap().free_clusters(), free_before);
+
+    file.write_all(&[0x33; 50]).await.unwrap();
+    file.flush().await.unwrap();
+    file.seek(SeekFrom::Start(0)).await.unwrap();
+    let mut data = vec![0; 2 * cluster_size];
+    file.read_exact(&mut data).await.unwrap();
+    let mut expected = vec![0x11; 2 * cluster_size];
+    expected[cluster_size + 100..cluster_size + 150].fill(0x22);
+    expected[cluster_size + 150..cluster_size + 200].fill(0x33);
+    assert_eq!(data, expected);
+}
+
+#[tokio::test]
+async fn test_write_surface_scan_keeps_free_space() {
+    let disk = formatted_disk().await;
+    let fs = mount(&disk).await;
+    let free_before = fs.stats().await.unwrap().free_clusters();
+    let report = fs
+        .scan_surface(SurfaceScanMode::Write, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.clusters_scanned, free_before);
+    assert_eq!(report.bad_clusters, 0);
+    assert_eq!(fs.stats().await.unwrap().free_clusters(), free_before);
+
+    // Every scanned cluster was handed back
+    let stored = fill_volume(&fs).await;
+    assert_eq!(stored, u64::from(free_before) * u64::from(fs.cluster_size()));
+}
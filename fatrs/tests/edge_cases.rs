@@ -789,6 +789,45 @@ async fn test_create_many_small_files() {
     cleanup_test_image(path);
 }
 
+/// Test that ordinary writes stop at the cluster reserve while critical handles can use it
+#[tokio::test]
+async fn test_reserved_clusters() {
+    let path = "target/test_reserved_clusters.img";
+    create_test_image(path, 10).unwrap();
+
+    // Reserve all but two of the free clusters
+    let (free, cluster_size) = {
+        let file = File::options().read(true).write(true).open(path).unwrap();
+        let fs = FileSystem::new(TestBlockDevice::new(file), FsOptions::new()).await.unwrap();
+        let stats = fs.stats().await.unwrap();
+        fs.unmount().await.unwrap();
+        (stats.free_clusters(), stats.cluster_size() as usize)
+    };
+    let file = File::options().read(true).write(true).open(path).unwrap();
+    let device = TestBlockDevice::new(file);
+    let options = FsOptions::new().reserved_clusters(free - 2);
+    let fs = FileSystem::new(device, options).await.unwrap();
+    assert_eq!(fs.reserved_clusters(), free - 2);
+    let root = fs.root_dir();
+
+    let mut log = root.create_file("log.txt").await.unwrap();
+    let result = log.write_all(&vec![0xAA; cluster_size * 4]).await;
+    assert!(matches!(result, Err(fatrs::Error::NotEnoughSpace)));
+    log.flush().await.unwrap();
+    drop(log);
+
+    let mut dump = root.create_file("crash.dmp").await.unwrap();
+    assert!(!dump.is_critical());
+    dump.set_critical(true);
+    dump.write_all(&vec![0xCD; cluster_size * 2]).await.unwrap();
+    dump.flush().await.unwrap();
+    drop(dump);
+
+    assert_eq!(fs.stats().await.unwrap().free_clusters(), free - 4);
+
+    cleanup_test_image(path);
+}
+
 // =============================================================================
 // SEEK EDGE CASES
 // =============================================================================
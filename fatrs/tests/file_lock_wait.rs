@@ -0,0 +1,188 @@
+//! Tests for waiting lock acquisition (`file-locking` feature)
+#![cfg(feature = "file-locking")]
+
+use std::io::Cursor;
+use std::pin::pin;
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::{Read, Seek, SeekFrom, Write};
+use fatrs::{Error, FileSystem, FormatVolumeOptions, FsOptions, LockType};
+
+async fn create_test_fs() -> FileSystem<FromTokio<tokio::fs::File>, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let test_path = format!("target/test_lock_wait_{}.img", id);
+
+    let file = tokio::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&test_path)
+        .await
+        .expect("Failed to create test image");
+    file.set_len(10 * 1024 * 1024).await.expect("Failed to set file size");
+
+    let mut device = FromTokio::new(file);
+    fatrs::format_volume(&mut device, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+
+    FileSystem::new(device, FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+#[tokio::test]
+async fn test_waiter_gets_lock_after_release() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("shared.bin").await.unwrap();
+    file.write_all(b"data").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let reader = root.open_file_locked("shared.bin").await.unwrap();
+    let waiting = root.open_file_locked_wait("shared.bin", LockType::Exclusive);
+    let release = async {
+        tokio::task::yield_now().await;
+        reader.close_and_unlock().await.unwrap();
+    };
+    let (writer, ()) = futures::join!(waiting, release);
+    let writer = writer.unwrap();
+    assert_eq!(writer.lock_type(), Some(LockType::Exclusive));
+    assert!(matches!(root.open_file_locked("shared.bin").await, Err(Error::FileLocked)));
+    writer.close_and_unlock().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_cancelled_wait_leaves_queue() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    root.create_file("busy.bin").await.unwrap();
+
+    let reader = root.open_file_locked("busy.bin").await.unwrap();
+    let (cancel, cancelled) = tokio::sync::oneshot::channel::<()>();
+    drop(cancel);
+    let result = root
+        .open_file_locked_timeout("busy.bin", LockType::Exclusive, async {
+            let _ = cancelled.await;
+        })
+        .await;
+    assert!(matches!(result, Err(Error::FileLocked)));
+
+    // Other readers are not blocked by the abandoned waiter
+    let other = root.open_file_locked("busy.bin").await.unwrap();
+    other.close_and_unlock().await.unwrap();
+    reader.close_and_unlock().await.unwrap();
+    root.create_file_locked("busy.bin")
+        .await
+        .unwrap()
+        .close_and_unlock()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_wait_without_contention_is_immediate() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    root.create_file("free.bin").await.unwrap();
+
+    let file = root
+        .open_file_locked_timeout("free.bin", LockType::Shared, async {})
+        .await
+        .unwrap();
+    assert_eq!(file.lock_type(), Some(LockType::Shared));
+    file.close_and_unlock().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dropped_handle_releases_lock() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    root.create_file("dropped.bin").await.unwrap();
+
+    let writer = root.create_file_locked("dropped.bin").await.unwrap();
+    let waiting = root.open_file_locked_wait("dropped.bin", LockType::Shared);
+    let release = async {
+        tokio::task::yield_now().await;
+        // e.g. an early return on an error path
+        drop(writer);
+    };
+    let (reader, ()) = futures::join!(waiting, release);
+    let reader = reader.unwrap();
+    assert_eq!(reader.lock_type(), Some(LockType::Shared));
+    drop(reader);
+    root.create_file_locked("dropped.bin").await.unwrap().close().unwrap();
+}
+
+/// In-memory volume whose I/O never waits, so a single poll drives an operation
+/// until it blocks on a lock
+async fn create_memory_fs() -> FileSystem<FromTokio<Cursor<Vec<u8>>>, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter> {
+    let mut device = FromTokio::new(Cursor::new(vec![0; 10 * 1024 * 1024]));
+    fatrs::format_volume(&mut device, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+    FileSystem::new(device, FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+#[tokio::test]
+async fn test_queued_file_cannot_be_removed() {
+    let fs = create_memory_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("queued.bin").await.unwrap();
+    file.write_all(b"before").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut holder = root
+        .open_file_locked_wait("queued.bin", LockType::Exclusive)
+        .await
+        .unwrap();
+    let mut waiting = pin!(root.open_file_locked_wait("queued.bin", LockType::Shared));
+    assert!(futures::poll!(waiting.as_mut()).is_pending());
+
+    // The holder grows the file and hands the lock over
+    holder.seek(SeekFrom::End(0)).await.unwrap();
+    holder.write_all(b" and after").await.unwrap();
+    holder.flush().await.unwrap();
+    holder.close_and_unlock().await.unwrap();
+
+    // The waiter has not run yet, but its entry is still in use
+    assert!(matches!(root.remove("queued.bin").await, Err(Error::FileBusy)));
+    assert!(matches!(
+        root.rename("queued.bin", &root, "moved.bin").await,
+        Err(Error::FileBusy)
+    ));
+
+    let mut reader = waiting.await.unwrap();
+    let mut content = [0; 32];
+    let len = reader.read(&mut content).await.unwrap();
+    assert_eq!(&content[..len], b"before and after");
+    reader.close_and_unlock().await.unwrap();
+
+    root.remove("queued.bin").await.unwrap();
+    assert_eq!(fs.open_file_count(), 0);
+}
+
+#[tokio::test]
+async fn test_timed_out_waiter_does_not_keep_file_busy() {
+    let fs = create_memory_fs().await;
+    let root = fs.root_dir();
+    root.create_file("timeout.bin").await.unwrap();
+
+    let holder = root.create_file_locked("timeout.bin").await.unwrap();
+    let result = root
+        .open_file_locked_timeout("timeout.bin", LockType::Shared, async {})
+        .await;
+    assert!(matches!(result, Err(Error::FileLocked)));
+    holder.close_and_unlock().await.unwrap();
+
+    root.remove("timeout.bin").await.unwrap();
+    assert_eq!(fs.open_file_count(), 0);
+}
@@ -0,0 +1,125 @@
+//! Tests for filesystem change notifications (`fs-events` feature)
+#![cfg(feature = "fs-events")]
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::Write;
+use fatrs::{FileSystem, FormatVolumeOptions, FsEventKind, FsOptions};
+
+async fn create_test_fs() -> FileSystem<FromTokio<tokio::fs::File>, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let test_path = format!("target/test_fs_events_{}.img", id);
+
+    let file = tokio::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&test_path)
+        .await
+        .expect("Failed to create test image");
+    file.set_len(10 * 1024 * 1024).await.expect("Failed to set file size");
+
+    let mut device = FromTokio::new(file);
+    fatrs::format_volume(&mut device, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+
+    FileSystem::new(device, FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+#[tokio::test]
+async fn test_write_session_events() {
+    let fs = create_test_fs().await;
+    let mut events = fs.subscribe();
+    let root = fs.root_dir();
+
+    root.create_dir("logs").await.unwrap();
+    let mut file = root.create_file("logs/today.txt").await.unwrap();
+    file.write_all(b"first").await.unwrap();
+    file.write_all(b"second").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let kinds: Vec<_> = std::iter::from_fn(|| events.try_next()).map(|e| (e.kind, e.path)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (FsEventKind::DirCreated, "logs".to_string()),
+            (FsEventKind::Created, "logs/today.txt".to_string()),
+            (FsEventKind::Modified, "logs/today.txt".to_string()),
+            (FsEventKind::ClosedAfterWrite, "logs/today.txt".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_read_only_session_has_no_close_event() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("data.bin").await.unwrap();
+    file.write_all(b"payload").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut events = fs.subscribe();
+    let file = root.open_file("data.bin").await.unwrap();
+    drop(file);
+    assert!(events.try_next().is_none());
+}
+
+#[tokio::test]
+async fn test_rename_and_remove_events() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("a.txt").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut events = fs.subscribe();
+    root.rename("a.txt", &root, "b.txt").await.unwrap();
+    root.remove("b.txt").await.unwrap();
+
+    let renamed = events.next().await;
+    assert_eq!(renamed.kind, FsEventKind::Renamed);
+    assert_eq!(renamed.path, "a.txt");
+    assert_eq!(renamed.new_path.as_deref(), Some("b.txt"));
+
+    let deleted = events.next().await;
+    assert_eq!(deleted.kind, FsEventKind::Deleted);
+    assert_eq!(deleted.path, "b.txt");
+}
+
+#[tokio::test]
+async fn test_unflushed_drop_is_not_reported_as_committed() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut events = fs.subscribe();
+
+    let mut file = root.create_file("dump.bin").await.unwrap();
+    file.write_all(b"partial").await.unwrap();
+    drop(file);
+
+    // Flushing and then writing again leaves the last write uncommitted
+    let mut file = root.open_file("dump.bin").await.unwrap();
+    file.write_all(b"more").await.unwrap();
+    file.flush().await.unwrap();
+    file.write_all(b"tail").await.unwrap();
+    drop(file);
+
+    let kinds: Vec<_> = std::iter::from_fn(|| events.try_next()).map(|e| e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            FsEventKind::Created,
+            FsEventKind::Modified,
+            FsEventKind::DroppedUnflushed,
+            FsEventKind::Modified,
+            FsEventKind::DroppedUnflushed,
+        ]
+    );
+}
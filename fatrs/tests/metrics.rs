@@ -0,0 +1,116 @@
+//! Tests for unified filesystem metrics (`metrics` feature)
+#![cfg(feature = "metrics")]
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::{Read, Write};
+use fatrs::{FileSystem, FormatVolumeOptions, FsOptions};
+
+async fn create_test_fs() -> FileSystem<FromTokio<tokio::fs::File>, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let test_path = format!("target/test_metrics_{}.img", id);
+
+    let file = tokio::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&test_path)
+        .await
+        .expect("Failed to create test image");
+    file.set_len(10 * 1024 * 1024).await.expect("Failed to set file size");
+
+    let mut device = FromTokio::new(file);
+    fatrs::format_volume(&mut device, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+
+    FileSystem::new(device, FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+#[tokio::test]
+async fn test_operation_and_byte_counts() {
+    let fs = create_test_fs().await;
+    fs.reset_metrics().await;
+    let root = fs.root_dir();
+
+    root.create_dir("data").await.unwrap();
+    let mut file = root.create_file("data/log.bin").await.unwrap();
+    file.write_all(&[0xAB; 3000]).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut file = root.open_file("data/log.bin").await.unwrap();
+    let mut buf = vec![0u8; 3000];
+    file.read_exact(&mut buf).await.unwrap();
+    drop(file);
+
+    let data = root.open_dir("data").await.unwrap();
+    root.rename("data/log.bin", &data, "old.bin").await.unwrap();
+    root.remove("data/old.bin").await.unwrap();
+
+    let metrics = fs.metrics().await;
+    assert_eq!(metrics.operations.dir_creates, 1);
+    assert_eq!(metrics.operations.creates, 1);
+    assert_eq!(metrics.operations.opens, 1);
+    assert_eq!(metrics.operations.renames, 1);
+    assert_eq!(metrics.operations.removes, 1);
+    assert!(metrics.operations.writes >= 1);
+    assert!(metrics.operations.reads >= 1);
+    assert_eq!(metrics.user_bytes_written, 3000);
+    assert_eq!(metrics.user_bytes_read, 3000);
+
+    // Metadata updates make the stream write more than the user did
+    assert!(metrics.stream.bytes_written > metrics.user_bytes_written);
+    assert!(metrics.write_amplification().unwrap() > 1.0);
+    assert!(metrics.stream.writes > 0);
+    assert!(metrics.stream.seeks > 0);
+}
+
+#[tokio::test]
+async fn test_reset_metrics() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("a.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    fs.reset_metrics().await;
+    let metrics = fs.metrics().await;
+    assert_eq!(metrics.operations, fatrs::OperationCounts::default());
+    assert_eq!(metrics.user_bytes_written, 0);
+    assert_eq!(metrics.stream, fatrs::StreamMetrics::default());
+    assert!(metrics.write_amplification().is_none());
+}
+
+#[tokio::test]
+async fn test_latency_histogram_requires_clock() {
+    fn clock() -> u64 {
+        use std::sync::OnceLock;
+        use std::time::Instant;
+
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_micros() as u64
+    }
+
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("a.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+    assert_eq!(fs.metrics().await.stream.write_latency.count(), 0);
+
+    fs.set_metrics_clock(Some(clock)).await;
+    file.write_all(b" world").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let metrics = fs.metrics().await;
+    assert!(metrics.stream.write_latency.count() > 0);
+    assert!(metrics.stream.flush_latency.count() > 0);
+}
@@ -0,0 +1,104 @@
+//! Tests for open-file tracking: open files cannot be removed or renamed
+#![cfg(feature = "alloc")]
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::{Read, Write};
+use fatrs::{Error, FileSystem, FormatVolumeOptions, FsOptions};
+
+async fn create_test_fs() -> FileSystem<FromTokio<tokio::fs::File>, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let test_path = format!("target/test_open_files_{}.img", id);
+
+    let file = tokio::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&test_path)
+        .await
+        .expect("Failed to create test image");
+    file.set_len(10 * 1024 * 1024).await.expect("Failed to set file size");
+
+    let mut device = FromTokio::new(file);
+    fatrs::format_volume(&mut device, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+
+    FileSystem::new(device, FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+#[tokio::test]
+async fn test_remove_open_file_is_refused() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut writer = root.create_file("busy.bin").await.unwrap();
+    writer.write_all(&[0x42; 8192]).await.unwrap();
+    writer.flush().await.unwrap();
+
+    assert!(matches!(root.remove("busy.bin").await, Err(Error::FileBusy)));
+    assert!(matches!(
+        root.rename("busy.bin", &root, "other.bin").await,
+        Err(Error::FileBusy)
+    ));
+
+    // The writer is unaffected and the file is intact
+    writer.write_all(b"tail").await.unwrap();
+    writer.flush().await.unwrap();
+    drop(writer);
+
+    let mut reader = root.open_file("busy.bin").await.unwrap();
+    let mut buf = vec![0u8; 8196];
+    reader.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf[8192..], b"tail");
+    drop(reader);
+
+    root.rename("busy.bin", &root, "other.bin").await.unwrap();
+    root.remove("other.bin").await.unwrap();
+    assert_eq!(fs.open_file_count(), 0);
+}
+
+#[tokio::test]
+async fn test_every_handle_keeps_file_open() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("shared.txt").await.unwrap();
+    file.write_all(b"data").await.unwrap();
+    file.flush().await.unwrap();
+
+    let clone = file.clone();
+    let reader = root.open_file("shared.txt").await.unwrap();
+    assert_eq!(fs.open_file_count(), 1);
+
+    drop(file);
+    drop(reader);
+    assert!(matches!(root.remove("shared.txt").await, Err(Error::FileBusy)));
+
+    // A closed file's context does not keep it open
+    let context = clone.close().unwrap();
+    assert_eq!(fs.open_file_count(), 0);
+    drop(context);
+    root.remove("shared.txt").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_other_files_in_directory_are_not_busy() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    root.create_dir("dir").await.unwrap();
+    let mut open = root.create_file("dir/open.txt").await.unwrap();
+    open.write_all(b"open").await.unwrap();
+    open.flush().await.unwrap();
+    let mut closed = root.create_file("dir/closed.txt").await.unwrap();
+    closed.write_all(b"closed").await.unwrap();
+    closed.flush().await.unwrap();
+    drop(closed);
+
+    root.remove("dir/closed.txt").await.unwrap();
+    assert!(matches!(root.remove("dir/open.txt").await, Err(Error::FileBusy)));
+    drop(open);
+}
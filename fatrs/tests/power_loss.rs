@@ -0,0 +1,307 @@
+//! Power-loss injection: cut power after every sector written by an operation and
+//! check that the volume still mounts and reads back consistently
+
+use aligned::{A4, Aligned};
+use embedded_io_async::{ErrorType, Read, Seek, SeekFrom, Write};
+use fatrs::{FileSystem, FormatVolumeOptions, FsOptions};
+use fatrs_adapters::{FaultError, FaultPlan, FaultyBlockDevice, explore_cut_points};
+use fatrs_block_device::BlockDevice;
+
+const IMAGE_SIZE: usize = 2 * 1024 * 1024;
+const SECTOR: usize = 512;
+
+/// RAM medium; cloning copies the content
+#[derive(Clone)]
+struct RamDisk(Vec<u8>);
+
+impl BlockDevice<SECTOR> for RamDisk {
+    type Error = std::convert::Infallible;
+    type Align = A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SECTOR]>],
+    ) -> Result<(), Self::Error> {
+        for (i, block) in data.iter_mut().enumerate() {
+            let start = (block_address as usize + i) * SECTOR;
+            block.copy_from_slice(&self.0[start..start + SECTOR]);
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SECTOR]>],
+    ) -> Result<(), Self::Error> {
+        for (i, block) in data.iter().enumerate() {
+            let start = (block_address as usize + i) * SECTOR;
+            self.0[start..start + SECTOR].copy_from_slice(&block[..]);
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.0.len() as u64)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Unbuffered byte stream over a block device, one sector at a time
+struct SectorStream<D> {
+    device: D,
+    pos: u64,
+}
+
+impl<D> SectorStream<D> {
+    fn new(device: D) -> Self {
+        Self { device, pos: 0 }
+    }
+
+    fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+fn io_error<E: core::fmt::Debug>(err: E) -> std::io::Error {
+    std::io::Error::other(format!("{err:?}"))
+}
+
+impl<D> ErrorType for SectorStream<D> {
+    type Error = std::io::Error;
+}
+
+impl<D: BlockDevice<SECTOR, Align = A4>> Read for SectorStream<D> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let size = self.device.size().await.map_err(io_error)?;
+        if buf.is_empty() || self.pos >= size {
+            return Ok(0);
+        }
+        let offset = self.pos as usize % SECTOR;
+        let len = buf.len().min(SECTOR - offset);
+        let mut block = [Aligned([0; SECTOR])];
+        self.device
+            .read((self.pos / SECTOR as u64) as u32, &mut block)
+            .await
+            .map_err(io_error)?;
+        buf[..len].copy_from_slice(&block[0][offset..offset + len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<D: BlockDevice<SECTOR, Align = A4>> Write for SectorStream<D> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let address = (self.pos / SECTOR as u64) as u32;
+        let offset = self.pos as usize % SECTOR;
+        let len = buf.len().min(SECTOR - offset);
+        let mut block = [Aligned([0; SECTOR])];
+        if len < SECTOR {
+            self.device
+                .read(address, &mut block)
+                .await
+                .map_err(io_error)?;
+        }
+        block[0][offset..offset + len].copy_from_slice(&buf[..len]);
+        self.device.write(address, &block).await.map_err(io_error)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.device.sync().await.map_err(io_error)
+    }
+}
+
+impl<D: BlockDevice<SECTOR, Align = A4>> Seek for SectorStream<D> {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let size = self.device.size().await.map_err(io_error)? as i64;
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(n) => size + n,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
+type Device = FaultyBlockDevice<RamDisk, SECTOR>;
+type TestFs<'a> = FileSystem<
+    &'a mut SectorStream<Device>,
+    fatrs::DefaultTimeProvider,
+    fatrs::LossyOemCpConverter,
+>;
+
+async fn formatted_image() -> RamDisk {
+    let mut disk = SectorStream::new(RamDisk(vec![0; IMAGE_SIZE]));
+    fatrs::format_volume(&mut disk, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+    disk.into_inner()
+}
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+/// Mount the image and walk every directory, reading every file in full
+async fn check_volume(image: RamDisk) -> Result<(), String> {
+    let mut disk = SectorStream::new(image);
+    let fs = FileSystem::new(&mut disk, FsOptions::new())
+        .await
+        .map_err(|e| format!("mount failed: {e:?}"))?;
+    fs.stats()
+        .await
+        .map_err(|e| format!("reading stats failed: {e:?}"))?;
+
+    let mut dirs = vec![String::new()];
+    while let Some(path) = dirs.pop() {
+        let dir = if path.is_empty() {
+            fs.root_dir()
+        } else {
+            fs.root_dir()
+                .open_dir(&path)
+                .await
+                .map_err(|e| format!("{path}: {e:?}"))?
+        };
+        let mut iter = dir.iter();
+        while let Some(entry) = iter.next().await {
+            let entry = entry.map_err(|e| format!("{path}: {e:?}"))?;
+            let name = entry.file_name();
+            if name == "." || name == ".." {
+                continue;
+            }
+            let full = format!("{path}/{name}");
+            if entry.is_dir() {
+                dirs.push(full.trim_start_matches('/').to_string());
+                continue;
+            }
+
+            let mut file = entry.to_file();
+            let mut content = Vec::new();
+            let mut buf = [0; 1024];
+            loop {
+                match file.read(&mut buf).await {
+                    Ok(0) => break,
+                    Ok(n) => content.extend_from_slice(&buf[..n]),
+                    Err(e) => return Err(format!("{full}: {e:?}")),
+                }
+            }
+            if content.len() as u64 != entry.len() {
+                return Err(format!(
+                    "{full}: read {} bytes, size is {}",
+                    content.len(),
+                    entry.len()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Mount, run `op` and hand the device back whatever the outcome
+async fn run<F>(device: Device, op: F) -> Device
+where
+    F: AsyncFnOnce(&TestFs<'_>) -> Result<(), fatrs::Error<std::io::Error>>,
+{
+    let mut disk = SectorStream::new(device);
+    if let Ok(fs) = FileSystem::new(&mut disk, FsOptions::new()).await {
+        if op(&fs).await.is_ok() {
+            let _ = fs.unmount().await;
+        }
+    }
+    disk.into_inner()
+}
+
+async fn assert_cut_points_consistent<F>(image: &RamDisk, plan: FaultPlan, op: F)
+where
+    F: AsyncFn(&TestFs<'_>) -> Result<(), fatrs::Error<std::io::Error>>,
+{
+    let report = explore_cut_points(
+        image,
+        &plan,
+        async |device| Box::pin(run(device, &op)).await,
+        async |image| Box::pin(check_volume(image)).await,
+    )
+    .await;
+    assert!(report.cut_points > 1);
+    assert!(
+        report.is_consistent(),
+        "{} of {} cut points left an inconsistent volume: {:?}",
+        report.failures.len(),
+        report.cut_points,
+        report.failures
+    );
+}
+
+#[tokio::test]
+#[ignore = "the directory entry size can reach the disk before the FAT chain"]
+async fn test_create_and_write_file_survives_power_loss() {
+    let image = formatted_image().await;
+    let data = pattern(3000);
+    assert_cut_points_consistent(&image, FaultPlan::new(), async |fs| {
+        let mut file = fs.root_dir().create_file("data.bin").await?;
+        file.write_all(&data).await?;
+        file.flush().await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_create_dir_survives_power_loss_with_write_cache() {
+    let image = formatted_image().await;
+    assert_cut_points_consistent(
+        &image,
+        FaultPlan::new().with_write_cache(true),
+        async |fs| {
+            let dir = fs.root_dir().create_dir("logs").await?;
+            let mut file = dir.create_file("boot.txt").await?;
+            file.write_all(b"booted").await?;
+            file.flush().await
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_injected_write_error_is_reported() {
+    let image = formatted_image().await;
+    let mut disk = SectorStream::new(FaultyBlockDevice::<_, SECTOR>::new(image, FaultPlan::new()));
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).await.unwrap();
+    let mut file = fs.root_dir().create_file("data.bin").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+    fs.unmount().await.unwrap();
+
+    let mut device = disk.into_inner();
+    let writes = device.stats().writes;
+    device.set_plan(FaultPlan::new().with_failed_write(writes));
+    let mut disk = SectorStream::new(device);
+    let fs = FileSystem::new(&mut disk, FsOptions::new()).await.unwrap();
+    let mut file = fs.root_dir().open_file("data.bin").await.unwrap();
+    let result = async {
+        file.write_all(&pattern(SECTOR)).await?;
+        file.flush().await
+    }
+    .await;
+    assert!(result.is_err());
+    drop(file);
+    drop(fs);
+
+    let device = disk.into_inner();
+    assert_eq!(device.stats().injected_errors, 1);
+    assert!(device.is_powered());
+    let err: FaultError<std::convert::Infallible> = FaultError::Injected;
+    assert_eq!(err.to_string(), "Injected I/O error");
+}
@@ -0,0 +1,194 @@
+//! Tests for per-directory space quotas (`quotas` feature)
+#![cfg(feature = "quotas")]
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::Write;
+use fatrs::{Error, FileSystem, FormatVolumeOptions, FsOptions, QuotaLimit};
+
+type TestFs = FileSystem<FromTokio<tokio::fs::File>, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter>;
+
+async fn create_test_image() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let test_path = format!("target/test_quotas_{}.img", id);
+
+    let file = tokio::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&test_path)
+        .await
+        .expect("Failed to create test image");
+    file.set_len(10 * 1024 * 1024).await.expect("Failed to set file size");
+
+    let mut device = FromTokio::new(file);
+    fatrs::format_volume(&mut device, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+    test_path
+}
+
+async fn mount(path: &str) -> TestFs {
+    let file = tokio::fs::File::options()
+        .read(true)
+        .write(true)
+        .open(path)
+        .await
+        .expect("Failed to open test image");
+    FileSystem::new(FromTokio::new(file), FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+#[tokio::test]
+async fn test_quota_limits_subtree_writes() {
+    let fs = mount(&create_test_image().await).await;
+    let root = fs.root_dir();
+    root.create_dir("logs").await.unwrap();
+    root.create_dir("logs/app").await.unwrap();
+    // "logs" and "logs/app" occupy one cluster each
+    fs.set_quota("/logs", QuotaLimit::Clusters(6)).await.unwrap();
+
+    let cluster_size = fs.cluster_size() as usize;
+    let mut file = root.create_file("logs/app/run.log").await.unwrap();
+    file.write_all(&vec![0x55; 4 * cluster_size]).await.unwrap();
+    let err = file.write_all(&vec![0x55; cluster_size]).await.unwrap_err();
+    assert!(matches!(err, Error::QuotaExceeded));
+    file.flush().await.unwrap();
+    drop(file);
+
+    let quotas = fs.quotas().await.unwrap();
+    let usage = &quotas[0];
+    assert_eq!(usage.path, "logs");
+    assert_eq!(usage.used_clusters, 6);
+    assert_eq!(usage.remaining_clusters(), 0);
+
+    // Other directories are not affected
+    let mut other = root.create_file("other.bin").await.unwrap();
+    other.write_all(&vec![0xAA; 2 * cluster_size]).await.unwrap();
+    other.flush().await.unwrap();
+    drop(other);
+
+    // Removing a file gives its clusters back
+    root.remove("logs/app/run.log").await.unwrap();
+    assert_eq!(fs.quotas().await.unwrap()[0].used_clusters, 2);
+    let mut file = root.create_file("logs/next.log").await.unwrap();
+    file.write_all(&vec![0x55; 3 * cluster_size]).await.unwrap();
+    file.flush().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_quota_persists_and_is_rescanned_at_mount() {
+    let path = create_test_image().await;
+    {
+        let fs = mount(&path).await;
+        let root = fs.root_dir();
+        root.create_dir("data").await.unwrap();
+        fs.set_quota("data", QuotaLimit::Bytes(8 * u64::from(fs.cluster_size())))
+            .await
+            .unwrap();
+        let mut file = root.create_file("data/blob.bin").await.unwrap();
+        file.write_all(&vec![1; 3 * fs.cluster_size() as usize]).await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+        drop(root);
+        fs.unmount().await.unwrap();
+    }
+
+    let fs = mount(&path).await;
+    let quotas = fs.quotas().await.unwrap();
+    assert_eq!(quotas.len(), 1);
+    assert_eq!(quotas[0].path, "data");
+    assert_eq!(quotas[0].limit_clusters, 8);
+    assert_eq!(quotas[0].used_clusters, 4);
+
+    // The metadata file is hidden
+    let root = fs.root_dir();
+    let mut iter = root.iter();
+    while let Some(entry) = iter.next().await {
+        let entry = entry.unwrap();
+        if entry.short_file_name() == fatrs::QUOTA_FILE_NAME {
+            assert!(entry.attributes().contains(fatrs::FileAttributes::HIDDEN));
+        }
+    }
+
+    assert!(fs.remove_quota("data").await.unwrap());
+    assert!(fs.quotas().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_moving_file_between_quotas() {
+    let fs = mount(&create_test_image().await).await;
+    let root = fs.root_dir();
+    root.create_dir("small").await.unwrap();
+    root.create_dir("big").await.unwrap();
+    fs.set_quota("small", QuotaLimit::Clusters(2)).await.unwrap();
+    fs.set_quota("big", QuotaLimit::Clusters(100)).await.unwrap();
+
+    let mut file = root.create_file("big/file.bin").await.unwrap();
+    file.write_all(&vec![7; 3 * fs.cluster_size() as usize]).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let small = root.open_dir("small").await.unwrap();
+    let err = root.rename("big/file.bin", &small, "file.bin").await.unwrap_err();
+    assert!(matches!(err, Error::QuotaExceeded));
+
+    root.rename("big/file.bin", &root, "file.bin").await.unwrap();
+    let quotas = fs.quotas().await.unwrap();
+    assert_eq!(quotas[1].used_clusters, 1);
+
+    // Renaming a quota root keeps the quota attached
+    root.rename("big", &root, "large").await.unwrap();
+    assert_eq!(fs.quotas().await.unwrap()[1].path, "large");
+}
+
+#[tokio::test]
+async fn test_moving_directory_into_quota() {
+    let fs = mount(&create_test_image().await).await;
+    let root = fs.root_dir();
+    root.create_dir("small").await.unwrap();
+    root.create_dir("tree").await.unwrap();
+    fs.set_quota("small", QuotaLimit::Clusters(3)).await.unwrap();
+
+    let mut file = root.create_file("tree/file.bin").await.unwrap();
+    file.write_all(&vec![7; 3 * fs.cluster_size() as usize]).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    // "tree" holds four clusters, "small" has room for two more
+    let small = root.open_dir("small").await.unwrap();
+    let err = root.rename("tree", &small, "tree").await.unwrap_err();
+    assert!(matches!(err, Error::QuotaExceeded));
+    assert_eq!(fs.quotas().await.unwrap()[0].used_clusters, 1);
+    root.open_file("tree/file.bin").await.unwrap();
+
+    // freeing clusters invalidates open directory handles
+    root.remove("tree/file.bin").await.unwrap();
+    let small = root.open_dir("small").await.unwrap();
+    root.rename("tree", &small, "tree").await.unwrap();
+    assert_eq!(fs.quotas().await.unwrap()[0].used_clusters, 2);
+}
+
+#[tokio::test]
+async fn test_quota_enforced_right_after_mount() {
+    let path = create_test_image().await;
+    {
+        let fs = mount(&path).await;
+        fs.root_dir().create_dir("data").await.unwrap();
+        fs.set_quota("data", QuotaLimit::Clusters(2)).await.unwrap();
+        fs.unmount().await.unwrap();
+    }
+
+    let fs = mount(&path).await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("data/blob.bin").await.unwrap();
+    let err = file
+        .write_all(&vec![1; 2 * fs.cluster_size() as usize])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::QuotaExceeded));
+}
@@ -0,0 +1,92 @@
+//! Tests for byte-range locks (`file-locking` feature)
+#![cfg(feature = "file-locking")]
+
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::{Seek, SeekFrom, Write};
+use fatrs::{Error, FileSystem, FormatVolumeOptions, FsOptions, LockType};
+
+async fn create_test_fs() -> FileSystem<FromTokio<tokio::fs::File>, fatrs::DefaultTimeProvider, fatrs::LossyOemCpConverter> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let test_path = format!("target/test_range_locks_{}.img", id);
+
+    let file = tokio::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&test_path)
+        .await
+        .expect("Failed to create test image");
+    file.set_len(10 * 1024 * 1024).await.expect("Failed to set file size");
+
+    let mut device = FromTokio::new(file);
+    fatrs::format_volume(&mut device, FormatVolumeOptions::new())
+        .await
+        .expect("Failed to format filesystem");
+
+    FileSystem::new(device, FsOptions::new())
+        .await
+        .expect("Failed to mount filesystem")
+}
+
+#[tokio::test]
+async fn test_writers_lock_disjoint_slots_of_ring_file() {
+    const SLOT: u64 = 4096;
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut ring = root.create_file("ring.bin").await.unwrap();
+    ring.write_all(&[0; 4 * SLOT as usize]).await.unwrap();
+    ring.flush().await.unwrap();
+    drop(ring);
+
+    let mut a = root.open_file("ring.bin").await.unwrap();
+    let mut b = root.open_file("ring.bin").await.unwrap();
+    a.lock_range(0, SLOT, LockType::Exclusive).await.unwrap();
+    b.lock_range(SLOT, SLOT, LockType::Exclusive).await.unwrap();
+    assert!(matches!(
+        b.lock_range(SLOT - 1, 2, LockType::Shared).await,
+        Err(Error::FileLocked)
+    ));
+
+    a.write_all(&[0xA; SLOT as usize]).await.unwrap();
+    b.seek(SeekFrom::Start(SLOT)).await.unwrap();
+    b.write_all(&[0xB; SLOT as usize]).await.unwrap();
+    a.flush().await.unwrap();
+    b.flush().await.unwrap();
+
+    // Released slots can be taken over by the other writer
+    a.unlock_range(0, SLOT).await;
+    b.lock_range(0, SLOT, LockType::Exclusive).await.unwrap();
+
+    // Dropping a handle releases its ranges
+    drop(b);
+    a.lock_range(0, 0, LockType::Exclusive).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_empty_file_can_be_locked() {
+    let fs = create_test_fs().await;
+    let root = fs.root_dir();
+    let mut file = root.create_file("empty.bin").await.unwrap();
+    let mut other = root.open_file("empty.bin").await.unwrap();
+
+    file.lock_range(0, 0, LockType::Exclusive).await.unwrap();
+    assert!(matches!(
+        other.lock_range(1024, 1, LockType::Shared).await,
+        Err(Error::FileLocked)
+    ));
+
+    // Whole-file locks work on empty files too
+    let locked = root.open_file_locked("empty.bin").await.unwrap();
+    assert!(matches!(root.create_file_locked("empty.bin").await, Err(Error::FileLocked)));
+    locked.close_and_unlock().await.unwrap();
+    root.create_file_locked("empty.bin")
+        .await
+        .unwrap()
+        .close_and_unlock()
+        .await
+        .unwrap();
+}
@@ -0,0 +1,362 @@
+//! 4096-byte logical sectors end to end: format, mount and use FAT12/16/32 volumes
+//! on a `BlockDevice<4096>` through the fatrs-adapters page streams, as on 4Kn
+//! eMMC or a NOR flash translation layer
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use aligned::{A4, Aligned};
+use embedded_io_async::{Read, Seek, SeekFrom, Write};
+use fatrs::{FatType, FileSystem, FormatVolumeOptions, FsOptions};
+use fatrs_adapters::{HeapPageStream, StackPageStream};
+use fatrs_block_device::BlockDevice;
+
+const SECTOR: usize = 4096;
+
+/// Sparse RAM medium of `SIZE`-byte blocks; clones share the content so the
+/// test can inspect what the filesystem wrote
+#[derive(Clone)]
+struct RamDisk<const SIZE: usize> {
+    blocks: Arc<Mutex<BTreeMap<u32, Box<[u8; SIZE]>>>>,
+    size: u64,
+}
+
+impl<const SIZE: usize> RamDisk<SIZE> {
+    fn new(size: u64) -> Self {
+        Self {
+            blocks: Arc::default(),
+            size,
+        }
+    }
+
+    /// Copy `len` bytes at byte `offset` out of the medium
+    fn bytes(&self, offset: u64, len: usize) -> Vec<u8> {
+        let blocks = self.blocks.lock().unwrap();
+        (offset..offset + len as u64)
+            .map(|pos| {
+                blocks
+                    .get(&((pos / SIZE as u64) as u32))
+                    .map_or(0, |block| block[(pos % SIZE as u64) as usize])
+            })
+            .collect()
+    }
+
+    fn u32_at(&self, offset: u64) -> u32 {
+        u32::from_le_bytes(self.bytes(offset, 4).try_into().unwrap())
+    }
+}
+
+impl<const SIZE: usize> BlockDevice<SIZE> for RamDisk<SIZE> {
+    type Error = std::convert::Infallible;
+    type Align = A4;
+
+    async fn read(
+        &self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let blocks = self.blocks.lock().unwrap();
+        for (i, block) in data.iter_mut().enumerate() {
+            match blocks.get(&(block_address + i as u32)) {
+                Some(stored) => block.copy_from_slice(&stored[..]),
+                None => block.fill(0),
+            }
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; SIZE]>],
+    ) -> Result<(), Self::Error> {
+        assert!(u64::from(block_address) + data.len() as u64 <= self.size / SIZE as u64);
+        let mut blocks = self.blocks.lock().unwrap();
+        for (i, block) in data.iter().enumerate() {
+            blocks.insert(block_address + i as u32, Box::new(**block));
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<u64, Self::Error> {
+        Ok(self.size)
+    }
+
+    async fn sync(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+fn pattern(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8 ^ seed).collect()
+}
+
+async fn read_to_end<R: Read>(file: &mut R) -> Vec<u8>
+where
+    R::Error: core::fmt::Debug,
+{
+    let mut content = Vec::new();
+    let mut buf = [0; 3000];
+    loop {
+        match file.read(&mut buf).await.unwrap() {
+            0 => return content,
+            n => content.extend_from_slice(&buf[..n]),
+        }
+    }
+}
+
+/// Write `data` in odd-sized chunks so writes straddle sector boundaries
+async fn write_chunked<W: Write>(file: &mut W, data: &[u8])
+where
+    W::Error: core::fmt::Debug,
+{
+    for chunk in data.chunks(5000) {
+        file.write_all(chunk).await.unwrap();
+    }
+    file.flush().await.unwrap();
+}
+
+/// Offset of the `n`-th 12-bit entry's first byte in a FAT12 table
+fn fat12_entry(disk: &RamDisk<SECTOR>, fat_start: u64, n: u32) -> u32 {
+    let raw = disk.bytes(fat_start + u64::from(n) * 3 / 2, 2);
+    let packed = u32::from(u16::from_le_bytes([raw[0], raw[1]]));
+    if n % 2 == 0 {
+        packed & 0xFFF
+    } else {
+        packed >> 4
+    }
+}
+
+#[tokio::test]
+async fn fat32_boot_sector_and_fsinfo_placement() {
+    // 70000 clusters of one 4K sector: just above the FAT32 minimum
+    let disk = RamDisk::<SECTOR>::new(70_000 * SECTOR as u64);
+    let mut stream = HeapPageStream::new_unwrap(disk.clone(), 64 * 1024);
+    let options = FormatVolumeOptions::new()
+        .bytes_per_sector(SECTOR as u16)
+        .bytes_per_cluster(SECTOR as u32)
+        .fat_type(FatType::Fat32)
+        .volume_label(*b"FOURK      ");
+    fatrs::format_volume(&mut stream, options).await.unwrap();
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+    stream.flush().await.unwrap();
+
+    // BPB and boot signature at the usual offsets, rest of the sector zeroed
+    let boot = disk.bytes(0, SECTOR);
+    assert_eq!(u16::from_le_bytes([boot[11], boot[12]]), SECTOR as u16);
+    assert_eq!(boot[13], 1);
+    assert_eq!(u16::from_le_bytes([boot[48], boot[49]]), 1, "FSInfo sector");
+    assert_eq!(
+        u16::from_le_bytes([boot[50], boot[51]]),
+        6,
+        "backup boot sector"
+    );
+    assert_eq!(&boot[510..512], &[0x55, 0xAA]);
+    assert!(boot[512..].iter().all(|&b| b == 0));
+
+    // FSInfo in logical sector 1, i.e. at byte 4096, with its signatures at
+    // the offsets fixed by the spec rather than at the end of the sector
+    let fs_info = SECTOR as u64;
+    assert_eq!(disk.u32_at(fs_info), 0x4161_5252);
+    assert_eq!(disk.u32_at(fs_info + 484), 0x6141_7272);
+    assert_eq!(disk.u32_at(fs_info + 508), 0xAA55_0000);
+    assert!(
+        disk.bytes(fs_info + 512, SECTOR - 512)
+            .iter()
+            .all(|&b| b == 0)
+    );
+
+    // Backup boot sector in logical sector 6
+    assert_eq!(disk.bytes(6 * SECTOR as u64, SECTOR), boot);
+
+    let data = pattern(300_000, 0x5A);
+    let free_before = {
+        let fs = FileSystem::new(&mut stream, FsOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(fs.fat_type(), FatType::Fat32);
+        assert_eq!(fs.cluster_size(), SECTOR as u32);
+        // Count free clusters so allocations keep FSInfo up to date
+        let free_before = fs.stats().await.unwrap().free_clusters();
+        let root = fs.root_dir();
+        let dir = root.create_dir("logs").await.unwrap();
+        let mut file = dir.create_file("big.bin").await.unwrap();
+        write_chunked(&mut file, &data).await;
+        drop((file, dir, root));
+        fs.unmount().await.unwrap();
+        free_before
+    };
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    // Unmount persists the free cluster count in FSInfo: "logs" and the
+    // file's 74 clusters were allocated
+    assert_eq!(disk.u32_at(fs_info + 488), free_before - 1 - 74);
+    let fs = FileSystem::new(&mut stream, FsOptions::new())
+        .await
+        .unwrap();
+    let stats = fs.stats().await.unwrap();
+    assert_eq!(stats.free_clusters(), free_before - 1 - 74);
+    // root dir, "logs" and the file
+    assert_eq!(stats.total_clusters() - stats.free_clusters(), 1 + 1 + 74);
+    let mut file = fs.root_dir().open_file("logs/big.bin").await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, data);
+}
+
+#[tokio::test]
+async fn fat16_on_4k_sectors() {
+    let disk = RamDisk::<SECTOR>::new(64 * 1024 * 1024);
+    let mut stream = HeapPageStream::new_unwrap(disk.clone(), 128 * 1024);
+    let options = FormatVolumeOptions::new()
+        .bytes_per_sector(SECTOR as u16)
+        .fat_type(FatType::Fat16);
+    fatrs::format_volume(&mut stream, options).await.unwrap();
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    let fs = FileSystem::new(&mut stream, FsOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(fs.fat_type(), FatType::Fat16);
+    let root = fs.root_dir();
+
+    // Fill the fixed root directory: 512 entries are exactly four 4K sectors
+    for i in 0..100 {
+        let mut file = root.create_file(&format!("F{i:03}.TXT")).await.unwrap();
+        file.write_all(format!("file {i}").as_bytes())
+            .await
+            .unwrap();
+        file.flush().await.unwrap();
+    }
+    let data = pattern(1_000_000, 0x11);
+    let mut file = root.create_file("large.bin").await.unwrap();
+    write_chunked(&mut file, &data).await;
+    file.seek(SeekFrom::Start(0)).await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, data);
+    drop((file, root));
+    fs.unmount().await.unwrap();
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    let fs = FileSystem::new(&mut stream, FsOptions::new())
+        .await
+        .unwrap();
+    let root = fs.root_dir();
+    let mut file = root.open_file("F099.TXT").await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, b"file 99");
+    let mut file = root.open_file("large.bin").await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, data);
+}
+
+#[tokio::test]
+async fn fat12_entries_straddling_4k_fat_sectors() {
+    // 3500 sectors with 4K clusters is FAT12 with a two-sector FAT; entry 2730
+    // occupies the last byte of the first FAT sector and the first of the second
+    let disk = RamDisk::<SECTOR>::new(3500 * SECTOR as u64);
+    let mut stream = StackPageStream::<_, SECTOR, SECTOR>::new(disk.clone());
+    let options = FormatVolumeOptions::new()
+        .bytes_per_sector(SECTOR as u16)
+        .bytes_per_cluster(SECTOR as u32)
+        .fat_type(FatType::Fat12);
+    fatrs::format_volume(&mut stream, options).await.unwrap();
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    let boot = disk.bytes(0, 512);
+    let reserved_sectors = u64::from(u16::from_le_bytes([boot[14], boot[15]]));
+    let sectors_per_fat = u64::from(u16::from_le_bytes([boot[22], boot[23]]));
+    assert_eq!(sectors_per_fat, 2);
+    let fat_start = reserved_sectors * SECTOR as u64;
+
+    // Two files so the straddling entry is both in the middle of a chain and,
+    // after the first file is truncated, freed and reallocated
+    let first = pattern(2800 * SECTOR, 0x33);
+    let second = pattern(500 * SECTOR + 123, 0x44);
+    {
+        let fs = FileSystem::new(&mut stream, FsOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(fs.fat_type(), FatType::Fat12);
+        let root = fs.root_dir();
+        let mut file = root.create_file("first.bin").await.unwrap();
+        write_chunked(&mut file, &first).await;
+        drop((file, root));
+        fs.unmount().await.unwrap();
+    }
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    // The chain is contiguous from cluster 2, so every entry links to the next
+    for n in [2729, 2730, 2731] {
+        assert_eq!(fat12_entry(&disk, fat_start, n), n + 1, "entry {n}");
+    }
+    // Both FAT copies agree
+    let fat_len = sectors_per_fat as usize * SECTOR;
+    assert_eq!(
+        disk.bytes(fat_start, fat_len),
+        disk.bytes(fat_start + fat_len as u64, fat_len)
+    );
+
+    let fs = FileSystem::new(&mut stream, FsOptions::new())
+        .await
+        .unwrap();
+    let root = fs.root_dir();
+    let mut file = root.open_file("first.bin").await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, first);
+    file.seek(SeekFrom::Start(2700 * SECTOR as u64))
+        .await
+        .unwrap();
+    file.truncate().await.unwrap();
+    drop(file);
+
+    let mut file = root.create_file("second.bin").await.unwrap();
+    write_chunked(&mut file, &second).await;
+    drop(file);
+    let stats = fs.stats().await.unwrap();
+    assert_eq!(stats.total_clusters() - stats.free_clusters(), 2700 + 501);
+    drop(root);
+    fs.unmount().await.unwrap();
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    let fs = FileSystem::new(&mut stream, FsOptions::new())
+        .await
+        .unwrap();
+    let root = fs.root_dir();
+    let mut file = root.open_file("first.bin").await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, &first[..2700 * SECTOR]);
+    let mut file = root.open_file("second.bin").await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, second);
+}
+
+#[tokio::test]
+async fn fat12_4k_volume_on_512_byte_blocks() {
+    // A 4K-sector filesystem over a 512-byte device read one block at a time:
+    // every 4K sector access is split into short reads and writes
+    let disk = RamDisk::<512>::new(3500 * SECTOR as u64);
+    let mut stream = StackPageStream::<_, 512, 512>::new(disk.clone());
+    let options = FormatVolumeOptions::new()
+        .bytes_per_sector(SECTOR as u16)
+        .bytes_per_cluster(SECTOR as u32)
+        .fat_type(FatType::Fat12);
+    fatrs::format_volume(&mut stream, options).await.unwrap();
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    let data = pattern(2800 * SECTOR, 0x77);
+    {
+        let fs = FileSystem::new(&mut stream, FsOptions::new())
+            .await
+            .unwrap();
+        let mut file = fs.root_dir().create_file("data.bin").await.unwrap();
+        write_chunked(&mut file, &data).await;
+        drop(file);
+        fs.unmount().await.unwrap();
+    }
+    stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    let fs = FileSystem::new(&mut stream, FsOptions::new())
+        .await
+        .unwrap();
+    let mut file = fs.root_dir().open_file("data.bin").await.unwrap();
+    assert_eq!(read_to_end(&mut file).await, data);
+}
+
+#[test]
+#[should_panic(expected = "Invalid bytes_per_sector")]
+fn bytes_per_sector_above_4096_is_rejected() {
+    let _ = FormatVolumeOptions::new().bytes_per_sector(8192);
+}